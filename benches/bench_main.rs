@@ -2,6 +2,7 @@ use criterion::BenchmarkId;
 use criterion::{criterion_group, criterion_main, Criterion};
 
 use std::fs::File;
+use std::io::Cursor;
 
 fn read_mp4(filename: &str) -> u64 {
     let f = File::open(filename).unwrap();
@@ -10,6 +11,46 @@ fn read_mp4(filename: &str) -> u64 {
     m.size()
 }
 
+fn write_mp4(sample_count: u32) -> Vec<u8> {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+    };
+    let mut writer = mp4::Mp4Writer::write_start(Cursor::new(Vec::new()), &config).unwrap();
+    let track_id = writer
+        .add_track(&mp4::TrackConfig::from(mp4::AacConfig::default()))
+        .unwrap();
+    // Alternating durations give stts an entry per sample.
+    for i in 0..sample_count {
+        let sample = mp4::Mp4Sample {
+            start_time: i as u64 * 1024 + (i / 2) as u64,
+            duration: 1024 + i % 2,
+            rendering_offset: 0,
+            is_sync: true,
+            bytes: mp4::Bytes::from(vec![0u8; 100 + (i % 50) as usize]),
+            encryption: None,
+        };
+        writer.write_sample(track_id, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+    writer.into_writer().into_inner()
+}
+
+fn read_samples(data: &[u8], index: bool) -> u64 {
+    let mut mp4 = mp4::Mp4Reader::read_header(Cursor::new(data), data.len() as u64).unwrap();
+    if index {
+        mp4.build_sample_index();
+    }
+    let mut size = 0;
+    for sample_id in 1..=mp4.sample_count(1).unwrap() {
+        let sample = mp4.read_sample(1, sample_id).unwrap().unwrap();
+        size += sample.bytes.len() as u64;
+    }
+    size
+}
+
 fn criterion_benchmark(c: &mut Criterion) {
     let filename = "tests/samples/minimal.mp4";
 
@@ -20,6 +61,27 @@ fn criterion_benchmark(c: &mut Criterion) {
             b.iter(|| read_mp4(s));
         },
     );
+
+    for sample_count in [1_000, 10_000, 100_000] {
+        let data = write_mp4(sample_count);
+        // Without the index, reading every sample takes quadratic time.
+        if sample_count <= 10_000 {
+            c.bench_with_input(
+                BenchmarkId::new("read_samples", sample_count),
+                &data,
+                |b, data| {
+                    b.iter(|| read_samples(data, false));
+                },
+            );
+        }
+        c.bench_with_input(
+            BenchmarkId::new("read_samples_indexed", sample_count),
+            &data,
+            |b, data| {
+                b.iter(|| read_samples(data, true));
+            },
+        );
+    }
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -3,10 +3,11 @@ use std::fs::File;
 use std::io::prelude::*;
 use std::io::{self, BufReader, BufWriter};
 use std::path::Path;
+use std::time::Duration;
 
 use mp4::{
-    AacConfig, AvcConfig, HevcConfig, MediaConfig, MediaType, Mp4Config, OpusConfig,
-    Result, TrackConfig, TtxtConfig, Vp9Config,
+    AacConfig, Ac3Config, Av1Config, AvcConfig, Ec3Config, FlacConfig, HevcConfig, MediaConfig,
+    MediaType, Mp4Config, OpusConfig, Result, TrackConfig, TtxtConfig, Vp9Config,
 };
 
 fn main() {
@@ -41,7 +42,6 @@ fn copy<P: AsRef<Path>>(src_filename: &P, dst_filename: &P) -> Result<()> {
         },
     )?;
 
-    // TODO interleaving
     for track in mp4_reader.tracks().values() {
         let media_conf = match track.media_type()? {
             MediaType::H264 => MediaConfig::AvcConfig(AvcConfig {
@@ -50,29 +50,7 @@ fn copy<P: AsRef<Path>>(src_filename: &P, dst_filename: &P) -> Result<()> {
                 seq_param_set: track.sequence_parameter_set()?.to_vec(),
                 pic_param_set: track.picture_parameter_set()?.to_vec(),
             }),
-            MediaType::H265 => MediaConfig::HevcConfig(HevcConfig {
-                width: Some(track.width()),
-                height: Some(track.height()),
-                configuration_version: Some(1),
-                general_profile_space: Some(0),
-                general_tier_flag: Some(false),
-                general_profile_idc: Some(1),
-                general_profile_compatibility_flags: Some(0),
-                general_constraint_indicator_flag: Some(0),
-                general_level_idc: Some(93),
-                min_spatial_segmentation_idc: Some(0),
-                parallelism_type: Some(0),
-                chroma_format_idc: Some(1),
-                bit_depth_luma_minus8: Some(0),
-                bit_depth_chroma_minus8: Some(0),
-                avg_frame_rate: Some(0),
-                constant_frame_rate: Some(0),
-                num_temporal_layers: Some(1),
-                temporal_id_nested: Some(false),
-                length_size_minus_one: Some(3),
-                arrays: Some(vec![]),
-                use_hvc1: true,
-            }),
+            MediaType::H265 => MediaConfig::HevcConfig(HevcConfig::from_track(track)?),
             MediaType::VP9 => MediaConfig::Vp9Config(Vp9Config {
                 width: track.width(),
                 height: track.height(),
@@ -86,6 +64,24 @@ fn copy<P: AsRef<Path>>(src_filename: &P, dst_filename: &P) -> Result<()> {
                 matrix_coefficients: 0,
                 codec_initialization_data_size: 0,
             }),
+            MediaType::AV1 => {
+                let av1c = &track.trak.mdia.minf.stbl.stsd.av01.as_ref().unwrap().av1c;
+                MediaConfig::Av1Config(Av1Config {
+                    width: track.width(),
+                    height: track.height(),
+                    seq_profile: av1c.seq_profile,
+                    seq_level_idx_0: av1c.seq_level_idx_0,
+                    seq_tier_0: av1c.seq_tier_0,
+                    high_bitdepth: av1c.high_bitdepth,
+                    twelve_bit: av1c.twelve_bit,
+                    monochrome: av1c.monochrome,
+                    chroma_subsampling_x: av1c.chroma_subsampling_x,
+                    chroma_subsampling_y: av1c.chroma_subsampling_y,
+                    chroma_sample_position: av1c.chroma_sample_position,
+                    initial_presentation_delay_minus_one: av1c.initial_presentation_delay_minus_one,
+                    config_obus: av1c.config_obus.clone(),
+                })
+            }
             MediaType::AAC => {
                 let default_aac_config = AacConfig::default();
                 MediaConfig::AacConfig(AacConfig {
@@ -114,28 +110,81 @@ fn copy<P: AsRef<Path>>(src_filename: &P, dst_filename: &P) -> Result<()> {
                 chan_conf: track.channel_config()?,
                 pre_skip: 0,
             }),
+            MediaType::AC3 => {
+                let dac3 = &track.trak.mdia.minf.stbl.stsd.ac3.as_ref().unwrap().dac3;
+                MediaConfig::Ac3Config(Ac3Config {
+                    fscod: dac3.fscod,
+                    bsid: dac3.bsid,
+                    bsmod: dac3.bsmod,
+                    acmod: dac3.acmod,
+                    lfeon: dac3.lfeon,
+                    bit_rate_code: dac3.bit_rate_code,
+                })
+            }
+            MediaType::EAC3 => {
+                let dec3 = &track.trak.mdia.minf.stbl.stsd.ec3.as_ref().unwrap().dec3;
+                MediaConfig::Ec3Config(Ec3Config {
+                    data_rate: dec3.data_rate,
+                    substreams: dec3.substreams.clone(),
+                    complexity_index_type_a: dec3.complexity_index_type_a,
+                })
+            }
+            MediaType::FLAC => {
+                let dfla = &track.trak.mdia.minf.stbl.stsd.flac.as_ref().unwrap().dfla;
+                MediaConfig::FlacConfig(FlacConfig {
+                    stream_info: dfla.stream_info.clone(),
+                    metadata_blocks: dfla.metadata_blocks.clone(),
+                })
+            }
             MediaType::TTXT => MediaConfig::TtxtConfig(TtxtConfig {}),
         };
 
         let track_conf = TrackConfig {
-            track_id: None,
+            track_id: Some(track.track_id()),
             track_type: track.track_type()?,
             timescale: track.timescale(),
             language: track.language().to_string(),
             media_conf,
             matrix: None,
+            samples_per_chunk: None,
+            duration_per_chunk: None,
+            references: track.references().to_vec(),
         };
 
         mp4_writer.add_track(&track_conf)?;
     }
 
-    for track_id in mp4_reader.tracks().keys().copied().collect::<Vec<u32>>() {
-        let sample_count = mp4_reader.sample_count(track_id)?;
-        for sample_idx in 0..sample_count {
-            let sample_id = sample_idx + 1;
-            let sample = mp4_reader.read_sample(track_id, sample_id)?.unwrap();
-            mp4_writer.write_sample(track_id, &sample)?;
-            // println!("copy {}:({})", sample_id, sample);
+    mp4_writer.set_interleave_window(Duration::from_millis(500));
+
+    // Copy samples across tracks in decode time order.
+    let mut next_samples: Vec<(u32, u32, u32)> = Vec::new();
+    for track in mp4_reader.tracks().values() {
+        if mp4_reader.sample_count(track.track_id())? > 0 {
+            next_samples.push((track.track_id(), 1, track.timescale()));
+        }
+    }
+    while !next_samples.is_empty() {
+        let mut next = 0;
+        let mut next_time = f64::MAX;
+        for (i, (track_id, sample_id, timescale)) in next_samples.iter().enumerate() {
+            if let Some(metadata) = mp4_reader.read_sample_metadata(*track_id, *sample_id)? {
+                let time = metadata.start_time as f64 / *timescale as f64;
+                if time < next_time {
+                    next = i;
+                    next_time = time;
+                }
+            }
+        }
+
+        let (track_id, sample_id, _) = next_samples[next];
+        let sample = mp4_reader.read_sample(track_id, sample_id)?.unwrap();
+        mp4_writer.write_sample(track_id, &sample)?;
+        // println!("copy {}:({})", sample_id, sample);
+
+        if sample_id < mp4_reader.sample_count(track_id)? {
+            next_samples[next].1 += 1;
+        } else {
+            next_samples.remove(next);
         }
     }
 
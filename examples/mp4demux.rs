@@ -0,0 +1,57 @@
+use std::env;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use mp4::{ElementaryStreamWriter, Result};
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 2 {
+        println!("Usage: mp4demux <filename> [output prefix]");
+        std::process::exit(1);
+    }
+
+    let prefix = match args.get(2) {
+        Some(prefix) => prefix.clone(),
+        None => Path::new(&args[1])
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| String::from("track")),
+    };
+
+    if let Err(err) = demux(&args[1], &prefix) {
+        let _ = writeln!(io::stderr(), "{}", err);
+    }
+}
+
+fn demux<P: AsRef<Path>>(filename: &P, prefix: &str) -> Result<()> {
+    let f = File::open(filename)?;
+    let size = f.metadata()?.len();
+    let reader = BufReader::new(f);
+
+    let mut mp4 = mp4::Mp4Reader::read_header(reader, size)?;
+
+    let mut track_ids: Vec<u32> = mp4.tracks().keys().copied().collect();
+    track_ids.sort_unstable();
+    for track_id in track_ids {
+        let track = mp4.tracks().get(&track_id).unwrap();
+
+        // Find the extension of the stream before creating its file.
+        let extension = match ElementaryStreamWriter::new(track, io::sink()) {
+            Ok(stream) => stream.extension(),
+            Err(err) => {
+                println!("track #{}: skipped, {}", track_id, err);
+                continue;
+            }
+        };
+
+        let path = format!("{}_{}.{}", prefix, track_id, extension);
+        let writer = BufWriter::new(File::create(&path)?);
+        mp4.demux_track(track_id, writer)?;
+        println!("track #{}: {}", track_id, path);
+    }
+    Ok(())
+}
@@ -1,145 +1,202 @@
 use std::env;
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::{self, BufReader};
+use std::io::{self, BufReader, SeekFrom};
 use std::path::Path;
 
-use mp4::{Mp4Box, Result};
+use mp4::{BoxNode, BoxTree, BoxType, BoxVisitor, FourCC, ParsedBox, Result, TrunBox};
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+/// Number of payload bytes shown for boxes the crate does not parse.
+const PREVIEW_SIZE: usize = 16;
 
-    if args.len() < 2 {
-        println!("Usage: mp4dump <filename>");
-        std::process::exit(1);
+fn main() {
+    let mut json = false;
+    let mut tables = false;
+    let mut filename = None;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--tables" => tables = true,
+            _ => filename = Some(arg),
+        }
     }
 
-    if let Err(err) = dump(&args[1]) {
+    let filename = match filename {
+        Some(filename) => filename,
+        None => {
+            println!("Usage: mp4dump [--json] [--tables] <filename>");
+            println!("  --json    print the box tree as JSON");
+            println!("  --tables  print the stts/ctts/stsz/stco/co64/trun entries");
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(err) = dump(&filename, json, tables) {
         let _ = writeln!(io::stderr(), "{}", err);
     }
 }
 
-fn dump<P: AsRef<Path>>(filename: &P) -> Result<()> {
+fn dump<P: AsRef<Path>>(filename: &P, json: bool, tables: bool) -> Result<()> {
     let f = File::open(filename)?;
-    let boxes = get_boxes(f)?;
+    let size = f.metadata()?.len();
+    let mut reader = BufReader::new(f);
+    let tree = BoxTree::read(&mut reader, size)?;
 
-    // print out boxes
-    for b in boxes.iter() {
-        println!("[{}] size={} {}", b.name, b.size, b.summary);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&tree).unwrap());
+        return Ok(());
     }
 
-    Ok(())
+    let mut printer = Printer {
+        reader,
+        tables,
+        result: Ok(()),
+    };
+    tree.visit(&mut printer);
+    printer.result
 }
 
-#[derive(Debug, Clone, PartialEq, Default)]
-pub struct Box {
-    name: String,
-    size: u64,
-    summary: String,
-    indent: u32,
+struct Printer<R> {
+    reader: R,
+    tables: bool,
+    result: Result<()>,
 }
 
-fn get_boxes(file: File) -> Result<Vec<Box>> {
-    let size = file.metadata()?.len();
-    let reader = BufReader::new(file);
-    let mp4 = mp4::Mp4Reader::read_header(reader, size)?;
-
-    // collect known boxes
-    let mut boxes = vec![
-        build_box(&mp4.ftyp),
-        build_box(&mp4.moov),
-        build_box(&mp4.moov.mvhd),
-    ];
-
-    if let Some(ref mvex) = &mp4.moov.mvex {
-        boxes.push(build_box(mvex));
-        if let Some(mehd) = &mvex.mehd {
-            boxes.push(build_box(mehd));
+impl<R: Read + Seek> Printer<R> {
+    fn print(&mut self, node: &BoxNode, depth: usize) -> Result<()> {
+        let indent = "  ".repeat(depth);
+        let mut line = format!(
+            "{}[{}] offset={} size={} header={}",
+            indent,
+            box_name(node.box_type),
+            node.offset,
+            node.size,
+            node.header_size
+        );
+        match node.parsed {
+            Some(ref parsed) => {
+                let summary = parsed.summary()?;
+                if !summary.is_empty() {
+                    line.push(' ');
+                    line.push_str(&summary);
+                }
+            }
+            None if node.children.is_empty() && node.data_size() > 0 => {
+                line.push_str(&format!(" data={}", self.preview(node)?));
+            }
+            None => {}
         }
-        boxes.push(build_box(&mvex.trex));
-    }
+        println!("{}", line);
 
-    // trak.
-    for track in mp4.tracks().values() {
-        boxes.push(build_box(&track.trak));
-        boxes.push(build_box(&track.trak.tkhd));
-        if let Some(ref edts) = track.trak.edts {
-            boxes.push(build_box(edts));
-            if let Some(ref elst) = edts.elst {
-                boxes.push(build_box(elst));
+        if self.tables {
+            if let Some(ref parsed) = node.parsed {
+                print_table(parsed, &indent);
             }
         }
+        Ok(())
+    }
 
-        // trak.mdia
-        let mdia = &track.trak.mdia;
-        boxes.push(build_box(mdia));
-        boxes.push(build_box(&mdia.mdhd));
-        boxes.push(build_box(&mdia.hdlr));
-        boxes.push(build_box(&track.trak.mdia.minf));
-
-        // trak.mdia.minf
-        let minf = &track.trak.mdia.minf;
-        if let Some(ref vmhd) = &minf.vmhd {
-            boxes.push(build_box(vmhd));
-        }
-        if let Some(ref smhd) = &minf.smhd {
-            boxes.push(build_box(smhd));
+    fn preview(&mut self, node: &BoxNode) -> Result<String> {
+        let len = node.data_size().min(PREVIEW_SIZE as u64) as usize;
+        let mut buf = vec![0u8; len];
+        self.reader.seek(SeekFrom::Start(node.data_offset()))?;
+        self.reader.read_exact(&mut buf)?;
+
+        let mut hex = buf
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if node.data_size() > len as u64 {
+            hex.push_str(" ...");
         }
+        Ok(hex)
+    }
+}
 
-        // trak.mdia.minf.stbl
-        let stbl = &track.trak.mdia.minf.stbl;
-        boxes.push(build_box(stbl));
-        boxes.push(build_box(&stbl.stsd));
-        if let Some(ref avc1) = &stbl.stsd.avc1 {
-            boxes.push(build_box(avc1));
+impl<R: Read + Seek> BoxVisitor for Printer<R> {
+    fn enter(&mut self, node: &BoxNode, depth: usize) -> bool {
+        if self.result.is_err() {
+            return false;
         }
-        if let Some(ref hev1) = &stbl.stsd.hev1 {
-            boxes.push(build_box(hev1));
-        }
-        if let Some(ref hvc1) = &stbl.stsd.hvc1 {
-            boxes.push(build_box(hvc1));
-        }
-        if let Some(ref mp4a) = &stbl.stsd.mp4a {
-            boxes.push(build_box(mp4a));
-        }
-        boxes.push(build_box(&stbl.stts));
-        if let Some(ref ctts) = &stbl.ctts {
-            boxes.push(build_box(ctts));
+        self.result = self.print(node, depth);
+        self.result.is_ok()
+    }
+}
+
+/// The box type with one character per byte, e.g. `©nam`, as written in
+/// [BoxTree] paths.
+fn box_name(box_type: BoxType) -> String {
+    FourCC::from(box_type)
+        .value
+        .iter()
+        .map(|&byte| char::from(byte))
+        .collect()
+}
+
+fn print_table(parsed: &ParsedBox, indent: &str) {
+    match parsed {
+        ParsedBox::Stts(stts) => {
+            for (i, entry) in stts.entries.iter().enumerate() {
+                println!(
+                    "{}  #{} sample_count={} sample_delta={}",
+                    indent,
+                    i + 1,
+                    entry.sample_count,
+                    entry.sample_delta
+                );
+            }
         }
-        if let Some(ref stss) = &stbl.stss {
-            boxes.push(build_box(stss));
+        ParsedBox::Ctts(ctts) => {
+            for (i, entry) in ctts.entries.iter().enumerate() {
+                println!(
+                    "{}  #{} sample_count={} sample_offset={}",
+                    indent,
+                    i + 1,
+                    entry.sample_count,
+                    entry.sample_offset
+                );
+            }
         }
-        boxes.push(build_box(&stbl.stsc));
-        boxes.push(build_box(&stbl.stsz));
-        if let Some(ref stco) = &stbl.stco {
-            boxes.push(build_box(stco));
+        ParsedBox::Stsz(stsz) => {
+            for i in 0..stsz.sample_count as usize {
+                let sample_size = match stsz.sample_size {
+                    0 => stsz.sample_sizes.get(i).copied().unwrap_or(0),
+                    sample_size => sample_size,
+                };
+                println!("{}  #{} sample_size={}", indent, i + 1, sample_size);
+            }
         }
-        if let Some(ref co64) = &stbl.co64 {
-            boxes.push(build_box(co64));
+        ParsedBox::Stco(stco) => {
+            for (i, offset) in stco.entries.iter().enumerate() {
+                println!("{}  #{} chunk_offset={}", indent, i + 1, offset);
+            }
         }
-    }
-
-    // If fragmented, add moof boxes.
-    for moof in mp4.moofs.iter() {
-        boxes.push(build_box(moof));
-        boxes.push(build_box(&moof.mfhd));
-        for traf in moof.trafs.iter() {
-            boxes.push(build_box(traf));
-            boxes.push(build_box(&traf.tfhd));
-            if let Some(ref trun) = &traf.trun {
-                boxes.push(build_box(trun));
+        ParsedBox::Co64(co64) => {
+            for (i, offset) in co64.entries.iter().enumerate() {
+                println!("{}  #{} chunk_offset={}", indent, i + 1, offset);
             }
         }
+        ParsedBox::Trun(trun) => print_trun(trun, indent),
+        _ => {}
     }
-
-    Ok(boxes)
 }
 
-fn build_box<M: Mp4Box + std::fmt::Debug>(m: &M) -> Box {
-    Box {
-        name: m.box_type().to_string(),
-        size: m.box_size(),
-        summary: m.summary().unwrap(),
-        indent: 0,
+fn print_trun(trun: &TrunBox, indent: &str) {
+    for i in 0..trun.sample_count as usize {
+        let mut line = format!("{}  #{}", indent, i + 1);
+        if let Some(duration) = trun.sample_durations.get(i) {
+            line.push_str(&format!(" duration={}", duration));
+        }
+        if let Some(size) = trun.sample_sizes.get(i) {
+            line.push_str(&format!(" size={}", size));
+        }
+        if let Some(flags) = trun.sample_flags.get(i) {
+            line.push_str(&format!(" flags={:#010x}", flags));
+        }
+        if let Some(cts) = trun.sample_cts.get(i) {
+            line.push_str(&format!(" cts_offset={}", cts));
+        }
+        println!("{}", line);
     }
 }
@@ -120,6 +120,31 @@ fn audio_info(track: &Mp4Track) -> Result<String> {
                 track.bitrate() / 1000
             ))
         }
+    } else if track.trak.mdia.minf.stbl.stsd.flac.is_some() {
+        Ok(format!(
+            "{} ({:?}), {} Hz, {} channels, {} bits",
+            track.media_type()?,
+            track.box_type()?,
+            track.sample_rate()?,
+            track.channel_count()?,
+            track.bits_per_sample()?
+        ))
+    } else if track.trak.mdia.minf.stbl.stsd.ac3.is_some()
+        || track.trak.mdia.minf.stbl.stsd.ec3.is_some()
+    {
+        let channel_config = match track.channel_config() {
+            Ok(val) => val.to_string(),
+            _ => "-".to_string(),
+        };
+
+        Ok(format!(
+            "{} ({:?}), {} Hz, {}, {} kb/s",
+            track.media_type()?,
+            track.box_type()?,
+            track.sample_freq_index()?.freq(),
+            channel_config,
+            track.bitrate() / 1000
+        ))
     } else {
         Err(Error::InvalidData("mp4a box not found"))
     }
@@ -0,0 +1,86 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use mp4::nal::NalFormat;
+use mp4::{ElementaryStream, Error, Mp4Config, Result};
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    let mut frame_rate = None;
+    if args.len() > 1 && args[0] == "--fps" {
+        frame_rate = parse_frame_rate(&args[1]);
+        if frame_rate.is_none() {
+            println!("Invalid frame rate: {}", args[1]);
+            std::process::exit(1);
+        }
+        args.drain(..2);
+    }
+
+    if args.len() < 2 {
+        println!("Usage: mp4mux [--fps <num>[/<den>]] <target file> <stream>...");
+        println!("Streams: .h264/.264 and .h265/.265/.hevc as Annex-B, .aac as ADTS, .ivf as VP9");
+        std::process::exit(1);
+    }
+
+    if let Err(err) = mux(&args[0], &args[1..], frame_rate) {
+        let _ = writeln!(io::stderr(), "{}", err);
+    }
+}
+
+fn parse_frame_rate(s: &str) -> Option<(u32, u32)> {
+    let mut parts = s.splitn(2, '/');
+    let num = parts.next()?.parse().ok()?;
+    let den = match parts.next() {
+        Some(den) => den.parse().ok()?,
+        None => 1,
+    };
+    Some((num, den))
+}
+
+fn mux(dst_filename: &str, src_filenames: &[String], frame_rate: Option<(u32, u32)>) -> Result<()> {
+    let mut streams = Vec::new();
+    for filename in src_filenames {
+        let data = fs::read(filename)?;
+        let extension = Path::new(filename)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let stream = match extension.as_str() {
+            "h264" | "264" | "avc" => {
+                ElementaryStream::from_annexb(&data, NalFormat::Avc, frame_rate)?
+            }
+            "h265" | "265" | "hevc" => {
+                ElementaryStream::from_annexb(&data, NalFormat::Hevc, frame_rate)?
+            }
+            "aac" | "adts" => ElementaryStream::from_adts(&data)?,
+            "ivf" => ElementaryStream::from_ivf(&data)?,
+            _ => return Err(Error::InvalidData("unknown stream extension")),
+        };
+        println!(
+            "{}: {} samples, timescale {}",
+            filename,
+            stream.samples.len(),
+            stream.track_config.timescale
+        );
+        streams.push(stream);
+    }
+
+    let config = Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![
+            str::parse("isom").unwrap(),
+            str::parse("iso2").unwrap(),
+            str::parse("mp41").unwrap(),
+        ],
+        timescale: 1000,
+    };
+    let writer = BufWriter::new(File::create(dst_filename)?);
+    mp4::mux_streams(writer, &config, &streams)?.flush()?;
+    Ok(())
+}
@@ -0,0 +1,506 @@
+//! Common Encryption (ISO/IEC 23001-7) of sample data with a known key.
+//!
+//! Only the `cenc` (AES-CTR) and `cbcs` (AES-CBC with a pattern) schemes are
+//! supported. The key identifiers, IVs and subsample maps come from the
+//! `tenc`, `senc`, `saiz` and `saio` boxes, see [SampleEncryptionEntry].
+
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use aes::{Aes128, Block};
+use std::convert::TryInto;
+use std::ops::Range;
+
+use crate::nal::{sample_entry_format, NalFormat};
+use crate::*;
+
+/// AES-CTR encryption of whole samples or subsamples.
+pub const SCHEME_CENC: FourCC = FourCC { value: *b"cenc" };
+
+/// AES-CBC encryption of subsamples with a pattern of encrypted and clear
+/// blocks, and a constant IV.
+pub const SCHEME_CBCS: FourCC = FourCC { value: *b"cbcs" };
+
+const BLOCK_SIZE: usize = 16;
+
+/// Parameters to encrypt the samples of a track with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptionConfig {
+    /// [SCHEME_CENC] or [SCHEME_CBCS].
+    pub scheme_type: FourCC,
+    pub kid: [u8; 16],
+    pub key: [u8; 16],
+
+    /// IV of the first sample. With `cenc` it is 8 or 16 bytes and advances
+    /// with every sample. With `cbcs` it is 16 bytes, shared by all samples.
+    pub iv: Vec<u8>,
+
+    /// Pattern of `cbcs` video tracks. Other tracks are encrypted whole.
+    pub crypt_byte_block: u8,
+    pub skip_byte_block: u8,
+
+    /// `pssh` boxes to store in the `moov`.
+    pub psshs: Vec<PsshBox>,
+}
+
+impl EncryptionConfig {
+    pub fn cenc(kid: [u8; 16], key: [u8; 16], iv: [u8; 8]) -> Self {
+        Self {
+            scheme_type: SCHEME_CENC,
+            kid,
+            key,
+            iv: iv.to_vec(),
+            crypt_byte_block: 0,
+            skip_byte_block: 0,
+            psshs: Vec::new(),
+        }
+    }
+
+    /// `cbcs` with the usual pattern of 1 encrypted block in 10.
+    pub fn cbcs(kid: [u8; 16], key: [u8; 16], iv: [u8; 16]) -> Self {
+        Self {
+            scheme_type: SCHEME_CBCS,
+            kid,
+            key,
+            iv: iv.to_vec(),
+            crypt_byte_block: 1,
+            skip_byte_block: 9,
+            psshs: Vec::new(),
+        }
+    }
+}
+
+/// Decrypt `data` in place, given the scheme and pattern of the track, the
+/// IV and subsamples of the sample, and the key.
+pub fn decrypt_sample(
+    data: &mut [u8],
+    key: &[u8; 16],
+    scheme_type: FourCC,
+    tenc: &TencBox,
+    entry: &SampleEncryptionEntry,
+) -> Result<()> {
+    crypt_sample(data, key, scheme_type, tenc, entry, false)
+}
+
+/// Encrypt `data` in place, the reverse of [decrypt_sample].
+pub fn encrypt_sample(
+    data: &mut [u8],
+    key: &[u8; 16],
+    scheme_type: FourCC,
+    tenc: &TencBox,
+    entry: &SampleEncryptionEntry,
+) -> Result<()> {
+    crypt_sample(data, key, scheme_type, tenc, entry, true)
+}
+
+fn crypt_sample(
+    data: &mut [u8],
+    key: &[u8; 16],
+    scheme_type: FourCC,
+    tenc: &TencBox,
+    entry: &SampleEncryptionEntry,
+    encrypt: bool,
+) -> Result<()> {
+    let cipher = Aes128::new(key.into());
+    let ranges = protected_ranges(data.len(), &entry.subsamples)?;
+    if scheme_type == SCHEME_CENC {
+        // CTR mode is its own inverse.
+        ctr(&cipher, &entry.iv, data, &ranges)
+    } else if scheme_type == SCHEME_CBCS {
+        cbc_pattern(
+            &cipher,
+            &entry.iv,
+            data,
+            &ranges,
+            (tenc.default_crypt_byte_block, tenc.default_skip_byte_block),
+            encrypt,
+        )
+    } else {
+        Err(Error::InvalidData("unsupported protection scheme"))
+    }
+}
+
+/// Byte ranges of a sample covered by encryption.
+fn protected_ranges(size: usize, subsamples: &[SubsampleEntry]) -> Result<Vec<Range<usize>>> {
+    if subsamples.is_empty() {
+        return Ok(std::iter::once(0..size).collect());
+    }
+
+    let mut ranges = Vec::with_capacity(subsamples.len());
+    let mut pos = 0usize;
+    for subsample in subsamples.iter() {
+        let start = pos + subsample.bytes_of_clear_data as usize;
+        pos = start + subsample.bytes_of_protected_data as usize;
+        ranges.push(start..pos);
+    }
+    if pos != size {
+        return Err(Error::InvalidData(
+            "subsamples do not match the sample size",
+        ));
+    }
+    Ok(ranges)
+}
+
+/// AES-CTR over the concatenation of `ranges`. An 8 byte IV is padded with
+/// a zero block counter.
+fn ctr(cipher: &Aes128, iv: &[u8], data: &mut [u8], ranges: &[Range<usize>]) -> Result<()> {
+    if iv.len() != 8 && iv.len() != BLOCK_SIZE {
+        return Err(Error::InvalidData("cenc iv must be 8 or 16 bytes"));
+    }
+    let mut counter = [0u8; BLOCK_SIZE];
+    counter[..iv.len()].copy_from_slice(iv);
+
+    let mut keystream = Block::default();
+    let mut used = BLOCK_SIZE;
+    for range in ranges.iter() {
+        for byte in data[range.clone()].iter_mut() {
+            if used == BLOCK_SIZE {
+                keystream.copy_from_slice(&counter);
+                cipher.encrypt_block(&mut keystream);
+                increment_counter(&mut counter);
+                used = 0;
+            }
+            *byte ^= keystream[used];
+            used += 1;
+        }
+    }
+    Ok(())
+}
+
+fn increment_counter(counter: &mut [u8; BLOCK_SIZE]) {
+    let low = u64::from_be_bytes(counter[8..].try_into().unwrap());
+    counter[8..].copy_from_slice(&low.wrapping_add(1).to_be_bytes());
+}
+
+/// AES-CBC of every range, restarting from the IV. Within a range, only
+/// the first `crypt` of every `crypt + skip` blocks are encrypted, and a
+/// trailing partial block is left in the clear. A pattern of 0 encrypted
+/// blocks encrypts every block.
+fn cbc_pattern(
+    cipher: &Aes128,
+    iv: &[u8],
+    data: &mut [u8],
+    ranges: &[Range<usize>],
+    (crypt, skip): (u8, u8),
+    encrypt: bool,
+) -> Result<()> {
+    if iv.len() != BLOCK_SIZE {
+        return Err(Error::InvalidData("cbcs iv must be 16 bytes"));
+    }
+    let (crypt, skip) = if crypt == 0 {
+        (1, 0)
+    } else {
+        (crypt as usize, skip as usize)
+    };
+
+    for range in ranges.iter() {
+        let mut chain = Block::clone_from_slice(iv);
+        for (i, block) in data[range.clone()].chunks_exact_mut(BLOCK_SIZE).enumerate() {
+            if i % (crypt + skip) >= crypt {
+                continue;
+            }
+            let block = Block::from_mut_slice(block);
+            if encrypt {
+                xor_block(block, &chain);
+                cipher.encrypt_block(block);
+                chain.copy_from_slice(block);
+            } else {
+                let next = *block;
+                cipher.decrypt_block(block);
+                xor_block(block, &chain);
+                chain = next;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn xor_block(block: &mut Block, other: &Block) {
+    for (byte, other) in block.iter_mut().zip(other.iter()) {
+        *byte ^= other;
+    }
+}
+
+/// Encryption state of a track being written.
+#[derive(Debug)]
+pub(crate) struct SampleEncryptor {
+    config: EncryptionConfig,
+
+    /// NAL unit format and length size of AVC and HEVC tracks, which get a
+    /// subsample per NAL unit to keep their headers in the clear.
+    nal_format: Option<(NalFormat, usize)>,
+    tenc: TencBox,
+    next_iv: Vec<u8>,
+}
+
+impl SampleEncryptor {
+    pub(crate) fn new(config: &EncryptionConfig, trak: &TrakBox) -> Result<Self> {
+        let stsd = &trak.mdia.minf.stbl.stsd;
+        let nal_format = sample_entry_format(stsd);
+
+        let tenc = if config.scheme_type == SCHEME_CENC {
+            if config.iv.len() != 8 && config.iv.len() != BLOCK_SIZE {
+                return Err(Error::InvalidData("cenc iv must be 8 or 16 bytes"));
+            }
+            TencBox {
+                default_is_protected: 1,
+                default_per_sample_iv_size: config.iv.len() as u8,
+                default_kid: config.kid,
+                ..TencBox::default()
+            }
+        } else if config.scheme_type == SCHEME_CBCS {
+            if config.iv.len() != BLOCK_SIZE {
+                return Err(Error::InvalidData("cbcs iv must be 16 bytes"));
+            }
+            let (crypt_byte_block, skip_byte_block) = if trak.mdia.minf.vmhd.is_some() {
+                (config.crypt_byte_block, config.skip_byte_block)
+            } else {
+                (0, 0)
+            };
+            TencBox {
+                version: 1,
+                default_crypt_byte_block: crypt_byte_block,
+                default_skip_byte_block: skip_byte_block,
+                default_is_protected: 1,
+                default_per_sample_iv_size: 0,
+                default_kid: config.kid,
+                default_constant_iv: Some(config.iv.clone()),
+                ..TencBox::default()
+            }
+        } else {
+            return Err(Error::InvalidData("unsupported protection scheme"));
+        };
+
+        Ok(Self {
+            config: config.clone(),
+            nal_format,
+            tenc,
+            next_iv: config.iv.clone(),
+        })
+    }
+
+    pub(crate) fn psshs(&self) -> &[PsshBox] {
+        &self.config.psshs
+    }
+
+    /// Protection scheme of a sample entry of type `original_format`.
+    pub(crate) fn sinf(&self, original_format: FourCC) -> SinfBox {
+        SinfBox {
+            frma: FrmaBox { original_format },
+            schm: Some(SchmBox {
+                scheme_type: self.config.scheme_type,
+                scheme_version: 0x0001_0000,
+                ..SchmBox::default()
+            }),
+            schi: Some(SchiBox {
+                tenc: Some(self.tenc.clone()),
+                unknown_boxes: Vec::new(),
+                child_order: ChildOrder::default(),
+            }),
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
+        }
+    }
+
+    /// Encrypt a sample in place, returning its `senc` entry.
+    pub(crate) fn encrypt(&mut self, data: &mut [u8]) -> Result<SampleEncryptionEntry> {
+        let subsamples = match self.nal_format {
+            Some((format, length_size)) => self.subsamples(data, format, length_size)?,
+            None => Vec::new(),
+        };
+        let mut entry = SampleEncryptionEntry {
+            iv: self.next_iv.clone(),
+            subsamples,
+        };
+        encrypt_sample(
+            data,
+            &self.config.key,
+            self.config.scheme_type,
+            &self.tenc,
+            &entry,
+        )?;
+
+        if self.config.scheme_type == SCHEME_CBCS {
+            // The constant IV is stored in tenc only.
+            entry.iv.clear();
+        } else if self.next_iv.len() == 8 {
+            let iv = u64::from_be_bytes(self.next_iv[..].try_into().unwrap());
+            self.next_iv = iv.wrapping_add(1).to_be_bytes().to_vec();
+        } else {
+            // Skip the counter blocks used by this sample.
+            let protected: u64 = protected_ranges(data.len(), &entry.subsamples)?
+                .iter()
+                .map(|range| range.len() as u64)
+                .sum();
+            let iv = u128::from_be_bytes(self.next_iv[..].try_into().unwrap());
+            let blocks = protected.div_ceil(BLOCK_SIZE as u64) as u128;
+            self.next_iv = iv.wrapping_add(blocks).to_be_bytes().to_vec();
+        }
+        Ok(entry)
+    }
+
+    /// One subsample per NAL unit, protecting the payload of VCL NAL units
+    /// only. With `cenc` the protected part is a whole number of blocks.
+    fn subsamples(
+        &self,
+        data: &[u8],
+        format: NalFormat,
+        length_size: usize,
+    ) -> Result<Vec<SubsampleEntry>> {
+        let header_size = format.header_size();
+
+        let mut subsamples = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            if pos + length_size > data.len() {
+                return Err(Error::InvalidData("truncated NAL unit length"));
+            }
+            let nal_size = data[pos..pos + length_size]
+                .iter()
+                .fold(0usize, |size, byte| size << 8 | *byte as usize);
+            let nal_start = pos + length_size;
+            let end = nal_start + nal_size;
+            if end > data.len() {
+                return Err(Error::InvalidData("NAL unit length exceeds the sample"));
+            }
+
+            let is_vcl = nal_size > header_size && format.is_vcl(&data[nal_start..end]);
+            let mut protected = 0;
+            if is_vcl {
+                protected = nal_size - header_size;
+                if self.config.scheme_type == SCHEME_CENC {
+                    protected -= protected % BLOCK_SIZE;
+                }
+            }
+
+            // Clear runs too long for a subsample are split off with
+            // nothing protected.
+            let mut clear = end - pos - protected;
+            while clear > u16::MAX as usize {
+                subsamples.push(SubsampleEntry {
+                    bytes_of_clear_data: u16::MAX,
+                    bytes_of_protected_data: 0,
+                });
+                clear -= u16::MAX as usize;
+            }
+            subsamples.push(SubsampleEntry {
+                bytes_of_clear_data: clear as u16,
+                bytes_of_protected_data: protected as u32,
+            });
+            pos = end;
+        }
+        Ok(subsamples)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    // NIST SP 800-38A, F.5.1 CTR-AES128.Encrypt.
+    #[test]
+    fn test_cenc_ctr_vector() {
+        let key: [u8; 16] = hex("2b7e151628aed2a6abf7158809cf4f3c")[..]
+            .try_into()
+            .unwrap();
+        let entry = SampleEncryptionEntry {
+            iv: hex("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff"),
+            subsamples: Vec::new(),
+        };
+        let plaintext = hex(concat!(
+            "6bc1bee22e409f96e93d7e117393172a",
+            "ae2d8a571e03ac9c9eb76fac45af8e51"
+        ));
+        let ciphertext = hex(concat!(
+            "874d6191b620e3261bef6864990db6ce",
+            "9806f66b7970fdff8617187bb9fffdff"
+        ));
+
+        let mut data = plaintext.clone();
+        let tenc = TencBox::default();
+        encrypt_sample(&mut data, &key, SCHEME_CENC, &tenc, &entry).unwrap();
+        assert_eq!(data, ciphertext);
+        decrypt_sample(&mut data, &key, SCHEME_CENC, &tenc, &entry).unwrap();
+        assert_eq!(data, plaintext);
+    }
+
+    // NIST SP 800-38A, F.2.1 CBC-AES128.Encrypt, as a 2:0 pattern.
+    #[test]
+    fn test_cbcs_cbc_vector() {
+        let key: [u8; 16] = hex("2b7e151628aed2a6abf7158809cf4f3c")[..]
+            .try_into()
+            .unwrap();
+        let entry = SampleEncryptionEntry {
+            iv: hex("000102030405060708090a0b0c0d0e0f"),
+            subsamples: Vec::new(),
+        };
+        let tenc = TencBox {
+            version: 1,
+            default_crypt_byte_block: 2,
+            ..TencBox::default()
+        };
+        let plaintext = hex(concat!(
+            "6bc1bee22e409f96e93d7e117393172a",
+            "ae2d8a571e03ac9c9eb76fac45af8e51",
+            "0102"
+        ));
+        let ciphertext = hex(concat!(
+            "7649abac8119b246cee98e9b12e9197d",
+            "5086cb9b507219ee95db113a917678b2",
+            "0102"
+        ));
+
+        let mut data = plaintext.clone();
+        encrypt_sample(&mut data, &key, SCHEME_CBCS, &tenc, &entry).unwrap();
+        assert_eq!(data, ciphertext);
+        decrypt_sample(&mut data, &key, SCHEME_CBCS, &tenc, &entry).unwrap();
+        assert_eq!(data, plaintext);
+    }
+
+    #[test]
+    fn test_cbcs_pattern_subsamples() {
+        let key = [0x42; 16];
+        let entry = SampleEncryptionEntry {
+            iv: vec![0x24; 16],
+            subsamples: vec![
+                SubsampleEntry {
+                    bytes_of_clear_data: 5,
+                    bytes_of_protected_data: 100,
+                },
+                SubsampleEntry {
+                    bytes_of_clear_data: 3,
+                    bytes_of_protected_data: 40,
+                },
+            ],
+        };
+        let tenc = TencBox {
+            version: 1,
+            default_crypt_byte_block: 1,
+            default_skip_byte_block: 2,
+            ..TencBox::default()
+        };
+        let plaintext: Vec<u8> = (0..148).map(|i| i as u8).collect();
+
+        let mut data = plaintext.clone();
+        encrypt_sample(&mut data, &key, SCHEME_CBCS, &tenc, &entry).unwrap();
+        // Clear bytes, skipped blocks and partial blocks are untouched.
+        assert_eq!(data[..5], plaintext[..5]);
+        assert_ne!(data[5..21], plaintext[5..21]);
+        assert_eq!(data[21..53], plaintext[21..53]);
+        assert_ne!(data[53..69], plaintext[53..69]);
+        assert_eq!(data[69..108], plaintext[69..108]);
+        assert_ne!(data[108..124], plaintext[108..124]);
+        assert_eq!(data[124..], plaintext[124..]);
+
+        decrypt_sample(&mut data, &key, SCHEME_CBCS, &tenc, &entry).unwrap();
+        assert_eq!(data, plaintext);
+
+        let mut data = plaintext[..147].to_vec();
+        assert!(decrypt_sample(&mut data, &key, SCHEME_CBCS, &tenc, &entry).is_err());
+    }
+}
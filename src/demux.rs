@@ -0,0 +1,388 @@
+//! Extraction of the elementary stream of a track, in the formats encoders
+//! and decoders exchange outside of MP4:
+//!
+//! * H.264 and HEVC as Annex-B, with the parameter sets before IDR pictures.
+//! * AAC as ADTS.
+//! * VP9 as IVF.
+//! * Opus as Ogg (RFC 7845).
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::io::Write;
+
+use crate::nal::{self, NalFormat};
+use crate::*;
+
+const ADTS_HEADER_SIZE: usize = 7;
+const IVF_HEADER_SIZE: u16 = 32;
+const OGG_MAX_SEGMENTS: usize = 255;
+const OGG_BOS: u8 = 0x02;
+const OGG_EOS: u8 = 0x04;
+const OGG_CONTINUED: u8 = 0x01;
+const OPUS_RATE: u64 = 48000;
+
+#[derive(Debug)]
+enum Stream {
+    AnnexB {
+        format: NalFormat,
+        length_size: usize,
+        parameter_sets: Vec<Vec<u8>>,
+    },
+    Adts {
+        profile: u8,
+        freq_index: u8,
+        chan_conf: u8,
+    },
+    Ivf,
+    Ogg {
+        serial: u32,
+        sequence: u32,
+        timescale: u32,
+        /// 48 kHz samples up to the end of the packet written last.
+        granule: u64,
+        /// Held back to flag the last page of the stream on finish.
+        pending: Option<(Vec<u8>, u64)>,
+    },
+}
+
+/// Writer of the elementary stream of one track.
+///
+/// Samples are passed in decoding order, as read by
+/// [Mp4Reader::read_sample]. Encrypted samples must be decrypted first.
+#[derive(Debug)]
+pub struct ElementaryStreamWriter<W> {
+    writer: W,
+    stream: Stream,
+    extension: &'static str,
+}
+
+impl<W: Write> ElementaryStreamWriter<W> {
+    /// Start the stream of `track`, writing the IVF or Ogg headers if any.
+    pub fn new(track: &Mp4Track, mut writer: W) -> Result<Self> {
+        let stsd = &track.trak.mdia.minf.stbl.stsd;
+        let (stream, extension) = match track.media_type()? {
+            media_type @ (MediaType::H264 | MediaType::H265) => {
+                let (format, length_size) = track.nal_format()?;
+                let parameter_sets = track
+                    .parameter_sets()?
+                    .into_iter()
+                    .map(|nal| nal.to_vec())
+                    .collect();
+                let stream = Stream::AnnexB {
+                    format,
+                    length_size,
+                    parameter_sets,
+                };
+                let extension = if media_type == MediaType::H264 {
+                    "h264"
+                } else {
+                    "h265"
+                };
+                (stream, extension)
+            }
+            MediaType::AAC => {
+                let esds = stsd
+                    .mp4a
+                    .as_ref()
+                    .and_then(|mp4a| mp4a.esds.as_ref())
+                    .ok_or_else(|| {
+                        Error::BoxInStblNotFound(track.track_id(), vec![BoxType::EsdsBox])
+                    })?;
+                let dec_specific = &esds.es_desc.dec_config.dec_specific;
+                let profile = match dec_specific.profile {
+                    profile @ 1..=4 => profile - 1,
+                    // HE-AAC, signalled implicitly over an AAC-LC core.
+                    5 | 29 => 1,
+                    _ => {
+                        return Err(Error::InvalidData(
+                            "audio object type cannot be stored in ADTS",
+                        ))
+                    }
+                };
+                if dec_specific.freq_index > 12 {
+                    return Err(Error::InvalidData(
+                        "explicit sample rate cannot be stored in ADTS",
+                    ));
+                }
+                if dec_specific.chan_conf == 0 || dec_specific.chan_conf > 7 {
+                    return Err(Error::InvalidData(
+                        "channel configuration cannot be stored in ADTS",
+                    ));
+                }
+                let stream = Stream::Adts {
+                    profile,
+                    freq_index: dec_specific.freq_index,
+                    chan_conf: dec_specific.chan_conf,
+                };
+                (stream, "aac")
+            }
+            MediaType::VP9 => {
+                let vp09 = stsd.vp09.as_ref().unwrap();
+                writer.write_all(b"DKIF")?;
+                writer.write_u16::<LittleEndian>(0)?; // version
+                writer.write_u16::<LittleEndian>(IVF_HEADER_SIZE)?;
+                writer.write_all(b"VP90")?;
+                writer.write_u16::<LittleEndian>(vp09.width)?;
+                writer.write_u16::<LittleEndian>(vp09.height)?;
+                // Timestamps are in the timescale of the track.
+                writer.write_u32::<LittleEndian>(track.timescale())?;
+                writer.write_u32::<LittleEndian>(1)?;
+                writer.write_u32::<LittleEndian>(track.sample_count())?;
+                writer.write_u32::<LittleEndian>(0)?; // unused
+                (Stream::Ivf, "ivf")
+            }
+            MediaType::OPUS => {
+                let dops = stsd
+                    .opus
+                    .as_ref()
+                    .and_then(|opus| opus.dops_box.as_ref())
+                    .ok_or_else(|| {
+                        Error::BoxInStblNotFound(track.track_id(), vec![BoxType::DopsBox])
+                    })?;
+                let mut stream = Stream::Ogg {
+                    serial: track.track_id(),
+                    sequence: 0,
+                    timescale: track.timescale(),
+                    granule: 0,
+                    pending: None,
+                };
+
+                let mut head = Vec::with_capacity(21 + dops.output_channel_count as usize);
+                head.extend_from_slice(b"OpusHead");
+                head.push(1); // version
+                head.push(dops.output_channel_count);
+                head.write_u16::<LittleEndian>(dops.pre_skip)?;
+                head.write_u32::<LittleEndian>(dops.input_sample_rate)?;
+                head.write_i16::<LittleEndian>(dops.output_gain)?;
+                head.push(dops.channel_mapping_family);
+                if let Some(ref table) = dops.channel_mapping_table {
+                    head.push(table.stream_count);
+                    head.push(table.coupled_count);
+                    head.extend_from_slice(&table.channel_mapping);
+                }
+                write_ogg_packet(&mut writer, &mut stream, &head, 0, OGG_BOS)?;
+
+                let vendor = b"mp4-rust";
+                let mut tags = Vec::with_capacity(20 + vendor.len());
+                tags.extend_from_slice(b"OpusTags");
+                tags.write_u32::<LittleEndian>(vendor.len() as u32)?;
+                tags.extend_from_slice(vendor);
+                tags.write_u32::<LittleEndian>(0)?; // user comments
+                write_ogg_packet(&mut writer, &mut stream, &tags, 0, 0)?;
+
+                (stream, "opus")
+            }
+            _ => {
+                return Err(Error::InvalidData(
+                    "no elementary stream format for this media type",
+                ))
+            }
+        };
+
+        Ok(Self {
+            writer,
+            stream,
+            extension,
+        })
+    }
+
+    /// File extension of the stream: `h264`, `h265`, `aac`, `ivf` or `opus`.
+    pub fn extension(&self) -> &'static str {
+        self.extension
+    }
+
+    pub fn write_sample(&mut self, sample: &Mp4Sample) -> Result<()> {
+        if sample.encryption.is_some() {
+            return Err(Error::InvalidData("cannot demux an encrypted sample"));
+        }
+
+        match self.stream {
+            Stream::AnnexB {
+                format,
+                length_size,
+                ref parameter_sets,
+            } => {
+                let parameter_sets: Vec<&[u8]> =
+                    parameter_sets.iter().map(|nal| nal.as_ref()).collect();
+                let annexb = nal::to_annexb_with_parameter_sets(
+                    &sample.bytes,
+                    length_size,
+                    format,
+                    &parameter_sets,
+                )?;
+                self.writer.write_all(&annexb)?;
+            }
+            Stream::Adts {
+                profile,
+                freq_index,
+                chan_conf,
+            } => {
+                let frame_length = ADTS_HEADER_SIZE + sample.bytes.len();
+                if frame_length > 0x1fff {
+                    return Err(Error::InvalidData("AAC frame too large for ADTS"));
+                }
+                let header = [
+                    0xff,
+                    0xf1, // MPEG-4, no CRC
+                    profile << 6 | freq_index << 2 | chan_conf >> 2,
+                    (chan_conf & 0x3) << 6 | (frame_length >> 11) as u8,
+                    (frame_length >> 3) as u8,
+                    (frame_length as u8 & 0x7) << 5 | 0x1f, // buffer fullness 0x7ff
+                    0xfc,
+                ];
+                self.writer.write_all(&header)?;
+                self.writer.write_all(&sample.bytes)?;
+            }
+            Stream::Ivf => {
+                self.writer
+                    .write_u32::<LittleEndian>(sample.bytes.len() as u32)?;
+                self.writer.write_u64::<LittleEndian>(sample.start_time)?;
+                self.writer.write_all(&sample.bytes)?;
+            }
+            Stream::Ogg {
+                timescale,
+                ref mut granule,
+                ref mut pending,
+                ..
+            } => {
+                *granule += sample.duration as u64 * OPUS_RATE / timescale.max(1) as u64;
+                if let Some((packet, granule)) = pending.replace((sample.bytes.to_vec(), *granule))
+                {
+                    write_ogg_packet(&mut self.writer, &mut self.stream, &packet, granule, 0)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// End the stream and return the writer.
+    pub fn finish(mut self) -> Result<W> {
+        if let Stream::Ogg {
+            ref mut pending, ..
+        } = self.stream
+        {
+            let (packet, granule) = pending.take().unwrap_or_default();
+            write_ogg_packet(
+                &mut self.writer,
+                &mut self.stream,
+                &packet,
+                granule,
+                OGG_EOS,
+            )?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Write a packet on as many Ogg pages as its lacing needs. `flags` apply to
+/// its first page for [OGG_BOS] and to its last page for [OGG_EOS].
+fn write_ogg_packet<W: Write>(
+    writer: &mut W,
+    stream: &mut Stream,
+    packet: &[u8],
+    granule: u64,
+    flags: u8,
+) -> Result<()> {
+    let (serial, sequence) = match stream {
+        Stream::Ogg {
+            serial,
+            ref mut sequence,
+            ..
+        } => (*serial, sequence),
+        _ => unreachable!(),
+    };
+
+    let mut rest = packet;
+    let mut header_type = flags & OGG_BOS;
+    loop {
+        // The packet ends on the first segment shorter than 255 bytes.
+        let ends = rest.len() < OGG_MAX_SEGMENTS * 255;
+        let data = if ends {
+            rest
+        } else {
+            &rest[..OGG_MAX_SEGMENTS * 255]
+        };
+        let mut lacing = vec![255u8; data.len() / 255];
+        if ends {
+            lacing.push((data.len() % 255) as u8);
+            header_type |= flags & OGG_EOS;
+        }
+
+        let mut page = Vec::with_capacity(27 + lacing.len() + data.len());
+        page.extend_from_slice(b"OggS");
+        page.push(0); // version
+        page.push(header_type);
+        // No packet ends on the page: -1.
+        page.write_u64::<LittleEndian>(if ends { granule } else { u64::MAX })?;
+        page.write_u32::<LittleEndian>(serial)?;
+        page.write_u32::<LittleEndian>(*sequence)?;
+        page.write_u32::<LittleEndian>(0)?; // CRC, over the page with 0 in place
+        page.push(lacing.len() as u8);
+        page.extend_from_slice(&lacing);
+        page.extend_from_slice(data);
+        let crc = ogg_crc(&page);
+        page[22..26].copy_from_slice(&crc.to_le_bytes());
+        writer.write_all(&page)?;
+
+        *sequence += 1;
+        if ends {
+            return Ok(());
+        }
+        rest = &rest[data.len()..];
+        header_type = OGG_CONTINUED;
+    }
+}
+
+/// CRC-32 of Ogg pages: polynomial 0x04c11db7, not reflected, starting from
+/// 0 and without a final XOR.
+fn ogg_crc(data: &[u8]) -> u32 {
+    let mut crc = 0u32;
+    for byte in data {
+        crc ^= (*byte as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                crc << 1 ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ogg_crc() {
+        assert_eq!(ogg_crc(b"123456789"), 0x89a1_897f);
+    }
+
+    #[test]
+    fn test_ogg_lacing() {
+        let mut stream = Stream::Ogg {
+            serial: 1,
+            sequence: 0,
+            timescale: 48000,
+            granule: 0,
+            pending: None,
+        };
+
+        // 255 * 255 bytes need a 256th, empty segment on a second page.
+        let mut buf = Vec::new();
+        let packet = vec![0x5a; 255 * 255];
+        write_ogg_packet(&mut buf, &mut stream, &packet, 960, OGG_EOS).unwrap();
+        let second = 27 + 255 + packet.len();
+        assert_eq!(buf.len(), second + 27 + 1);
+
+        assert_eq!(buf[5], 0);
+        assert_eq!(buf[6..14], u64::MAX.to_le_bytes());
+        assert_eq!(buf[26], 255);
+        assert_eq!(&buf[second..second + 4], b"OggS");
+        assert_eq!(buf[second + 5], OGG_CONTINUED | OGG_EOS);
+        assert_eq!(buf[second + 6..second + 14], 960u64.to_le_bytes());
+        assert_eq!(buf[second + 18..second + 22], 1u32.to_le_bytes());
+        assert_eq!(buf[second + 26..], [1, 0]);
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::Write;
+use std::time::Duration;
+
+use crate::mp4box::*;
+use crate::track::Mp4FragmentTrackWriter;
+use crate::*;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FragmentConfig {
+    /// Minimum duration of a fragment, measured on the leading track (the
+    /// first video track, or the first track if there is no video).
+    pub fragment_duration: Duration,
+
+    /// Only start a new fragment on a sync sample of the leading track.
+    pub split_on_sync: bool,
+}
+
+impl Default for FragmentConfig {
+    fn default() -> Self {
+        Self {
+            fragment_duration: Duration::from_secs(2),
+            split_on_sync: true,
+        }
+    }
+}
+
+/// Writer for fragmented MP4 (fMP4), as used by DASH and HLS.
+///
+/// The output starts with an init segment (`ftyp` + `moov` with an `mvex`
+/// describing every track), followed by `moof`+`mdat` pairs. Fragment sizes
+/// are known before they are written, so the underlying writer does not need
+/// to be seekable.
+#[derive(Debug)]
+pub struct Mp4FragmentWriter<W> {
+    writer: W,
+    tracks: HashMap<u32, Mp4FragmentTrackWriter>,
+    fragment_config: FragmentConfig,
+    timescale: u32,
+    sequence_number: u32,
+    init_written: bool,
+}
+
+impl<W> Mp4FragmentWriter<W> {
+    /// Consume self, returning the inner writer.
+    pub fn into_writer(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Mp4FragmentWriter<W> {
+    /// Write the `ftyp` box and return a writer ready to accept tracks.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mp4::{FragmentConfig, Mp4Config, Mp4FragmentWriter};
+    ///
+    /// # fn main() -> mp4::Result<()> {
+    /// let config = Mp4Config {
+    ///     major_brand: str::parse("iso6").unwrap(),
+    ///     minor_version: 0,
+    ///     compatible_brands: vec![
+    ///         str::parse("iso6").unwrap(),
+    ///         str::parse("dash").unwrap(),
+    ///     ],
+    ///     timescale: 1000,
+    /// };
+    ///
+    /// let mut writer =
+    ///     Mp4FragmentWriter::write_start(Vec::new(), &config, &FragmentConfig::default())?;
+    /// writer.write_end()?;
+    ///
+    /// let data: Vec<u8> = writer.into_writer();
+    /// # Ok(()) }
+    /// ```
+    pub fn write_start(
+        mut writer: W,
+        config: &Mp4Config,
+        fragment_config: &FragmentConfig,
+    ) -> Result<Self> {
+        let ftyp = FtypBox {
+            major_brand: config.major_brand,
+            minor_version: config.minor_version,
+            compatible_brands: config.compatible_brands.clone(),
+        };
+        ftyp.write_box(&mut writer)?;
+
+        Ok(Self {
+            writer,
+            tracks: HashMap::new(),
+            fragment_config: fragment_config.clone(),
+            timescale: config.timescale,
+            sequence_number: 1,
+            init_written: false,
+        })
+    }
+
+    pub fn add_track(&mut self, config: &TrackConfig) -> Result<u32> {
+        if self.init_written {
+            return Err(Error::InvalidData(
+                "tracks must be added before the init segment is written",
+            ));
+        }
+        let track_id = match config.track_id {
+            Some(track_id) => track_id,
+            None => self.tracks.len() as u32 + 1,
+        };
+        if track_id == 0 {
+            return Err(Error::InvalidData("illegal track id 0"));
+        }
+        if self.tracks.contains_key(&track_id) {
+            return Err(Error::InvalidData("track_id already exists"));
+        }
+        let track = Mp4FragmentTrackWriter::new(track_id, config)?;
+        self.tracks.insert(track_id, track);
+        Ok(track_id)
+    }
+
+    pub fn track_ids(&self) -> Vec<u32> {
+        self.tracks.keys().cloned().collect()
+    }
+
+    /// Encrypt the samples of a track with Common Encryption. This must be
+    /// done before the init segment is written.
+    ///
+    /// The sample entry is stored as `encv` or `enca` with a `sinf`, the
+    /// `pssh` boxes of `config` go into the `moov`, and every fragment gets
+    /// `senc`, `saiz` and `saio` boxes with the IV and subsamples of each
+    /// sample. AVC and HEVC samples are split into a subsample per NAL unit,
+    /// keeping NAL unit headers and non-VCL NAL units in the clear.
+    pub fn set_track_encryption(&mut self, track_id: u32, config: &EncryptionConfig) -> Result<()> {
+        if self.init_written {
+            return Err(Error::InvalidData(
+                "encryption must be set before the init segment is written",
+            ));
+        }
+        match self.tracks.get_mut(&track_id) {
+            Some(track) => track.set_encryption(config),
+            None => Err(Error::TrakNotFound(track_id)),
+        }
+    }
+
+    /// Write the `moov` box completing the init segment.
+    ///
+    /// This is done implicitly by the first call to [Self::write_sample] or
+    /// [Self::write_end]; calling it explicitly is only needed to emit the
+    /// init segment before any media is available.
+    pub fn write_init_segment(&mut self) -> Result<()> {
+        if self.init_written {
+            return Ok(());
+        }
+
+        let mut moov = MoovBox::default();
+        moov.mvhd.timescale = self.timescale;
+        moov.mvhd.next_track_id = self.tracks.keys().max().copied().unwrap_or(0) + 1;
+
+        let mut mvex = MvexBox::default();
+        for track_id in self.sorted_track_ids() {
+            let track = &self.tracks[&track_id];
+            moov.traks.push(track.trak().clone());
+            mvex.trexs.push(track.trex());
+            for pssh in track.psshs() {
+                if !moov.psshs.contains(pssh) {
+                    moov.psshs.push(pssh.clone());
+                }
+            }
+        }
+        moov.mvex = Some(mvex);
+
+        moov.write_box(&mut self.writer)?;
+        self.init_written = true;
+        Ok(())
+    }
+
+    pub fn write_sample(&mut self, track_id: u32, sample: &Mp4Sample) -> Result<()> {
+        if !self.tracks.contains_key(&track_id) {
+            return Err(Error::TrakNotFound(track_id));
+        }
+        self.write_init_segment()?;
+
+        if self.is_fragment_boundary(track_id, sample) {
+            self.flush_fragment()?;
+        }
+
+        if let Some(track) = self.tracks.get_mut(&track_id) {
+            track.write_sample(sample)?;
+        }
+        Ok(())
+    }
+
+    /// Write the buffered samples of every track as a `moof`+`mdat` pair.
+    pub fn flush_fragment(&mut self) -> Result<()> {
+        self.write_init_segment()?;
+
+        let mut trafs = Vec::new();
+        let mut chunks = Vec::new();
+        for track_id in self.sorted_track_ids() {
+            if let Some(track) = self.tracks.get_mut(&track_id) {
+                if let Some((traf, chunk)) = track.take_fragment() {
+                    trafs.push(traf);
+                    chunks.push(chunk);
+                }
+            }
+        }
+        if trafs.is_empty() {
+            return Ok(());
+        }
+
+        let mut moof = MoofBox {
+            mfhd: MfhdBox {
+                sequence_number: self.sequence_number,
+                ..MfhdBox::default()
+            },
+            trafs,
+            ..MoofBox::default()
+        };
+
+        set_aux_info_offsets(&mut moof);
+
+        let data_size: u64 = chunks.iter().map(|chunk| chunk.len() as u64).sum();
+        let mut mdat_size = HEADER_SIZE + data_size;
+        if mdat_size > u32::MAX as u64 {
+            // largesize
+            mdat_size += 8;
+        }
+        let mut data_offset = moof.box_size() + (mdat_size - data_size);
+        for (traf, chunk) in moof.trafs.iter_mut().zip(chunks.iter()) {
+            for trun in traf.truns.iter_mut() {
+                trun.data_offset =
+                    Some(i32::try_from(data_offset).map_err(|_| {
+                        Error::InvalidData("fragment too large for trun data offset")
+                    })?);
+            }
+            data_offset += chunk.len() as u64;
+        }
+
+        moof.write_box(&mut self.writer)?;
+        BoxHeader::new(BoxType::MdatBox, mdat_size).write(&mut self.writer)?;
+        for chunk in chunks.iter() {
+            self.writer.write_all(chunk)?;
+        }
+
+        self.sequence_number += 1;
+        Ok(())
+    }
+
+    /// Flush the last fragment. The init segment is written if no samples
+    /// were written.
+    pub fn write_end(&mut self) -> Result<()> {
+        self.flush_fragment()?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn sorted_track_ids(&self) -> Vec<u32> {
+        let mut track_ids = self.track_ids();
+        track_ids.sort_unstable();
+        track_ids
+    }
+
+    fn lead_track_id(&self) -> Option<u32> {
+        let track_ids = self.sorted_track_ids();
+        track_ids
+            .iter()
+            .find(|track_id| self.tracks[*track_id].is_video())
+            .or_else(|| track_ids.first())
+            .copied()
+    }
+
+    fn is_fragment_boundary(&self, track_id: u32, sample: &Mp4Sample) -> bool {
+        if self.lead_track_id() != Some(track_id) {
+            return false;
+        }
+        if self.fragment_config.split_on_sync && !sample.is_sync {
+            return false;
+        }
+        let track = &self.tracks[&track_id];
+        !track.is_empty() && track.buffered_duration() >= self.fragment_config.fragment_duration
+    }
+}
+
+/// Point the `saio` of every `traf` at the entries of its `senc`, relative
+/// to the start of the `moof`.
+fn set_aux_info_offsets(moof: &mut MoofBox) {
+    let mut traf_offset = HEADER_SIZE + moof.mfhd.box_size();
+    for traf in moof.trafs.iter_mut() {
+        let traf_size = traf.box_size();
+        if let (Some(saio), Some(senc)) = (traf.saio.as_mut(), traf.senc.as_ref()) {
+            let mut senc_offset = traf_offset + HEADER_SIZE + traf.tfhd.box_size();
+            if let Some(ref tfdt) = traf.tfdt {
+                senc_offset += tfdt.box_size();
+            }
+            senc_offset += traf.truns.iter().map(|trun| trun.box_size()).sum::<u64>();
+            if let Some(ref saiz) = traf.saiz {
+                senc_offset += saiz.box_size();
+            }
+            senc_offset += saio.box_size();
+
+            // Skip the senc header and sample count.
+            let offset = senc_offset + senc.box_size() - senc.data.len() as u64;
+            saio.offsets = vec![offset];
+        }
+        traf_offset += traf_size;
+    }
+}
@@ -0,0 +1,413 @@
+//! H.264 sequence parameter set parsing (ITU-T H.264, 7.3.2.1.1 and E.1.1).
+
+use std::convert::TryFrom;
+
+use crate::nal::{rbsp, AnnexBNalUnits, BitReader};
+use crate::*;
+
+const NAL_TYPE_SPS: u8 = 7;
+const NAL_TYPE_PPS: u8 = 8;
+
+/// Sample aspect ratios of `aspect_ratio_idc` 1 to 16 (Table E-1).
+const SAMPLE_ASPECT_RATIOS: [(u16, u16); 16] = [
+    (1, 1),
+    (12, 11),
+    (10, 11),
+    (16, 11),
+    (40, 33),
+    (24, 11),
+    (20, 11),
+    (32, 11),
+    (80, 33),
+    (18, 11),
+    (15, 11),
+    (64, 33),
+    (160, 99),
+    (4, 3),
+    (3, 2),
+    (2, 1),
+];
+
+/// The fields of an H.264 SPS needed to describe a track.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AvcSps {
+    pub profile_idc: u8,
+    /// `constraint_set0_flag` to `constraint_set5_flag` and the reserved
+    /// bits, as stored in `avcC`.
+    pub profile_compatibility: u8,
+    pub level_idc: u8,
+    pub seq_parameter_set_id: u32,
+    pub chroma_format_idc: u8,
+    pub separate_colour_plane: bool,
+    pub bit_depth_luma_minus8: u8,
+    pub bit_depth_chroma_minus8: u8,
+    pub frame_mbs_only: bool,
+    pub log2_max_frame_num: u8,
+    pub pic_order_cnt_type: u8,
+    /// Only set for `pic_order_cnt_type` 0.
+    pub log2_max_pic_order_cnt_lsb: u8,
+
+    /// Dimensions of the decoded pictures after cropping.
+    pub width: u32,
+    pub height: u32,
+
+    /// Sample aspect ratio, when signalled in the VUI.
+    pub sample_aspect_ratio: Option<(u16, u16)>,
+
+    /// `num_units_in_tick` and `time_scale` of the VUI timing information.
+    pub timing: Option<(u32, u32)>,
+    pub fixed_frame_rate: bool,
+}
+
+impl AvcSps {
+    /// Parse an SPS NAL unit, starting with its NAL unit header.
+    pub fn parse(nal: &[u8]) -> Result<Self> {
+        match nal.first() {
+            Some(header) if header & 0x1f == NAL_TYPE_SPS => {}
+            _ => return Err(Error::InvalidData("not an H.264 SPS")),
+        }
+        let rbsp = rbsp(&nal[1..]);
+        let mut reader = BitReader::new(&rbsp);
+
+        let mut sps = AvcSps {
+            profile_idc: reader.read_bits(8)? as u8,
+            profile_compatibility: reader.read_bits(8)? as u8,
+            level_idc: reader.read_bits(8)? as u8,
+            seq_parameter_set_id: reader.read_ue()?,
+            chroma_format_idc: 1,
+            ..AvcSps::default()
+        };
+
+        if sps.has_chroma_info() {
+            sps.chroma_format_idc = read_small_ue(&mut reader, 3)?;
+            if sps.chroma_format_idc == 3 {
+                sps.separate_colour_plane = reader.read_bit()?;
+            }
+            sps.bit_depth_luma_minus8 = read_small_ue(&mut reader, 6)?;
+            sps.bit_depth_chroma_minus8 = read_small_ue(&mut reader, 6)?;
+            reader.read_bit()?; // qpprime_y_zero_transform_bypass_flag
+            if reader.read_bit()? {
+                // seq_scaling_matrix_present_flag
+                let count = if sps.chroma_format_idc == 3 { 12 } else { 8 };
+                for i in 0..count {
+                    if reader.read_bit()? {
+                        skip_scaling_list(&mut reader, if i < 6 { 16 } else { 64 })?;
+                    }
+                }
+            }
+        }
+
+        sps.log2_max_frame_num = read_small_ue(&mut reader, 12)? + 4;
+        sps.pic_order_cnt_type = read_small_ue(&mut reader, 2)?;
+        match sps.pic_order_cnt_type {
+            0 => {
+                sps.log2_max_pic_order_cnt_lsb = read_small_ue(&mut reader, 12)? + 4;
+            }
+            1 => {
+                reader.read_bit()?; // delta_pic_order_always_zero_flag
+                reader.read_se()?; // offset_for_non_ref_pic
+                reader.read_se()?; // offset_for_top_to_bottom_field
+                let num_ref_frames_in_pic_order_cnt_cycle = reader.read_ue()?;
+                for _ in 0..num_ref_frames_in_pic_order_cnt_cycle {
+                    reader.read_se()?; // offset_for_ref_frame
+                }
+            }
+            _ => {}
+        }
+        reader.read_ue()?; // max_num_ref_frames
+        reader.read_bit()?; // gaps_in_frame_num_value_allowed_flag
+
+        let pic_width_in_mbs = reader.read_ue()? as u64 + 1;
+        let pic_height_in_map_units = reader.read_ue()? as u64 + 1;
+        sps.frame_mbs_only = reader.read_bit()?;
+        if !sps.frame_mbs_only {
+            reader.read_bit()?; // mb_adaptive_frame_field_flag
+        }
+        reader.read_bit()?; // direct_8x8_inference_flag
+
+        let frame_height_factor = if sps.frame_mbs_only { 1 } else { 2 };
+        let mut width = pic_width_in_mbs * 16;
+        let mut height = frame_height_factor * pic_height_in_map_units * 16;
+        if reader.read_bit()? {
+            // frame_cropping_flag
+            let (crop_unit_x, crop_unit_y) = match sps.chroma_array_type() {
+                0 => (1, frame_height_factor),
+                1 => (2, 2 * frame_height_factor),
+                2 => (2, frame_height_factor),
+                _ => (1, frame_height_factor),
+            };
+            let left = reader.read_ue()? as u64;
+            let right = reader.read_ue()? as u64;
+            let top = reader.read_ue()? as u64;
+            let bottom = reader.read_ue()? as u64;
+            width = width
+                .checked_sub(crop_unit_x * (left + right))
+                .ok_or(Error::InvalidData("SPS cropping exceeds the picture"))?;
+            height = height
+                .checked_sub(crop_unit_y * (top + bottom))
+                .ok_or(Error::InvalidData("SPS cropping exceeds the picture"))?;
+        }
+        sps.width =
+            u32::try_from(width).map_err(|_| Error::InvalidData("SPS picture too large"))?;
+        sps.height =
+            u32::try_from(height).map_err(|_| Error::InvalidData("SPS picture too large"))?;
+
+        if reader.read_bit()? {
+            // vui_parameters_present_flag
+            sps.read_vui(&mut reader)?;
+        }
+
+        Ok(sps)
+    }
+
+    /// Whether the profile stores chroma format and bit depths in the SPS.
+    fn has_chroma_info(&self) -> bool {
+        matches!(
+            self.profile_idc,
+            100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135
+        )
+    }
+
+    /// Whether the profile needs the `avcC` high profile extension, which
+    /// ISO/IEC 14496-15 requires for every profile but Baseline, Main and
+    /// Extended.
+    pub fn has_high_profile_ext(&self) -> bool {
+        !matches!(self.profile_idc, 66 | 77 | 88)
+    }
+
+    fn chroma_array_type(&self) -> u8 {
+        if self.separate_colour_plane {
+            0
+        } else {
+            self.chroma_format_idc
+        }
+    }
+
+    /// `pic_order_cnt_lsb` of a slice NAL unit of a picture using this SPS,
+    /// `None` unless `pic_order_cnt_type` is 0.
+    pub(crate) fn slice_pic_order_cnt_lsb(&self, nal: &[u8]) -> Result<Option<u32>> {
+        if self.pic_order_cnt_type != 0 || nal.is_empty() {
+            return Ok(None);
+        }
+        let is_idr = nal[0] & 0x1f == 5;
+        let rbsp = rbsp(&nal[1..]);
+        let mut reader = BitReader::new(&rbsp);
+        reader.read_ue()?; // first_mb_in_slice
+        reader.read_ue()?; // slice_type
+        reader.read_ue()?; // pic_parameter_set_id
+        if self.separate_colour_plane {
+            reader.skip_bits(2)?; // colour_plane_id
+        }
+        reader.skip_bits(self.log2_max_frame_num as usize)?; // frame_num
+        if !self.frame_mbs_only && reader.read_bit()? {
+            // field_pic_flag
+            reader.read_bit()?; // bottom_field_flag
+        }
+        if is_idr {
+            reader.read_ue()?; // idr_pic_id
+        }
+        let lsb = reader.read_bits(self.log2_max_pic_order_cnt_lsb as u32)?;
+        Ok(Some(lsb))
+    }
+
+    /// Frame rate of the VUI timing information, which counts fields.
+    pub fn frame_rate(&self) -> Option<f64> {
+        match self.timing {
+            Some((num_units_in_tick, time_scale)) if num_units_in_tick > 0 => {
+                Some(time_scale as f64 / (2.0 * num_units_in_tick as f64))
+            }
+            _ => None,
+        }
+    }
+
+    /// Read the start of the VUI, up to the timing information.
+    fn read_vui(&mut self, reader: &mut BitReader) -> Result<()> {
+        if reader.read_bit()? {
+            // aspect_ratio_info_present_flag
+            let aspect_ratio_idc = reader.read_bits(8)? as usize;
+            self.sample_aspect_ratio = if aspect_ratio_idc == 255 {
+                // Extended_SAR
+                let sar_width = reader.read_bits(16)? as u16;
+                let sar_height = reader.read_bits(16)? as u16;
+                Some((sar_width, sar_height))
+            } else {
+                aspect_ratio_idc
+                    .checked_sub(1)
+                    .and_then(|index| SAMPLE_ASPECT_RATIOS.get(index))
+                    .copied()
+            };
+        }
+        if reader.read_bit()? {
+            // overscan_info_present_flag
+            reader.read_bit()?; // overscan_appropriate_flag
+        }
+        if reader.read_bit()? {
+            // video_signal_type_present_flag
+            reader.skip_bits(4)?; // video_format, video_full_range_flag
+            if reader.read_bit()? {
+                // colour_description_present_flag
+                reader.skip_bits(24)?;
+            }
+        }
+        if reader.read_bit()? {
+            // chroma_loc_info_present_flag
+            reader.read_ue()?;
+            reader.read_ue()?;
+        }
+        if reader.read_bit()? {
+            // timing_info_present_flag
+            let num_units_in_tick = reader.read_bits(32)?;
+            let time_scale = reader.read_bits(32)?;
+            self.timing = Some((num_units_in_tick, time_scale));
+            self.fixed_frame_rate = reader.read_bit()?;
+        }
+        Ok(())
+    }
+}
+
+fn read_small_ue(reader: &mut BitReader, max: u32) -> Result<u8> {
+    let value = reader.read_ue()?;
+    if value > max {
+        return Err(Error::InvalidData("SPS field out of range"));
+    }
+    Ok(value as u8)
+}
+
+fn skip_scaling_list(reader: &mut BitReader, size: usize) -> Result<()> {
+    let mut last_scale = 8i32;
+    let mut next_scale = 8i32;
+    for _ in 0..size {
+        if next_scale != 0 {
+            let delta_scale = reader.read_se()?;
+            next_scale = (last_scale + delta_scale).rem_euclid(256);
+        }
+        if next_scale != 0 {
+            last_scale = next_scale;
+        }
+    }
+    Ok(())
+}
+
+impl AvcConfig {
+    /// Build the configuration of a track from its SPS and PPS NAL units,
+    /// taking the picture size from the SPS.
+    pub fn from_sps_pps(sps: &[u8], pps: &[u8]) -> Result<Self> {
+        let parsed = AvcSps::parse(sps)?;
+        match pps.first() {
+            Some(header) if header & 0x1f == NAL_TYPE_PPS => {}
+            _ => return Err(Error::InvalidData("not an H.264 PPS")),
+        }
+        Ok(AvcConfig {
+            width: u16::try_from(parsed.width)
+                .map_err(|_| Error::InvalidData("SPS picture too large"))?,
+            height: u16::try_from(parsed.height)
+                .map_err(|_| Error::InvalidData("SPS picture too large"))?,
+            seq_param_set: sps.to_vec(),
+            pic_param_set: pps.to_vec(),
+        })
+    }
+
+    /// Build the configuration of a track from an Annex-B access unit
+    /// holding its first SPS and PPS, as output by most encoders for a key
+    /// frame.
+    pub fn from_annexb(data: &[u8]) -> Result<Self> {
+        let mut sps = None;
+        let mut pps = None;
+        for nal in AnnexBNalUnits::new(data) {
+            match nal[0] & 0x1f {
+                NAL_TYPE_SPS if sps.is_none() => sps = Some(nal),
+                NAL_TYPE_PPS if pps.is_none() => pps = Some(nal),
+                _ => {}
+            }
+        }
+        match (sps, pps) {
+            (Some(sps), Some(pps)) => Self::from_sps_pps(sps, pps),
+            (None, _) => Err(Error::InvalidData("no SPS in the access unit")),
+            (_, None) => Err(Error::InvalidData("no PPS in the access unit")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::{AvcCBox, BoxHeader, ReadBox, WriteBox};
+    use std::io::Cursor;
+
+    // SPS of tests/samples/minimal.mp4: High profile, 320x240.
+    const SPS_HIGH: [u8; 24] = [
+        0x67, 0x64, 0x00, 0x0D, 0xAC, 0xD9, 0x41, 0x41, 0xFA, 0x10, 0x00, 0x00, 0x03, 0x00, 0x10,
+        0x00, 0x00, 0x03, 0x03, 0x20, 0xF1, 0x42, 0x99, 0x60,
+    ];
+
+    #[test]
+    fn test_parse_sps_high() {
+        let sps = AvcSps::parse(&SPS_HIGH).unwrap();
+        assert_eq!(sps.profile_idc, 100);
+        assert_eq!(sps.level_idc, 13);
+        assert_eq!(sps.chroma_format_idc, 1);
+        assert_eq!(sps.bit_depth_luma_minus8, 0);
+        assert!(sps.frame_mbs_only);
+        assert_eq!((sps.width, sps.height), (320, 240));
+        assert_eq!(sps.sample_aspect_ratio, None);
+        assert_eq!(sps.timing, Some((1, 50)));
+        assert_eq!(sps.frame_rate(), Some(25.0));
+        assert!(sps.has_high_profile_ext());
+    }
+
+    #[test]
+    fn test_parse_sps_high_444() {
+        // High 4:4:4 Predictive, 4:2:2 10 bit, 320x240.
+        let sps = [0x67, 0xF4, 0x00, 0x1E, 0xB6, 0xCB, 0x40, 0xA0, 0xFC, 0x80];
+        let pps = [0x68, 0xEB, 0xE3, 0xCB, 0x22, 0xC0];
+        let parsed = AvcSps::parse(&sps).unwrap();
+        assert_eq!(parsed.profile_idc, 244);
+        assert_eq!(parsed.chroma_format_idc, 2);
+        assert_eq!(parsed.bit_depth_luma_minus8, 2);
+        assert_eq!(parsed.bit_depth_chroma_minus8, 2);
+        assert_eq!((parsed.width, parsed.height), (320, 240));
+        assert!(parsed.has_high_profile_ext());
+
+        let avcc = AvcCBox::new(&sps, &pps);
+        let ext = avcc.high_profile_ext.as_ref().unwrap();
+        assert_eq!(ext.chroma_format, 2);
+        assert_eq!(ext.bit_depth_luma_minus8, 2);
+
+        let mut buf = Vec::new();
+        avcc.write_box(&mut buf).unwrap();
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst = AvcCBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(dst.high_profile_ext, avcc.high_profile_ext);
+    }
+
+    #[test]
+    fn test_parse_sps_cropped() {
+        // Baseline 1920x1080, coded as 1920x1088 and cropped by 8 lines.
+        let sps = [0x67, 0x42, 0xc0, 0x28, 0xda, 0x01, 0xe0, 0x08, 0x9f, 0x95];
+        let sps = AvcSps::parse(&sps).unwrap();
+        assert_eq!(sps.profile_idc, 66);
+        assert_eq!(sps.profile_compatibility, 0xc0);
+        assert_eq!((sps.width, sps.height), (1920, 1080));
+        assert!(!sps.has_high_profile_ext());
+    }
+
+    #[test]
+    fn test_config_from_annexb() {
+        let pps = [0x68, 0xEB, 0xE3, 0xCB, 0x22, 0xC0];
+        let mut data = vec![0, 0, 0, 1, 0x09, 0xf0];
+        data.extend_from_slice(&[0, 0, 0, 1]);
+        data.extend_from_slice(&SPS_HIGH);
+        data.extend_from_slice(&[0, 0, 1]);
+        data.extend_from_slice(&pps);
+        data.extend_from_slice(&[0, 0, 1, 0x65, 0x88, 0x84]);
+
+        let config = AvcConfig::from_annexb(&data).unwrap();
+        assert_eq!(config.width, 320);
+        assert_eq!(config.height, 240);
+        assert_eq!(config.seq_param_set, SPS_HIGH);
+        assert_eq!(config.pic_param_set, pps);
+
+        assert!(AvcConfig::from_annexb(&data[..6]).is_err());
+    }
+}
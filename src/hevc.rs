@@ -0,0 +1,435 @@
+//! HEVC sequence parameter set parsing (ITU-T H.265, 7.3.2.2 and 7.3.3)
+//! and `hvcC` construction from parameter sets.
+
+use std::convert::TryFrom;
+
+use crate::nal::{rbsp, AnnexBNalUnits, BitReader};
+use crate::*;
+
+const NAL_TYPE_VPS: u8 = 32;
+const NAL_TYPE_SPS: u8 = 33;
+const NAL_TYPE_PPS: u8 = 34;
+const NAL_TYPE_PREFIX_SEI: u8 = 39;
+
+/// Type of an HEVC NAL unit, from its two byte header.
+fn nal_unit_type(nal: &[u8]) -> Option<u8> {
+    if nal.len() < 2 {
+        return None;
+    }
+    Some(nal[0] >> 1 & 0x3f)
+}
+
+/// The general profile, tier and level of a `profile_tier_level()`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct HevcProfileTierLevel {
+    pub general_profile_space: u8,
+    pub general_tier_flag: bool,
+    pub general_profile_idc: u8,
+    pub general_profile_compatibility_flags: u32,
+    /// The 48 bits following the compatibility flags.
+    pub general_constraint_indicator_flags: u64,
+    pub general_level_idc: u8,
+}
+
+impl HevcProfileTierLevel {
+    fn read(reader: &mut BitReader, max_sub_layers_minus1: u8) -> Result<Self> {
+        let general_profile_space = reader.read_bits(2)? as u8;
+        let general_tier_flag = reader.read_bit()?;
+        let general_profile_idc = reader.read_bits(5)? as u8;
+        let general_profile_compatibility_flags = reader.read_bits(32)?;
+        let general_constraint_indicator_flags =
+            (reader.read_bits(16)? as u64) << 32 | reader.read_bits(32)? as u64;
+        let general_level_idc = reader.read_bits(8)? as u8;
+
+        let mut sub_layer_flags = Vec::with_capacity(max_sub_layers_minus1 as usize);
+        for _ in 0..max_sub_layers_minus1 {
+            let profile_present = reader.read_bit()?;
+            let level_present = reader.read_bit()?;
+            sub_layer_flags.push((profile_present, level_present));
+        }
+        if max_sub_layers_minus1 > 0 {
+            reader.skip_bits(2 * (8 - max_sub_layers_minus1 as usize))?; // reserved_zero_2bits
+        }
+        for (profile_present, level_present) in sub_layer_flags {
+            if profile_present {
+                reader.skip_bits(88)?;
+            }
+            if level_present {
+                reader.skip_bits(8)?;
+            }
+        }
+
+        Ok(HevcProfileTierLevel {
+            general_profile_space,
+            general_tier_flag,
+            general_profile_idc,
+            general_profile_compatibility_flags,
+            general_constraint_indicator_flags,
+            general_level_idc,
+        })
+    }
+}
+
+/// The fields of an HEVC SPS needed to describe a track.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct HevcSps {
+    pub video_parameter_set_id: u8,
+    pub max_sub_layers_minus1: u8,
+    pub temporal_id_nesting: bool,
+    pub profile_tier_level: HevcProfileTierLevel,
+    pub seq_parameter_set_id: u32,
+    pub chroma_format_idc: u8,
+    pub separate_colour_plane: bool,
+
+    /// Dimensions of the decoded pictures after the conformance window.
+    pub width: u32,
+    pub height: u32,
+
+    pub bit_depth_luma_minus8: u8,
+    pub bit_depth_chroma_minus8: u8,
+    pub log2_max_pic_order_cnt_lsb: u8,
+}
+
+impl HevcSps {
+    /// Parse an SPS NAL unit, starting with its NAL unit header.
+    pub fn parse(nal: &[u8]) -> Result<Self> {
+        if nal_unit_type(nal) != Some(NAL_TYPE_SPS) {
+            return Err(Error::InvalidData("not an HEVC SPS"));
+        }
+        let rbsp = rbsp(&nal[2..]);
+        let mut reader = BitReader::new(&rbsp);
+
+        let video_parameter_set_id = reader.read_bits(4)? as u8;
+        let max_sub_layers_minus1 = reader.read_bits(3)? as u8;
+        if max_sub_layers_minus1 > 6 {
+            return Err(Error::InvalidData("invalid sps_max_sub_layers_minus1"));
+        }
+        let temporal_id_nesting = reader.read_bit()?;
+        let profile_tier_level = HevcProfileTierLevel::read(&mut reader, max_sub_layers_minus1)?;
+        let seq_parameter_set_id = reader.read_ue()?;
+        let chroma_format_idc = read_small_ue(&mut reader, 3)?;
+        let separate_colour_plane = chroma_format_idc == 3 && reader.read_bit()?;
+
+        let mut width = reader.read_ue()? as u64;
+        let mut height = reader.read_ue()? as u64;
+        if reader.read_bit()? {
+            // conformance_window_flag
+            let (sub_width, sub_height) = match (chroma_format_idc, separate_colour_plane) {
+                (1, _) => (2, 2),
+                (2, _) => (2, 1),
+                _ => (1, 1),
+            };
+            let left = reader.read_ue()? as u64;
+            let right = reader.read_ue()? as u64;
+            let top = reader.read_ue()? as u64;
+            let bottom = reader.read_ue()? as u64;
+            width = width
+                .checked_sub(sub_width * (left + right))
+                .ok_or(Error::InvalidData(
+                    "SPS conformance window exceeds the picture",
+                ))?;
+            height = height
+                .checked_sub(sub_height * (top + bottom))
+                .ok_or(Error::InvalidData(
+                    "SPS conformance window exceeds the picture",
+                ))?;
+        }
+        let bit_depth_luma_minus8 = read_small_ue(&mut reader, 8)?;
+        let bit_depth_chroma_minus8 = read_small_ue(&mut reader, 8)?;
+        let log2_max_pic_order_cnt_lsb = read_small_ue(&mut reader, 12)? + 4;
+
+        Ok(HevcSps {
+            video_parameter_set_id,
+            max_sub_layers_minus1,
+            temporal_id_nesting,
+            profile_tier_level,
+            seq_parameter_set_id,
+            chroma_format_idc,
+            separate_colour_plane,
+            width: u32::try_from(width).map_err(|_| Error::InvalidData("SPS picture too large"))?,
+            height: u32::try_from(height)
+                .map_err(|_| Error::InvalidData("SPS picture too large"))?,
+            bit_depth_luma_minus8,
+            bit_depth_chroma_minus8,
+            log2_max_pic_order_cnt_lsb,
+        })
+    }
+
+    /// `slice_pic_order_cnt_lsb` of the first slice segment of a picture
+    /// using this SPS, 0 for IDR pictures. `None` for the other slice
+    /// segments, which do not start a picture.
+    pub(crate) fn slice_pic_order_cnt_lsb(
+        &self,
+        nal: &[u8],
+        ppss: &[HevcPps],
+    ) -> Result<Option<u32>> {
+        let nal_type = nal_unit_type(nal).ok_or(Error::InvalidData("truncated slice header"))?;
+        let rbsp = rbsp(&nal[2..]);
+        let mut reader = BitReader::new(&rbsp);
+        if !reader.read_bit()? {
+            // first_slice_segment_in_pic_flag
+            return Ok(None);
+        }
+        if (16..=23).contains(&nal_type) {
+            reader.read_bit()?; // no_output_of_prior_pics_flag
+        }
+        let pps_id = reader.read_ue()?;
+        let pps = ppss
+            .iter()
+            .find(|pps| pps.pic_parameter_set_id == pps_id)
+            .ok_or(Error::InvalidData("slice refers to a missing PPS"))?;
+        if nal_type == 19 || nal_type == 20 {
+            // IDR_W_RADL, IDR_N_LP
+            return Ok(Some(0));
+        }
+        reader.skip_bits(pps.num_extra_slice_header_bits as usize)?;
+        reader.read_ue()?; // slice_type
+        if pps.output_flag_present {
+            reader.read_bit()?; // pic_output_flag
+        }
+        if self.separate_colour_plane {
+            reader.skip_bits(2)?; // colour_plane_id
+        }
+        let lsb = reader.read_bits(self.log2_max_pic_order_cnt_lsb as u32)?;
+        Ok(Some(lsb))
+    }
+}
+
+/// The start of an HEVC PPS (ITU-T H.265, 7.3.2.3), up to the fields that
+/// change how slice headers are parsed.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) struct HevcPps {
+    pub(crate) pic_parameter_set_id: u32,
+    pub(crate) output_flag_present: bool,
+    pub(crate) num_extra_slice_header_bits: u8,
+}
+
+impl HevcPps {
+    pub(crate) fn parse(nal: &[u8]) -> Result<Self> {
+        if nal_unit_type(nal) != Some(NAL_TYPE_PPS) {
+            return Err(Error::InvalidData("not an HEVC PPS"));
+        }
+        let rbsp = rbsp(&nal[2..]);
+        let mut reader = BitReader::new(&rbsp);
+        let pic_parameter_set_id = reader.read_ue()?;
+        reader.read_ue()?; // pps_seq_parameter_set_id
+        reader.read_bit()?; // dependent_slice_segments_enabled_flag
+        let output_flag_present = reader.read_bit()?;
+        let num_extra_slice_header_bits = reader.read_bits(3)? as u8;
+        Ok(HevcPps {
+            pic_parameter_set_id,
+            output_flag_present,
+            num_extra_slice_header_bits,
+        })
+    }
+}
+
+fn read_small_ue(reader: &mut BitReader, max: u32) -> Result<u8> {
+    let value = reader.read_ue()?;
+    if value > max {
+        return Err(Error::InvalidData("SPS field out of range"));
+    }
+    Ok(value as u8)
+}
+
+impl HvcCBox {
+    /// Build an `hvcC` from the VPS, SPS, PPS and prefix SEI NAL units of a
+    /// stream, taking the profile, chroma format, bit depths and temporal
+    /// layers from the first SPS. Other NAL units are ignored.
+    pub fn from_nal_units<'a, I>(nal_units: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        Ok(hvcc_from_nal_units(nal_units)?.0)
+    }
+}
+
+fn hvcc_from_nal_units<'a, I>(nal_units: I) -> Result<(HvcCBox, HevcSps)>
+where
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    let mut arrays: Vec<HvcCArray> = Vec::new();
+    for nal in nal_units {
+        let nal_type = match nal_unit_type(nal) {
+            Some(nal_type @ (NAL_TYPE_VPS | NAL_TYPE_SPS | NAL_TYPE_PPS | NAL_TYPE_PREFIX_SEI)) => {
+                nal_type
+            }
+            _ => continue,
+        };
+        let size = u16::try_from(nal.len())
+            .map_err(|_| Error::InvalidData("parameter set too large for hvcC"))?;
+        let nalu = HvcCArrayNalu {
+            size,
+            data: nal.to_vec(),
+        };
+        match arrays
+            .iter_mut()
+            .find(|array| array.nal_unit_type == nal_type)
+        {
+            Some(array) => array.nalus.push(nalu),
+            None => arrays.push(HvcCArray {
+                // SEI messages may also be carried in the samples.
+                completeness: nal_type != NAL_TYPE_PREFIX_SEI,
+                nal_unit_type: nal_type,
+                nalus: vec![nalu],
+            }),
+        }
+    }
+    // VPS, SPS, PPS then SEI, as recommended by ISO/IEC 14496-15.
+    arrays.sort_by_key(|array| array.nal_unit_type);
+
+    let sps = arrays
+        .iter()
+        .find(|array| array.nal_unit_type == NAL_TYPE_SPS)
+        .ok_or(Error::InvalidData("no SPS in the NAL units"))?;
+    let sps = HevcSps::parse(&sps.nalus[0].data)?;
+    let profile_tier_level = &sps.profile_tier_level;
+
+    let hvcc = HvcCBox {
+        configuration_version: 1,
+        general_profile_space: profile_tier_level.general_profile_space,
+        general_tier_flag: profile_tier_level.general_tier_flag,
+        general_profile_idc: profile_tier_level.general_profile_idc,
+        general_profile_compatibility_flags: profile_tier_level.general_profile_compatibility_flags,
+        general_constraint_indicator_flag: profile_tier_level.general_constraint_indicator_flags,
+        general_level_idc: profile_tier_level.general_level_idc,
+        // Unknown without parsing the VUI and the PPS.
+        min_spatial_segmentation_idc: 0,
+        parallelism_type: 0,
+        chroma_format_idc: sps.chroma_format_idc,
+        bit_depth_luma_minus8: sps.bit_depth_luma_minus8,
+        bit_depth_chroma_minus8: sps.bit_depth_chroma_minus8,
+        avg_frame_rate: 0,
+        constant_frame_rate: 0,
+        num_temporal_layers: sps.max_sub_layers_minus1 + 1,
+        temporal_id_nested: sps.temporal_id_nesting,
+        length_size_minus_one: 3,
+        arrays,
+    };
+    Ok((hvcc, sps))
+}
+
+impl HevcConfig {
+    /// Build the configuration of a track from its parameter sets, see
+    /// [HvcCBox::from_nal_units].
+    pub fn from_nal_units<'a, I>(nal_units: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        let (hvcc, sps) = hvcc_from_nal_units(nal_units)?;
+        let width =
+            u16::try_from(sps.width).map_err(|_| Error::InvalidData("SPS picture too large"))?;
+        let height =
+            u16::try_from(sps.height).map_err(|_| Error::InvalidData("SPS picture too large"))?;
+        Ok(Self::from_hvcc(&hvcc, width, height))
+    }
+
+    /// Build the configuration of a track from an Annex-B access unit
+    /// holding its parameter sets.
+    pub fn from_annexb(data: &[u8]) -> Result<Self> {
+        Self::from_nal_units(AnnexBNalUnits::new(data))
+    }
+
+    /// Copy the configuration of an `hev1` or `hvc1` track, keeping its
+    /// `hvcC` as is.
+    pub fn from_track(track: &Mp4Track) -> Result<Self> {
+        let stsd = &track.trak.mdia.minf.stbl.stsd;
+        let (hvcc, use_hvc1) = if let Some(ref hev1) = stsd.hev1 {
+            (&hev1.hvcc, false)
+        } else if let Some(ref hvc1) = stsd.hvc1 {
+            (&hvc1.hvcc, true)
+        } else {
+            return Err(Error::BoxInStblNotFound(
+                track.track_id(),
+                vec![BoxType::Hev1Box, BoxType::Hvc1Box],
+            ));
+        };
+        Ok(Self {
+            use_hvc1,
+            ..Self::from_hvcc(hvcc, track.width(), track.height())
+        })
+    }
+
+    fn from_hvcc(hvcc: &HvcCBox, width: u16, height: u16) -> Self {
+        Self {
+            width: Some(width),
+            height: Some(height),
+            configuration_version: Some(hvcc.configuration_version),
+            general_profile_space: Some(hvcc.general_profile_space),
+            general_tier_flag: Some(hvcc.general_tier_flag),
+            general_profile_idc: Some(hvcc.general_profile_idc),
+            general_profile_compatibility_flags: Some(hvcc.general_profile_compatibility_flags),
+            general_constraint_indicator_flag: Some(hvcc.general_constraint_indicator_flag),
+            general_level_idc: Some(hvcc.general_level_idc),
+            min_spatial_segmentation_idc: Some(hvcc.min_spatial_segmentation_idc),
+            parallelism_type: Some(hvcc.parallelism_type),
+            chroma_format_idc: Some(hvcc.chroma_format_idc),
+            bit_depth_luma_minus8: Some(hvcc.bit_depth_luma_minus8),
+            bit_depth_chroma_minus8: Some(hvcc.bit_depth_chroma_minus8),
+            avg_frame_rate: Some(hvcc.avg_frame_rate),
+            constant_frame_rate: Some(hvcc.constant_frame_rate),
+            num_temporal_layers: Some(hvcc.num_temporal_layers),
+            temporal_id_nested: Some(hvcc.temporal_id_nested),
+            length_size_minus_one: Some(hvcc.length_size_minus_one),
+            arrays: Some(hvcc.arrays.clone()),
+            use_hvc1: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Main profile, level 3.1, 1280x720 coded as 1280x736 with a
+    // conformance window.
+    const SPS_MAIN: [u8; 26] = [
+        0x42, 0x01, 0x01, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00, 0x00,
+        0x03, 0x00, 0x5d, 0xa0, 0x02, 0x80, 0x80, 0x2e, 0x1f, 0x13, 0xc0,
+    ];
+    const VPS: [u8; 4] = [0x40, 0x01, 0x0c, 0x01];
+    const PPS: [u8; 4] = [0x44, 0x01, 0xc1, 0x72];
+
+    #[test]
+    fn test_parse_sps() {
+        let sps = HevcSps::parse(&SPS_MAIN).unwrap();
+        assert_eq!(sps.max_sub_layers_minus1, 0);
+        assert!(sps.temporal_id_nesting);
+        assert_eq!(sps.profile_tier_level.general_profile_idc, 1);
+        assert_eq!(
+            sps.profile_tier_level.general_profile_compatibility_flags,
+            0x6000_0000
+        );
+        assert_eq!(
+            sps.profile_tier_level.general_constraint_indicator_flags,
+            0x9000_0000_0000
+        );
+        assert_eq!(sps.profile_tier_level.general_level_idc, 93);
+        assert_eq!(sps.chroma_format_idc, 1);
+        assert_eq!((sps.width, sps.height), (1280, 720));
+        assert_eq!(sps.bit_depth_luma_minus8, 0);
+    }
+
+    #[test]
+    fn test_hvcc_from_annexb() {
+        let mut data = Vec::new();
+        // Out of order, with a slice that is not part of the configuration.
+        for nal in [&PPS[..], &SPS_MAIN, &VPS, &[0x26, 0x01, 0xaf]] {
+            data.extend_from_slice(&[0, 0, 0, 1]);
+            data.extend_from_slice(nal);
+        }
+        let config = HevcConfig::from_annexb(&data).unwrap();
+        assert_eq!((config.width, config.height), (Some(1280), Some(720)));
+        assert_eq!(config.general_level_idc, Some(93));
+        assert_eq!(config.num_temporal_layers, Some(1));
+        assert_eq!(config.temporal_id_nested, Some(true));
+
+        let arrays = config.arrays.unwrap();
+        let types: Vec<u8> = arrays.iter().map(|array| array.nal_unit_type).collect();
+        assert_eq!(types, vec![32, 33, 34]);
+        assert_eq!(arrays[1].nalus[0].data, SPS_MAIN);
+        assert_eq!(arrays[1].nalus[0].size, SPS_MAIN.len() as u16);
+
+        assert!(HevcConfig::from_nal_units(vec![&VPS[..], &PPS]).is_err());
+    }
+}
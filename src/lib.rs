@@ -85,9 +85,38 @@ pub use track::{Mp4Track, TrackConfig};
 mod reader;
 pub use reader::Mp4Reader;
 
+mod parser;
+pub use parser::{Mp4Event, Mp4Parser, SampleData};
+
+mod tree;
+pub use tree::{BoxNode, BoxTree, BoxVisitor, ParsedBox};
+
 mod writer;
 pub use writer::{Mp4Config, Mp4Writer};
 
+mod fragment;
+pub use fragment::{FragmentConfig, Mp4FragmentWriter};
+
+mod stream;
+pub use stream::Mp4StreamWriter;
+
+mod demux;
+pub use demux::ElementaryStreamWriter;
+
+mod mux;
+pub use mux::{mux_streams, ElementaryStream};
+
+pub mod nal;
+
+mod h264;
+pub use h264::AvcSps;
+
+mod hevc;
+pub use hevc::{HevcProfileTierLevel, HevcSps};
+
+mod cenc;
+pub use cenc::{decrypt_sample, encrypt_sample, EncryptionConfig, SCHEME_CBCS, SCHEME_CENC};
+
 pub fn read_mp4(f: File) -> Result<Mp4Reader<BufReader<File>>> {
     let size = f.metadata()?.len();
     let reader = BufReader::new(f);
@@ -0,0 +1,380 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Sample rates indexed by `fscod`.
+const AC3_SAMPLE_RATES: [u32; 3] = [48000, 44100, 32000];
+
+/// Nominal bit rates in kbit/s indexed by `bit_rate_code`.
+const AC3_BIT_RATES: [u32; 19] = [
+    32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384, 448, 512, 576, 640,
+];
+
+/// Full bandwidth channels indexed by `acmod`.
+const AC3_ACMOD_CHANNELS: [u16; 8] = [2, 1, 2, 3, 3, 4, 4, 5];
+
+pub(crate) fn ac3_sample_rate(fscod: u8) -> Result<u32> {
+    AC3_SAMPLE_RATES
+        .get(fscod as usize)
+        .copied()
+        .ok_or(Error::InvalidData("invalid ac-3 fscod"))
+}
+
+pub(crate) fn ac3_channel_count(acmod: u8, lfeon: bool) -> u16 {
+    AC3_ACMOD_CHANNELS[(acmod & 0x07) as usize] + lfeon as u16
+}
+
+/// Map an audio coding mode to the closest channel configuration. Modes
+/// without an equivalent, such as dual mono, 2/1 and 2/2, are an error.
+pub(crate) fn ac3_channel_config(acmod: u8, lfeon: bool) -> Result<ChannelConfig> {
+    match (acmod, lfeon) {
+        (1, false) => Ok(ChannelConfig::Mono),
+        (2, false) => Ok(ChannelConfig::Stereo),
+        (3, false) => Ok(ChannelConfig::Three),
+        (5, false) => Ok(ChannelConfig::Four),
+        (7, false) => Ok(ChannelConfig::Five),
+        (7, true) => Ok(ChannelConfig::FiveOne),
+        _ => Err(Error::InvalidData(
+            "ac-3 channel layout has no channel configuration",
+        )),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Ac3Box {
+    pub data_reference_index: u16,
+    pub channel_count: u16,
+    pub sample_size: u16,
+
+    #[serde(with = "value_u32")]
+    pub sample_rate: FixedPointU16,
+    pub dac3: Dac3Box,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
+}
+
+impl Default for Ac3Box {
+    fn default() -> Self {
+        Self {
+            data_reference_index: 0,
+            channel_count: 2,
+            sample_size: 16,
+            sample_rate: FixedPointU16::new(48000),
+            dac3: Dac3Box::default(),
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
+        }
+    }
+}
+
+impl Ac3Box {
+    pub fn new(config: &Ac3Config) -> Self {
+        let dac3 = Dac3Box::new(config);
+        Self {
+            data_reference_index: 1,
+            channel_count: dac3.channel_count(),
+            sample_size: 16,
+            sample_rate: FixedPointU16::new(dac3.sample_rate().unwrap_or(48000) as u16),
+            dac3,
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
+        }
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::Ac3Box
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + 28 + self.dac3.box_size();
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
+        size
+    }
+}
+
+impl Mp4Box for Ac3Box {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "channel_count={} sample_size={} sample_rate={}",
+            self.channel_count,
+            self.sample_size,
+            self.sample_rate.value()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for Ac3Box {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        reader.read_u32::<BigEndian>()?; // reserved
+        reader.read_u16::<BigEndian>()?; // reserved
+        let data_reference_index = reader.read_u16::<BigEndian>()?;
+        reader.read_u64::<BigEndian>()?; // reserved
+        let channel_count = reader.read_u16::<BigEndian>()?;
+        let sample_size = reader.read_u16::<BigEndian>()?;
+        reader.read_u32::<BigEndian>()?; // pre-defined, reserved
+        let sample_rate = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
+
+        let mut dac3 = None;
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
+
+        // Stop short of trailing bytes too small to hold a box.
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current + HEADER_SIZE <= end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "ac-3 box contains a box with a larger size than it",
+                ));
+            }
+            child_order.push(name);
+            match name {
+                BoxType::Dac3Box => {
+                    dac3 = Some(Dac3Box::read_box(reader, s)?);
+                }
+                _ => {
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
+                }
+            }
+            current = reader.stream_position()?;
+        }
+
+        let dac3 = match dac3 {
+            Some(dac3) => dac3,
+            None => return Err(Error::InvalidData("dac3 not found")),
+        };
+
+        skip_bytes_to(reader, end)?;
+
+        Ok(Ac3Box {
+            data_reference_index,
+            channel_count,
+            sample_size,
+            sample_rate,
+            dac3,
+            unknown_boxes,
+            child_order,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for Ac3Box {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.data_reference_index)?;
+
+        writer.write_u64::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.channel_count)?;
+        writer.write_u16::<BigEndian>(self.sample_size)?;
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u32::<BigEndian>(self.sample_rate.raw_value())?;
+
+        let mut children = ChildWriter::new();
+        children.push(&self.dac3);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
+
+        Ok(size)
+    }
+}
+
+/// AC-3 specific box, from ETSI TS 102 366 Annex F.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Dac3Box {
+    pub fscod: u8,
+    pub bsid: u8,
+    pub bsmod: u8,
+    pub acmod: u8,
+    pub lfeon: bool,
+    pub bit_rate_code: u8,
+}
+
+impl Default for Dac3Box {
+    fn default() -> Self {
+        Self::new(&Ac3Config::default())
+    }
+}
+
+impl Dac3Box {
+    pub fn new(config: &Ac3Config) -> Self {
+        Self {
+            fscod: config.fscod,
+            bsid: config.bsid,
+            bsmod: config.bsmod,
+            acmod: config.acmod,
+            lfeon: config.lfeon,
+            bit_rate_code: config.bit_rate_code,
+        }
+    }
+
+    pub fn sample_rate(&self) -> Result<u32> {
+        ac3_sample_rate(self.fscod)
+    }
+
+    pub fn channel_count(&self) -> u16 {
+        ac3_channel_count(self.acmod, self.lfeon)
+    }
+
+    pub fn channel_config(&self) -> Result<ChannelConfig> {
+        ac3_channel_config(self.acmod, self.lfeon)
+    }
+
+    /// Nominal bit rate in bit/s.
+    pub fn bitrate(&self) -> Result<u32> {
+        AC3_BIT_RATES
+            .get(self.bit_rate_code as usize)
+            .map(|kbps| kbps * 1000)
+            .ok_or(Error::InvalidData("invalid ac-3 bit_rate_code"))
+    }
+}
+
+impl Mp4Box for Dac3Box {
+    fn box_type(&self) -> BoxType {
+        BoxType::Dac3Box
+    }
+
+    fn box_size(&self) -> u64 {
+        HEADER_SIZE + 3
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "fscod={} bsid={} bsmod={} acmod={} lfeon={} bit_rate_code={}",
+            self.fscod, self.bsid, self.bsmod, self.acmod, self.lfeon, self.bit_rate_code
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for Dac3Box {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let bits = reader.read_u24::<BigEndian>()?;
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(Dac3Box {
+            fscod: (bits >> 22) as u8 & 0x03,
+            bsid: (bits >> 17) as u8 & 0x1F,
+            bsmod: (bits >> 14) as u8 & 0x07,
+            acmod: (bits >> 11) as u8 & 0x07,
+            lfeon: (bits >> 10) & 0x01 != 0,
+            bit_rate_code: (bits >> 5) as u8 & 0x1F,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for Dac3Box {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        let bits = (self.fscod as u32 & 0x03) << 22
+            | (self.bsid as u32 & 0x1F) << 17
+            | (self.bsmod as u32 & 0x07) << 14
+            | (self.acmod as u32 & 0x07) << 11
+            | (self.lfeon as u32) << 10
+            | (self.bit_rate_code as u32 & 0x1F) << 5;
+        writer.write_u24::<BigEndian>(bits)?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_ac3() {
+        let src_box = Ac3Box {
+            data_reference_index: 1,
+            channel_count: 6,
+            sample_size: 16,
+            sample_rate: FixedPointU16::new(48000),
+            dac3: Dac3Box {
+                fscod: 0,
+                bsid: 8,
+                bsmod: 0,
+                acmod: 7,
+                lfeon: true,
+                bit_rate_code: 15,
+            },
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Ac3Box);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = Ac3Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.dac3.sample_rate().unwrap(), 48000);
+        assert_eq!(dst_box.dac3.channel_count(), 6);
+        assert_eq!(dst_box.dac3.bitrate().unwrap(), 448000);
+    }
+
+    #[test]
+    fn test_dac3() {
+        // 48 kHz, bsid 8, 3/2 with LFE, 448 kbit/s
+        let data = [
+            0x00, 0x00, 0x00, 0x0B, 0x64, 0x61, 0x63, 0x33, 0x10, 0x3D, 0xE0,
+        ];
+        let mut reader = Cursor::new(&data);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Dac3Box);
+
+        let dac3 = Dac3Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(dac3.fscod, 0);
+        assert_eq!(dac3.bsid, 8);
+        assert_eq!(dac3.bsmod, 0);
+        assert_eq!(dac3.acmod, 7);
+        assert!(dac3.lfeon);
+        assert_eq!(dac3.bit_rate_code, 15);
+
+        let mut buf = Vec::new();
+        dac3.write_box(&mut buf).unwrap();
+        assert_eq!(buf, data);
+    }
+}
@@ -0,0 +1,422 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Av01Box {
+    pub data_reference_index: u16,
+    pub width: u16,
+    pub height: u16,
+
+    #[serde(with = "value_u32")]
+    pub horizresolution: FixedPointU16,
+
+    #[serde(with = "value_u32")]
+    pub vertresolution: FixedPointU16,
+    pub frame_count: u16,
+    pub depth: u16,
+    pub av1c: Av1CBox,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
+}
+
+impl Default for Av01Box {
+    fn default() -> Self {
+        Av01Box {
+            data_reference_index: 0,
+            width: 0,
+            height: 0,
+            horizresolution: FixedPointU16::new(0x48),
+            vertresolution: FixedPointU16::new(0x48),
+            frame_count: 1,
+            depth: 0x0018,
+            av1c: Av1CBox::default(),
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
+        }
+    }
+}
+
+impl Av01Box {
+    pub fn new(config: &Av1Config) -> Self {
+        Av01Box {
+            data_reference_index: 1,
+            width: config.width,
+            height: config.height,
+            horizresolution: FixedPointU16::new(0x48),
+            vertresolution: FixedPointU16::new(0x48),
+            frame_count: 1,
+            depth: 0x0018,
+            av1c: Av1CBox::new(config),
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
+        }
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::Av01Box
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + 8 + 70 + self.av1c.box_size();
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
+        size
+    }
+}
+
+impl Mp4Box for Av01Box {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "data_reference_index={} width={} height={} frame_count={}",
+            self.data_reference_index, self.width, self.height, self.frame_count
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for Av01Box {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        reader.read_u32::<BigEndian>()?; // reserved
+        reader.read_u16::<BigEndian>()?; // reserved
+        let data_reference_index = reader.read_u16::<BigEndian>()?;
+
+        reader.read_u32::<BigEndian>()?; // pre-defined, reserved
+        reader.read_u64::<BigEndian>()?; // pre-defined
+        reader.read_u32::<BigEndian>()?; // pre-defined
+        let width = reader.read_u16::<BigEndian>()?;
+        let height = reader.read_u16::<BigEndian>()?;
+        let horizresolution = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
+        let vertresolution = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
+        reader.read_u32::<BigEndian>()?; // reserved
+        let frame_count = reader.read_u16::<BigEndian>()?;
+        skip_bytes(reader, 32)?; // compressorname
+        let depth = reader.read_u16::<BigEndian>()?;
+        reader.read_i16::<BigEndian>()?; // pre-defined
+
+        let mut av1c = None;
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
+
+        // Stop short of trailing bytes too small to hold a box.
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current + HEADER_SIZE <= end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "av01 box contains a box with a larger size than it",
+                ));
+            }
+            child_order.push(name);
+            match name {
+                BoxType::Av1CBox => {
+                    av1c = Some(Av1CBox::read_box(reader, s)?);
+                }
+                _ => {
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
+                }
+            }
+            current = reader.stream_position()?;
+        }
+
+        let av1c = match av1c {
+            Some(av1c) => av1c,
+            None => return Err(Error::InvalidData("av1c not found")),
+        };
+
+        skip_bytes_to(reader, end)?;
+
+        Ok(Av01Box {
+            data_reference_index,
+            width,
+            height,
+            horizresolution,
+            vertresolution,
+            frame_count,
+            depth,
+            av1c,
+            unknown_boxes,
+            child_order,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for Av01Box {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.data_reference_index)?;
+
+        writer.write_u32::<BigEndian>(0)?; // pre-defined, reserved
+        writer.write_u64::<BigEndian>(0)?; // pre-defined
+        writer.write_u32::<BigEndian>(0)?; // pre-defined
+        writer.write_u16::<BigEndian>(self.width)?;
+        writer.write_u16::<BigEndian>(self.height)?;
+        writer.write_u32::<BigEndian>(self.horizresolution.raw_value())?;
+        writer.write_u32::<BigEndian>(self.vertresolution.raw_value())?;
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.frame_count)?;
+        // skip compressorname
+        write_zeros(writer, 32)?;
+        writer.write_u16::<BigEndian>(self.depth)?;
+        writer.write_i16::<BigEndian>(-1)?; // pre-defined
+
+        let mut children = ChildWriter::new();
+        children.push(&self.av1c);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
+
+        Ok(size)
+    }
+}
+
+/// AV1 codec configuration record, as defined in the AV1 ISOBMFF binding.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct Av1CBox {
+    pub version: u8,
+    pub seq_profile: u8,
+    pub seq_level_idx_0: u8,
+    pub seq_tier_0: bool,
+    pub high_bitdepth: bool,
+    pub twelve_bit: bool,
+    pub monochrome: bool,
+    pub chroma_subsampling_x: bool,
+    pub chroma_subsampling_y: bool,
+    pub chroma_sample_position: u8,
+    pub initial_presentation_delay_minus_one: Option<u8>,
+
+    /// Sequence header and metadata OBUs, in low overhead bitstream format.
+    #[serde(skip_serializing)]
+    pub config_obus: Vec<u8>,
+}
+
+impl Av1CBox {
+    pub fn new(config: &Av1Config) -> Self {
+        Self {
+            version: 1,
+            seq_profile: config.seq_profile,
+            seq_level_idx_0: config.seq_level_idx_0,
+            seq_tier_0: config.seq_tier_0,
+            high_bitdepth: config.high_bitdepth,
+            twelve_bit: config.twelve_bit,
+            monochrome: config.monochrome,
+            chroma_subsampling_x: config.chroma_subsampling_x,
+            chroma_subsampling_y: config.chroma_subsampling_y,
+            chroma_sample_position: config.chroma_sample_position,
+            initial_presentation_delay_minus_one: config.initial_presentation_delay_minus_one,
+            config_obus: config.config_obus.clone(),
+        }
+    }
+
+    pub fn bit_depth(&self) -> u8 {
+        match (self.high_bitdepth, self.twelve_bit) {
+            (true, true) => 12,
+            (true, false) => 10,
+            _ => 8,
+        }
+    }
+}
+
+impl Mp4Box for Av1CBox {
+    fn box_type(&self) -> BoxType {
+        BoxType::Av1CBox
+    }
+
+    fn box_size(&self) -> u64 {
+        HEADER_SIZE + 4 + self.config_obus.len() as u64
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "seq_profile={} seq_level_idx_0={} bit_depth={}",
+            self.seq_profile,
+            self.seq_level_idx_0,
+            self.bit_depth()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for Av1CBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        if size < HEADER_SIZE + 4 {
+            return Err(Error::InvalidData("av1c box is too small"));
+        }
+
+        let b = reader.read_u8()?;
+        if b & 0x80 == 0 {
+            return Err(Error::InvalidData("av1c marker bit not set"));
+        }
+        let version = b & 0x7F;
+
+        let b = reader.read_u8()?;
+        let seq_profile = b >> 5;
+        let seq_level_idx_0 = b & 0x1F;
+
+        let b = reader.read_u8()?;
+        let seq_tier_0 = b & 0x80 != 0;
+        let high_bitdepth = b & 0x40 != 0;
+        let twelve_bit = b & 0x20 != 0;
+        let monochrome = b & 0x10 != 0;
+        let chroma_subsampling_x = b & 0x08 != 0;
+        let chroma_subsampling_y = b & 0x04 != 0;
+        let chroma_sample_position = b & 0x03;
+
+        let b = reader.read_u8()?;
+        let initial_presentation_delay_minus_one =
+            if b & 0x10 != 0 { Some(b & 0x0F) } else { None };
+
+        let mut config_obus = vec![0u8; (size - HEADER_SIZE - 4) as usize];
+        reader.read_exact(&mut config_obus)?;
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(Av1CBox {
+            version,
+            seq_profile,
+            seq_level_idx_0,
+            seq_tier_0,
+            high_bitdepth,
+            twelve_bit,
+            monochrome,
+            chroma_subsampling_x,
+            chroma_subsampling_y,
+            chroma_sample_position,
+            initial_presentation_delay_minus_one,
+            config_obus,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for Av1CBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u8(0x80 | self.version)?; // marker
+        writer.write_u8((self.seq_profile << 5) | (self.seq_level_idx_0 & 0x1F))?;
+        writer.write_u8(
+            ((self.seq_tier_0 as u8) << 7)
+                | ((self.high_bitdepth as u8) << 6)
+                | ((self.twelve_bit as u8) << 5)
+                | ((self.monochrome as u8) << 4)
+                | ((self.chroma_subsampling_x as u8) << 3)
+                | ((self.chroma_subsampling_y as u8) << 2)
+                | (self.chroma_sample_position & 0x03),
+        )?;
+        match self.initial_presentation_delay_minus_one {
+            Some(delay) => writer.write_u8(0x10 | (delay & 0x0F))?,
+            None => writer.write_u8(0)?,
+        }
+        writer.write_all(&self.config_obus)?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_av01() {
+        let src_box = Av01Box {
+            data_reference_index: 1,
+            width: 1920,
+            height: 1080,
+            horizresolution: FixedPointU16::new(0x48),
+            vertresolution: FixedPointU16::new(0x48),
+            frame_count: 1,
+            depth: 24,
+            av1c: Av1CBox {
+                version: 1,
+                seq_profile: 0,
+                seq_level_idx_0: 8,
+                seq_tier_0: false,
+                high_bitdepth: true,
+                twelve_bit: false,
+                monochrome: false,
+                chroma_subsampling_x: true,
+                chroma_subsampling_y: true,
+                chroma_sample_position: 0,
+                initial_presentation_delay_minus_one: None,
+                config_obus: vec![
+                    0x0A, 0x0B, 0x00, 0x00, 0x00, 0x42, 0xAB, 0xBF, 0xC3, 0x70, 0x0B, 0xE0, 0x40,
+                ],
+            },
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Av01Box);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = Av01Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.av1c.bit_depth(), 10);
+    }
+
+    #[test]
+    fn test_av1c() {
+        // 4:4:4 12-bit, with an initial presentation delay
+        let data = [
+            0x00, 0x00, 0x00, 0x0C, 0x61, 0x76, 0x31, 0x43, 0x81, 0x4D, 0xE0, 0x13,
+        ];
+        let mut reader = Cursor::new(&data);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Av1CBox);
+
+        let av1c = Av1CBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(av1c.seq_profile, 2);
+        assert_eq!(av1c.seq_level_idx_0, 13);
+        assert!(av1c.seq_tier_0);
+        assert_eq!(av1c.bit_depth(), 12);
+        assert!(!av1c.chroma_subsampling_x);
+        assert!(!av1c.chroma_subsampling_y);
+        assert_eq!(av1c.initial_presentation_delay_minus_one, Some(3));
+        assert!(av1c.config_obus.is_empty());
+
+        let mut buf = Vec::new();
+        av1c.write_box(&mut buf).unwrap();
+        assert_eq!(buf, data);
+    }
+}
@@ -18,6 +18,11 @@ pub struct Avc1Box {
     pub frame_count: u16,
     pub depth: u16,
     pub avcc: AvcCBox,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
 }
 
 impl Default for Avc1Box {
@@ -31,6 +36,8 @@ impl Default for Avc1Box {
             frame_count: 1,
             depth: 0x0018,
             avcc: AvcCBox::default(),
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
         }
     }
 }
@@ -46,6 +53,8 @@ impl Avc1Box {
             frame_count: 1,
             depth: 0x0018,
             avcc: AvcCBox::new(&config.seq_param_set, &config.pic_param_set),
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
         }
     }
 
@@ -54,7 +63,11 @@ impl Avc1Box {
     }
 
     pub fn get_size(&self) -> u64 {
-        HEADER_SIZE + 8 + 70 + self.avcc.box_size()
+        let mut size = HEADER_SIZE + 8 + 70 + self.avcc.box_size();
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
+        size
     }
 }
 
@@ -101,12 +114,14 @@ impl<R: Read + Seek> ReadBox<&mut R> for Avc1Box {
         let depth = reader.read_u16::<BigEndian>()?;
         reader.read_i16::<BigEndian>()?; // pre-defined
 
+        let mut avcc = None;
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
+
+        // Stop short of trailing bytes too small to hold a box.
+        let mut current = reader.stream_position()?;
         let end = start + size;
-        loop {
-            let current = reader.stream_position()?;
-            if current >= end {
-                return Err(Error::InvalidData("avcc not found"));
-            }
+        while current + HEADER_SIZE <= end {
             let header = BoxHeader::read(reader)?;
             let BoxHeader { name, size: s } = header;
             if s > size {
@@ -114,25 +129,38 @@ impl<R: Read + Seek> ReadBox<&mut R> for Avc1Box {
                     "avc1 box contains a box with a larger size than it",
                 ));
             }
-            if name == BoxType::AvcCBox {
-                let avcc = AvcCBox::read_box(reader, s)?;
-
-                skip_bytes_to(reader, start + size)?;
-
-                return Ok(Avc1Box {
-                    data_reference_index,
-                    width,
-                    height,
-                    horizresolution,
-                    vertresolution,
-                    frame_count,
-                    depth,
-                    avcc,
-                });
-            } else {
-                skip_bytes_to(reader, current + s)?;
+            child_order.push(name);
+            match name {
+                BoxType::AvcCBox => {
+                    avcc = Some(AvcCBox::read_box(reader, s)?);
+                }
+                _ => {
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
+                }
             }
+            current = reader.stream_position()?;
         }
+
+        let avcc = match avcc {
+            Some(avcc) => avcc,
+            None => return Err(Error::InvalidData("avcc not found")),
+        };
+
+        skip_bytes_to(reader, end)?;
+
+        Ok(Avc1Box {
+            data_reference_index,
+            width,
+            height,
+            horizresolution,
+            vertresolution,
+            frame_count,
+            depth,
+            avcc,
+            unknown_boxes,
+            child_order,
+        })
     }
 }
 
@@ -159,7 +187,10 @@ impl<W: Write> WriteBox<&mut W> for Avc1Box {
         writer.write_u16::<BigEndian>(self.depth)?;
         writer.write_i16::<BigEndian>(-1)?; // pre-defined
 
-        self.avcc.write_box(writer)?;
+        let mut children = ChildWriter::new();
+        children.push(&self.avcc);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
 
         Ok(size)
     }
@@ -174,10 +205,27 @@ pub struct AvcCBox {
     pub length_size_minus_one: u8,
     pub sequence_parameter_sets: Vec<NalUnit>,
     pub picture_parameter_sets: Vec<NalUnit>,
+
+    /// Stored for every profile but Baseline, Main and Extended (66, 77 and
+    /// 88), and missing from the files of some older muxers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub high_profile_ext: Option<AvcCHighProfileExt>,
 }
 
 impl AvcCBox {
+    /// Build an `avcC` from the SPS and PPS, filling the profile, level and
+    /// high profile extension from the parsed SPS. An SPS that cannot be
+    /// parsed leaves the extension out.
     pub fn new(sps: &[u8], pps: &[u8]) -> Self {
+        let high_profile_ext = match AvcSps::parse(sps) {
+            Ok(parsed) if parsed.has_high_profile_ext() => Some(AvcCHighProfileExt {
+                chroma_format: parsed.chroma_format_idc,
+                bit_depth_luma_minus8: parsed.bit_depth_luma_minus8,
+                bit_depth_chroma_minus8: parsed.bit_depth_chroma_minus8,
+                sequence_parameter_set_exts: Vec::new(),
+            }),
+            _ => None,
+        };
         Self {
             configuration_version: 1,
             avc_profile_indication: sps[1],
@@ -186,8 +234,31 @@ impl AvcCBox {
             length_size_minus_one: 0xff, // length_size = 4
             sequence_parameter_sets: vec![NalUnit::from(sps)],
             picture_parameter_sets: vec![NalUnit::from(pps)],
+            high_profile_ext,
         }
     }
+
+    fn has_high_profile(&self) -> bool {
+        !matches!(self.avc_profile_indication, 66 | 77 | 88)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct AvcCHighProfileExt {
+    pub chroma_format: u8,
+    pub bit_depth_luma_minus8: u8,
+    pub bit_depth_chroma_minus8: u8,
+    pub sequence_parameter_set_exts: Vec<NalUnit>,
+}
+
+impl AvcCHighProfileExt {
+    fn size(&self) -> u64 {
+        let mut size = 4;
+        for sps_ext in self.sequence_parameter_set_exts.iter() {
+            size += sps_ext.size() as u64;
+        }
+        size
+    }
 }
 
 impl Mp4Box for AvcCBox {
@@ -203,6 +274,9 @@ impl Mp4Box for AvcCBox {
         for pps in self.picture_parameter_sets.iter() {
             size += pps.size() as u64;
         }
+        if let Some(ref ext) = self.high_profile_ext {
+            size += ext.size();
+        }
         size
     }
 
@@ -238,9 +312,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for AvcCBox {
             picture_parameter_sets.push(nal_unit);
         }
 
-        skip_bytes_to(reader, start + size)?;
-
-        Ok(AvcCBox {
+        let mut avcc = AvcCBox {
             configuration_version,
             avc_profile_indication,
             profile_compatibility,
@@ -248,7 +320,29 @@ impl<R: Read + Seek> ReadBox<&mut R> for AvcCBox {
             length_size_minus_one,
             sequence_parameter_sets,
             picture_parameter_sets,
-        })
+            high_profile_ext: None,
+        };
+        if avcc.has_high_profile() && reader.stream_position()? + 4 <= start + size {
+            let chroma_format = reader.read_u8()? & 0x3;
+            let bit_depth_luma_minus8 = reader.read_u8()? & 0x7;
+            let bit_depth_chroma_minus8 = reader.read_u8()? & 0x7;
+            let num_of_sps_exts = reader.read_u8()?;
+            let mut sequence_parameter_set_exts = Vec::with_capacity(num_of_sps_exts as usize);
+            for _ in 0..num_of_sps_exts {
+                let nal_unit = NalUnit::read(reader)?;
+                sequence_parameter_set_exts.push(nal_unit);
+            }
+            avcc.high_profile_ext = Some(AvcCHighProfileExt {
+                chroma_format,
+                bit_depth_luma_minus8,
+                bit_depth_chroma_minus8,
+                sequence_parameter_set_exts,
+            });
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(avcc)
     }
 }
 
@@ -270,6 +364,15 @@ impl<W: Write> WriteBox<&mut W> for AvcCBox {
         for pps in self.picture_parameter_sets.iter() {
             pps.write(writer)?;
         }
+        if let Some(ref ext) = self.high_profile_ext {
+            writer.write_u8(ext.chroma_format | 0xFC)?;
+            writer.write_u8(ext.bit_depth_luma_minus8 | 0xF8)?;
+            writer.write_u8(ext.bit_depth_chroma_minus8 | 0xF8)?;
+            writer.write_u8(ext.sequence_parameter_set_exts.len() as u8)?;
+            for sps_ext in ext.sequence_parameter_set_exts.iter() {
+                sps_ext.write(writer)?;
+            }
+        }
         Ok(size)
     }
 }
@@ -337,7 +440,18 @@ mod tests {
                 picture_parameter_sets: vec![NalUnit {
                     bytes: vec![0x68, 0xEB, 0xE3, 0xCB, 0x22, 0xC0],
                 }],
+                high_profile_ext: Some(AvcCHighProfileExt {
+                    chroma_format: 1,
+                    bit_depth_luma_minus8: 0,
+                    bit_depth_chroma_minus8: 0,
+                    sequence_parameter_set_exts: Vec::new(),
+                }),
             },
+            unknown_boxes: vec![UnknownBox::new(
+                BoxType::from(0x62747274), // btrt
+                vec![0, 0, 0, 0, 0, 0x0f, 0x42, 0x40, 0, 0x0f, 0x42, 0x40],
+            )],
+            child_order: ChildOrder::default(),
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
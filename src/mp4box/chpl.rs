@@ -0,0 +1,152 @@
+use serde::Serialize;
+use std::convert::TryFrom;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Nero chapter list, stored in `moov/udta`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct ChplBox {
+    pub version: u8,
+    pub flags: u32,
+    pub chapters: Vec<ChplChapter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct ChplChapter {
+    /// Start time in 100 nanosecond units.
+    pub start: u64,
+    pub title: String,
+}
+
+impl ChplBox {
+    /// Units of [ChplChapter::start] per second.
+    pub const TIMESCALE: u64 = 10_000_000;
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::ChplBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 1;
+        if self.version > 0 {
+            size += 4;
+        }
+        for chapter in self.chapters.iter() {
+            size += 8 + 1 + chapter.title.len() as u64;
+        }
+        size
+    }
+}
+
+impl Mp4Box for ChplBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("chapter_count={}", self.chapters.len());
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for ChplBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+        if version > 0 {
+            reader.read_u32::<BigEndian>()?; // reserved
+        }
+
+        let chapter_count = reader.read_u8()?;
+        let mut chapters = Vec::with_capacity(chapter_count as usize);
+        for _ in 0..chapter_count {
+            let start = reader.read_u64::<BigEndian>()?;
+            let title_len = reader.read_u8()?;
+            let mut title = vec![0u8; title_len as usize];
+            reader.read_exact(&mut title)?;
+            chapters.push(ChplChapter {
+                start,
+                title: String::from_utf8_lossy(&title).into_owned(),
+            });
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(ChplBox {
+            version,
+            flags,
+            chapters,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for ChplBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+        if self.version > 0 {
+            writer.write_u32::<BigEndian>(0)?; // reserved
+        }
+
+        let chapter_count = u8::try_from(self.chapters.len())
+            .map_err(|_| Error::InvalidData("chpl box holds at most 255 chapters"))?;
+        writer.write_u8(chapter_count)?;
+        for chapter in self.chapters.iter() {
+            let title_len = u8::try_from(chapter.title.len())
+                .map_err(|_| Error::InvalidData("chpl chapter title longer than 255 bytes"))?;
+            writer.write_u64::<BigEndian>(chapter.start)?;
+            writer.write_u8(title_len)?;
+            writer.write_all(chapter.title.as_bytes())?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_chpl() {
+        let src_box = ChplBox {
+            version: 1,
+            flags: 0,
+            chapters: vec![
+                ChplChapter {
+                    start: 0,
+                    title: String::from("Opening"),
+                },
+                ChplChapter {
+                    start: 15 * ChplBox::TIMESCALE,
+                    title: String::from("Credits"),
+                },
+            ],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::ChplBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = ChplBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -6,6 +6,11 @@ use crate::mp4box::*;
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct DinfBox {
     dref: DrefBox,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
 }
 
 impl DinfBox {
@@ -14,7 +19,11 @@ impl DinfBox {
     }
 
     pub fn get_size(&self) -> u64 {
-        HEADER_SIZE + self.dref.box_size()
+        let mut size = HEADER_SIZE + self.dref.box_size();
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
+        size
     }
 }
 
@@ -42,7 +51,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for DinfBox {
         let start = box_start(reader)?;
 
         let mut dref = None;
+        let mut unknown_boxes = Vec::new();
 
+        let mut child_order = ChildOrder::default();
         let mut current = reader.stream_position()?;
         let end = start + size;
         while current < end {
@@ -55,13 +66,15 @@ impl<R: Read + Seek> ReadBox<&mut R> for DinfBox {
                 ));
             }
 
+            child_order.push(name);
+
             match name {
                 BoxType::DrefBox => {
                     dref = Some(DrefBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
                 }
             }
 
@@ -76,6 +89,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for DinfBox {
 
         Ok(DinfBox {
             dref: dref.unwrap(),
+            unknown_boxes,
+            child_order,
         })
     }
 }
@@ -84,7 +99,10 @@ impl<W: Write> WriteBox<&mut W> for DinfBox {
     fn write_box(&self, writer: &mut W) -> Result<u64> {
         let size = self.box_size();
         BoxHeader::new(self.box_type(), size).write(writer)?;
-        self.dref.write_box(writer)?;
+        let mut children = ChildWriter::new();
+        children.push(&self.dref);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
         Ok(size)
     }
 }
@@ -96,6 +114,12 @@ pub struct DrefBox {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub url: Option<UrlBox>,
+
+    /// Data entries other than the first `url `, e.g. `urn `.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
 }
 
 impl Default for DrefBox {
@@ -104,6 +128,8 @@ impl Default for DrefBox {
             version: 0,
             flags: 0,
             url: Some(UrlBox::default()),
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
         }
     }
 }
@@ -118,6 +144,9 @@ impl DrefBox {
         if let Some(ref url) = self.url {
             size += url.box_size();
         }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
         size
     }
 }
@@ -151,6 +180,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for DrefBox {
         let end = start + size;
 
         let mut url = None;
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
 
         let entry_count = reader.read_u32::<BigEndian>()?;
         for _i in 0..entry_count {
@@ -167,12 +198,15 @@ impl<R: Read + Seek> ReadBox<&mut R> for DrefBox {
                 ));
             }
 
+            child_order.push(name);
+
             match name {
-                BoxType::UrlBox => {
+                BoxType::UrlBox if url.is_none() => {
                     url = Some(UrlBox::read_box(reader, s)?);
                 }
                 _ => {
-                    skip_box(reader, s)?;
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
                 }
             }
 
@@ -185,6 +219,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for DrefBox {
             version,
             flags,
             url,
+            unknown_boxes,
+            child_order,
         })
     }
 }
@@ -196,11 +232,13 @@ impl<W: Write> WriteBox<&mut W> for DrefBox {
 
         write_box_header_ext(writer, self.version, self.flags)?;
 
-        writer.write_u32::<BigEndian>(1)?;
+        let entry_count = self.url.iter().count() + self.unknown_boxes.len();
+        writer.write_u32::<BigEndian>(entry_count as u32)?;
 
-        if let Some(ref url) = self.url {
-            url.write_box(writer)?;
-        }
+        let mut children = ChildWriter::new();
+        children.extend(&self.url);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
 
         Ok(size)
     }
@@ -232,7 +270,7 @@ impl UrlBox {
         let mut size = HEADER_SIZE + HEADER_EXT_SIZE;
 
         if !self.location.is_empty() {
-            size += self.location.bytes().len() as u64 + 1;
+            size += self.location.len() as u64 + 1;
         }
 
         size
@@ -300,3 +338,39 @@ impl<W: Write> WriteBox<&mut W> for UrlBox {
         Ok(size)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_dinf() {
+        let src_box = DinfBox {
+            dref: DrefBox {
+                unknown_boxes: vec![UnknownBox::new(
+                    BoxType::from(u32::from_be_bytes(*b"urn ")),
+                    vec![0, 0, 0, 1, b'u', b'r', b'n', 0],
+                )],
+                ..DrefBox::default()
+            },
+            unknown_boxes: vec![UnknownBox::new(
+                BoxType::from(u32::from_be_bytes(*b"xtra")),
+                vec![1, 2, 3],
+            )],
+            child_order: ChildOrder::default(),
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::DinfBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = DinfBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
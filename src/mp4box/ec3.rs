@@ -0,0 +1,464 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::ac3::{ac3_channel_config, ac3_channel_count, ac3_sample_rate};
+use crate::mp4box::*;
+
+/// Bits of `chan_loc` that stand for a pair of channels rather than one.
+const CHAN_LOC_PAIRS: u16 = 0b0_0111_0011;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Ec3Box {
+    pub data_reference_index: u16,
+    pub channel_count: u16,
+    pub sample_size: u16,
+
+    #[serde(with = "value_u32")]
+    pub sample_rate: FixedPointU16,
+    pub dec3: Dec3Box,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
+}
+
+impl Default for Ec3Box {
+    fn default() -> Self {
+        Self {
+            data_reference_index: 0,
+            channel_count: 2,
+            sample_size: 16,
+            sample_rate: FixedPointU16::new(48000),
+            dec3: Dec3Box::default(),
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
+        }
+    }
+}
+
+impl Ec3Box {
+    pub fn new(config: &Ec3Config) -> Self {
+        let dec3 = Dec3Box::new(config);
+        Self {
+            data_reference_index: 1,
+            channel_count: dec3.channel_count(),
+            sample_size: 16,
+            sample_rate: FixedPointU16::new(dec3.sample_rate().unwrap_or(48000) as u16),
+            dec3,
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
+        }
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::Ec3Box
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + 28 + self.dec3.box_size();
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
+        size
+    }
+}
+
+impl Mp4Box for Ec3Box {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "channel_count={} sample_size={} sample_rate={}",
+            self.channel_count,
+            self.sample_size,
+            self.sample_rate.value()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for Ec3Box {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        reader.read_u32::<BigEndian>()?; // reserved
+        reader.read_u16::<BigEndian>()?; // reserved
+        let data_reference_index = reader.read_u16::<BigEndian>()?;
+        reader.read_u64::<BigEndian>()?; // reserved
+        let channel_count = reader.read_u16::<BigEndian>()?;
+        let sample_size = reader.read_u16::<BigEndian>()?;
+        reader.read_u32::<BigEndian>()?; // pre-defined, reserved
+        let sample_rate = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
+
+        let mut dec3 = None;
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
+
+        // Stop short of trailing bytes too small to hold a box.
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current + HEADER_SIZE <= end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "ec-3 box contains a box with a larger size than it",
+                ));
+            }
+            child_order.push(name);
+            match name {
+                BoxType::Dec3Box => {
+                    dec3 = Some(Dec3Box::read_box(reader, s)?);
+                }
+                _ => {
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
+                }
+            }
+            current = reader.stream_position()?;
+        }
+
+        let dec3 = match dec3 {
+            Some(dec3) => dec3,
+            None => return Err(Error::InvalidData("dec3 not found")),
+        };
+
+        skip_bytes_to(reader, end)?;
+
+        Ok(Ec3Box {
+            data_reference_index,
+            channel_count,
+            sample_size,
+            sample_rate,
+            dec3,
+            unknown_boxes,
+            child_order,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for Ec3Box {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.data_reference_index)?;
+
+        writer.write_u64::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.channel_count)?;
+        writer.write_u16::<BigEndian>(self.sample_size)?;
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u32::<BigEndian>(self.sample_rate.raw_value())?;
+
+        let mut children = ChildWriter::new();
+        children.push(&self.dec3);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
+
+        Ok(size)
+    }
+}
+
+/// An independent substream of an E-AC-3 stream, with the channels added
+/// by its dependent substreams.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct Ec3Substream {
+    pub fscod: u8,
+    pub bsid: u8,
+    pub asvc: bool,
+    pub bsmod: u8,
+    pub acmod: u8,
+    pub lfeon: bool,
+    pub num_dep_sub: u8,
+    pub chan_loc: u16,
+}
+
+impl Ec3Substream {
+    pub fn channel_count(&self) -> u16 {
+        let mut count = ac3_channel_count(self.acmod, self.lfeon);
+        if self.num_dep_sub > 0 {
+            count += (self.chan_loc & 0x1FF).count_ones() as u16;
+            count += (self.chan_loc & CHAN_LOC_PAIRS).count_ones() as u16;
+        }
+        count
+    }
+
+    fn size(&self) -> u64 {
+        if self.num_dep_sub > 0 {
+            4
+        } else {
+            3
+        }
+    }
+}
+
+/// E-AC-3 specific box, from ETSI TS 102 366 Annex F.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Dec3Box {
+    /// Data rate in kbit/s.
+    pub data_rate: u16,
+    pub substreams: Vec<Ec3Substream>,
+
+    /// Set for Dolby Atmos streams using the joint object coding extension.
+    pub complexity_index_type_a: Option<u8>,
+}
+
+impl Default for Dec3Box {
+    fn default() -> Self {
+        Self::new(&Ec3Config::default())
+    }
+}
+
+impl Dec3Box {
+    pub fn new(config: &Ec3Config) -> Self {
+        Self {
+            data_rate: config.data_rate,
+            substreams: config.substreams.clone(),
+            complexity_index_type_a: config.complexity_index_type_a,
+        }
+    }
+
+    fn first_substream(&self) -> Result<&Ec3Substream> {
+        self.substreams
+            .first()
+            .ok_or(Error::InvalidData("dec3 has no independent substream"))
+    }
+
+    pub fn sample_rate(&self) -> Result<u32> {
+        ac3_sample_rate(self.first_substream()?.fscod)
+    }
+
+    /// Data rate in bit/s.
+    pub fn bitrate(&self) -> u32 {
+        self.data_rate as u32 * 1000
+    }
+
+    /// Channels of the main program, carried by the first independent
+    /// substream and its dependent substreams.
+    pub fn channel_count(&self) -> u16 {
+        self.first_substream()
+            .map(|substream| substream.channel_count())
+            .unwrap_or(0)
+    }
+
+    pub fn channel_config(&self) -> Result<ChannelConfig> {
+        let substream = self.first_substream()?;
+        if substream.num_dep_sub > 0 {
+            match substream.channel_count() {
+                8 if substream.lfeon => Ok(ChannelConfig::SevenOne),
+                _ => Err(Error::InvalidData(
+                    "e-ac-3 channel layout has no channel configuration",
+                )),
+            }
+        } else {
+            ac3_channel_config(substream.acmod, substream.lfeon)
+        }
+    }
+}
+
+impl Mp4Box for Dec3Box {
+    fn box_type(&self) -> BoxType {
+        BoxType::Dec3Box
+    }
+
+    fn box_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + 2;
+        for substream in self.substreams.iter() {
+            size += substream.size();
+        }
+        if self.complexity_index_type_a.is_some() {
+            size += 2;
+        }
+        size
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "data_rate={} substreams={}",
+            self.data_rate,
+            self.substreams.len()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for Dec3Box {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+        let end = start + size;
+
+        let bits = reader.read_u16::<BigEndian>()?;
+        let data_rate = bits >> 3;
+        let num_ind_sub = (bits & 0x07) + 1;
+
+        let mut substreams = Vec::with_capacity(num_ind_sub as usize);
+        for _ in 0..num_ind_sub {
+            let bits = reader.read_u24::<BigEndian>()?;
+            let num_dep_sub = (bits >> 1) as u8 & 0x0F;
+            let chan_loc = if num_dep_sub > 0 {
+                ((bits as u16 & 0x01) << 8) | reader.read_u8()? as u16
+            } else {
+                0
+            };
+            substreams.push(Ec3Substream {
+                fscod: (bits >> 22) as u8 & 0x03,
+                bsid: (bits >> 17) as u8 & 0x1F,
+                asvc: (bits >> 15) & 0x01 != 0,
+                bsmod: (bits >> 12) as u8 & 0x07,
+                acmod: (bits >> 9) as u8 & 0x07,
+                lfeon: (bits >> 8) & 0x01 != 0,
+                num_dep_sub,
+                chan_loc,
+            });
+        }
+
+        let mut complexity_index_type_a = None;
+        if reader.stream_position()? + 2 <= end {
+            let flag_ec3_extension_type_a = reader.read_u8()? & 0x01 != 0;
+            let complexity_index = reader.read_u8()?;
+            if flag_ec3_extension_type_a {
+                complexity_index_type_a = Some(complexity_index);
+            }
+        }
+
+        skip_bytes_to(reader, end)?;
+
+        Ok(Dec3Box {
+            data_rate,
+            substreams,
+            complexity_index_type_a,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for Dec3Box {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        if self.substreams.is_empty() || self.substreams.len() > 8 {
+            return Err(Error::InvalidData(
+                "dec3 must have between 1 and 8 independent substreams",
+            ));
+        }
+
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        let num_ind_sub = self.substreams.len() as u16 - 1;
+        writer.write_u16::<BigEndian>((self.data_rate << 3) | num_ind_sub)?;
+
+        for substream in self.substreams.iter() {
+            let mut bits = (substream.fscod as u32 & 0x03) << 22
+                | (substream.bsid as u32 & 0x1F) << 17
+                | (substream.asvc as u32) << 15
+                | (substream.bsmod as u32 & 0x07) << 12
+                | (substream.acmod as u32 & 0x07) << 9
+                | (substream.lfeon as u32) << 8
+                | (substream.num_dep_sub as u32 & 0x0F) << 1;
+            if substream.num_dep_sub > 0 {
+                bits |= (substream.chan_loc as u32 >> 8) & 0x01;
+                writer.write_u24::<BigEndian>(bits)?;
+                writer.write_u8(substream.chan_loc as u8)?;
+            } else {
+                writer.write_u24::<BigEndian>(bits)?;
+            }
+        }
+
+        if let Some(complexity_index) = self.complexity_index_type_a {
+            writer.write_u8(0x01)?; // flag_ec3_extension_type_a
+            writer.write_u8(complexity_index)?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_ec3() {
+        let src_box = Ec3Box {
+            data_reference_index: 1,
+            channel_count: 8,
+            sample_size: 16,
+            sample_rate: FixedPointU16::new(48000),
+            dec3: Dec3Box {
+                data_rate: 1024,
+                substreams: vec![Ec3Substream {
+                    fscod: 0,
+                    bsid: 16,
+                    asvc: false,
+                    bsmod: 0,
+                    acmod: 7,
+                    lfeon: true,
+                    num_dep_sub: 1,
+                    chan_loc: 0x02,
+                }],
+                complexity_index_type_a: Some(16),
+            },
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Ec3Box);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = Ec3Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.dec3.channel_count(), 8);
+        assert_eq!(
+            dst_box.dec3.channel_config().unwrap(),
+            ChannelConfig::SevenOne
+        );
+    }
+
+    #[test]
+    fn test_dec3() {
+        // 192 kbit/s, one independent substream, 48 kHz 3/2 with LFE
+        let data = [
+            0x00, 0x00, 0x00, 0x0D, 0x64, 0x65, 0x63, 0x33, 0x06, 0x00, 0x20, 0x0F, 0x00,
+        ];
+        let mut reader = Cursor::new(&data);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Dec3Box);
+
+        let dec3 = Dec3Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(dec3.data_rate, 192);
+        assert_eq!(dec3.substreams.len(), 1);
+        assert_eq!(dec3.substreams[0].bsid, 16);
+        assert_eq!(dec3.substreams[0].acmod, 7);
+        assert!(dec3.substreams[0].lfeon);
+        assert_eq!(dec3.complexity_index_type_a, None);
+        assert_eq!(dec3.sample_rate().unwrap(), 48000);
+        assert_eq!(dec3.channel_config().unwrap(), ChannelConfig::FiveOne);
+
+        let mut buf = Vec::new();
+        dec3.write_box(&mut buf).unwrap();
+        assert_eq!(buf, data);
+    }
+}
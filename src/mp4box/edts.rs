@@ -7,6 +7,11 @@ use crate::mp4box::*;
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct EdtsBox {
     pub elst: Option<ElstBox>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
 }
 
 impl EdtsBox {
@@ -23,6 +28,9 @@ impl EdtsBox {
         if let Some(ref elst) = self.elst {
             size += elst.box_size();
         }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
         size
     }
 }
@@ -52,17 +60,31 @@ impl<R: Read + Seek> ReadBox<&mut R> for EdtsBox {
 
         let mut edts = EdtsBox::new();
 
-        let header = BoxHeader::read(reader)?;
-        let BoxHeader { name, size: s } = header;
-        if s > size {
-            return Err(Error::InvalidData(
-                "edts box contains a box with a larger size than it",
-            ));
-        }
-
-        if let BoxType::ElstBox = name {
-            let elst = ElstBox::read_box(reader, s)?;
-            edts.elst = Some(elst);
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            // Get box header.
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "edts box contains a box with a larger size than it",
+                ));
+            }
+
+            edts.child_order.push(name);
+
+            match name {
+                BoxType::ElstBox => {
+                    edts.elst = Some(ElstBox::read_box(reader, s)?);
+                }
+                _ => {
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    edts.unknown_boxes.push(unknown);
+                }
+            }
+
+            current = reader.stream_position()?;
         }
 
         skip_bytes_to(reader, start + size)?;
@@ -76,9 +98,10 @@ impl<W: Write> WriteBox<&mut W> for EdtsBox {
         let size = self.box_size();
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
-        if let Some(ref elst) = self.elst {
-            elst.write_box(writer)?;
-        }
+        let mut children = ChildWriter::new();
+        children.extend(&self.elst);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
 
         Ok(size)
     }
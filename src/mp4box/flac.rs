@@ -0,0 +1,527 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::convert::TryFrom;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Metadata block type of STREAMINFO.
+const FLAC_STREAMINFO: u8 = 0;
+
+/// Size of the STREAMINFO metadata block body.
+const FLAC_STREAMINFO_SIZE: u32 = 34;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FlacBox {
+    pub data_reference_index: u16,
+    pub channel_count: u16,
+    pub sample_size: u16,
+
+    #[serde(with = "value_u32")]
+    pub sample_rate: FixedPointU16,
+    pub dfla: DflaBox,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
+}
+
+impl Default for FlacBox {
+    fn default() -> Self {
+        Self::new(&FlacConfig::default())
+    }
+}
+
+impl FlacBox {
+    pub fn new(config: &FlacConfig) -> Self {
+        let stream_info = &config.stream_info;
+        // Rates that do not fit the 16.16 field are written as 0, leaving
+        // STREAMINFO as the only source of the rate.
+        let sample_rate = u16::try_from(stream_info.sample_rate).unwrap_or(0);
+        Self {
+            data_reference_index: 1,
+            channel_count: stream_info.channels as u16,
+            sample_size: stream_info.bits_per_sample as u16,
+            sample_rate: FixedPointU16::new(sample_rate),
+            dfla: DflaBox::new(config),
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
+        }
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::FlacBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + 28 + self.dfla.box_size();
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
+        size
+    }
+}
+
+impl Mp4Box for FlacBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "channel_count={} sample_size={} sample_rate={}",
+            self.channel_count,
+            self.sample_size,
+            self.sample_rate.value()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for FlacBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        reader.read_u32::<BigEndian>()?; // reserved
+        reader.read_u16::<BigEndian>()?; // reserved
+        let data_reference_index = reader.read_u16::<BigEndian>()?;
+        reader.read_u64::<BigEndian>()?; // reserved
+        let channel_count = reader.read_u16::<BigEndian>()?;
+        let sample_size = reader.read_u16::<BigEndian>()?;
+        reader.read_u32::<BigEndian>()?; // pre-defined, reserved
+        let sample_rate = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
+
+        let mut dfla = None;
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
+
+        // Stop short of trailing bytes too small to hold a box.
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current + HEADER_SIZE <= end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "fLaC box contains a box with a larger size than it",
+                ));
+            }
+            child_order.push(name);
+            match name {
+                BoxType::DflaBox => {
+                    dfla = Some(DflaBox::read_box(reader, s)?);
+                }
+                _ => {
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
+                }
+            }
+            current = reader.stream_position()?;
+        }
+
+        let dfla = match dfla {
+            Some(dfla) => dfla,
+            None => return Err(Error::InvalidData("dfLa not found")),
+        };
+
+        skip_bytes_to(reader, end)?;
+
+        Ok(FlacBox {
+            data_reference_index,
+            channel_count,
+            sample_size,
+            sample_rate,
+            dfla,
+            unknown_boxes,
+            child_order,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for FlacBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.data_reference_index)?;
+
+        writer.write_u64::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.channel_count)?;
+        writer.write_u16::<BigEndian>(self.sample_size)?;
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u32::<BigEndian>(self.sample_rate.raw_value())?;
+
+        let mut children = ChildWriter::new();
+        children.push(&self.dfla);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
+
+        Ok(size)
+    }
+}
+
+/// Body of the FLAC STREAMINFO metadata block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FlacStreamInfo {
+    pub min_block_size: u16,
+    pub max_block_size: u16,
+    pub min_frame_size: u32,
+    pub max_frame_size: u32,
+    pub sample_rate: u32,
+    pub channels: u8,
+    pub bits_per_sample: u8,
+    pub total_samples: u64,
+
+    #[serde(skip_serializing)]
+    pub md5: [u8; 16],
+}
+
+impl Default for FlacStreamInfo {
+    fn default() -> Self {
+        Self {
+            min_block_size: 4096,
+            max_block_size: 4096,
+            min_frame_size: 0,
+            max_frame_size: 0,
+            sample_rate: 48000,
+            channels: 2,
+            bits_per_sample: 16,
+            total_samples: 0,
+            md5: [0; 16],
+        }
+    }
+}
+
+impl FlacStreamInfo {
+    fn read<R: Read>(reader: &mut R) -> Result<Self> {
+        let min_block_size = reader.read_u16::<BigEndian>()?;
+        let max_block_size = reader.read_u16::<BigEndian>()?;
+        let min_frame_size = reader.read_u24::<BigEndian>()?;
+        let max_frame_size = reader.read_u24::<BigEndian>()?;
+
+        // sample_rate(20) channels-1(3) bits_per_sample-1(5) total_samples(36)
+        let bits = reader.read_u64::<BigEndian>()?;
+        let mut md5 = [0; 16];
+        reader.read_exact(&mut md5)?;
+
+        Ok(FlacStreamInfo {
+            min_block_size,
+            max_block_size,
+            min_frame_size,
+            max_frame_size,
+            sample_rate: (bits >> 44) as u32,
+            channels: ((bits >> 41) & 0x07) as u8 + 1,
+            bits_per_sample: ((bits >> 36) & 0x1F) as u8 + 1,
+            total_samples: bits & 0x0F_FFFF_FFFF,
+            md5,
+        })
+    }
+
+    /// Check that the fields fit their STREAMINFO encoding, where channels
+    /// and bits_per_sample are stored minus one.
+    fn validate(&self) -> Result<()> {
+        if !(1..=8).contains(&self.channels) {
+            return Err(Error::InvalidData("flac channels must be between 1 and 8"));
+        }
+        if !(4..=32).contains(&self.bits_per_sample) {
+            return Err(Error::InvalidData(
+                "flac bits_per_sample must be between 4 and 32",
+            ));
+        }
+        Ok(())
+    }
+
+    fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        self.validate()?;
+
+        writer.write_u16::<BigEndian>(self.min_block_size)?;
+        writer.write_u16::<BigEndian>(self.max_block_size)?;
+        writer.write_u24::<BigEndian>(self.min_frame_size)?;
+        writer.write_u24::<BigEndian>(self.max_frame_size)?;
+
+        let bits = (self.sample_rate as u64 & 0x0F_FFFF) << 44
+            | (self.channels as u64 - 1) << 41
+            | (self.bits_per_sample as u64 - 1) << 36
+            | self.total_samples & 0x0F_FFFF_FFFF;
+        writer.write_u64::<BigEndian>(bits)?;
+        writer.write_all(&self.md5)?;
+
+        Ok(())
+    }
+}
+
+/// A FLAC metadata block other than STREAMINFO, such as SEEKTABLE or
+/// VORBIS_COMMENT, kept as raw bytes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FlacMetadataBlock {
+    pub block_type: u8,
+
+    #[serde(skip_serializing)]
+    pub data: Vec<u8>,
+}
+
+/// FLAC specific box, from the FLAC in ISOBMFF encapsulation spec.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct DflaBox {
+    pub version: u8,
+    pub flags: u32,
+    pub stream_info: FlacStreamInfo,
+
+    /// Metadata blocks following STREAMINFO, in stream order.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub metadata_blocks: Vec<FlacMetadataBlock>,
+}
+
+impl DflaBox {
+    pub fn new(config: &FlacConfig) -> Self {
+        Self {
+            version: 0,
+            flags: 0,
+            stream_info: config.stream_info.clone(),
+            metadata_blocks: config.metadata_blocks.clone(),
+        }
+    }
+
+    /// Map the channel count to the FLAC default channel assignment. Four
+    /// and seven channels have no equivalent configuration.
+    pub fn channel_config(&self) -> Result<ChannelConfig> {
+        match self.stream_info.channels {
+            1 => Ok(ChannelConfig::Mono),
+            2 => Ok(ChannelConfig::Stereo),
+            3 => Ok(ChannelConfig::Three),
+            5 => Ok(ChannelConfig::Five),
+            6 => Ok(ChannelConfig::FiveOne),
+            8 => Ok(ChannelConfig::SevenOne),
+            _ => Err(Error::InvalidData(
+                "flac channel count has no channel configuration",
+            )),
+        }
+    }
+}
+
+impl Mp4Box for DflaBox {
+    fn box_type(&self) -> BoxType {
+        BoxType::DflaBox
+    }
+
+    fn box_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 4 + FLAC_STREAMINFO_SIZE as u64;
+        for block in self.metadata_blocks.iter() {
+            size += 4 + block.data.len() as u64;
+        }
+        size
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "sample_rate={} channels={} bits_per_sample={} total_samples={} metadata_blocks={}",
+            self.stream_info.sample_rate,
+            self.stream_info.channels,
+            self.stream_info.bits_per_sample,
+            self.stream_info.total_samples,
+            self.metadata_blocks.len()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for DflaBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+
+        let mut stream_info = None;
+        let mut metadata_blocks = Vec::new();
+
+        let end = start + size;
+        loop {
+            if reader.stream_position()? + 4 > end {
+                return Err(Error::InvalidData("dfLa metadata block out of bounds"));
+            }
+            let block_header = reader.read_u32::<BigEndian>()?;
+            let last = block_header & 0x8000_0000 != 0;
+            let block_type = (block_header >> 24) as u8 & 0x7F;
+            let length = block_header & 0x00FF_FFFF;
+            if reader.stream_position()? + length as u64 > end {
+                return Err(Error::InvalidData("dfLa metadata block out of bounds"));
+            }
+
+            if stream_info.is_none() {
+                if block_type != FLAC_STREAMINFO || length != FLAC_STREAMINFO_SIZE {
+                    return Err(Error::InvalidData("dfLa does not start with streaminfo"));
+                }
+                stream_info = Some(FlacStreamInfo::read(reader)?);
+            } else {
+                let mut data = vec![0; length as usize];
+                reader.read_exact(&mut data)?;
+                metadata_blocks.push(FlacMetadataBlock { block_type, data });
+            }
+
+            if last {
+                break;
+            }
+        }
+
+        skip_bytes_to(reader, end)?;
+
+        Ok(DflaBox {
+            version,
+            flags,
+            stream_info: stream_info.unwrap(),
+            metadata_blocks,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for DflaBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        self.stream_info.validate()?;
+        for block in self.metadata_blocks.iter() {
+            if block.block_type >= 0x7F || block.data.len() > 0x00FF_FFFF {
+                return Err(Error::InvalidData("invalid flac metadata block"));
+            }
+        }
+
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        let last_flag = |last: bool| if last { 0x8000_0000 } else { 0 };
+
+        let last = self.metadata_blocks.is_empty();
+        writer.write_u32::<BigEndian>(last_flag(last) | FLAC_STREAMINFO_SIZE)?;
+        self.stream_info.write(writer)?;
+
+        for (i, block) in self.metadata_blocks.iter().enumerate() {
+            let last = i + 1 == self.metadata_blocks.len();
+            writer.write_u32::<BigEndian>(
+                last_flag(last) | (block.block_type as u32) << 24 | block.data.len() as u32,
+            )?;
+            writer.write_all(&block.data)?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_flac() {
+        let src_box = FlacBox {
+            data_reference_index: 1,
+            channel_count: 2,
+            sample_size: 24,
+            sample_rate: FixedPointU16::new(0),
+            dfla: DflaBox {
+                version: 0,
+                flags: 0,
+                stream_info: FlacStreamInfo {
+                    min_block_size: 4608,
+                    max_block_size: 4608,
+                    min_frame_size: 14,
+                    max_frame_size: 27_403,
+                    sample_rate: 96000,
+                    channels: 2,
+                    bits_per_sample: 24,
+                    total_samples: 8_640_000,
+                    md5: [0xAB; 16],
+                },
+                metadata_blocks: vec![FlacMetadataBlock {
+                    block_type: 4,
+                    data: b"\x0d\x00\x00\x00reference libFLAC 1.4.3\x00\x00\x00\x00".to_vec(),
+                }],
+            },
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::FlacBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = FlacBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_dfla() {
+        // 44.1 kHz, stereo, 16 bits, 441000 samples
+        let data = [
+            0x00, 0x00, 0x00, 0x32, 0x64, 0x66, 0x4C, 0x61, 0x00, 0x00, 0x00, 0x00, 0x80, 0x00,
+            0x00, 0x22, 0x10, 0x00, 0x10, 0x00, 0x00, 0x00, 0x10, 0x00, 0x20, 0x00, 0x0A, 0xC4,
+            0x42, 0xF0, 0x00, 0x06, 0xBA, 0xA8, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+            0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F, 0x10,
+        ];
+        let mut reader = Cursor::new(&data);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::DflaBox);
+
+        let dfla = DflaBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(dfla.stream_info.min_block_size, 4096);
+        assert_eq!(dfla.stream_info.max_block_size, 4096);
+        assert_eq!(dfla.stream_info.min_frame_size, 16);
+        assert_eq!(dfla.stream_info.max_frame_size, 8192);
+        assert_eq!(dfla.stream_info.sample_rate, 44100);
+        assert_eq!(dfla.stream_info.channels, 2);
+        assert_eq!(dfla.stream_info.bits_per_sample, 16);
+        assert_eq!(dfla.stream_info.total_samples, 441_000);
+        assert!(dfla.metadata_blocks.is_empty());
+
+        let mut buf = Vec::new();
+        dfla.write_box(&mut buf).unwrap();
+        assert_eq!(buf, data);
+    }
+
+    #[test]
+    fn test_dfla_invalid_stream_info() {
+        let invalid = [(0, 16), (9, 16), (2, 0), (2, 3), (2, 33)];
+        for (channels, bits_per_sample) in invalid {
+            let dfla = DflaBox {
+                stream_info: FlacStreamInfo {
+                    channels,
+                    bits_per_sample,
+                    ..FlacStreamInfo::default()
+                },
+                ..DflaBox::default()
+            };
+            let mut buf = Vec::new();
+            assert!(matches!(
+                dfla.write_box(&mut buf),
+                Err(Error::InvalidData(_))
+            ));
+            assert!(buf.is_empty());
+
+            let mut buf = Vec::new();
+            assert!(matches!(
+                dfla.stream_info.write(&mut buf),
+                Err(Error::InvalidData(_))
+            ));
+        }
+    }
+}
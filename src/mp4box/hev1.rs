@@ -18,6 +18,11 @@ pub struct Hev1Box {
     pub frame_count: u16,
     pub depth: u16,
     pub hvcc: HvcCBox,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
 }
 
 impl Default for Hev1Box {
@@ -31,6 +36,8 @@ impl Default for Hev1Box {
             frame_count: 1,
             depth: 0x0018,
             hvcc: HvcCBox::default(),
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
         }
     }
 }
@@ -69,6 +76,8 @@ impl Hev1Box {
                 length_size_minus_one: config.length_size_minus_one.unwrap_or(3),
                 arrays: config.arrays.clone().unwrap_or_default(),
             },
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
         }
     }
 
@@ -77,7 +86,11 @@ impl Hev1Box {
     }
 
     pub fn get_size(&self) -> u64 {
-        HEADER_SIZE + 8 + 70 + self.hvcc.box_size()
+        let mut size = HEADER_SIZE + 8 + 70 + self.hvcc.box_size();
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
+        size
     }
 }
 
@@ -124,31 +137,53 @@ impl<R: Read + Seek> ReadBox<&mut R> for Hev1Box {
         let depth = reader.read_u16::<BigEndian>()?;
         reader.read_i16::<BigEndian>()?; // pre-defined
 
-        let header = BoxHeader::read(reader)?;
-        let BoxHeader { name, size: s } = header;
-        if s > size {
-            return Err(Error::InvalidData(
-                "hev1 box contains a box with a larger size than it",
-            ));
-        }
-        if name == BoxType::HvcCBox {
-            let hvcc = HvcCBox::read_box(reader, s)?;
-
-            skip_bytes_to(reader, start + size)?;
-
-            Ok(Hev1Box {
-                data_reference_index,
-                width,
-                height,
-                horizresolution,
-                vertresolution,
-                frame_count,
-                depth,
-                hvcc,
-            })
-        } else {
-            Err(Error::InvalidData("hvcc not found"))
+        let mut hvcc = None;
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
+
+        // Stop short of trailing bytes too small to hold a box.
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current + HEADER_SIZE <= end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "hev1 box contains a box with a larger size than it",
+                ));
+            }
+            child_order.push(name);
+            match name {
+                BoxType::HvcCBox => {
+                    hvcc = Some(HvcCBox::read_box(reader, s)?);
+                }
+                _ => {
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
+                }
+            }
+            current = reader.stream_position()?;
         }
+
+        let hvcc = match hvcc {
+            Some(hvcc) => hvcc,
+            None => return Err(Error::InvalidData("hvcc not found")),
+        };
+
+        skip_bytes_to(reader, end)?;
+
+        Ok(Hev1Box {
+            data_reference_index,
+            width,
+            height,
+            horizresolution,
+            vertresolution,
+            frame_count,
+            depth,
+            hvcc,
+            unknown_boxes,
+            child_order,
+        })
     }
 }
 
@@ -175,7 +210,10 @@ impl<W: Write> WriteBox<&mut W> for Hev1Box {
         writer.write_u16::<BigEndian>(self.depth)?;
         writer.write_i16::<BigEndian>(-1)?; // pre-defined
 
-        self.hvcc.write_box(writer)?;
+        let mut children = ChildWriter::new();
+        children.push(&self.hvcc);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
 
         Ok(size)
     }
@@ -293,11 +331,13 @@ pub struct HvcCArray {
 }
 
 impl<R: Read + Seek> ReadBox<&mut R> for HvcCBox {
-    fn read_box(reader: &mut R, _size: u64) -> Result<Self> {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
         let configuration_version = reader.read_u8()?;
         let params = reader.read_u8()?;
-        let general_profile_space = params & 0b11000000 >> 6;
-        let general_tier_flag = (params & 0b00100000 >> 5) > 0;
+        let general_profile_space = params >> 6;
+        let general_tier_flag = (params & 0b00100000) > 0;
         let general_profile_idc = params & 0b00011111;
 
         let general_profile_compatibility_flags = reader.read_u32::<BigEndian>()?;
@@ -311,9 +351,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for HvcCBox {
         let avg_frame_rate = reader.read_u16::<BigEndian>()?;
 
         let params = reader.read_u8()?;
-        let constant_frame_rate = params & 0b11000000 >> 6;
-        let num_temporal_layers = params & 0b00111000 >> 3;
-        let temporal_id_nested = (params & 0b00000100 >> 2) > 0;
+        let constant_frame_rate = params >> 6;
+        let num_temporal_layers = (params & 0b00111000) >> 3;
+        let temporal_id_nested = (params & 0b00000100) > 0;
         let length_size_minus_one = params & 0b000011;
 
         let num_of_arrays = reader.read_u8()?;
@@ -340,6 +380,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for HvcCBox {
             });
         }
 
+        skip_bytes_to(reader, start + size)?;
+
         Ok(HvcCBox {
             configuration_version,
             general_profile_space,
@@ -427,6 +469,8 @@ mod tests {
                 configuration_version: 1,
                 ..Default::default()
             },
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -440,4 +484,46 @@ mod tests {
         let dst_box = Hev1Box::read_box(&mut reader, header.size).unwrap();
         assert_eq!(src_box, dst_box);
     }
+
+    #[test]
+    fn test_hvcc() {
+        let src_box = HvcCBox {
+            configuration_version: 1,
+            general_profile_space: 1,
+            general_tier_flag: true,
+            general_profile_idc: 2,
+            general_profile_compatibility_flags: 0x2000_0000,
+            general_constraint_indicator_flag: 0x9000_0000_0000,
+            general_level_idc: 120,
+            min_spatial_segmentation_idc: 0,
+            parallelism_type: 0,
+            chroma_format_idc: 1,
+            bit_depth_luma_minus8: 2,
+            bit_depth_chroma_minus8: 2,
+            avg_frame_rate: 0,
+            constant_frame_rate: 1,
+            num_temporal_layers: 3,
+            temporal_id_nested: true,
+            length_size_minus_one: 3,
+            arrays: vec![HvcCArray {
+                completeness: true,
+                nal_unit_type: VPS,
+                nalus: vec![HvcCArrayNalu {
+                    size: 4,
+                    data: vec![0x40, 0x01, 0x0c, 0x01],
+                }],
+            }],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::HvcCBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = HvcCBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
 }
@@ -18,6 +18,11 @@ pub struct Hvc1Box {
     pub frame_count: u16,
     pub depth: u16,
     pub hvcc: HvcCBox,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
 }
 
 impl Default for Hvc1Box {
@@ -31,6 +36,8 @@ impl Default for Hvc1Box {
             frame_count: 1,
             depth: 0x0018,
             hvcc: HvcCBox::default(),
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
         }
     }
 }
@@ -69,6 +76,8 @@ impl Hvc1Box {
                 length_size_minus_one: config.length_size_minus_one.unwrap_or(3),
                 arrays: config.arrays.clone().unwrap_or_default(),
             },
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
         }
     }
 
@@ -77,7 +86,11 @@ impl Hvc1Box {
     }
 
     pub fn get_size(&self) -> u64 {
-        HEADER_SIZE + 8 + 70 + self.hvcc.box_size()
+        let mut size = HEADER_SIZE + 8 + 70 + self.hvcc.box_size();
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
+        size
     }
 }
 
@@ -124,31 +137,53 @@ impl<R: Read + Seek> ReadBox<&mut R> for Hvc1Box {
         let depth = reader.read_u16::<BigEndian>()?;
         reader.read_i16::<BigEndian>()?; // pre-defined
 
-        let header = BoxHeader::read(reader)?;
-        let BoxHeader { name, size: s } = header;
-        if s > size {
-            return Err(Error::InvalidData(
-                "hev1 box contains a box with a larger size than it",
-            ));
-        }
-        if name == BoxType::HvcCBox {
-            let hvcc = HvcCBox::read_box(reader, s)?;
-
-            skip_bytes_to(reader, start + size)?;
-
-            Ok(Hvc1Box {
-                data_reference_index,
-                width,
-                height,
-                horizresolution,
-                vertresolution,
-                frame_count,
-                depth,
-                hvcc,
-            })
-        } else {
-            Err(Error::InvalidData("hvcc not found"))
+        let mut hvcc = None;
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
+
+        // Stop short of trailing bytes too small to hold a box.
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current + HEADER_SIZE <= end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "hvc1 box contains a box with a larger size than it",
+                ));
+            }
+            child_order.push(name);
+            match name {
+                BoxType::HvcCBox => {
+                    hvcc = Some(HvcCBox::read_box(reader, s)?);
+                }
+                _ => {
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
+                }
+            }
+            current = reader.stream_position()?;
         }
+
+        let hvcc = match hvcc {
+            Some(hvcc) => hvcc,
+            None => return Err(Error::InvalidData("hvcc not found")),
+        };
+
+        skip_bytes_to(reader, end)?;
+
+        Ok(Hvc1Box {
+            data_reference_index,
+            width,
+            height,
+            horizresolution,
+            vertresolution,
+            frame_count,
+            depth,
+            hvcc,
+            unknown_boxes,
+            child_order,
+        })
     }
 }
 
@@ -175,7 +210,10 @@ impl<W: Write> WriteBox<&mut W> for Hvc1Box {
         writer.write_u16::<BigEndian>(self.depth)?;
         writer.write_i16::<BigEndian>(-1)?; // pre-defined
 
-        self.hvcc.write_box(writer)?;
+        let mut children = ChildWriter::new();
+        children.push(&self.hvcc);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
 
         Ok(size)
     }
@@ -201,6 +239,8 @@ mod tests {
                 configuration_version: 1,
                 ..Default::default()
             },
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -1,5 +1,6 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::io::{Read, Seek};
 
 use byteorder::ByteOrder;
@@ -11,6 +12,15 @@ use crate::mp4box::*;
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct IlstBox {
     pub items: HashMap<MetadataKey, IlstItemBox>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub free_form: Vec<IlstFreeFormBox>,
+
+    /// Items with no [MetadataKey], such as `stik` or `purd`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
 }
 
 impl IlstBox {
@@ -23,8 +33,127 @@ impl IlstBox {
         for item in self.items.values() {
             size += item.get_size();
         }
+        for item in self.free_form.iter() {
+            size += item.get_size();
+        }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
         size
     }
+
+    /// Set a text item, such as [MetadataKey::Title] or [MetadataKey::Artist].
+    pub fn set_text(&mut self, key: MetadataKey, value: &str) {
+        self.set_data(key, DataType::Text, value.as_bytes().to_vec());
+    }
+
+    pub fn set_year(&mut self, year: u32) {
+        self.set_text(MetadataKey::Year, &year.to_string());
+    }
+
+    /// Set the cover art, with [DataType::Image] for JPEG, [DataType::Png]
+    /// or [DataType::Bmp].
+    pub fn set_poster(&mut self, image: Vec<u8>, data_type: DataType) {
+        self.set_data(MetadataKey::Poster, data_type, image);
+    }
+
+    /// Add an image to the cover art, after those already set.
+    pub fn add_poster(&mut self, image: Vec<u8>, data_type: DataType) {
+        let data = DataBox {
+            data: image,
+            data_type,
+        };
+        self.items
+            .entry(MetadataKey::Poster)
+            .or_default()
+            .data
+            .push(data);
+    }
+
+    /// All the images of the cover art, see [Metadata::poster] for the first.
+    pub fn posters(&self) -> impl Iterator<Item = &[u8]> {
+        self.items
+            .get(&MetadataKey::Poster)
+            .into_iter()
+            .flat_map(|item| item.data.iter().map(|data| data.data.as_slice()))
+    }
+
+    /// Set the track number and the total number of tracks (0 if unknown).
+    pub fn set_track_number(&mut self, number: u16, total: u16) {
+        let mut data = vec![0; 8];
+        BigEndian::write_u16(&mut data[2..], number);
+        BigEndian::write_u16(&mut data[4..], total);
+        self.set_data(MetadataKey::TrackNumber, DataType::Binary, data);
+    }
+
+    /// Set the disc number and the total number of discs (0 if unknown).
+    pub fn set_disc_number(&mut self, number: u16, total: u16) {
+        let mut data = vec![0; 6];
+        BigEndian::write_u16(&mut data[2..], number);
+        BigEndian::write_u16(&mut data[4..], total);
+        self.set_data(MetadataKey::DiscNumber, DataType::Binary, data);
+    }
+
+    pub fn set_tempo(&mut self, bpm: u16) {
+        let data = bpm.to_be_bytes().to_vec();
+        self.set_data(MetadataKey::Tempo, DataType::TempoCpil, data);
+    }
+
+    pub fn set_compilation(&mut self, compilation: bool) {
+        let data = vec![compilation as u8];
+        self.set_data(MetadataKey::Compilation, DataType::TempoCpil, data);
+    }
+
+    /// Set the content rating: 0 for none, 1 for explicit, 2 for clean.
+    pub fn set_rating(&mut self, rating: u8) {
+        self.set_data(MetadataKey::Rating, DataType::TempoCpil, vec![rating]);
+    }
+
+    /// Set a free-form text item, replacing any item with the same `mean`
+    /// and `name`.
+    pub fn set_free_form(&mut self, mean: &str, name: &str, value: &str) {
+        let data = DataBox {
+            data: value.as_bytes().to_vec(),
+            data_type: DataType::Text,
+        };
+        match self
+            .free_form
+            .iter_mut()
+            .find(|item| item.mean == mean && item.name == name)
+        {
+            Some(item) => item.data = vec![data],
+            None => self.free_form.push(IlstFreeFormBox {
+                mean: mean.to_string(),
+                name: name.to_string(),
+                data: vec![data],
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn set_data(&mut self, key: MetadataKey, data_type: DataType, data: Vec<u8>) {
+        let data = DataBox { data, data_type };
+        self.items.insert(
+            key,
+            IlstItemBox {
+                data: vec![data],
+                ..Default::default()
+            },
+        );
+    }
+
+    /// The first value of an item.
+    fn data(&self, key: MetadataKey) -> Option<&DataBox> {
+        self.items.get(&key).and_then(|item| item.data.first())
+    }
+
+    fn text(&self, key: MetadataKey) -> Option<Cow<'_, str>> {
+        self.data(key).map(data_to_str)
+    }
+
+    fn int(&self, key: MetadataKey) -> Option<u64> {
+        self.data(key).and_then(data_to_int)
+    }
 }
 
 impl Mp4Box for IlstBox {
@@ -41,7 +170,8 @@ impl Mp4Box for IlstBox {
     }
 
     fn summary(&self) -> Result<String> {
-        let s = format!("item_count={}", self.items.len());
+        let item_count = self.items.len() + self.free_form.len() + self.unknown_boxes.len();
+        let s = format!("item_count={}", item_count);
         Ok(s)
     }
 }
@@ -51,6 +181,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for IlstBox {
         let start = box_start(reader)?;
 
         let mut items = HashMap::new();
+        let mut free_form = Vec::new();
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -64,23 +197,15 @@ impl<R: Read + Seek> ReadBox<&mut R> for IlstBox {
                 ));
             }
 
-            match name {
-                BoxType::NameBox => {
-                    items.insert(MetadataKey::Title, IlstItemBox::read_box(reader, s)?);
-                }
-                BoxType::DayBox => {
-                    items.insert(MetadataKey::Year, IlstItemBox::read_box(reader, s)?);
-                }
-                BoxType::CovrBox => {
-                    items.insert(MetadataKey::Poster, IlstItemBox::read_box(reader, s)?);
-                }
-                BoxType::DescBox => {
-                    items.insert(MetadataKey::Summary, IlstItemBox::read_box(reader, s)?);
-                }
-                _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
-                }
+            child_order.push(name);
+
+            if name == BoxType::FreeFormBox {
+                free_form.push(IlstFreeFormBox::read_box(reader, s)?);
+            } else if let Some(key) = item_key(name) {
+                items.insert(key, IlstItemBox::read_box(reader, s)?);
+            } else {
+                let unknown = UnknownBox::read(reader, &header)?;
+                unknown_boxes.push(unknown);
             }
 
             current = reader.stream_position()?;
@@ -88,7 +213,12 @@ impl<R: Read + Seek> ReadBox<&mut R> for IlstBox {
 
         skip_bytes_to(reader, start + size)?;
 
-        Ok(IlstBox { items })
+        Ok(IlstBox {
+            items,
+            free_form,
+            unknown_boxes,
+            child_order,
+        })
     }
 }
 
@@ -97,28 +227,113 @@ impl<W: Write> WriteBox<&mut W> for IlstBox {
         let size = self.box_size();
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
-        for (key, value) in &self.items {
-            let name = match key {
-                MetadataKey::Title => BoxType::NameBox,
-                MetadataKey::Year => BoxType::DayBox,
-                MetadataKey::Poster => BoxType::CovrBox,
-                MetadataKey::Summary => BoxType::DescBox,
-            };
-            BoxHeader::new(name, value.get_size()).write(writer)?;
-            value.data.write_box(writer)?;
+        let mut keys: Vec<&MetadataKey> = self.items.keys().collect();
+        keys.sort();
+
+        let mut children = ChildWriter::new();
+        for key in keys {
+            let item = &self.items[key];
+            let box_type = item_box_type(key);
+            children.push_with(box_type, move |writer| item.write(writer, box_type));
+        }
+        for item in self.free_form.iter() {
+            children.push_with(BoxType::FreeFormBox, move |writer| item.write(writer));
         }
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
         Ok(size)
     }
 }
 
+fn item_key(name: BoxType) -> Option<MetadataKey> {
+    let key = match name {
+        BoxType::NameBox => MetadataKey::Title,
+        BoxType::DayBox => MetadataKey::Year,
+        BoxType::CovrBox => MetadataKey::Poster,
+        BoxType::DescBox => MetadataKey::Summary,
+        BoxType::ArtBox => MetadataKey::Artist,
+        BoxType::AartBox => MetadataKey::AlbumArtist,
+        BoxType::AlbBox => MetadataKey::Album,
+        BoxType::GenBox => MetadataKey::Genre,
+        BoxType::GnreBox => MetadataKey::GenreId,
+        BoxType::WrtBox => MetadataKey::Composer,
+        BoxType::CmtBox => MetadataKey::Comment,
+        BoxType::TrknBox => MetadataKey::TrackNumber,
+        BoxType::DiskBox => MetadataKey::DiscNumber,
+        BoxType::TmpoBox => MetadataKey::Tempo,
+        BoxType::CpilBox => MetadataKey::Compilation,
+        BoxType::TooBox => MetadataKey::EncoderTool,
+        BoxType::SonmBox => MetadataKey::SortTitle,
+        BoxType::SoarBox => MetadataKey::SortArtist,
+        BoxType::SoaaBox => MetadataKey::SortAlbumArtist,
+        BoxType::SoalBox => MetadataKey::SortAlbum,
+        BoxType::SocoBox => MetadataKey::SortComposer,
+        BoxType::RtngBox => MetadataKey::Rating,
+        _ => return None,
+    };
+    Some(key)
+}
+
+fn item_box_type(key: &MetadataKey) -> BoxType {
+    match key {
+        MetadataKey::Title => BoxType::NameBox,
+        MetadataKey::Year => BoxType::DayBox,
+        MetadataKey::Poster => BoxType::CovrBox,
+        MetadataKey::Summary => BoxType::DescBox,
+        MetadataKey::Artist => BoxType::ArtBox,
+        MetadataKey::AlbumArtist => BoxType::AartBox,
+        MetadataKey::Album => BoxType::AlbBox,
+        MetadataKey::Genre => BoxType::GenBox,
+        MetadataKey::GenreId => BoxType::GnreBox,
+        MetadataKey::Composer => BoxType::WrtBox,
+        MetadataKey::Comment => BoxType::CmtBox,
+        MetadataKey::TrackNumber => BoxType::TrknBox,
+        MetadataKey::DiscNumber => BoxType::DiskBox,
+        MetadataKey::Tempo => BoxType::TmpoBox,
+        MetadataKey::Compilation => BoxType::CpilBox,
+        MetadataKey::EncoderTool => BoxType::TooBox,
+        MetadataKey::SortTitle => BoxType::SonmBox,
+        MetadataKey::SortArtist => BoxType::SoarBox,
+        MetadataKey::SortAlbumArtist => BoxType::SoaaBox,
+        MetadataKey::SortAlbum => BoxType::SoalBox,
+        MetadataKey::SortComposer => BoxType::SocoBox,
+        MetadataKey::Rating => BoxType::RtngBox,
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct IlstItemBox {
-    pub data: DataBox,
+    /// The values of the item, usually one, though `covr` may hold several
+    /// images.
+    pub data: Vec<DataBox>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
 }
 
 impl IlstItemBox {
     fn get_size(&self) -> u64 {
-        HEADER_SIZE + self.data.box_size()
+        let mut size = HEADER_SIZE;
+        for data in self.data.iter() {
+            size += data.box_size();
+        }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
+        size
+    }
+
+    fn write<W: Write>(&self, writer: &mut W, box_type: BoxType) -> Result<u64> {
+        let size = self.get_size();
+        BoxHeader::new(box_type, size).write(writer)?;
+
+        let mut children = ChildWriter::new();
+        children.extend(&self.data);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
+        Ok(size)
     }
 }
 
@@ -126,7 +341,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for IlstItemBox {
     fn read_box(reader: &mut R, size: u64) -> Result<Self> {
         let start = box_start(reader)?;
 
-        let mut data = None;
+        let mut data = Vec::new();
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -140,65 +357,456 @@ impl<R: Read + Seek> ReadBox<&mut R> for IlstItemBox {
                 ));
             }
 
+            child_order.push(name);
+
             match name {
                 BoxType::DataBox => {
-                    data = Some(DataBox::read_box(reader, s)?);
+                    data.push(DataBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
                 }
             }
 
             current = reader.stream_position()?;
         }
 
-        if data.is_none() {
+        if data.is_empty() {
             return Err(Error::BoxNotFound(BoxType::DataBox));
         }
 
         skip_bytes_to(reader, start + size)?;
 
         Ok(IlstItemBox {
-            data: data.unwrap(),
+            data,
+            unknown_boxes,
+            child_order,
+        })
+    }
+}
+
+/// A free-form (`----`) item, named by a reverse domain `mean` such as
+/// `com.apple.iTunes` and a `name` within it.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct IlstFreeFormBox {
+    pub mean: String,
+    pub name: String,
+    pub data: Vec<DataBox>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
+}
+
+impl IlstFreeFormBox {
+    fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE
+            + HEADER_SIZE
+            + HEADER_EXT_SIZE
+            + self.mean.len() as u64
+            + HEADER_SIZE
+            + HEADER_EXT_SIZE
+            + self.name.len() as u64;
+        for data in self.data.iter() {
+            size += data.box_size();
+        }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
+        size
+    }
+
+    fn write<W: Write>(&self, writer: &mut W) -> Result<u64> {
+        let size = self.get_size();
+        BoxHeader::new(BoxType::FreeFormBox, size).write(writer)?;
+
+        let mut children = ChildWriter::new();
+        children.push_with(BoxType::MeanBox, move |writer| {
+            write_free_form_string(writer, BoxType::MeanBox, &self.mean)
+        });
+        children.push_with(BoxType::FreeFormNameBox, move |writer| {
+            write_free_form_string(writer, BoxType::FreeFormNameBox, &self.name)
+        });
+        children.extend(&self.data);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
+        Ok(size)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for IlstFreeFormBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let mut mean = None;
+        let mut name = None;
+        let mut data = Vec::new();
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            // Get box header.
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader {
+                name: box_name,
+                size: s,
+            } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "ilst free-form box contains a box with a larger size than it",
+                ));
+            }
+
+            child_order.push(box_name);
+
+            match box_name {
+                BoxType::MeanBox => {
+                    mean = Some(read_free_form_string(reader, s)?);
+                }
+                BoxType::FreeFormNameBox => {
+                    name = Some(read_free_form_string(reader, s)?);
+                }
+                BoxType::DataBox => {
+                    data.push(DataBox::read_box(reader, s)?);
+                }
+                _ => {
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
+                }
+            }
+
+            current = reader.stream_position()?;
+        }
+
+        if data.is_empty() {
+            return Err(Error::BoxNotFound(BoxType::DataBox));
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(IlstFreeFormBox {
+            mean: mean.ok_or(Error::BoxNotFound(BoxType::MeanBox))?,
+            name: name.ok_or(Error::BoxNotFound(BoxType::FreeFormNameBox))?,
+            data,
+            unknown_boxes,
+            child_order,
         })
     }
 }
 
+fn read_free_form_string<R: Read + Seek>(reader: &mut R, size: u64) -> Result<String> {
+    if size < HEADER_SIZE + HEADER_EXT_SIZE {
+        return Err(Error::InvalidData("free-form string box too small"));
+    }
+    read_box_header_ext(reader)?;
+    let mut buf = vec![0u8; (size - HEADER_SIZE - HEADER_EXT_SIZE) as usize];
+    reader.read_exact(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+fn write_free_form_string<W: Write>(writer: &mut W, name: BoxType, value: &str) -> Result<u64> {
+    let size = HEADER_SIZE + HEADER_EXT_SIZE + value.len() as u64;
+    BoxHeader::new(name, size).write(writer)?;
+    write_box_header_ext(writer, 0, 0)?;
+    writer.write_all(value.as_bytes())?;
+    Ok(size)
+}
+
 impl<'a> Metadata<'a> for IlstBox {
-    fn title(&self) -> Option<Cow<str>> {
-        self.items.get(&MetadataKey::Title).map(item_to_str)
+    fn title(&self) -> Option<Cow<'_, str>> {
+        self.text(MetadataKey::Title)
     }
 
     fn year(&self) -> Option<u32> {
-        self.items.get(&MetadataKey::Year).and_then(item_to_u32)
+        self.data(MetadataKey::Year).and_then(data_to_u32)
     }
 
     fn poster(&self) -> Option<&[u8]> {
-        self.items.get(&MetadataKey::Poster).map(item_to_bytes)
+        self.data(MetadataKey::Poster)
+            .map(|data| data.data.as_slice())
+    }
+
+    fn summary(&self) -> Option<Cow<'_, str>> {
+        self.text(MetadataKey::Summary)
+    }
+
+    fn artist(&self) -> Option<Cow<'_, str>> {
+        self.text(MetadataKey::Artist)
+    }
+
+    fn album_artist(&self) -> Option<Cow<'_, str>> {
+        self.text(MetadataKey::AlbumArtist)
+    }
+
+    fn album(&self) -> Option<Cow<'_, str>> {
+        self.text(MetadataKey::Album)
+    }
+
+    fn genre(&self) -> Option<Cow<'_, str>> {
+        self.text(MetadataKey::Genre).or_else(|| {
+            let id = self.int(MetadataKey::GenreId)?;
+            let genre = ID3V1_GENRES.get(usize::try_from(id.checked_sub(1)?).ok()?)?;
+            Some(Cow::Borrowed(*genre))
+        })
+    }
+
+    fn composer(&self) -> Option<Cow<'_, str>> {
+        self.text(MetadataKey::Composer)
+    }
+
+    fn comment(&self) -> Option<Cow<'_, str>> {
+        self.text(MetadataKey::Comment)
     }
 
-    fn summary(&self) -> Option<Cow<str>> {
-        self.items.get(&MetadataKey::Summary).map(item_to_str)
+    fn track_number(&self) -> Option<(u16, u16)> {
+        self.data(MetadataKey::TrackNumber)
+            .and_then(data_to_number_pair)
+    }
+
+    fn disc_number(&self) -> Option<(u16, u16)> {
+        self.data(MetadataKey::DiscNumber)
+            .and_then(data_to_number_pair)
+    }
+
+    fn tempo(&self) -> Option<u16> {
+        self.int(MetadataKey::Tempo)
+            .and_then(|tempo| u16::try_from(tempo).ok())
+    }
+
+    fn compilation(&self) -> Option<bool> {
+        self.int(MetadataKey::Compilation)
+            .map(|compilation| compilation != 0)
+    }
+
+    fn encoder_tool(&self) -> Option<Cow<'_, str>> {
+        self.text(MetadataKey::EncoderTool)
+    }
+
+    fn sort_title(&self) -> Option<Cow<'_, str>> {
+        self.text(MetadataKey::SortTitle)
+    }
+
+    fn sort_artist(&self) -> Option<Cow<'_, str>> {
+        self.text(MetadataKey::SortArtist)
+    }
+
+    fn sort_album_artist(&self) -> Option<Cow<'_, str>> {
+        self.text(MetadataKey::SortAlbumArtist)
+    }
+
+    fn sort_album(&self) -> Option<Cow<'_, str>> {
+        self.text(MetadataKey::SortAlbum)
+    }
+
+    fn sort_composer(&self) -> Option<Cow<'_, str>> {
+        self.text(MetadataKey::SortComposer)
+    }
+
+    fn rating(&self) -> Option<u8> {
+        self.int(MetadataKey::Rating)
+            .and_then(|rating| u8::try_from(rating).ok())
+    }
+
+    fn free_form(&self, mean: &str, name: &str) -> Option<Cow<'_, str>> {
+        self.free_form
+            .iter()
+            .find(|item| item.mean == mean && item.name == name)
+            .and_then(|item| item.data.first())
+            .map(data_to_str)
     }
 }
 
-fn item_to_bytes(item: &IlstItemBox) -> &[u8] {
-    &item.data.data
+fn data_to_str(data: &DataBox) -> Cow<'_, str> {
+    String::from_utf8_lossy(&data.data)
 }
 
-fn item_to_str(item: &IlstItemBox) -> Cow<str> {
-    String::from_utf8_lossy(&item.data.data)
+fn data_to_u32(data: &DataBox) -> Option<u32> {
+    match data.data_type {
+        DataType::Binary if data.data.len() == 4 => Some(BigEndian::read_u32(&data.data)),
+        DataType::Text => String::from_utf8_lossy(&data.data).parse::<u32>().ok(),
+        _ => None,
+    }
 }
 
-fn item_to_u32(item: &IlstItemBox) -> Option<u32> {
-    match item.data.data_type {
-        DataType::Binary if item.data.data.len() == 4 => Some(BigEndian::read_u32(&item.data.data)),
-        DataType::Text => String::from_utf8_lossy(&item.data.data).parse::<u32>().ok(),
+/// Read a big-endian integer item, stored in 1, 2, 4 or 8 bytes.
+fn data_to_int(data: &DataBox) -> Option<u64> {
+    let bytes = &data.data;
+    match data.data_type {
+        DataType::Binary | DataType::TempoCpil if matches!(bytes.len(), 1 | 2 | 4 | 8) => {
+            Some(BigEndian::read_uint(bytes, bytes.len()))
+        }
         _ => None,
     }
 }
 
+/// Read a `trkn` or `disk` item: 2 reserved bytes, the number and the total.
+fn data_to_number_pair(data: &DataBox) -> Option<(u16, u16)> {
+    let bytes = &data.data;
+    if bytes.len() < 6 {
+        return None;
+    }
+    Some((
+        BigEndian::read_u16(&bytes[2..]),
+        BigEndian::read_u16(&bytes[4..]),
+    ))
+}
+
+/// The ID3v1 genres with the Winamp extensions, which `gnre` numbers from 1.
+const ID3V1_GENRES: [&str; 148] = [
+    "Blues",
+    "Classic Rock",
+    "Country",
+    "Dance",
+    "Disco",
+    "Funk",
+    "Grunge",
+    "Hip-Hop",
+    "Jazz",
+    "Metal",
+    "New Age",
+    "Oldies",
+    "Other",
+    "Pop",
+    "R&B",
+    "Rap",
+    "Reggae",
+    "Rock",
+    "Techno",
+    "Industrial",
+    "Alternative",
+    "Ska",
+    "Death Metal",
+    "Pranks",
+    "Soundtrack",
+    "Euro-Techno",
+    "Ambient",
+    "Trip-Hop",
+    "Vocal",
+    "Jazz+Funk",
+    "Fusion",
+    "Trance",
+    "Classical",
+    "Instrumental",
+    "Acid",
+    "House",
+    "Game",
+    "Sound Clip",
+    "Gospel",
+    "Noise",
+    "AlternRock",
+    "Bass",
+    "Soul",
+    "Punk",
+    "Space",
+    "Meditative",
+    "Instrumental Pop",
+    "Instrumental Rock",
+    "Ethnic",
+    "Gothic",
+    "Darkwave",
+    "Techno-Industrial",
+    "Electronic",
+    "Pop-Folk",
+    "Eurodance",
+    "Dream",
+    "Southern Rock",
+    "Comedy",
+    "Cult",
+    "Gangsta",
+    "Top 40",
+    "Christian Rap",
+    "Pop/Funk",
+    "Jungle",
+    "Native American",
+    "Cabaret",
+    "New Wave",
+    "Psychedelic",
+    "Rave",
+    "Showtunes",
+    "Trailer",
+    "Lo-Fi",
+    "Tribal",
+    "Acid Punk",
+    "Acid Jazz",
+    "Polka",
+    "Retro",
+    "Musical",
+    "Rock & Roll",
+    "Hard Rock",
+    "Folk",
+    "Folk-Rock",
+    "National Folk",
+    "Swing",
+    "Fast Fusion",
+    "Bebop",
+    "Latin",
+    "Revival",
+    "Celtic",
+    "Bluegrass",
+    "Avantgarde",
+    "Gothic Rock",
+    "Progressive Rock",
+    "Psychedelic Rock",
+    "Symphonic Rock",
+    "Slow Rock",
+    "Big Band",
+    "Chorus",
+    "Easy Listening",
+    "Acoustic",
+    "Humour",
+    "Speech",
+    "Chanson",
+    "Opera",
+    "Chamber Music",
+    "Sonata",
+    "Symphony",
+    "Booty Bass",
+    "Primus",
+    "Porn Groove",
+    "Satire",
+    "Slow Jam",
+    "Club",
+    "Tango",
+    "Samba",
+    "Folklore",
+    "Ballad",
+    "Power Ballad",
+    "Rhythmic Soul",
+    "Freestyle",
+    "Duet",
+    "Punk Rock",
+    "Drum Solo",
+    "A capella",
+    "Euro-House",
+    "Dance Hall",
+    "Goa",
+    "Drum & Bass",
+    "Club-House",
+    "Hardcore",
+    "Terror",
+    "Indie",
+    "BritPop",
+    "Afro-Punk",
+    "Polsk Punk",
+    "Beat",
+    "Christian Gangsta Rap",
+    "Heavy Metal",
+    "Black Metal",
+    "Crossover",
+    "Contemporary Christian",
+    "Christian Rock",
+    "Merengue",
+    "Salsa",
+    "Thrash Metal",
+    "Anime",
+    "JPop",
+    "Synthpop",
+];
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -207,20 +815,50 @@ mod tests {
 
     #[test]
     fn test_ilst() {
+        let src_text = IlstItemBox {
+            data: vec![DataBox::default()],
+            ..Default::default()
+        };
         let src_year = IlstItemBox {
-            data: DataBox {
+            data: vec![DataBox {
                 data_type: DataType::Text,
                 data: b"test_year".to_vec(),
-            },
+            }],
+            unknown_boxes: vec![UnknownBox::new(
+                BoxType::from(u32::from_be_bytes(*b"itif")),
+                vec![0, 0, 0, 0, 0, 0, 0, 1],
+            )],
+            child_order: ChildOrder::default(),
+        };
+        let src_poster = IlstItemBox {
+            data: vec![
+                DataBox {
+                    data_type: DataType::Image,
+                    data: vec![1; 4],
+                },
+                DataBox {
+                    data_type: DataType::Png,
+                    data: vec![2; 4],
+                },
+            ],
+            ..Default::default()
         };
         let src_box = IlstBox {
             items: [
-                (MetadataKey::Title, IlstItemBox::default()),
+                (MetadataKey::Title, src_text.clone()),
                 (MetadataKey::Year, src_year),
-                (MetadataKey::Poster, IlstItemBox::default()),
-                (MetadataKey::Summary, IlstItemBox::default()),
+                (MetadataKey::Poster, src_poster),
+                (MetadataKey::Summary, src_text),
             ]
             .into(),
+            free_form: Vec::new(),
+            unknown_boxes: vec![UnknownBox::new(
+                BoxType::from(u32::from_be_bytes(*b"stik")),
+                vec![
+                    0, 0, 0, 17, b'd', b'a', b't', b'a', 0, 0, 0, 21, 0, 0, 0, 0, 9,
+                ],
+            )],
+            child_order: ChildOrder::default(),
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -235,6 +873,51 @@ mod tests {
         assert_eq!(src_box, dst_box);
     }
 
+    #[test]
+    fn test_ilst_items() {
+        let mut src_box = IlstBox::default();
+        src_box.set_text(MetadataKey::Artist, "artist");
+        src_box.set_text(MetadataKey::SortArtist, "sort artist");
+        src_box.set_year(2008);
+        src_box.set_track_number(3, 12);
+        src_box.set_disc_number(1, 2);
+        src_box.set_tempo(120);
+        src_box.set_compilation(true);
+        src_box.set_rating(2);
+        src_box.set_poster(vec![1; 4], DataType::Image);
+        src_box.add_poster(vec![2; 4], DataType::Png);
+        src_box.set_data(MetadataKey::GenreId, DataType::Binary, vec![0, 18]);
+        src_box.set_free_form("com.apple.iTunes", "iTunNORM", "00000001");
+        src_box.set_free_form("com.apple.iTunes", "iTunNORM", "00000002");
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst_box = IlstBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+
+        assert_eq!(dst_box.artist(), Some("artist".into()));
+        assert_eq!(dst_box.sort_artist(), Some("sort artist".into()));
+        assert_eq!(dst_box.year(), Some(2008));
+        assert_eq!(dst_box.track_number(), Some((3, 12)));
+        assert_eq!(dst_box.disc_number(), Some((1, 2)));
+        assert_eq!(dst_box.tempo(), Some(120));
+        assert_eq!(dst_box.compilation(), Some(true));
+        assert_eq!(dst_box.rating(), Some(2));
+        assert_eq!(dst_box.poster(), Some(&[1; 4][..]));
+        assert_eq!(dst_box.posters().count(), 2);
+        assert_eq!(dst_box.genre(), Some("Rock".into()));
+        assert_eq!(dst_box.album(), None);
+        assert_eq!(dst_box.free_form.len(), 1);
+        assert_eq!(
+            dst_box.free_form("com.apple.iTunes", "iTunNORM"),
+            Some("00000002".into())
+        );
+        assert_eq!(dst_box.free_form("com.apple.iTunes", "iTunSMPB"), None);
+    }
+
     #[test]
     fn test_ilst_empty() {
         let src_box = IlstBox::default();
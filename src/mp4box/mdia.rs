@@ -9,6 +9,10 @@ pub struct MdiaBox {
     pub mdhd: MdhdBox,
     pub hdlr: HdlrBox,
     pub minf: MinfBox,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
 }
 
 impl MdiaBox {
@@ -17,7 +21,12 @@ impl MdiaBox {
     }
 
     pub fn get_size(&self) -> u64 {
-        HEADER_SIZE + self.mdhd.box_size() + self.hdlr.box_size() + self.minf.box_size()
+        let mut size =
+            HEADER_SIZE + self.mdhd.box_size() + self.hdlr.box_size() + self.minf.box_size();
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
+        size
     }
 }
 
@@ -48,6 +57,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for MdiaBox {
         let mut hdlr = None;
         let mut minf = None;
 
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
         let mut current = reader.stream_position()?;
         let end = start + size;
         while current < end {
@@ -60,6 +71,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for MdiaBox {
                 ));
             }
 
+            child_order.push(name);
+
             match name {
                 BoxType::MdhdBox => {
                     mdhd = Some(MdhdBox::read_box(reader, s)?);
@@ -71,8 +84,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for MdiaBox {
                     minf = Some(MinfBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
                 }
             }
 
@@ -95,6 +108,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for MdiaBox {
             mdhd: mdhd.unwrap(),
             hdlr: hdlr.unwrap(),
             minf: minf.unwrap(),
+            unknown_boxes,
+            child_order,
         })
     }
 }
@@ -104,9 +119,12 @@ impl<W: Write> WriteBox<&mut W> for MdiaBox {
         let size = self.box_size();
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
-        self.mdhd.write_box(writer)?;
-        self.hdlr.write_box(writer)?;
-        self.minf.write_box(writer)?;
+        let mut children = ChildWriter::new();
+        children.push(&self.mdhd);
+        children.push(&self.hdlr);
+        children.push(&self.minf);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
 
         Ok(size)
     }
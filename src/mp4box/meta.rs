@@ -13,6 +13,12 @@ pub enum MetaBox {
     Mdir {
         #[serde(skip_serializing_if = "Option::is_none")]
         ilst: Option<IlstBox>,
+
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        unknown_boxes: Vec<UnknownBox>,
+
+        #[serde(skip)]
+        child_order: ChildOrder,
     },
 
     #[serde(skip)]
@@ -22,6 +28,9 @@ pub enum MetaBox {
 
         #[serde(skip)]
         data: Vec<(BoxType, Vec<u8>)>,
+
+        #[serde(skip)]
+        child_order: ChildOrder,
     },
 }
 
@@ -35,13 +44,20 @@ impl MetaBox {
     pub fn get_size(&self) -> u64 {
         let mut size = HEADER_SIZE + HEADER_EXT_SIZE;
         match self {
-            Self::Mdir { ilst } => {
+            Self::Mdir {
+                ilst,
+                unknown_boxes,
+                ..
+            } => {
                 size += HdlrBox::default().box_size();
                 if let Some(ilst) = ilst {
                     size += ilst.box_size();
                 }
+                for unknown in unknown_boxes.iter() {
+                    size += unknown.box_size();
+                }
             }
-            Self::Unknown { hdlr, data } => {
+            Self::Unknown { hdlr, data, .. } => {
                 size += hdlr.box_size()
                     + data
                         .iter()
@@ -69,7 +85,7 @@ impl Mp4Box for MetaBox {
     fn summary(&self) -> Result<String> {
         let s = match self {
             Self::Mdir { .. } => "hdlr=ilst".to_string(),
-            Self::Unknown { hdlr, data } => {
+            Self::Unknown { hdlr, data, .. } => {
                 format!("hdlr={} data_len={}", hdlr.handler_type, data.len())
             }
         };
@@ -82,6 +98,7 @@ impl Default for MetaBox {
         Self::Unknown {
             hdlr: Default::default(),
             data: Default::default(),
+            child_order: Default::default(),
         }
     }
 }
@@ -139,6 +156,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for MetaBox {
         current = reader.stream_position()?;
 
         let mut ilst = None;
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
 
         match hdlr.handler_type {
             MDIR => {
@@ -147,20 +166,29 @@ impl<R: Read + Seek> ReadBox<&mut R> for MetaBox {
                     let header = BoxHeader::read(reader)?;
                     let BoxHeader { name, size: s } = header;
 
+                    child_order.push(name);
+
                     match name {
+                        BoxType::HdlrBox => {
+                            skip_box(reader, s)?;
+                        }
                         BoxType::IlstBox => {
                             ilst = Some(IlstBox::read_box(reader, s)?);
                         }
                         _ => {
-                            // XXX warn!()
-                            skip_box(reader, s)?;
+                            let unknown = UnknownBox::read(reader, &header)?;
+                            unknown_boxes.push(unknown);
                         }
                     }
 
                     current = reader.stream_position()?;
                 }
 
-                Ok(MetaBox::Mdir { ilst })
+                Ok(MetaBox::Mdir {
+                    ilst,
+                    unknown_boxes,
+                    child_order,
+                })
             }
             _ => {
                 let mut data = Vec::new();
@@ -170,6 +198,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for MetaBox {
                     let header = BoxHeader::read(reader)?;
                     let BoxHeader { name, size: s } = header;
 
+                    child_order.push(name);
+
                     match name {
                         BoxType::HdlrBox => {
                             skip_box(reader, s)?;
@@ -185,7 +215,11 @@ impl<R: Read + Seek> ReadBox<&mut R> for MetaBox {
                     current = reader.stream_position()?;
                 }
 
-                Ok(MetaBox::Unknown { hdlr, data })
+                Ok(MetaBox::Unknown {
+                    hdlr,
+                    data,
+                    child_order,
+                })
             }
         }
     }
@@ -198,28 +232,41 @@ impl<W: Write> WriteBox<&mut W> for MetaBox {
 
         write_box_header_ext(writer, 0, 0)?;
 
-        let hdlr = match self {
-            Self::Mdir { .. } => HdlrBox {
-                handler_type: MDIR,
-                ..Default::default()
-            },
-            Self::Unknown { hdlr, .. } => hdlr.clone(),
+        let mdir_hdlr = HdlrBox {
+            handler_type: MDIR,
+            ..Default::default()
         };
-        hdlr.write_box(writer)?;
 
-        match self {
-            Self::Mdir { ilst } => {
-                if let Some(ilst) = ilst {
-                    ilst.write_box(writer)?;
-                }
+        let mut children = ChildWriter::new();
+        let child_order = match self {
+            Self::Mdir {
+                ilst,
+                unknown_boxes,
+                child_order,
+            } => {
+                children.push(&mdir_hdlr);
+                children.extend(ilst);
+                children.extend(unknown_boxes);
+                child_order
             }
-            Self::Unknown { data, .. } => {
+            Self::Unknown {
+                hdlr,
+                data,
+                child_order,
+            } => {
+                children.push(hdlr);
                 for (box_type, data) in data {
-                    BoxHeader::new(*box_type, data.len() as u64 + HEADER_SIZE).write(writer)?;
-                    writer.write_all(data)?;
+                    children.push_with(*box_type, move |writer: &mut W| {
+                        let size = data.len() as u64 + HEADER_SIZE;
+                        BoxHeader::new(*box_type, size).write(writer)?;
+                        writer.write_all(data)?;
+                        Ok(size)
+                    });
                 }
+                child_order
             }
-        }
+        };
+        children.write(writer, child_order)?;
         Ok(size)
     }
 }
@@ -232,7 +279,11 @@ mod tests {
 
     #[test]
     fn test_meta_mdir_empty() {
-        let src_box = MetaBox::Mdir { ilst: None };
+        let src_box = MetaBox::Mdir {
+            ilst: None,
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
+        };
 
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -251,6 +302,8 @@ mod tests {
     fn test_meta_mdir() {
         let src_box = MetaBox::Mdir {
             ilst: Some(IlstBox::default()),
+            unknown_boxes: vec![UnknownBox::new(BoxType::FreeBox, vec![0; 4])],
+            child_order: ChildOrder::default(),
         };
 
         let mut buf = Vec::new();
@@ -275,12 +328,18 @@ mod tests {
 
         let meta_box = MetaBox::read_box(&mut reader, header.size).unwrap();
 
-        // this contains \xa9too box in the ilst
-        // it designates the tool that created the file, but is not yet supported by this crate
+        // the \xa9too box in the ilst designates the tool that created the file
+        let mut ilst = IlstBox::default();
+        ilst.set_text(
+            MetadataKey::EncoderTool,
+            "TMPGEnc Video Mastering Works 7 Version 7.0.15.17",
+        );
         assert_eq!(
             meta_box,
             MetaBox::Mdir {
-                ilst: Some(IlstBox::default())
+                ilst: Some(ilst),
+                unknown_boxes: Vec::new(),
+                child_order: ChildOrder::default(),
             }
         );
     }
@@ -295,6 +354,7 @@ mod tests {
         let src_box = MetaBox::Unknown {
             hdlr: src_hdlr,
             data: vec![src_data],
+            child_order: ChildOrder::default(),
         };
 
         let mut buf = Vec::new();
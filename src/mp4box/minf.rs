@@ -14,6 +14,10 @@ pub struct MinfBox {
 
     pub dinf: DinfBox,
     pub stbl: StblBox,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
 }
 
 impl MinfBox {
@@ -31,6 +35,9 @@ impl MinfBox {
         }
         size += self.dinf.box_size();
         size += self.stbl.box_size();
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
         size
     }
 }
@@ -63,6 +70,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for MinfBox {
         let mut dinf = None;
         let mut stbl = None;
 
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
         let mut current = reader.stream_position()?;
         let end = start + size;
         while current < end {
@@ -75,6 +84,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for MinfBox {
                 ));
             }
 
+            child_order.push(name);
+
             match name {
                 BoxType::VmhdBox => {
                     vmhd = Some(VmhdBox::read_box(reader, s)?);
@@ -89,8 +100,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for MinfBox {
                     stbl = Some(StblBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
                 }
             }
 
@@ -111,6 +122,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for MinfBox {
             smhd,
             dinf: dinf.unwrap(),
             stbl: stbl.unwrap(),
+            unknown_boxes,
+            child_order,
         })
     }
 }
@@ -120,14 +133,13 @@ impl<W: Write> WriteBox<&mut W> for MinfBox {
         let size = self.box_size();
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
-        if let Some(ref vmhd) = self.vmhd {
-            vmhd.write_box(writer)?;
-        }
-        if let Some(ref smhd) = self.smhd {
-            smhd.write_box(writer)?;
-        }
-        self.dinf.write_box(writer)?;
-        self.stbl.write_box(writer)?;
+        let mut children = ChildWriter::new();
+        children.extend(&self.vmhd);
+        children.extend(&self.smhd);
+        children.push(&self.dinf);
+        children.push(&self.stbl);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
 
         Ok(size)
     }
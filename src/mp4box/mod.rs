@@ -14,11 +14,17 @@
 //! moov
 //!     mvhd
 //!     udta
+//!         chpl
 //!         meta
 //!             ilst
 //!                 data
+//!                 ----
+//!                     mean
+//!                     name
+//!                     data
 //!     trak
 //!         tkhd
+//!         tref
 //!         mdia
 //!             mdhd
 //!             hdlr
@@ -27,8 +33,18 @@
 //!                     stsd
 //!                         avc1
 //!                         hev1
+//!                         av01
 //!                         mp4a
+//!                         ac-3
+//!                         ec-3
+//!                         fLaC
 //!                         tx3g
+//!                         encv, enca
+//!                             sinf
+//!                                 frma
+//!                                 schm
+//!                                 schi
+//!                                     tenc
 //!                     stts
 //!                     stsc
 //!                     stsz
@@ -36,6 +52,9 @@
 //!                     stco
 //!                     co64
 //!                     ctts
+//!                     saiz
+//!                     saio
+//!                     senc
 //!                 dinf
 //!                     dref
 //!                 smhd
@@ -45,6 +64,7 @@
 //!     mvex
 //!         mehd
 //!         trex
+//!     pssh
 //! emsg
 //! moof
 //!     mfhd
@@ -52,24 +72,34 @@
 //!         tfhd
 //!         tfdt
 //!         trun
+//!         saiz
+//!         saio
+//!         senc
+//!     pssh
 //! mdat
 //! free
 //!
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::collections::{HashMap, VecDeque};
 use std::convert::TryInto;
 use std::io::{Read, Seek, SeekFrom, Write};
 
 use crate::*;
 
+pub(crate) mod ac3;
+pub(crate) mod av01;
 pub(crate) mod avc1;
+pub(crate) mod chpl;
 pub(crate) mod co64;
 pub(crate) mod ctts;
 pub(crate) mod data;
 pub(crate) mod dinf;
+pub(crate) mod ec3;
 pub(crate) mod edts;
 pub(crate) mod elst;
 pub(crate) mod emsg;
+pub(crate) mod flac;
 pub(crate) mod ftyp;
 pub(crate) mod hdlr;
 pub(crate) mod hev1;
@@ -87,7 +117,12 @@ pub(crate) mod mp4a;
 pub(crate) mod mvex;
 pub(crate) mod mvhd;
 pub(crate) mod opus;
+pub(crate) mod pssh;
+pub(crate) mod saio;
+pub(crate) mod saiz;
+pub(crate) mod senc;
 pub(crate) mod sidx;
+pub(crate) mod sinf;
 pub(crate) mod smhd;
 pub(crate) mod stbl;
 pub(crate) mod stco;
@@ -96,31 +131,40 @@ pub(crate) mod stsd;
 pub(crate) mod stss;
 pub(crate) mod stsz;
 pub(crate) mod stts;
+pub(crate) mod tenc;
 pub(crate) mod tfdt;
 pub(crate) mod tfhd;
 pub(crate) mod tkhd;
 pub(crate) mod traf;
 pub(crate) mod trak;
+pub(crate) mod tref;
 pub(crate) mod trex;
 pub(crate) mod trun;
 pub(crate) mod tx3g;
 pub(crate) mod udta;
+pub(crate) mod unknown;
 pub(crate) mod vmhd;
 pub(crate) mod vp09;
 pub(crate) mod vpcc;
 
-pub use avc1::Avc1Box;
+pub use ac3::{Ac3Box, Dac3Box};
+pub use av01::{Av01Box, Av1CBox};
+pub use avc1::{Avc1Box, AvcCBox, AvcCHighProfileExt};
+pub use chpl::{ChplBox, ChplChapter};
 pub use co64::Co64Box;
 pub use ctts::CttsBox;
 pub use data::DataBox;
 pub use dinf::DinfBox;
+pub use ec3::{Dec3Box, Ec3Box, Ec3Substream};
 pub use edts::EdtsBox;
 pub use elst::ElstBox;
 pub use emsg::EmsgBox;
+pub use flac::{DflaBox, FlacBox, FlacMetadataBlock, FlacStreamInfo};
 pub use ftyp::FtypBox;
 pub use hdlr::HdlrBox;
-pub use hev1::Hev1Box;
-pub use ilst::IlstBox;
+pub use hev1::{Hev1Box, HvcCArray, HvcCArrayNalu, HvcCBox};
+pub use hvc1::Hvc1Box;
+pub use ilst::{IlstBox, IlstFreeFormBox, IlstItemBox};
 pub use mdhd::MdhdBox;
 pub use mdia::MdiaBox;
 pub use mehd::MehdBox;
@@ -133,7 +177,12 @@ pub use mp4a::Mp4aBox;
 pub use mvex::MvexBox;
 pub use mvhd::MvhdBox;
 pub use opus::OpusBox;
+pub use pssh::PsshBox;
+pub use saio::SaioBox;
+pub use saiz::SaizBox;
+pub use senc::{SampleEncryptionEntry, SencBox, SubsampleEntry};
 pub use sidx::SidxBox;
+pub use sinf::{FrmaBox, SchiBox, SchmBox, SinfBox};
 pub use smhd::SmhdBox;
 pub use stbl::StblBox;
 pub use stco::StcoBox;
@@ -142,15 +191,18 @@ pub use stsd::StsdBox;
 pub use stss::StssBox;
 pub use stsz::StszBox;
 pub use stts::SttsBox;
+pub use tenc::TencBox;
 pub use tfdt::TfdtBox;
 pub use tfhd::TfhdBox;
 pub use tkhd::TkhdBox;
 pub use traf::TrafBox;
 pub use trak::TrakBox;
+pub use tref::{TrackReference, TrackReferenceType, TrefBox};
 pub use trex::TrexBox;
 pub use trun::TrunBox;
 pub use tx3g::Tx3gBox;
 pub use udta::UdtaBox;
+pub use unknown::UnknownBox;
 pub use vmhd::VmhdBox;
 pub use vp09::Vp09Box;
 pub use vpcc::VpccBox;
@@ -222,9 +274,11 @@ boxtype! {
     StcoBox => 0x7374636F,
     Co64Box => 0x636F3634,
     TrakBox => 0x7472616b,
+    TrefBox => 0x74726566,
     TrafBox => 0x74726166,
     TrunBox => 0x7472756E,
     UdtaBox => 0x75647461,
+    ChplBox => 0x6368706c,
     MetaBox => 0x6d657461,
     DinfBox => 0x64696e66,
     DrefBox => 0x64726566,
@@ -240,12 +294,52 @@ boxtype! {
     Tx3gBox => 0x74783367,
     VpccBox => 0x76706343,
     Vp09Box => 0x76703039,
+    Av01Box => 0x61763031,
+    Av1CBox => 0x61763143,
+    Ac3Box  => 0x61632d33,
+    Dac3Box => 0x64616333,
+    Ec3Box  => 0x65632d33,
+    Dec3Box => 0x64656333,
+    FlacBox => 0x664c6143,
+    DflaBox => 0x64664c61,
+    EncvBox => 0x656e6376,
+    EncaBox => 0x656e6361,
+    SinfBox => 0x73696e66,
+    FrmaBox => 0x66726d61,
+    SchmBox => 0x7363686d,
+    SchiBox => 0x73636869,
+    TencBox => 0x74656e63,
+    PsshBox => 0x70737368,
+    SencBox => 0x73656e63,
+    SaizBox => 0x7361697a,
+    SaioBox => 0x7361696f,
     DataBox => 0x64617461,
     IlstBox => 0x696c7374,
     NameBox => 0xa96e616d,
     DayBox => 0xa9646179,
     CovrBox => 0x636f7672,
     DescBox => 0x64657363,
+    ArtBox => 0xa9415254,
+    AartBox => 0x61415254,
+    AlbBox => 0xa9616c62,
+    GenBox => 0xa967656e,
+    GnreBox => 0x676e7265,
+    WrtBox => 0xa9777274,
+    CmtBox => 0xa9636d74,
+    TrknBox => 0x74726b6e,
+    DiskBox => 0x6469736b,
+    TmpoBox => 0x746d706f,
+    CpilBox => 0x6370696c,
+    TooBox => 0xa9746f6f,
+    SonmBox => 0x736f6e6d,
+    SoarBox => 0x736f6172,
+    SoaaBox => 0x736f6161,
+    SoalBox => 0x736f616c,
+    SocoBox => 0x736f636f,
+    RtngBox => 0x72746e67,
+    FreeFormBox => 0x2d2d2d2d,
+    MeanBox => 0x6d65616e,
+    FreeFormNameBox => 0x6e616d65,
     WideBox => 0x77696465,
     WaveBox => 0x77617665
 }
@@ -368,6 +462,94 @@ pub fn write_zeros<W: Write>(writer: &mut W, size: u64) -> Result<()> {
     Ok(())
 }
 
+/// Types of the children of a box in the order they were read in, so that
+/// writing the box back keeps that order.
+///
+/// It is not part of the value of the box: boxes compare equal whatever
+/// their child order.
+#[derive(Debug, Clone, Default)]
+pub struct ChildOrder(Vec<BoxType>);
+
+impl ChildOrder {
+    pub(crate) fn push(&mut self, box_type: BoxType) {
+        self.0.push(box_type);
+    }
+}
+
+impl PartialEq for ChildOrder {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for ChildOrder {}
+
+type WriteChild<'a, W> = Box<dyn FnOnce(&mut W) -> Result<u64> + 'a>;
+
+/// Children of a box being written, see [ChildWriter::write].
+pub(crate) struct ChildWriter<'a, W> {
+    children: Vec<(BoxType, Option<WriteChild<'a, W>>)>,
+}
+
+impl<'a, W: Write> ChildWriter<'a, W> {
+    pub(crate) fn new() -> Self {
+        Self {
+            children: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push<B>(&mut self, child: &'a B)
+    where
+        B: Mp4Box + for<'w> WriteBox<&'w mut W>,
+    {
+        self.push_with(child.box_type(), move |writer| child.write_box(writer));
+    }
+
+    pub(crate) fn extend<I, B>(&mut self, children: I)
+    where
+        I: IntoIterator<Item = &'a B>,
+        B: Mp4Box + for<'w> WriteBox<&'w mut W> + 'a,
+    {
+        for child in children {
+            self.push(child);
+        }
+    }
+
+    /// Add a child of type `box_type` written by `write`.
+    pub(crate) fn push_with<F>(&mut self, box_type: BoxType, write: F)
+    where
+        F: FnOnce(&mut W) -> Result<u64> + 'a,
+    {
+        self.children.push((box_type, Some(Box::new(write))));
+    }
+
+    /// Write the children in `order`, each type taking the next child added
+    /// with it, then the children `order` has no room for in the order they
+    /// were added.
+    pub(crate) fn write(mut self, writer: &mut W, order: &ChildOrder) -> Result<()> {
+        let mut by_type: HashMap<u32, VecDeque<usize>> = HashMap::new();
+        for (i, (box_type, _)) in self.children.iter().enumerate() {
+            by_type.entry((*box_type).into()).or_default().push_back(i);
+        }
+        for box_type in order.0.iter() {
+            let next = by_type
+                .get_mut(&(*box_type).into())
+                .and_then(|indexes| indexes.pop_front());
+            if let Some(i) = next {
+                if let Some(write) = self.children[i].1.take() {
+                    write(writer)?;
+                }
+            }
+        }
+        for (_, write) in self.children {
+            if let Some(write) = write {
+                write(writer)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 mod value_u32 {
     use crate::types::FixedPointU16;
     use serde::{self, Serializer};
@@ -2,7 +2,7 @@ use serde::Serialize;
 use std::io::{Read, Seek, Write};
 
 use crate::mp4box::*;
-use crate::mp4box::{mfhd::MfhdBox, traf::TrafBox};
+use crate::mp4box::{mfhd::MfhdBox, pssh::PsshBox, traf::TrafBox};
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct MoofBox {
@@ -10,6 +10,13 @@ pub struct MoofBox {
 
     #[serde(rename = "traf")]
     pub trafs: Vec<TrafBox>,
+
+    #[serde(rename = "pssh", skip_serializing_if = "Vec::is_empty")]
+    pub psshs: Vec<PsshBox>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
 }
 
 impl MoofBox {
@@ -22,6 +29,12 @@ impl MoofBox {
         for traf in self.trafs.iter() {
             size += traf.box_size();
         }
+        for pssh in self.psshs.iter() {
+            size += pssh.box_size();
+        }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
         size
     }
 }
@@ -51,7 +64,10 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoofBox {
 
         let mut mfhd = None;
         let mut trafs = Vec::new();
+        let mut psshs = Vec::new();
 
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
         let mut current = reader.stream_position()?;
         let end = start + size;
         while current < end {
@@ -64,6 +80,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoofBox {
                 ));
             }
 
+            child_order.push(name);
+
             match name {
                 BoxType::MfhdBox => {
                     mfhd = Some(MfhdBox::read_box(reader, s)?);
@@ -72,9 +90,13 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoofBox {
                     let traf = TrafBox::read_box(reader, s)?;
                     trafs.push(traf);
                 }
+                BoxType::PsshBox => {
+                    let pssh = PsshBox::read_box(reader, s)?;
+                    psshs.push(pssh);
+                }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
                 }
             }
             current = reader.stream_position()?;
@@ -89,6 +111,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoofBox {
         Ok(MoofBox {
             mfhd: mfhd.unwrap(),
             trafs,
+            psshs,
+            unknown_boxes,
+            child_order,
         })
     }
 }
@@ -98,10 +123,12 @@ impl<W: Write> WriteBox<&mut W> for MoofBox {
         let size = self.box_size();
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
-        self.mfhd.write_box(writer)?;
-        for traf in self.trafs.iter() {
-            traf.write_box(writer)?;
-        }
+        let mut children = ChildWriter::new();
+        children.push(&self.mfhd);
+        children.extend(&self.trafs);
+        children.extend(&self.psshs);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
         Ok(0)
     }
 }
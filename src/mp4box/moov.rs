@@ -3,7 +3,7 @@ use std::io::{Read, Seek, Write};
 
 use crate::meta::MetaBox;
 use crate::mp4box::*;
-use crate::mp4box::{mvex::MvexBox, mvhd::MvhdBox, trak::TrakBox, udta::UdtaBox};
+use crate::mp4box::{mvex::MvexBox, mvhd::MvhdBox, pssh::PsshBox, trak::TrakBox, udta::UdtaBox};
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct MoovBox {
@@ -20,6 +20,13 @@ pub struct MoovBox {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub udta: Option<UdtaBox>,
+
+    #[serde(rename = "pssh", skip_serializing_if = "Vec::is_empty")]
+    pub psshs: Vec<PsshBox>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
 }
 
 impl MoovBox {
@@ -35,9 +42,18 @@ impl MoovBox {
         if let Some(meta) = &self.meta {
             size += meta.box_size();
         }
+        if let Some(mvex) = &self.mvex {
+            size += mvex.box_size();
+        }
         if let Some(udta) = &self.udta {
             size += udta.box_size();
         }
+        for pssh in self.psshs.iter() {
+            size += pssh.box_size();
+        }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
         size
     }
 }
@@ -70,7 +86,10 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoovBox {
         let mut udta = None;
         let mut mvex = None;
         let mut traks = Vec::new();
+        let mut psshs = Vec::new();
 
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
         let mut current = reader.stream_position()?;
         let end = start + size;
         while current < end {
@@ -83,6 +102,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoovBox {
                 ));
             }
 
+            child_order.push(name);
+
             match name {
                 BoxType::MvhdBox => {
                     mvhd = Some(MvhdBox::read_box(reader, s)?);
@@ -100,9 +121,13 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoovBox {
                 BoxType::UdtaBox => {
                     udta = Some(UdtaBox::read_box(reader, s)?);
                 }
+                BoxType::PsshBox => {
+                    let pssh = PsshBox::read_box(reader, s)?;
+                    psshs.push(pssh);
+                }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
                 }
             }
 
@@ -121,6 +146,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoovBox {
             udta,
             mvex,
             traks,
+            psshs,
+            unknown_boxes,
+            child_order,
         })
     }
 }
@@ -130,16 +158,15 @@ impl<W: Write> WriteBox<&mut W> for MoovBox {
         let size = self.box_size();
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
-        self.mvhd.write_box(writer)?;
-        for trak in self.traks.iter() {
-            trak.write_box(writer)?;
-        }
-        if let Some(meta) = &self.meta {
-            meta.write_box(writer)?;
-        }
-        if let Some(udta) = &self.udta {
-            udta.write_box(writer)?;
-        }
+        let mut children = ChildWriter::new();
+        children.push(&self.mvhd);
+        children.extend(&self.traks);
+        children.extend(&self.meta);
+        children.extend(&self.mvex);
+        children.extend(&self.udta);
+        children.extend(&self.psshs);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
         Ok(0)
     }
 }
@@ -148,16 +175,34 @@ impl<W: Write> WriteBox<&mut W> for MoovBox {
 mod tests {
     use super::*;
     use crate::mp4box::BoxHeader;
+    use crate::mp4box::TrexBox;
     use std::io::Cursor;
 
     #[test]
     fn test_moov() {
         let src_box = MoovBox {
             mvhd: MvhdBox::default(),
-            mvex: None, // XXX mvex is not written currently
+            mvex: Some(MvexBox {
+                mehd: None,
+                trexs: vec![TrexBox::default()],
+                unknown_boxes: Vec::new(),
+                child_order: ChildOrder::default(),
+            }),
             traks: vec![],
             meta: Some(MetaBox::default()),
             udta: Some(UdtaBox::default()),
+            psshs: vec![PsshBox {
+                version: 0,
+                flags: 0,
+                system_id: [0xed; 16],
+                kids: Vec::new(),
+                data: vec![1, 2, 3, 4],
+            }],
+            unknown_boxes: vec![UnknownBox::new(
+                BoxType::from(0x696f6473), // iods
+                vec![0, 0, 0, 0, 1, 2, 3, 4],
+            )],
+            child_order: ChildOrder::default(),
         };
 
         let mut buf = Vec::new();
@@ -15,6 +15,11 @@ pub struct Mp4aBox {
     #[serde(with = "value_u32")]
     pub samplerate: FixedPointU16,
     pub esds: Option<EsdsBox>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
 }
 
 impl Default for Mp4aBox {
@@ -27,6 +32,8 @@ impl Default for Mp4aBox {
             samplerate: FixedPointU16::new(48000),
             qt_bytes: None,
             esds: Some(EsdsBox::default()),
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
         }
     }
 }
@@ -41,6 +48,8 @@ impl Mp4aBox {
             samplerate: FixedPointU16::new(config.freq_index.freq() as u16),
             qt_bytes: config.qt_bytes.clone(),
             esds: Some(EsdsBox::new(config)),
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
         }
     }
 
@@ -58,6 +67,9 @@ impl Mp4aBox {
         if let Some(ref esds) = self.esds {
             size += esds.box_size();
         }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
         size
     }
 }
@@ -112,10 +124,13 @@ impl<R: Read + Seek> ReadBox<&mut R> for Mp4aBox {
         }
         // Find esds in mp4a or wave
         let mut esds = None;
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
+        let mut wave_end = 0;
         let end = start + size;
         loop {
             let current = reader.stream_position()?;
-            if current >= end {
+            if current + HEADER_SIZE > end {
                 break;
             }
             let header = BoxHeader::read(reader)?;
@@ -125,15 +140,19 @@ impl<R: Read + Seek> ReadBox<&mut R> for Mp4aBox {
                     "mp4a box contains a box with a larger size than it",
                 ));
             }
+            child_order.push(name);
             if name == BoxType::EsdsBox {
                 esds = Some(EsdsBox::read_box(reader, s)?);
-                break;
             } else if name == BoxType::WaveBox {
                 // Typically contains frma, mp4a, esds, and a terminator atom
-            } else {
-                // Skip boxes
+                wave_end = current + s;
+            } else if current < wave_end {
+                // Skip the rest of wave, esds is written directly in mp4a
                 let skip_to = current + s;
                 skip_bytes_to(reader, skip_to)?;
+            } else {
+                let unknown = UnknownBox::read(reader, &header)?;
+                unknown_boxes.push(unknown);
             }
         }
 
@@ -141,12 +160,14 @@ impl<R: Read + Seek> ReadBox<&mut R> for Mp4aBox {
 
         Ok(Mp4aBox {
             data_reference_index,
-            sound_version: sound_version,
+            sound_version,
             channelcount,
             samplesize,
             samplerate,
             qt_bytes,
             esds,
+            unknown_boxes,
+            child_order,
         })
     }
 }
@@ -172,13 +193,14 @@ impl<W: Write> WriteBox<&mut W> for Mp4aBox {
 
         if self.sound_version != 0 {
             if let Some(ref qt_bytes) = self.qt_bytes {
-                writer.write_all(&qt_bytes)?;
+                writer.write_all(qt_bytes)?;
             }
         }
 
-        if let Some(ref esds) = self.esds {
-            esds.write_box(writer)?;
-        }
+        let mut children = ChildWriter::new();
+        children.extend(&self.esds);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
 
         Ok(size)
     }
@@ -301,13 +323,13 @@ fn read_desc<R: Read>(reader: &mut R) -> Result<(u8, u32)> {
 }
 
 fn size_of_length(_: u32) -> u32 {
-    return 4;
+    4
 }
 
 fn write_desc<W: Write>(writer: &mut W, tag: u8, size: u32) -> Result<u64> {
     writer.write_u8(tag)?;
 
-    if size as u64 > std::u32::MAX as u64 {
+    if size as u64 > u32::MAX as u64 {
         return Err(Error::InvalidData("invalid descriptor length range"));
     }
 
@@ -338,8 +360,8 @@ impl ESDescriptor {
         let sl_config = SLConfigDescriptor::new();
         Self {
             es_id: config.es_id.unwrap_or(0),
-            dec_config: dec_config,
-            sl_config: sl_config,
+            dec_config,
+            sl_config,
         }
     }
 }
@@ -496,7 +518,7 @@ impl<W: Write> WriteDesc<&mut W> for DecoderConfigDescriptor {
         write_desc(writer, Self::desc_tag(), size)?;
 
         writer.write_u8(self.object_type_indication)?;
-        writer.write_u8((self.stream_type << 2) | ((self.up_stream & 0x01) << 1) | 0)?;
+        writer.write_u8((self.stream_type << 2) | ((self.up_stream & 0x01) << 1))?;
         writer.write_u24::<BigEndian>(self.buffer_size_db)?;
         writer.write_u32::<BigEndian>(self.max_bitrate)?;
         writer.write_u32::<BigEndian>(self.avg_bitrate)?;
@@ -683,6 +705,8 @@ mod tests {
                     sl_config: SLConfigDescriptor::default(),
                 },
             }),
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -727,6 +751,8 @@ mod tests {
                     sl_config: SLConfigDescriptor::default(),
                 },
             }),
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -750,6 +776,11 @@ mod tests {
             samplerate: FixedPointU16::new(48000),
             qt_bytes: None,
             esds: None,
+            unknown_boxes: vec![UnknownBox::new(
+                BoxType::from(0x62747274), // btrt
+                vec![0, 0, 0, 0, 0, 0x01, 0x08, 0xef, 0, 0x01, 0x08, 0xef],
+            )],
+            child_order: ChildOrder::default(),
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -7,16 +7,34 @@ use crate::mp4box::{mehd::MehdBox, trex::TrexBox};
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct MvexBox {
     pub mehd: Option<MehdBox>,
-    pub trex: TrexBox,
+
+    #[serde(rename = "trex")]
+    pub trexs: Vec<TrexBox>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
 }
 
 impl MvexBox {
     pub fn get_type(&self) -> BoxType {
-        BoxType::MdiaBox
+        BoxType::MvexBox
     }
 
     pub fn get_size(&self) -> u64 {
-        HEADER_SIZE + self.mehd.as_ref().map(|x| x.box_size()).unwrap_or(0) + self.trex.box_size()
+        let mut size = HEADER_SIZE + self.mehd.as_ref().map(|x| x.box_size()).unwrap_or(0);
+        for trex in self.trexs.iter() {
+            size += trex.box_size();
+        }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
+        size
+    }
+
+    pub fn trex(&self, track_id: u32) -> Option<&TrexBox> {
+        self.trexs.iter().find(|trex| trex.track_id == track_id)
     }
 }
 
@@ -34,7 +52,7 @@ impl Mp4Box for MvexBox {
     }
 
     fn summary(&self) -> Result<String> {
-        let s = String::new();
+        let s = format!("trexs={}", self.trexs.len());
         Ok(s)
     }
 }
@@ -44,8 +62,10 @@ impl<R: Read + Seek> ReadBox<&mut R> for MvexBox {
         let start = box_start(reader)?;
 
         let mut mehd = None;
-        let mut trex = None;
+        let mut trexs = Vec::new();
+        let mut unknown_boxes = Vec::new();
 
+        let mut child_order = ChildOrder::default();
         let mut current = reader.stream_position()?;
         let end = start + size;
         while current < end {
@@ -58,23 +78,26 @@ impl<R: Read + Seek> ReadBox<&mut R> for MvexBox {
                 ));
             }
 
+            child_order.push(name);
+
             match name {
                 BoxType::MehdBox => {
                     mehd = Some(MehdBox::read_box(reader, s)?);
                 }
                 BoxType::TrexBox => {
-                    trex = Some(TrexBox::read_box(reader, s)?);
+                    let trex = TrexBox::read_box(reader, s)?;
+                    trexs.push(trex);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
                 }
             }
 
             current = reader.stream_position()?;
         }
 
-        if trex.is_none() {
+        if trexs.is_empty() {
             return Err(Error::BoxNotFound(BoxType::TrexBox));
         }
 
@@ -82,7 +105,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for MvexBox {
 
         Ok(MvexBox {
             mehd,
-            trex: trex.unwrap(),
+            trexs,
+            unknown_boxes,
+            child_order,
         })
     }
 }
@@ -92,11 +117,67 @@ impl<W: Write> WriteBox<&mut W> for MvexBox {
         let size = self.box_size();
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
-        if let Some(mehd) = &self.mehd {
-            mehd.write_box(writer)?;
-        }
-        self.trex.write_box(writer)?;
+        let mut children = ChildWriter::new();
+        children.extend(&self.mehd);
+        children.extend(&self.trexs);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
 
         Ok(size)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_mvex() {
+        let src_box = MvexBox {
+            mehd: Some(MehdBox {
+                version: 0,
+                flags: 0,
+                fragment_duration: 32,
+            }),
+            trexs: vec![
+                TrexBox {
+                    version: 0,
+                    flags: 0,
+                    track_id: 1,
+                    default_sample_description_index: 1,
+                    default_sample_duration: 512,
+                    default_sample_size: 0,
+                    default_sample_flags: 0,
+                },
+                TrexBox {
+                    version: 0,
+                    flags: 0,
+                    track_id: 2,
+                    default_sample_description_index: 1,
+                    default_sample_duration: 1024,
+                    default_sample_size: 0,
+                    default_sample_flags: 0,
+                },
+            ],
+            unknown_boxes: vec![UnknownBox::new(
+                BoxType::from(u32::from_be_bytes(*b"leva")),
+                vec![0, 0, 0, 0, 0],
+            )],
+            child_order: ChildOrder::default(),
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::MvexBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = MvexBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.trex(2).unwrap().default_sample_duration, 1024);
+    }
+}
@@ -13,6 +13,11 @@ pub struct OpusBox {
     #[serde(with = "value_u32")]
     pub sample_rate: FixedPointU16,
     pub dops_box: Option<DopsBox>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
 }
 
 impl Default for OpusBox {
@@ -23,6 +28,8 @@ impl Default for OpusBox {
             sample_size: 16,
             sample_rate: FixedPointU16::new(48000),
             dops_box: Some(DopsBox::default()),
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
         }
     }
 }
@@ -35,6 +42,8 @@ impl OpusBox {
             sample_rate: FixedPointU16::new(config.freq_index.freq() as u16),
             dops_box: Some(DopsBox::new(config)),
             data_reference_index: 1,
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
         }
     }
 
@@ -47,6 +56,9 @@ impl OpusBox {
         if let Some(ref dops_box) = self.dops_box {
             size += dops_box.box_size();
         }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
         size
     }
 }
@@ -90,25 +102,43 @@ impl<R: Read + Seek> ReadBox<&mut R> for OpusBox {
         reader.read_u32::<BigEndian>()?; // pre-defined, reserved
         let sample_rate = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
 
-        let header = BoxHeader::read(reader)?;
-        let BoxHeader { name, size: s } = header;
-
-        if s > size {
-            return Err(Error::InvalidData(
-                "opus box contains a box with a larger size than it",
-            ));
-        }
         let mut dops_box = None;
-        if name == BoxType::DopsBox {
-            dops_box = Some(DopsBox::read_box(reader, s)?);
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            // Get box header.
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "opus box contains a box with a larger size than it",
+                ));
+            }
+            child_order.push(name);
+            match name {
+                BoxType::DopsBox => {
+                    dops_box = Some(DopsBox::read_box(reader, s)?);
+                }
+                _ => {
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
+                }
+            }
+            current = reader.stream_position()?;
         }
-        skip_bytes_to(reader, start + size)?;
+
+        skip_bytes_to(reader, end)?;
         Ok(OpusBox {
             data_reference_index,
             channel_count,
             sample_size,
             sample_rate,
             dops_box,
+            unknown_boxes,
+            child_order,
         })
     }
 }
@@ -128,9 +158,10 @@ impl<W: Write> WriteBox<&mut W> for OpusBox {
         writer.write_u32::<BigEndian>(0)?; // reserved
         writer.write_u32::<BigEndian>(self.sample_rate.raw_value())?;
 
-        if let Some(ref dops_box) = self.dops_box {
-            dops_box.write_box(writer)?;
-        }
+        let mut children = ChildWriter::new();
+        children.extend(&self.dops_box);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
         Ok(size)
     }
 }
@@ -300,6 +331,11 @@ mod tests {
                     channel_mapping: [0, 4, 1, 2, 3, 5].to_vec(),
                 }),
             }),
+            unknown_boxes: vec![UnknownBox::new(
+                BoxType::from(u32::from_be_bytes(*b"btrt")),
+                vec![0; 12],
+            )],
+            child_order: ChildOrder::default(),
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -330,6 +366,8 @@ mod tests {
                 channel_mapping_family: 0,
                 channel_mapping_table: None,
             }),
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -0,0 +1,160 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Protection system specific header box, carrying the data a DRM system
+/// needs to acquire keys (ISO/IEC 23001-7, 8.1).
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct PsshBox {
+    pub version: u8,
+    pub flags: u32,
+    pub system_id: [u8; 16],
+
+    /// Key IDs the data applies to, only stored from version 1 on.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub kids: Vec<[u8; 16]>,
+
+    #[serde(skip_serializing)]
+    pub data: Vec<u8>,
+}
+
+impl PsshBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::PsshBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 16;
+        if self.version > 0 {
+            size += 4 + 16 * self.kids.len() as u64;
+        }
+        size + 4 + self.data.len() as u64
+    }
+}
+
+impl Mp4Box for PsshBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let system_id: String = self.system_id.iter().map(|b| format!("{b:02x}")).collect();
+        let s = format!(
+            "system_id={} kids={} data_size={}",
+            system_id,
+            self.kids.len(),
+            self.data.len()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for PsshBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+
+        let mut system_id = [0; 16];
+        reader.read_exact(&mut system_id)?;
+
+        let end = start + size;
+        let mut kids = Vec::new();
+        if version > 0 {
+            let kid_count = reader.read_u32::<BigEndian>()?;
+            if reader.stream_position()? + 16 * kid_count as u64 > end {
+                return Err(Error::InvalidData("pssh kid count too large"));
+            }
+            for _ in 0..kid_count {
+                let mut kid = [0; 16];
+                reader.read_exact(&mut kid)?;
+                kids.push(kid);
+            }
+        }
+
+        let data_size = reader.read_u32::<BigEndian>()?;
+        if reader.stream_position()? + data_size as u64 > end {
+            return Err(Error::InvalidData("pssh data size too large"));
+        }
+        let mut data = vec![0; data_size as usize];
+        reader.read_exact(&mut data)?;
+
+        skip_bytes_to(reader, end)?;
+
+        Ok(PsshBox {
+            version,
+            flags,
+            system_id,
+            kids,
+            data,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for PsshBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        if self.version == 0 && !self.kids.is_empty() {
+            return Err(Error::InvalidData("pssh version 0 cannot hold kids"));
+        }
+
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        writer.write_all(&self.system_id)?;
+        if self.version > 0 {
+            writer.write_u32::<BigEndian>(self.kids.len() as u32)?;
+            for kid in self.kids.iter() {
+                writer.write_all(kid)?;
+            }
+        }
+        writer.write_u32::<BigEndian>(self.data.len() as u32)?;
+        writer.write_all(&self.data)?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_pssh_v1() {
+        let src_box = PsshBox {
+            version: 1,
+            flags: 0,
+            // W3C Clear Key
+            system_id: [
+                0x10, 0x77, 0xef, 0xec, 0xc0, 0xb2, 0x4d, 0x02, 0xac, 0xe3, 0x3c, 0x1e, 0x52, 0xe2,
+                0xfb, 0x4b,
+            ],
+            kids: vec![[0x01; 16], [0x02; 16]],
+            data: Vec::new(),
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::PsshBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = PsshBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
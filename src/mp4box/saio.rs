@@ -0,0 +1,159 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Sample auxiliary information offsets box (ISO/IEC 14496-12, 8.7.9).
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SaioBox {
+    pub version: u8,
+    pub flags: u32,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aux_info_type: Option<FourCC>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aux_info_type_parameter: Option<u32>,
+
+    /// One offset for all the samples, or one per chunk. In a `traf` they
+    /// are relative to the same base as the `trun` data offsets, otherwise
+    /// to the start of the file.
+    pub offsets: Vec<u64>,
+}
+
+impl SaioBox {
+    pub const FLAG_AUX_INFO_TYPE: u32 = 0x01;
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SaioBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 4;
+        if SaioBox::FLAG_AUX_INFO_TYPE & self.flags != 0 {
+            size += 8;
+        }
+        let offset_size = if self.version == 0 { 4 } else { 8 };
+        size + offset_size * self.offsets.len() as u64
+    }
+}
+
+impl Mp4Box for SaioBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("offsets={:?}", self.offsets);
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SaioBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+
+        let (aux_info_type, aux_info_type_parameter) = if SaioBox::FLAG_AUX_INFO_TYPE & flags != 0 {
+            (
+                Some(reader.read_u32::<BigEndian>()?.into()),
+                Some(reader.read_u32::<BigEndian>()?),
+            )
+        } else {
+            (None, None)
+        };
+
+        let entry_count = reader.read_u32::<BigEndian>()?;
+        let offset_size = if version == 0 { 4 } else { 8 };
+        if reader.stream_position()? + offset_size * entry_count as u64 > start + size {
+            return Err(Error::InvalidData("saio entry count too large"));
+        }
+        let mut offsets = Vec::with_capacity(entry_count as usize);
+        for _ in 0..entry_count {
+            let offset = if version == 0 {
+                reader.read_u32::<BigEndian>()? as u64
+            } else {
+                reader.read_u64::<BigEndian>()?
+            };
+            offsets.push(offset);
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SaioBox {
+            version,
+            flags,
+            aux_info_type,
+            aux_info_type_parameter,
+            offsets,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SaioBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        if self.version == 0 && self.offsets.iter().any(|offset| *offset > u32::MAX as u64) {
+            return Err(Error::InvalidData("saio version 0 offset out of range"));
+        }
+
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        if SaioBox::FLAG_AUX_INFO_TYPE & self.flags != 0 {
+            let aux_info_type = self.aux_info_type.unwrap_or_default();
+            writer.write_u32::<BigEndian>(aux_info_type.into())?;
+            writer.write_u32::<BigEndian>(self.aux_info_type_parameter.unwrap_or(0))?;
+        }
+        writer.write_u32::<BigEndian>(self.offsets.len() as u32)?;
+        for offset in self.offsets.iter() {
+            if self.version == 0 {
+                writer.write_u32::<BigEndian>(*offset as u32)?;
+            } else {
+                writer.write_u64::<BigEndian>(*offset)?;
+            }
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_saio() {
+        let src_box = SaioBox {
+            version: 1,
+            flags: 0,
+            aux_info_type: None,
+            aux_info_type_parameter: None,
+            offsets: vec![0x1_0000_0000],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SaioBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SaioBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -0,0 +1,182 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Sample auxiliary information sizes box (ISO/IEC 14496-12, 8.7.8).
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SaizBox {
+    pub version: u8,
+    pub flags: u32,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aux_info_type: Option<FourCC>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aux_info_type_parameter: Option<u32>,
+
+    /// Size shared by every sample, or 0 when `sample_info_sizes` is used.
+    pub default_sample_info_size: u8,
+    pub sample_count: u32,
+
+    #[serde(skip_serializing)]
+    pub sample_info_sizes: Vec<u8>,
+}
+
+impl SaizBox {
+    pub const FLAG_AUX_INFO_TYPE: u32 = 0x01;
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SaizBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 5;
+        if SaizBox::FLAG_AUX_INFO_TYPE & self.flags != 0 {
+            size += 8;
+        }
+        if self.default_sample_info_size == 0 {
+            size += self.sample_count as u64;
+        }
+        size
+    }
+
+    /// Size of the auxiliary information of the sample at `index`, counted
+    /// from 0.
+    pub fn sample_info_size(&self, index: u32) -> Option<u8> {
+        if index >= self.sample_count {
+            None
+        } else if self.default_sample_info_size != 0 {
+            Some(self.default_sample_info_size)
+        } else {
+            self.sample_info_sizes.get(index as usize).copied()
+        }
+    }
+}
+
+impl Mp4Box for SaizBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "default_sample_info_size={} sample_count={}",
+            self.default_sample_info_size, self.sample_count
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SaizBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+
+        let (aux_info_type, aux_info_type_parameter) = if SaizBox::FLAG_AUX_INFO_TYPE & flags != 0 {
+            (
+                Some(reader.read_u32::<BigEndian>()?.into()),
+                Some(reader.read_u32::<BigEndian>()?),
+            )
+        } else {
+            (None, None)
+        };
+
+        let default_sample_info_size = reader.read_u8()?;
+        let sample_count = reader.read_u32::<BigEndian>()?;
+
+        let mut sample_info_sizes = Vec::new();
+        if default_sample_info_size == 0 {
+            if reader.stream_position()? + sample_count as u64 > start + size {
+                return Err(Error::InvalidData("saiz sample count too large"));
+            }
+            sample_info_sizes = vec![0; sample_count as usize];
+            reader.read_exact(&mut sample_info_sizes)?;
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SaizBox {
+            version,
+            flags,
+            aux_info_type,
+            aux_info_type_parameter,
+            default_sample_info_size,
+            sample_count,
+            sample_info_sizes,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SaizBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        if self.default_sample_info_size == 0
+            && self.sample_info_sizes.len() != self.sample_count as usize
+        {
+            return Err(Error::InvalidData(
+                "saiz sample info sizes do not match the sample count",
+            ));
+        }
+
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        if SaizBox::FLAG_AUX_INFO_TYPE & self.flags != 0 {
+            let aux_info_type = self.aux_info_type.unwrap_or_default();
+            writer.write_u32::<BigEndian>(aux_info_type.into())?;
+            writer.write_u32::<BigEndian>(self.aux_info_type_parameter.unwrap_or(0))?;
+        }
+        writer.write_u8(self.default_sample_info_size)?;
+        writer.write_u32::<BigEndian>(self.sample_count)?;
+        if self.default_sample_info_size == 0 {
+            writer.write_all(&self.sample_info_sizes)?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_saiz() {
+        let src_box = SaizBox {
+            version: 0,
+            flags: SaizBox::FLAG_AUX_INFO_TYPE,
+            aux_info_type: Some(str::parse("cenc").unwrap()),
+            aux_info_type_parameter: Some(0),
+            default_sample_info_size: 0,
+            sample_count: 3,
+            sample_info_sizes: vec![16, 22, 16],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SaizBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SaizBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.sample_info_size(1), Some(22));
+        assert_eq!(dst_box.sample_info_size(3), None);
+    }
+}
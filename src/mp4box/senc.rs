@@ -0,0 +1,261 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+use crate::mp4box::*;
+
+/// Clear and protected byte counts of one subsample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct SubsampleEntry {
+    pub bytes_of_clear_data: u16,
+    pub bytes_of_protected_data: u32,
+}
+
+/// Encryption parameters of one sample: its IV and, for subsample
+/// encryption, which byte ranges are left in the clear.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SampleEncryptionEntry {
+    pub iv: Vec<u8>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub subsamples: Vec<SubsampleEntry>,
+}
+
+impl SampleEncryptionEntry {
+    /// Parse the auxiliary information of one sample, as stored in `senc`
+    /// or pointed at by `saiz` and `saio`.
+    pub fn read<R: Read>(reader: &mut R, iv_size: u8, has_subsamples: bool) -> Result<Self> {
+        let mut iv = vec![0; iv_size as usize];
+        reader.read_exact(&mut iv)?;
+
+        let mut subsamples = Vec::new();
+        if has_subsamples {
+            let subsample_count = reader.read_u16::<BigEndian>()?;
+            for _ in 0..subsample_count {
+                subsamples.push(SubsampleEntry {
+                    bytes_of_clear_data: reader.read_u16::<BigEndian>()?,
+                    bytes_of_protected_data: reader.read_u32::<BigEndian>()?,
+                });
+            }
+        }
+
+        Ok(SampleEncryptionEntry { iv, subsamples })
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W, has_subsamples: bool) -> Result<()> {
+        writer.write_all(&self.iv)?;
+        if has_subsamples {
+            writer.write_u16::<BigEndian>(self.subsamples.len() as u16)?;
+            for subsample in self.subsamples.iter() {
+                writer.write_u16::<BigEndian>(subsample.bytes_of_clear_data)?;
+                writer.write_u32::<BigEndian>(subsample.bytes_of_protected_data)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn size(&self, has_subsamples: bool) -> u64 {
+        let mut size = self.iv.len() as u64;
+        if has_subsamples {
+            size += 2 + 6 * self.subsamples.len() as u64;
+        }
+        size
+    }
+}
+
+/// Sample encryption box (ISO/IEC 23001-7, 7.2).
+///
+/// The IV size of the entries is not stored in the box but in `tenc`, so
+/// the entries are kept as raw bytes and parsed with [SencBox::entries].
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SencBox {
+    pub version: u8,
+    pub flags: u32,
+    pub sample_count: u32,
+
+    #[serde(skip_serializing)]
+    pub data: Vec<u8>,
+}
+
+impl SencBox {
+    pub const FLAG_USE_SUBSAMPLE_ENCRYPTION: u32 = 0x02;
+
+    pub fn new(entries: &[SampleEncryptionEntry]) -> Self {
+        let has_subsamples = entries.iter().any(|entry| !entry.subsamples.is_empty());
+        let mut data = Vec::new();
+        for entry in entries.iter() {
+            // Writing to a Vec does not fail.
+            entry.write(&mut data, has_subsamples).unwrap();
+        }
+        Self {
+            version: 0,
+            flags: if has_subsamples {
+                SencBox::FLAG_USE_SUBSAMPLE_ENCRYPTION
+            } else {
+                0
+            },
+            sample_count: entries.len() as u32,
+            data,
+        }
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SencBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + HEADER_EXT_SIZE + 4 + self.data.len() as u64
+    }
+
+    pub fn has_subsamples(&self) -> bool {
+        SencBox::FLAG_USE_SUBSAMPLE_ENCRYPTION & self.flags != 0
+    }
+
+    /// Parse the entries, given the per-sample IV size from `tenc`.
+    pub fn entries(&self, iv_size: u8) -> Result<Vec<SampleEncryptionEntry>> {
+        let mut reader = Cursor::new(&self.data);
+        let mut entries = Vec::with_capacity(self.sample_count as usize);
+        for _ in 0..self.sample_count {
+            let entry = SampleEncryptionEntry::read(&mut reader, iv_size, self.has_subsamples())
+                .map_err(|_| Error::InvalidData("senc entries do not match the iv size"))?;
+            entries.push(entry);
+        }
+        if reader.position() != self.data.len() as u64 {
+            return Err(Error::InvalidData("senc entries do not match the iv size"));
+        }
+        Ok(entries)
+    }
+
+    /// Parse the entry of the sample at `index`, counted from 0, without
+    /// parsing the entries after it.
+    pub fn entry(&self, iv_size: u8, index: u32) -> Result<Option<SampleEncryptionEntry>> {
+        if index >= self.sample_count {
+            return Ok(None);
+        }
+
+        let mut reader = Cursor::new(&self.data);
+        if self.has_subsamples() {
+            for _ in 0..index {
+                reader.seek(SeekFrom::Current(iv_size as i64))?;
+                let subsample_count = reader.read_u16::<BigEndian>()?;
+                reader.seek(SeekFrom::Current(6 * subsample_count as i64))?;
+            }
+        } else {
+            reader.set_position(index as u64 * iv_size as u64);
+        }
+        let entry = SampleEncryptionEntry::read(&mut reader, iv_size, self.has_subsamples())
+            .map_err(|_| Error::InvalidData("senc entries do not match the iv size"))?;
+        Ok(Some(entry))
+    }
+}
+
+impl Mp4Box for SencBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "sample_count={} subsamples={}",
+            self.sample_count,
+            self.has_subsamples()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SencBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+
+        let sample_count = reader.read_u32::<BigEndian>()?;
+
+        let data_size = (start + size).saturating_sub(reader.stream_position()?);
+        let mut data = Vec::new();
+        reader.take(data_size).read_to_end(&mut data)?;
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SencBox {
+            version,
+            flags,
+            sample_count,
+            data,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SencBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        writer.write_u32::<BigEndian>(self.sample_count)?;
+        writer.write_all(&self.data)?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_senc_subsamples() {
+        let entries = vec![
+            SampleEncryptionEntry {
+                iv: vec![0, 0, 0, 0, 0, 0, 0, 1],
+                subsamples: vec![
+                    SubsampleEntry {
+                        bytes_of_clear_data: 5,
+                        bytes_of_protected_data: 1024,
+                    },
+                    SubsampleEntry {
+                        bytes_of_clear_data: 4,
+                        bytes_of_protected_data: 96,
+                    },
+                ],
+            },
+            SampleEncryptionEntry {
+                iv: vec![0, 0, 0, 0, 0, 0, 0, 2],
+                subsamples: vec![SubsampleEntry {
+                    bytes_of_clear_data: 5,
+                    bytes_of_protected_data: 32,
+                }],
+            },
+        ];
+        let src_box = SencBox::new(&entries);
+        assert!(src_box.has_subsamples());
+
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SencBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SencBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.entries(8).unwrap(), entries);
+        assert_eq!(dst_box.entry(8, 1).unwrap().unwrap(), entries[1]);
+        assert_eq!(dst_box.entry(8, 2).unwrap(), None);
+        assert!(dst_box.entries(16).is_err());
+    }
+}
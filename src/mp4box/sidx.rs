@@ -168,7 +168,7 @@ impl<W: Write> WriteBox<&mut W> for SidxBox {
 
 impl Segment {
     fn size(&self) -> usize {
-        12 as usize
+        12_usize
     }
 
     fn read<R: Read + Seek>(reader: &mut R) -> Result<Self> {
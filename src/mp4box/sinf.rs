@@ -0,0 +1,460 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::tenc::TencBox;
+use crate::mp4box::*;
+
+/// Protection scheme information box, found in `encv` and `enca` sample
+/// entries (ISO/IEC 14496-12, 8.12.1).
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SinfBox {
+    pub frma: FrmaBox,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schm: Option<SchmBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schi: Option<SchiBox>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
+}
+
+impl SinfBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SinfBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + self.frma.box_size();
+        if let Some(ref schm) = self.schm {
+            size += schm.box_size();
+        }
+        if let Some(ref schi) = self.schi {
+            size += schi.box_size();
+        }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
+        size
+    }
+
+    pub fn tenc(&self) -> Option<&TencBox> {
+        self.schi.as_ref().and_then(|schi| schi.tenc.as_ref())
+    }
+}
+
+impl Mp4Box for SinfBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("original_format={}", self.frma.original_format);
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SinfBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let mut frma = None;
+        let mut schm = None;
+        let mut schi = None;
+
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            // Get box header.
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "sinf box contains a box with a larger size than it",
+                ));
+            }
+
+            child_order.push(name);
+
+            match name {
+                BoxType::FrmaBox => {
+                    frma = Some(FrmaBox::read_box(reader, s)?);
+                }
+                BoxType::SchmBox => {
+                    schm = Some(SchmBox::read_box(reader, s)?);
+                }
+                BoxType::SchiBox => {
+                    schi = Some(SchiBox::read_box(reader, s)?);
+                }
+                _ => {
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
+                }
+            }
+
+            current = reader.stream_position()?;
+        }
+
+        if frma.is_none() {
+            return Err(Error::BoxNotFound(BoxType::FrmaBox));
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SinfBox {
+            frma: frma.unwrap(),
+            schm,
+            schi,
+            unknown_boxes,
+            child_order,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SinfBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        let mut children = ChildWriter::new();
+        children.push(&self.frma);
+        children.extend(&self.schm);
+        children.extend(&self.schi);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
+
+        Ok(size)
+    }
+}
+
+/// Original format box, holding the sample entry type before encryption.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct FrmaBox {
+    pub original_format: FourCC,
+}
+
+impl Mp4Box for FrmaBox {
+    fn box_type(&self) -> BoxType {
+        BoxType::FrmaBox
+    }
+
+    fn box_size(&self) -> u64 {
+        HEADER_SIZE + 4
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("original_format={}", self.original_format);
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for FrmaBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let original_format = reader.read_u32::<BigEndian>()?.into();
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(FrmaBox { original_format })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for FrmaBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u32::<BigEndian>((&self.original_format).into())?;
+
+        Ok(size)
+    }
+}
+
+/// Scheme type box, naming the protection scheme such as `cenc` or `cbcs`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SchmBox {
+    pub version: u8,
+    pub flags: u32,
+    pub scheme_type: FourCC,
+    pub scheme_version: u32,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scheme_uri: Option<String>,
+}
+
+impl SchmBox {
+    pub const FLAG_SCHEME_URI: u32 = 0x01;
+}
+
+impl Mp4Box for SchmBox {
+    fn box_type(&self) -> BoxType {
+        BoxType::SchmBox
+    }
+
+    fn box_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 8;
+        if let Some(ref scheme_uri) = self.scheme_uri {
+            size += scheme_uri.len() as u64 + 1;
+        }
+        size
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "scheme_type={} scheme_version={:#x}",
+            self.scheme_type, self.scheme_version
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SchmBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+
+        let scheme_type = reader.read_u32::<BigEndian>()?.into();
+        let scheme_version = reader.read_u32::<BigEndian>()?;
+
+        let scheme_uri = if SchmBox::FLAG_SCHEME_URI & flags != 0 {
+            let uri_size = (start + size).saturating_sub(reader.stream_position()?);
+            let mut buf = Vec::new();
+            reader.take(uri_size).read_to_end(&mut buf)?;
+            if let Some(end) = buf.iter().position(|&b| b == b'\0') {
+                buf.truncate(end);
+            }
+            match String::from_utf8(buf) {
+                Ok(scheme_uri) => Some(scheme_uri),
+                Err(_) => return Err(Error::InvalidData("invalid utf8")),
+            }
+        } else {
+            None
+        };
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SchmBox {
+            version,
+            flags,
+            scheme_type,
+            scheme_version,
+            scheme_uri,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SchmBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        let mut flags = self.flags & !SchmBox::FLAG_SCHEME_URI;
+        if self.scheme_uri.is_some() {
+            flags |= SchmBox::FLAG_SCHEME_URI;
+        }
+        write_box_header_ext(writer, self.version, flags)?;
+
+        writer.write_u32::<BigEndian>((&self.scheme_type).into())?;
+        writer.write_u32::<BigEndian>(self.scheme_version)?;
+        if let Some(ref scheme_uri) = self.scheme_uri {
+            writer.write_all(scheme_uri.as_bytes())?;
+            writer.write_u8(0)?;
+        }
+
+        Ok(size)
+    }
+}
+
+/// Scheme information box. Holds `tenc` for the Common Encryption schemes.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SchiBox {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenc: Option<TencBox>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
+}
+
+impl Mp4Box for SchiBox {
+    fn box_type(&self) -> BoxType {
+        BoxType::SchiBox
+    }
+
+    fn box_size(&self) -> u64 {
+        let mut size = HEADER_SIZE;
+        if let Some(ref tenc) = self.tenc {
+            size += tenc.box_size();
+        }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
+        size
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = String::new();
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SchiBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let mut tenc = None;
+
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            // Get box header.
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "schi box contains a box with a larger size than it",
+                ));
+            }
+
+            child_order.push(name);
+
+            match name {
+                BoxType::TencBox => {
+                    tenc = Some(TencBox::read_box(reader, s)?);
+                }
+                _ => {
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
+                }
+            }
+
+            current = reader.stream_position()?;
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SchiBox {
+            tenc,
+            unknown_boxes,
+            child_order,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SchiBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        let mut children = ChildWriter::new();
+        children.extend(&self.tenc);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_sinf() {
+        let src_box = SinfBox {
+            frma: FrmaBox {
+                original_format: BoxType::Avc1Box.into(),
+            },
+            schm: Some(SchmBox {
+                version: 0,
+                flags: 0,
+                scheme_type: str::parse("cenc").unwrap(),
+                scheme_version: 0x0001_0000,
+                scheme_uri: None,
+            }),
+            schi: Some(SchiBox {
+                tenc: Some(TencBox {
+                    version: 0,
+                    flags: 0,
+                    default_crypt_byte_block: 0,
+                    default_skip_byte_block: 0,
+                    default_is_protected: 1,
+                    default_per_sample_iv_size: 8,
+                    default_kid: [0x11; 16],
+                    default_constant_iv: None,
+                }),
+                unknown_boxes: Vec::new(),
+                child_order: ChildOrder::default(),
+            }),
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SinfBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SinfBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.tenc().unwrap().default_per_sample_iv_size, 8);
+    }
+
+    #[test]
+    fn test_schm_uri() {
+        let src_box = SchmBox {
+            version: 0,
+            flags: SchmBox::FLAG_SCHEME_URI,
+            scheme_type: str::parse("cbcs").unwrap(),
+            scheme_version: 0x0001_0000,
+            scheme_uri: Some(String::from("urn:example:scheme")),
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SchmBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SchmBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -3,8 +3,8 @@ use std::io::{Read, Seek, Write};
 
 use crate::mp4box::*;
 use crate::mp4box::{
-    co64::Co64Box, ctts::CttsBox, stco::StcoBox, stsc::StscBox, stsd::StsdBox, stss::StssBox,
-    stsz::StszBox, stts::SttsBox,
+    co64::Co64Box, ctts::CttsBox, saio::SaioBox, saiz::SaizBox, senc::SencBox, stco::StcoBox,
+    stsc::StscBox, stsd::StsdBox, stss::StssBox, stsz::StszBox, stts::SttsBox,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
@@ -25,6 +25,20 @@ pub struct StblBox {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub co64: Option<Co64Box>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saiz: Option<SaizBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saio: Option<SaioBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub senc: Option<SencBox>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
 }
 
 impl StblBox {
@@ -50,6 +64,18 @@ impl StblBox {
         if let Some(ref co64) = self.co64 {
             size += co64.box_size();
         }
+        if let Some(ref saiz) = self.saiz {
+            size += saiz.box_size();
+        }
+        if let Some(ref saio) = self.saio {
+            size += saio.box_size();
+        }
+        if let Some(ref senc) = self.senc {
+            size += senc.box_size();
+        }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
         size
     }
 }
@@ -85,7 +111,12 @@ impl<R: Read + Seek> ReadBox<&mut R> for StblBox {
         let mut stsz = None;
         let mut stco = None;
         let mut co64 = None;
+        let mut saiz = None;
+        let mut saio = None;
+        let mut senc = None;
 
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
         let mut current = reader.stream_position()?;
         let end = start + size;
         while current < end {
@@ -98,6 +129,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for StblBox {
                 ));
             }
 
+            child_order.push(name);
+
             match name {
                 BoxType::StsdBox => {
                     stsd = Some(StsdBox::read_box(reader, s)?);
@@ -123,9 +156,18 @@ impl<R: Read + Seek> ReadBox<&mut R> for StblBox {
                 BoxType::Co64Box => {
                     co64 = Some(Co64Box::read_box(reader, s)?);
                 }
+                BoxType::SaizBox if saiz.is_none() => {
+                    saiz = Some(SaizBox::read_box(reader, s)?);
+                }
+                BoxType::SaioBox if saio.is_none() => {
+                    saio = Some(SaioBox::read_box(reader, s)?);
+                }
+                BoxType::SencBox if senc.is_none() => {
+                    senc = Some(SencBox::read_box(reader, s)?);
+                }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
                 }
             }
             current = reader.stream_position()?;
@@ -158,6 +200,11 @@ impl<R: Read + Seek> ReadBox<&mut R> for StblBox {
             stsz: stsz.unwrap(),
             stco,
             co64,
+            saiz,
+            saio,
+            senc,
+            unknown_boxes,
+            child_order,
         })
     }
 }
@@ -167,22 +214,20 @@ impl<W: Write> WriteBox<&mut W> for StblBox {
         let size = self.box_size();
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
-        self.stsd.write_box(writer)?;
-        self.stts.write_box(writer)?;
-        if let Some(ref ctts) = self.ctts {
-            ctts.write_box(writer)?;
-        }
-        if let Some(ref stss) = self.stss {
-            stss.write_box(writer)?;
-        }
-        self.stsc.write_box(writer)?;
-        self.stsz.write_box(writer)?;
-        if let Some(ref stco) = self.stco {
-            stco.write_box(writer)?;
-        }
-        if let Some(ref co64) = self.co64 {
-            co64.write_box(writer)?;
-        }
+        let mut children = ChildWriter::new();
+        children.push(&self.stsd);
+        children.push(&self.stts);
+        children.extend(&self.ctts);
+        children.extend(&self.stss);
+        children.push(&self.stsc);
+        children.push(&self.stsz);
+        children.extend(&self.stco);
+        children.extend(&self.co64);
+        children.extend(&self.saiz);
+        children.extend(&self.saio);
+        children.extend(&self.senc);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
 
         Ok(size)
     }
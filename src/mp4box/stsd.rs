@@ -1,11 +1,13 @@
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use serde::Serialize;
-use std::io::{Read, Seek, Write};
+use std::convert::TryFrom;
+use std::io::{Cursor, Read, Seek, Write};
 
 use crate::mp4box::vp09::Vp09Box;
 use crate::mp4box::*;
 use crate::mp4box::{
-    avc1::Avc1Box, hev1::Hev1Box, hvc1::Hvc1Box, mp4a::Mp4aBox, opus::OpusBox, tx3g::Tx3gBox,
+    ac3::Ac3Box, av01::Av01Box, avc1::Avc1Box, ec3::Ec3Box, flac::FlacBox, hev1::Hev1Box,
+    hvc1::Hvc1Box, mp4a::Mp4aBox, opus::OpusBox, sinf::SinfBox, tx3g::Tx3gBox,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
@@ -25,14 +27,72 @@ pub struct StsdBox {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub vp09: Option<Vp09Box>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub av01: Option<Av01Box>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mp4a: Option<Mp4aBox>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub opus: Option<OpusBox>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ac3: Option<Ac3Box>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ec3: Option<Ec3Box>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flac: Option<FlacBox>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tx3g: Option<Tx3gBox>,
+
+    /// Protection scheme of the parsed sample entry. When set, the entry is
+    /// an `encv` or `enca` whose original format is in `sinf.frma`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sinf: Option<SinfBox>,
+
+    /// Sample entries of a type this crate does not parse, or beyond the
+    /// first parsed one.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+
+    /// Position of the parsed sample entry among `unknown_boxes`, to write
+    /// the entries back in their original order.
+    #[serde(skip)]
+    pub entry_index: usize,
+}
+
+/// Evaluate `$body` with `$entry` bound to the parsed sample entry, if any.
+macro_rules! with_entry {
+    ($stsd:expr, $entry:ident => $body:expr) => {
+        if let Some(ref $entry) = $stsd.avc1 {
+            Some($body)
+        } else if let Some(ref $entry) = $stsd.hev1 {
+            Some($body)
+        } else if let Some(ref $entry) = $stsd.hvc1 {
+            Some($body)
+        } else if let Some(ref $entry) = $stsd.vp09 {
+            Some($body)
+        } else if let Some(ref $entry) = $stsd.av01 {
+            Some($body)
+        } else if let Some(ref $entry) = $stsd.mp4a {
+            Some($body)
+        } else if let Some(ref $entry) = $stsd.opus {
+            Some($body)
+        } else if let Some(ref $entry) = $stsd.ac3 {
+            Some($body)
+        } else if let Some(ref $entry) = $stsd.ec3 {
+            Some($body)
+        } else if let Some(ref $entry) = $stsd.flac {
+            Some($body)
+        } else if let Some(ref $entry) = $stsd.tx3g {
+            Some($body)
+        } else {
+            None
+        }
+    };
 }
 
 impl StsdBox {
@@ -42,23 +102,186 @@ impl StsdBox {
 
     pub fn get_size(&self) -> u64 {
         let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 4;
-        if let Some(ref avc1) = self.avc1 {
-            size += avc1.box_size();
-        } else if let Some(ref hev1) = self.hev1 {
-            size += hev1.box_size();
-        } else if let Some(ref hvc1) = self.hvc1 {
-            size += hvc1.box_size();
-        } else if let Some(ref vp09) = self.vp09 {
-            size += vp09.box_size();
-        } else if let Some(ref mp4a) = self.mp4a {
-            size += mp4a.box_size();
-        } else if let Some(ref opus) = self.opus {
-            size += opus.box_size();
-        } else if let Some(ref tx3g) = self.tx3g {
-            size += tx3g.box_size();
+        size += with_entry!(self, entry => entry.box_size()).unwrap_or(0);
+        if let Some(ref sinf) = self.sinf {
+            if self.has_entry() {
+                size += sinf.box_size();
+            }
+        }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
         }
         size
     }
+
+    /// Whether one of the sample entries is parsed.
+    pub fn has_entry(&self) -> bool {
+        self.entry_type().is_some()
+    }
+
+    /// Type of the parsed sample entry, ignoring `sinf`.
+    pub fn entry_type(&self) -> Option<BoxType> {
+        with_entry!(self, entry => entry.box_type())
+    }
+
+    /// Type the parsed sample entry is stored as when `sinf` is set.
+    pub fn protected_type(&self) -> BoxType {
+        if self.mp4a.is_some()
+            || self.opus.is_some()
+            || self.ac3.is_some()
+            || self.ec3.is_some()
+            || self.flac.is_some()
+        {
+            BoxType::EncaBox
+        } else {
+            BoxType::EncvBox
+        }
+    }
+
+    fn read_entry<R: Read + Seek>(&mut self, reader: &mut R, header: &BoxHeader) -> Result<()> {
+        let parsed = self.has_entry();
+        let s = header.size;
+        match header.name {
+            BoxType::Avc1Box if !parsed => {
+                self.avc1 = Some(Avc1Box::read_box(reader, s)?);
+            }
+            BoxType::Hev1Box if !parsed => {
+                self.hev1 = Some(Hev1Box::read_box(reader, s)?);
+            }
+            BoxType::Hvc1Box if !parsed => {
+                self.hvc1 = Some(Hvc1Box::read_box(reader, s)?);
+            }
+            BoxType::Vp09Box if !parsed => {
+                self.vp09 = Some(Vp09Box::read_box(reader, s)?);
+            }
+            BoxType::Av01Box if !parsed => {
+                self.av01 = Some(Av01Box::read_box(reader, s)?);
+            }
+            BoxType::Mp4aBox if !parsed => {
+                self.mp4a = Some(Mp4aBox::read_box(reader, s)?);
+            }
+            BoxType::OpusBox if !parsed => {
+                self.opus = Some(OpusBox::read_box(reader, s)?);
+            }
+            BoxType::Ac3Box if !parsed => {
+                self.ac3 = Some(Ac3Box::read_box(reader, s)?);
+            }
+            BoxType::Ec3Box if !parsed => {
+                self.ec3 = Some(Ec3Box::read_box(reader, s)?);
+            }
+            BoxType::FlacBox if !parsed => {
+                self.flac = Some(FlacBox::read_box(reader, s)?);
+            }
+            BoxType::Tx3gBox if !parsed => {
+                self.tx3g = Some(Tx3gBox::read_box(reader, s)?);
+            }
+            BoxType::EncvBox | BoxType::EncaBox if !parsed => {
+                let entry = UnknownBox::read(reader, header)?;
+                self.read_protected_entry(entry)?;
+            }
+            _ => {
+                let unknown = UnknownBox::read(reader, header)?;
+                self.unknown_boxes.push(unknown);
+            }
+        }
+        if !parsed && self.has_entry() {
+            self.entry_index = self.unknown_boxes.len();
+        }
+        Ok(())
+    }
+
+    /// Parse an `encv` or `enca` entry as its original format, moving its
+    /// `sinf` to `self.sinf`. Entries whose original format is not parsed
+    /// are kept unchanged in `unknown_boxes`.
+    fn read_protected_entry(&mut self, entry: UnknownBox) -> Result<()> {
+        let payload = &entry.payload;
+
+        // Child boxes follow the fixed fields of the visual or audio sample
+        // entry. QuickTime sound versions 1 and 2 add 16 and 36 bytes.
+        let mut offset = if entry.box_type == BoxType::EncvBox {
+            78
+        } else {
+            match payload.get(8..10) {
+                Some([0, 1]) => 44,
+                Some([0, 2]) => 64,
+                _ => 28,
+            }
+        };
+
+        let mut sinf = None;
+        while offset + HEADER_SIZE as usize <= payload.len() {
+            let mut reader = Cursor::new(&payload[offset..]);
+            let header = BoxHeader::read(&mut reader)?;
+            let box_end = match usize::try_from(header.size) {
+                Ok(size) if size >= HEADER_SIZE as usize && offset + size <= payload.len() => {
+                    offset + size
+                }
+                _ => break,
+            };
+            if header.name == BoxType::SinfBox {
+                sinf = Some((
+                    SinfBox::read_box(&mut reader, header.size)?,
+                    offset..box_end,
+                ));
+                break;
+            }
+            offset = box_end;
+        }
+        let (sinf, range) = match sinf {
+            Some(sinf) => sinf,
+            None => {
+                self.unknown_boxes.push(entry);
+                return Ok(());
+            }
+        };
+
+        let original_type = BoxType::from(u32::from(sinf.frma.original_format));
+        if original_type == BoxType::EncvBox || original_type == BoxType::EncaBox {
+            self.unknown_boxes.push(entry);
+            return Ok(());
+        }
+
+        let size = HEADER_SIZE + (payload.len() - range.len()) as u64;
+        let mut buf = Vec::with_capacity(size as usize);
+        BoxHeader::new(original_type, size).write(&mut buf)?;
+        buf.extend_from_slice(&payload[..range.start]);
+        buf.extend_from_slice(&payload[range.end..]);
+
+        let mut reader = Cursor::new(buf);
+        let header = BoxHeader::read(&mut reader)?;
+        self.read_entry(&mut reader, &header)?;
+        if self.has_entry() {
+            self.sinf = Some(sinf);
+        } else {
+            self.unknown_boxes.pop();
+            self.unknown_boxes.push(entry);
+        }
+        Ok(())
+    }
+
+    fn write_entry<W: Write>(&self, writer: &mut W) -> Result<()> {
+        with_entry!(self, entry => entry.write_box(writer)).transpose()?;
+        Ok(())
+    }
+
+    /// Write the parsed sample entry, under its protected type if `sinf`
+    /// is set.
+    fn write_parsed_entry<W: Write>(&self, writer: &mut W) -> Result<()> {
+        match self.sinf {
+            Some(ref sinf) => {
+                // Store the entry under its protected type, with the
+                // original type kept in sinf.
+                let mut buf = Vec::new();
+                self.write_entry(&mut buf)?;
+                let entry_size = buf.len() as u64 + sinf.box_size();
+                BoxHeader::new(self.protected_type(), entry_size).write(writer)?;
+                writer.write_all(&buf[HEADER_SIZE as usize..])?;
+                sinf.write_box(writer)?;
+            }
+            None => self.write_entry(writer)?,
+        }
+        Ok(())
+    }
 }
 
 impl Mp4Box for StsdBox {
@@ -86,63 +309,34 @@ impl<R: Read + Seek> ReadBox<&mut R> for StsdBox {
 
         let (version, flags) = read_box_header_ext(reader)?;
 
-        reader.read_u32::<BigEndian>()?; // XXX entry_count
-
-        let mut avc1 = None;
-        let mut hev1 = None;
-        let mut hvc1 = None;
-        let mut vp09 = None;
-        let mut mp4a = None;
-        let mut opus = None;
-        let mut tx3g = None;
-
-        // Get box header.
-        let header = BoxHeader::read(reader)?;
-        let BoxHeader { name, size: s } = header;
-        if s > size {
-            return Err(Error::InvalidData(
-                "stsd box contains a box with a larger size than it",
-            ));
-        }
+        let entry_count = reader.read_u32::<BigEndian>()?;
 
-        match name {
-            BoxType::Avc1Box => {
-                avc1 = Some(Avc1Box::read_box(reader, s)?);
-            }
-            BoxType::Hev1Box => {
-                hev1 = Some(Hev1Box::read_box(reader, s)?);
-            }
-            BoxType::Hvc1Box => {
-                hvc1 = Some(Hvc1Box::read_box(reader, s)?);
-            }
-            BoxType::Vp09Box => {
-                vp09 = Some(Vp09Box::read_box(reader, s)?);
-            }
-            BoxType::Mp4aBox => {
-                mp4a = Some(Mp4aBox::read_box(reader, s)?);
-            }
-            BoxType::OpusBox => {
-                opus = Some(OpusBox::read_box(reader, s)?);
+        let mut stsd = StsdBox {
+            version,
+            flags,
+            ..StsdBox::default()
+        };
+
+        let end = start + size;
+        for _ in 0..entry_count {
+            if reader.stream_position()? + HEADER_SIZE > end {
+                break;
             }
-            BoxType::Tx3gBox => {
-                tx3g = Some(Tx3gBox::read_box(reader, s)?);
+
+            // Get box header.
+            let header = BoxHeader::read(reader)?;
+            if header.size > size {
+                return Err(Error::InvalidData(
+                    "stsd box contains a box with a larger size than it",
+                ));
             }
-            _ => {}
+
+            stsd.read_entry(reader, &header)?;
         }
 
         skip_bytes_to(reader, start + size)?;
 
-        Ok(StsdBox {
-            version,
-            flags,
-            avc1,
-            hev1,
-            hvc1,
-            vp09,
-            opus,
-            mp4a,
-            tx3g,
-        })
+        Ok(stsd)
     }
 }
 
@@ -153,24 +347,133 @@ impl<W: Write> WriteBox<&mut W> for StsdBox {
 
         write_box_header_ext(writer, self.version, self.flags)?;
 
-        writer.write_u32::<BigEndian>(1)?; // entry_count
-
-        if let Some(ref avc1) = self.avc1 {
-            avc1.write_box(writer)?;
-        } else if let Some(ref hev1) = self.hev1 {
-            hev1.write_box(writer)?;
-        } else if let Some(ref hvc1) = self.hvc1 {
-            hvc1.write_box(writer)?;
-        } else if let Some(ref vp09) = self.vp09 {
-            vp09.write_box(writer)?;
-        } else if let Some(ref mp4a) = self.mp4a {
-            mp4a.write_box(writer)?;
-        } else if let Some(ref tx3g) = self.tx3g {
-            tx3g.write_box(writer)?;
-        } else if let Some(ref opus) = self.opus {
-            opus.write_box(writer)?;
+        let mut entry_count = self.unknown_boxes.len() as u32;
+        if self.has_entry() {
+            entry_count += 1;
+        }
+        writer.write_u32::<BigEndian>(entry_count)?;
+
+        let entry_index = self.entry_index.min(self.unknown_boxes.len());
+        for unknown in self.unknown_boxes[..entry_index].iter() {
+            unknown.write_box(writer)?;
+        }
+        if self.has_entry() {
+            self.write_parsed_entry(writer)?;
+        }
+        for unknown in self.unknown_boxes[entry_index..].iter() {
+            unknown.write_box(writer)?;
         }
 
         Ok(size)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::sinf::{FrmaBox, SchiBox, SchmBox};
+    use crate::mp4box::tenc::TencBox;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_stsd_encv() {
+        let mut avc1 = Avc1Box::new(&AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x0D],
+            pic_param_set: vec![0x68, 0xEB, 0xE3, 0xCB],
+        });
+        // Only the low two bits are stored.
+        avc1.avcc.length_size_minus_one = 3;
+        let src_box = StsdBox {
+            avc1: Some(avc1),
+            sinf: Some(SinfBox {
+                frma: FrmaBox {
+                    original_format: BoxType::Avc1Box.into(),
+                },
+                schm: Some(SchmBox {
+                    scheme_type: str::parse("cenc").unwrap(),
+                    scheme_version: 0x0001_0000,
+                    ..SchmBox::default()
+                }),
+                schi: Some(SchiBox {
+                    tenc: Some(TencBox {
+                        default_is_protected: 1,
+                        default_per_sample_iv_size: 16,
+                        default_kid: [0x5A; 16],
+                        ..TencBox::default()
+                    }),
+                    unknown_boxes: Vec::new(),
+                    child_order: ChildOrder::default(),
+                }),
+                unknown_boxes: Vec::new(),
+                child_order: ChildOrder::default(),
+            }),
+            ..StsdBox::default()
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+        assert_eq!(&buf[20..24], b"encv");
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::StsdBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = StsdBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_stsd_entry_order() {
+        let src_box = StsdBox {
+            tx3g: Some(Tx3gBox::default()),
+            unknown_boxes: vec![
+                UnknownBox::new(BoxType::from(u32::from_be_bytes(*b"mp4v")), vec![0; 78]),
+                UnknownBox::new(BoxType::Tx3gBox, vec![0; 38]),
+            ],
+            entry_index: 1,
+            ..StsdBox::default()
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+        assert_eq!(&buf[20..24], b"mp4v");
+        assert_eq!(&buf[20 + 86..24 + 86], b"tx3g");
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst_box = StsdBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+
+        let mut dst_buf = Vec::new();
+        dst_box.write_box(&mut dst_buf).unwrap();
+        assert_eq!(buf, dst_buf);
+    }
+
+    #[test]
+    fn test_stsd_encv_unknown_format() {
+        let sinf = SinfBox {
+            frma: FrmaBox {
+                original_format: str::parse("mp4v").unwrap(),
+            },
+            ..SinfBox::default()
+        };
+        let mut payload = vec![0; 78];
+        sinf.write_box(&mut payload).unwrap();
+        let src_box = StsdBox {
+            unknown_boxes: vec![UnknownBox::new(BoxType::EncvBox, payload)],
+            ..StsdBox::default()
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst_box = StsdBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert!(dst_box.sinf.is_none());
+    }
+}
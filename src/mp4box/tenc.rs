@@ -0,0 +1,179 @@
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Track encryption box, holding the default encryption parameters of a
+/// track (ISO/IEC 23001-7, 8.2).
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct TencBox {
+    pub version: u8,
+    pub flags: u32,
+
+    /// Pattern encryption, only stored from version 1 on.
+    pub default_crypt_byte_block: u8,
+    pub default_skip_byte_block: u8,
+
+    pub default_is_protected: u8,
+    pub default_per_sample_iv_size: u8,
+    pub default_kid: [u8; 16],
+
+    /// IV shared by every sample, for protected tracks without per-sample IVs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_constant_iv: Option<Vec<u8>>,
+}
+
+impl TencBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::TencBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 20;
+        if let Some(ref constant_iv) = self.default_constant_iv {
+            size += 1 + constant_iv.len() as u64;
+        }
+        size
+    }
+}
+
+impl Mp4Box for TencBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "is_protected={} per_sample_iv_size={} crypt_byte_block={} skip_byte_block={}",
+            self.default_is_protected,
+            self.default_per_sample_iv_size,
+            self.default_crypt_byte_block,
+            self.default_skip_byte_block
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for TencBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+
+        reader.read_u8()?; // reserved
+        let pattern = reader.read_u8()?;
+        let (default_crypt_byte_block, default_skip_byte_block) = if version > 0 {
+            (pattern >> 4, pattern & 0x0F)
+        } else {
+            (0, 0)
+        };
+        let default_is_protected = reader.read_u8()?;
+        let default_per_sample_iv_size = reader.read_u8()?;
+        let mut default_kid = [0; 16];
+        reader.read_exact(&mut default_kid)?;
+
+        let default_constant_iv = if default_is_protected == 1 && default_per_sample_iv_size == 0 {
+            let iv_size = reader.read_u8()?;
+            let mut constant_iv = vec![0; iv_size as usize];
+            reader.read_exact(&mut constant_iv)?;
+            Some(constant_iv)
+        } else {
+            None
+        };
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(TencBox {
+            version,
+            flags,
+            default_crypt_byte_block,
+            default_skip_byte_block,
+            default_is_protected,
+            default_per_sample_iv_size,
+            default_kid,
+            default_constant_iv,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for TencBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        if let Some(ref constant_iv) = self.default_constant_iv {
+            if self.default_is_protected != 1 || self.default_per_sample_iv_size != 0 {
+                return Err(Error::InvalidData(
+                    "tenc constant iv requires a protected track without per-sample ivs",
+                ));
+            }
+            if constant_iv.len() > u8::MAX as usize {
+                return Err(Error::InvalidData("tenc constant iv too long"));
+            }
+        }
+
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        writer.write_u8(0)?; // reserved
+        if self.version > 0 {
+            writer.write_u8(
+                (self.default_crypt_byte_block & 0x0F) << 4 | self.default_skip_byte_block & 0x0F,
+            )?;
+        } else {
+            writer.write_u8(0)?; // reserved
+        }
+        writer.write_u8(self.default_is_protected)?;
+        writer.write_u8(self.default_per_sample_iv_size)?;
+        writer.write_all(&self.default_kid)?;
+        if let Some(ref constant_iv) = self.default_constant_iv {
+            writer.write_u8(constant_iv.len() as u8)?;
+            writer.write_all(constant_iv)?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_tenc_cbcs() {
+        let src_box = TencBox {
+            version: 1,
+            flags: 0,
+            default_crypt_byte_block: 1,
+            default_skip_byte_block: 9,
+            default_is_protected: 1,
+            default_per_sample_iv_size: 0,
+            default_kid: [
+                0x90, 0x1a, 0x3c, 0x42, 0x5e, 0x71, 0x4b, 0x03, 0x8c, 0x4d, 0x55, 0x6e, 0x0f, 0x21,
+                0x87, 0xa9,
+            ],
+            default_constant_iv: Some(vec![0x42; 16]),
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::TencBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = TencBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
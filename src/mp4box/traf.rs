@@ -2,13 +2,29 @@ use serde::Serialize;
 use std::io::{Read, Seek, Write};
 
 use crate::mp4box::*;
-use crate::mp4box::{tfdt::TfdtBox, tfhd::TfhdBox, trun::TrunBox};
+use crate::mp4box::{
+    saio::SaioBox, saiz::SaizBox, senc::SencBox, tfdt::TfdtBox, tfhd::TfhdBox, trun::TrunBox,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct TrafBox {
     pub tfhd: TfhdBox,
     pub tfdt: Option<TfdtBox>,
-    pub trun: Option<TrunBox>,
+    #[serde(rename = "trun")]
+    pub truns: Vec<TrunBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saiz: Option<SaizBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saio: Option<SaioBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub senc: Option<SencBox>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
 }
 
 impl TrafBox {
@@ -22,9 +38,21 @@ impl TrafBox {
         if let Some(ref tfdt) = self.tfdt {
             size += tfdt.box_size();
         }
-        if let Some(ref trun) = self.trun {
+        for trun in self.truns.iter() {
             size += trun.box_size();
         }
+        if let Some(ref saiz) = self.saiz {
+            size += saiz.box_size();
+        }
+        if let Some(ref saio) = self.saio {
+            size += saio.box_size();
+        }
+        if let Some(ref senc) = self.senc {
+            size += senc.box_size();
+        }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
         size
     }
 }
@@ -43,7 +71,7 @@ impl Mp4Box for TrafBox {
     }
 
     fn summary(&self) -> Result<String> {
-        let s = String::new();
+        let s = format!("track_id={} truns={}", self.tfhd.track_id, self.truns.len());
         Ok(s)
     }
 }
@@ -54,8 +82,13 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrafBox {
 
         let mut tfhd = None;
         let mut tfdt = None;
-        let mut trun = None;
+        let mut truns = Vec::new();
+        let mut saiz = None;
+        let mut saio = None;
+        let mut senc = None;
 
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
         let mut current = reader.stream_position()?;
         let end = start + size;
         while current < end {
@@ -68,6 +101,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrafBox {
                 ));
             }
 
+            child_order.push(name);
+
             match name {
                 BoxType::TfhdBox => {
                     tfhd = Some(TfhdBox::read_box(reader, s)?);
@@ -76,11 +111,21 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrafBox {
                     tfdt = Some(TfdtBox::read_box(reader, s)?);
                 }
                 BoxType::TrunBox => {
-                    trun = Some(TrunBox::read_box(reader, s)?);
+                    let trun = TrunBox::read_box(reader, s)?;
+                    truns.push(trun);
+                }
+                BoxType::SaizBox if saiz.is_none() => {
+                    saiz = Some(SaizBox::read_box(reader, s)?);
+                }
+                BoxType::SaioBox if saio.is_none() => {
+                    saio = Some(SaioBox::read_box(reader, s)?);
+                }
+                BoxType::SencBox if senc.is_none() => {
+                    senc = Some(SencBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
                 }
             }
 
@@ -96,7 +141,12 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrafBox {
         Ok(TrafBox {
             tfhd: tfhd.unwrap(),
             tfdt,
-            trun,
+            truns,
+            saiz,
+            saio,
+            senc,
+            unknown_boxes,
+            child_order,
         })
     }
 }
@@ -106,14 +156,86 @@ impl<W: Write> WriteBox<&mut W> for TrafBox {
         let size = self.box_size();
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
-        self.tfhd.write_box(writer)?;
-        if let Some(ref tfdt) = self.tfdt {
-            tfdt.write_box(writer)?;
-        }
-        if let Some(ref trun) = self.trun {
-            trun.write_box(writer)?;
-        }
+        let mut children = ChildWriter::new();
+        children.push(&self.tfhd);
+        children.extend(&self.tfdt);
+        children.extend(&self.truns);
+        children.extend(&self.saiz);
+        children.extend(&self.saio);
+        children.extend(&self.senc);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
 
         Ok(size)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_traf_many_truns() {
+        let trun = TrunBox {
+            version: 0,
+            flags: TrunBox::FLAG_DATA_OFFSET | TrunBox::FLAG_SAMPLE_SIZE,
+            data_offset: Some(200),
+            sample_count: 3,
+            sample_sizes: vec![1165, 11, 11],
+            ..TrunBox::default()
+        };
+        let src_box = TrafBox {
+            tfhd: TfhdBox {
+                version: 0,
+                flags: TfhdBox::FLAG_DEFAULT_BASE_IS_MOOF,
+                track_id: 1,
+                ..TfhdBox::default()
+            },
+            tfdt: Some(TfdtBox {
+                version: 1,
+                flags: 0,
+                base_media_decode_time: 3072,
+            }),
+            truns: vec![
+                trun.clone(),
+                TrunBox {
+                    data_offset: Some(1387),
+                    sample_sizes: vec![8545, 10126, 10866],
+                    ..trun
+                },
+            ],
+            saiz: Some(SaizBox {
+                default_sample_info_size: 8,
+                sample_count: 6,
+                ..SaizBox::default()
+            }),
+            saio: Some(SaioBox {
+                offsets: vec![0x60],
+                ..SaioBox::default()
+            }),
+            senc: Some(SencBox {
+                sample_count: 6,
+                data: (1..=6u64).flat_map(|iv| iv.to_be_bytes()).collect(),
+                ..SencBox::default()
+            }),
+            unknown_boxes: vec![UnknownBox::new(
+                BoxType::from(0x73677064), // sgpd
+                vec![0, 0, 0, 0, 0x72, 0x6f, 0x6c, 0x6c, 0, 0, 0, 0],
+            )],
+            child_order: ChildOrder::default(),
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::TrafBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = TrafBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
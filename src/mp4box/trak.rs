@@ -3,12 +3,15 @@ use std::io::{Read, Seek, Write};
 
 use crate::meta::MetaBox;
 use crate::mp4box::*;
-use crate::mp4box::{edts::EdtsBox, mdia::MdiaBox, tkhd::TkhdBox};
+use crate::mp4box::{edts::EdtsBox, mdia::MdiaBox, tkhd::TkhdBox, tref::TrefBox};
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct TrakBox {
     pub tkhd: TkhdBox,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tref: Option<TrefBox>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub edts: Option<EdtsBox>,
 
@@ -16,6 +19,10 @@ pub struct TrakBox {
     pub meta: Option<MetaBox>,
 
     pub mdia: MdiaBox,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
 }
 
 impl TrakBox {
@@ -26,10 +33,16 @@ impl TrakBox {
     pub fn get_size(&self) -> u64 {
         let mut size = HEADER_SIZE;
         size += self.tkhd.box_size();
+        if let Some(ref tref) = self.tref {
+            size += tref.box_size();
+        }
         if let Some(ref edts) = self.edts {
             size += edts.box_size();
         }
         size += self.mdia.box_size();
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
         size
     }
 }
@@ -58,10 +71,13 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrakBox {
         let start = box_start(reader)?;
 
         let mut tkhd = None;
+        let mut tref = None;
         let mut edts = None;
         let mut meta = None;
         let mut mdia = None;
 
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
         let mut current = reader.stream_position()?;
         let end = start + size;
         while current < end {
@@ -74,10 +90,15 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrakBox {
                 ));
             }
 
+            child_order.push(name);
+
             match name {
                 BoxType::TkhdBox => {
                     tkhd = Some(TkhdBox::read_box(reader, s)?);
                 }
+                BoxType::TrefBox => {
+                    tref = Some(TrefBox::read_box(reader, s)?);
+                }
                 BoxType::EdtsBox => {
                     edts = Some(EdtsBox::read_box(reader, s)?);
                 }
@@ -88,8 +109,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrakBox {
                     mdia = Some(MdiaBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
                 }
             }
 
@@ -107,9 +128,12 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrakBox {
 
         Ok(TrakBox {
             tkhd: tkhd.unwrap(),
+            tref,
             edts,
             meta,
             mdia: mdia.unwrap(),
+            unknown_boxes,
+            child_order,
         })
     }
 }
@@ -119,11 +143,13 @@ impl<W: Write> WriteBox<&mut W> for TrakBox {
         let size = self.box_size();
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
-        self.tkhd.write_box(writer)?;
-        if let Some(ref edts) = self.edts {
-            edts.write_box(writer)?;
-        }
-        self.mdia.write_box(writer)?;
+        let mut children = ChildWriter::new();
+        children.push(&self.tkhd);
+        children.extend(&self.tref);
+        children.extend(&self.edts);
+        children.push(&self.mdia);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
 
         Ok(size)
     }
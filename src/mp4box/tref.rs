@@ -0,0 +1,220 @@
+use serde::Serialize;
+use std::fmt;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Track reference box, linking a track to other tracks by type, e.g. `chap`
+/// for the text track holding its chapters.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct TrefBox {
+    pub references: Vec<TrackReference>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TrackReference {
+    pub reference_type: TrackReferenceType,
+    pub track_ids: Vec<u32>,
+}
+
+/// How a track relates to the tracks it references.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum TrackReferenceType {
+    /// `chap`: text tracks holding the chapters of this track.
+    Chapter,
+    /// `hint`: media tracks sent by this hint track.
+    Hint,
+    /// `cdsc`: tracks described by this timed metadata track.
+    ContentDescription,
+    /// `subt`: subtitle tracks of this track.
+    Subtitle,
+    /// `vdep`: tracks whose depth this auxiliary video track carries.
+    VideoDepth,
+    /// `sync`: tracks this track is synchronised with.
+    Sync,
+    /// `font`: tracks carrying the fonts used by this track.
+    Font,
+    Other(FourCC),
+}
+
+impl From<FourCC> for TrackReferenceType {
+    fn from(fourcc: FourCC) -> Self {
+        match &fourcc.value {
+            b"chap" => TrackReferenceType::Chapter,
+            b"hint" => TrackReferenceType::Hint,
+            b"cdsc" => TrackReferenceType::ContentDescription,
+            b"subt" => TrackReferenceType::Subtitle,
+            b"vdep" => TrackReferenceType::VideoDepth,
+            b"sync" => TrackReferenceType::Sync,
+            b"font" => TrackReferenceType::Font,
+            _ => TrackReferenceType::Other(fourcc),
+        }
+    }
+}
+
+impl From<TrackReferenceType> for FourCC {
+    fn from(reference_type: TrackReferenceType) -> FourCC {
+        match reference_type {
+            TrackReferenceType::Chapter => FourCC::from(*b"chap"),
+            TrackReferenceType::Hint => FourCC::from(*b"hint"),
+            TrackReferenceType::ContentDescription => FourCC::from(*b"cdsc"),
+            TrackReferenceType::Subtitle => FourCC::from(*b"subt"),
+            TrackReferenceType::VideoDepth => FourCC::from(*b"vdep"),
+            TrackReferenceType::Sync => FourCC::from(*b"sync"),
+            TrackReferenceType::Font => FourCC::from(*b"font"),
+            TrackReferenceType::Other(fourcc) => fourcc,
+        }
+    }
+}
+
+impl fmt::Display for TrackReferenceType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", FourCC::from(*self))
+    }
+}
+
+impl TrefBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::TrefBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE;
+        for reference in self.references.iter() {
+            size += HEADER_SIZE + 4 * reference.track_ids.len() as u64;
+        }
+        size
+    }
+
+    /// The ids of all the tracks referenced with `reference_type`.
+    pub fn track_ids(&self, reference_type: TrackReferenceType) -> Vec<u32> {
+        self.references
+            .iter()
+            .filter(|reference| reference.reference_type == reference_type)
+            .flat_map(|reference| reference.track_ids.iter().copied())
+            .collect()
+    }
+}
+
+impl Mp4Box for TrefBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = self
+            .references
+            .iter()
+            .map(|reference| format!("{}={:?}", reference.reference_type, reference.track_ids))
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for TrefBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let mut references = Vec::new();
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            // Get box header.
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size || s < HEADER_SIZE {
+                return Err(Error::InvalidData(
+                    "tref box contains a box with an invalid size",
+                ));
+            }
+
+            let track_id_count = (s - HEADER_SIZE) / 4;
+            let mut track_ids = Vec::with_capacity(track_id_count as usize);
+            for _ in 0..track_id_count {
+                track_ids.push(reader.read_u32::<BigEndian>()?);
+            }
+            references.push(TrackReference {
+                reference_type: FourCC::from(name).into(),
+                track_ids,
+            });
+
+            skip_bytes_to(reader, current + s)?;
+            current = reader.stream_position()?;
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(TrefBox { references })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for TrefBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        for reference in self.references.iter() {
+            let reference_size = HEADER_SIZE + 4 * reference.track_ids.len() as u64;
+            let name = BoxType::from(u32::from(FourCC::from(reference.reference_type)));
+            BoxHeader::new(name, reference_size).write(writer)?;
+            for track_id in reference.track_ids.iter() {
+                writer.write_u32::<BigEndian>(*track_id)?;
+            }
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_tref() {
+        let src_box = TrefBox {
+            references: vec![
+                TrackReference {
+                    reference_type: TrackReferenceType::Chapter,
+                    track_ids: vec![3],
+                },
+                TrackReference {
+                    reference_type: TrackReferenceType::Sync,
+                    track_ids: vec![1, 2],
+                },
+                TrackReference {
+                    reference_type: TrackReferenceType::Other(FourCC::from(*b"mpod")),
+                    track_ids: vec![4],
+                },
+            ],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::TrefBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = TrefBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.track_ids(TrackReferenceType::Chapter), vec![3]);
+        assert_eq!(
+            dst_box.references[2].reference_type,
+            TrackReferenceType::from(FourCC::from(*b"mpod"))
+        );
+    }
+}
@@ -13,6 +13,10 @@ pub struct Tx3gBox {
     pub bg_color_rgba: RgbaColor,
     pub box_record: [i16; 4],
     pub style_record: [u8; 12],
+
+    /// Child boxes, e.g. the `ftab` font table.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
@@ -38,6 +42,7 @@ impl Default for Tx3gBox {
             },
             box_record: [0, 0, 0, 0],
             style_record: [0, 0, 0, 0, 0, 1, 0, 16, 255, 255, 255, 255],
+            unknown_boxes: Vec::new(),
         }
     }
 }
@@ -48,7 +53,11 @@ impl Tx3gBox {
     }
 
     pub fn get_size(&self) -> u64 {
-        HEADER_SIZE + 6 + 32
+        let mut size = HEADER_SIZE + 6 + 32;
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
+        size
     }
 }
 
@@ -112,7 +121,24 @@ impl<R: Read + Seek> ReadBox<&mut R> for Tx3gBox {
             reader.read_u8()?,
         ];
 
-        skip_bytes_to(reader, start + size)?;
+        let mut unknown_boxes = Vec::new();
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current + HEADER_SIZE <= end {
+            // Get box header.
+            let header = BoxHeader::read(reader)?;
+            if header.size > size {
+                return Err(Error::InvalidData(
+                    "tx3g box contains a box with a larger size than it",
+                ));
+            }
+            let unknown = UnknownBox::read(reader, &header)?;
+            unknown_boxes.push(unknown);
+            current = reader.stream_position()?;
+        }
+
+        skip_bytes_to(reader, end)?;
 
         Ok(Tx3gBox {
             data_reference_index,
@@ -122,6 +148,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for Tx3gBox {
             bg_color_rgba,
             box_record,
             style_record,
+            unknown_boxes,
         })
     }
 }
@@ -147,6 +174,9 @@ impl<W: Write> WriteBox<&mut W> for Tx3gBox {
         for n in 0..12 {
             writer.write_u8(self.style_record[n])?;
         }
+        for unknown in self.unknown_boxes.iter() {
+            unknown.write_box(writer)?;
+        }
 
         Ok(size)
     }
@@ -173,6 +203,10 @@ mod tests {
             },
             box_record: [0, 0, 0, 0],
             style_record: [0, 0, 0, 0, 0, 1, 0, 16, 255, 255, 255, 255],
+            unknown_boxes: vec![UnknownBox::new(
+                BoxType::from(u32::from_be_bytes(*b"ftab")),
+                vec![0, 1, 0, 1, 5, b'S', b'e', b'r', b'i', b'f'],
+            )],
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -2,6 +2,7 @@ use std::io::{Read, Seek};
 
 use serde::Serialize;
 
+use crate::mp4box::chpl::ChplBox;
 use crate::mp4box::meta::MetaBox;
 use crate::mp4box::*;
 
@@ -9,6 +10,14 @@ use crate::mp4box::*;
 pub struct UdtaBox {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub meta: Option<MetaBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chpl: Option<ChplBox>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
 }
 
 impl UdtaBox {
@@ -21,6 +30,12 @@ impl UdtaBox {
         if let Some(meta) = &self.meta {
             size += meta.box_size();
         }
+        if let Some(chpl) = &self.chpl {
+            size += chpl.box_size();
+        }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
         size
     }
 }
@@ -48,7 +63,10 @@ impl<R: Read + Seek> ReadBox<&mut R> for UdtaBox {
         let start = box_start(reader)?;
 
         let mut meta = None;
+        let mut chpl = None;
+        let mut unknown_boxes = Vec::new();
 
+        let mut child_order = ChildOrder::default();
         let mut current = reader.stream_position()?;
         let end = start + size;
         while current < end {
@@ -61,13 +79,18 @@ impl<R: Read + Seek> ReadBox<&mut R> for UdtaBox {
                 ));
             }
 
+            child_order.push(name);
+
             match name {
                 BoxType::MetaBox => {
                     meta = Some(MetaBox::read_box(reader, s)?);
                 }
+                BoxType::ChplBox => {
+                    chpl = Some(ChplBox::read_box(reader, s)?);
+                }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
                 }
             }
 
@@ -76,7 +99,12 @@ impl<R: Read + Seek> ReadBox<&mut R> for UdtaBox {
 
         skip_bytes_to(reader, start + size)?;
 
-        Ok(UdtaBox { meta })
+        Ok(UdtaBox {
+            meta,
+            chpl,
+            unknown_boxes,
+            child_order,
+        })
     }
 }
 
@@ -85,9 +113,11 @@ impl<W: Write> WriteBox<&mut W> for UdtaBox {
         let size = self.box_size();
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
-        if let Some(meta) = &self.meta {
-            meta.write_box(writer)?;
-        }
+        let mut children = ChildWriter::new();
+        children.extend(&self.meta);
+        children.extend(&self.chpl);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
         Ok(size)
     }
 }
@@ -100,7 +130,7 @@ mod tests {
 
     #[test]
     fn test_udta_empty() {
-        let src_box = UdtaBox { meta: None };
+        let src_box = UdtaBox::default();
 
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -119,6 +149,12 @@ mod tests {
     fn test_udta() {
         let src_box = UdtaBox {
             meta: Some(MetaBox::default()),
+            chpl: Some(ChplBox::default()),
+            unknown_boxes: vec![UnknownBox::new(
+                BoxType::from(u32::from_be_bytes(*b"\xa9too")),
+                b"Lavf60.16.100".to_vec(),
+            )],
+            child_order: ChildOrder::default(),
         };
 
         let mut buf = Vec::new();
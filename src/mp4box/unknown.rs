@@ -0,0 +1,111 @@
+use serde::{Serialize, Serializer};
+use std::io::{Read, Write};
+
+use crate::mp4box::*;
+
+/// A box that is not parsed by its parent, kept as raw bytes so that it is
+/// written back unchanged.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnknownBox {
+    #[serde(serialize_with = "serialize_box_type")]
+    pub box_type: BoxType,
+
+    #[serde(skip_serializing)]
+    pub payload: Vec<u8>,
+}
+
+pub(crate) fn serialize_box_type<S: Serializer>(
+    box_type: &BoxType,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_str(box_type)
+}
+
+impl UnknownBox {
+    pub fn new(box_type: BoxType, payload: Vec<u8>) -> Self {
+        Self { box_type, payload }
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        self.box_type
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let size = HEADER_SIZE + self.payload.len() as u64;
+        if size > u32::MAX as u64 {
+            // largesize
+            size + 8
+        } else {
+            size
+        }
+    }
+
+    /// Read the payload of a box whose header has already been read.
+    pub fn read<R: Read>(reader: &mut R, header: &BoxHeader) -> Result<Self> {
+        if header.size < HEADER_SIZE {
+            return Err(Error::InvalidData("box size smaller than its header"));
+        }
+        let mut payload = Vec::new();
+        reader
+            .take(header.size - HEADER_SIZE)
+            .read_to_end(&mut payload)?;
+        if payload.len() as u64 != header.size - HEADER_SIZE {
+            return Err(Error::InvalidData("box extends past the end of its parent"));
+        }
+        Ok(Self::new(header.name, payload))
+    }
+}
+
+impl Mp4Box for UnknownBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("payload_size={}", self.payload.len());
+        Ok(s)
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for UnknownBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_all(&self.payload)?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_unknown() {
+        let src_box = UnknownBox::new(BoxType::from(0x62747274), vec![0, 0, 0, 1, 2, 3, 4]);
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::from(0x62747274));
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = UnknownBox::read(&mut reader, &header).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.to_json().unwrap(), r#"{"box_type":"btrt"}"#);
+    }
+}
@@ -20,6 +20,11 @@ pub struct Vp09Box {
     pub depth: u16,
     pub end_code: u16,
     pub vpcc: VpccBox,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+    #[serde(skip)]
+    pub child_order: ChildOrder,
 }
 
 impl Vp09Box {
@@ -59,8 +64,10 @@ impl Vp09Box {
                 color_primaries: config.color_primaries,
                 transfer_characteristics: config.transfer_characteristics,
                 matrix_coefficients: config.matrix_coefficients,
-                codec_initialization_data_size: config .codec_initialization_data_size,
+                codec_initialization_data_size: config.codec_initialization_data_size,
             },
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
         }
     }
 }
@@ -71,7 +78,11 @@ impl Mp4Box for Vp09Box {
     }
 
     fn box_size(&self) -> u64 {
-        0x6A
+        let mut size = 0x6A;
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
+        size
     }
 
     fn to_json(&self) -> Result<String> {
@@ -119,17 +130,39 @@ impl<R: Read + Seek> ReadBox<&mut R> for Vp09Box {
         let depth: u16 = reader.read_u16::<BigEndian>()?;
         let end_code: u16 = reader.read_u16::<BigEndian>()?;
 
-        let vpcc = {
+        let mut vpcc = None;
+        let mut unknown_boxes = Vec::new();
+        let mut child_order = ChildOrder::default();
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            // Get box header.
             let header = BoxHeader::read(reader)?;
             if header.size > size {
                 return Err(Error::InvalidData(
                     "vp09 box contains a box with a larger size than it",
                 ));
             }
-            VpccBox::read_box(reader, header.size)?
+            child_order.push(header.name);
+            match header.name {
+                BoxType::VpccBox => {
+                    vpcc = Some(VpccBox::read_box(reader, header.size)?);
+                }
+                _ => {
+                    let unknown = UnknownBox::read(reader, &header)?;
+                    unknown_boxes.push(unknown);
+                }
+            }
+            current = reader.stream_position()?;
+        }
+
+        let vpcc = match vpcc {
+            Some(vpcc) => vpcc,
+            None => return Err(Error::InvalidData("vpcc not found")),
         };
 
-        skip_bytes_to(reader, start + size)?;
+        skip_bytes_to(reader, end)?;
 
         Ok(Self {
             version,
@@ -147,6 +180,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for Vp09Box {
             depth,
             end_code,
             vpcc,
+            unknown_boxes,
+            child_order,
         })
     }
 }
@@ -172,7 +207,10 @@ impl<W: Write> WriteBox<&mut W> for Vp09Box {
         writer.write_all(&self.compressorname)?;
         writer.write_u16::<BigEndian>(self.depth)?;
         writer.write_u16::<BigEndian>(self.end_code)?;
-        VpccBox::write_box(&self.vpcc, writer)?;
+        let mut children = ChildWriter::new();
+        children.push(&self.vpcc);
+        children.extend(&self.unknown_boxes);
+        children.write(writer, &self.child_order)?;
 
         Ok(size)
     }
@@ -186,7 +224,7 @@ mod tests {
 
     #[test]
     fn test_vpcc() {
-        let src_box = Vp09Box::new(&Vp9Config {
+        let mut src_box = Vp09Box::new(&Vp9Config {
             width: 1920,
             height: 1080,
             profile: 0,
@@ -199,6 +237,10 @@ mod tests {
             matrix_coefficients: 0,
             codec_initialization_data_size: 0,
         });
+        src_box.unknown_boxes.push(UnknownBox::new(
+            BoxType::from(u32::from_be_bytes(*b"pasp")),
+            vec![0, 0, 0, 1, 0, 0, 0, 1],
+        ));
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
         assert_eq!(buf.len(), src_box.box_size() as usize);
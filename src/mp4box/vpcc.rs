@@ -51,6 +51,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for VpccBox {
             let b = reader.read_u8()?;
             (b >> 4, b << 4 >> 5, b & 0x01 == 1)
         };
+        let color_primaries: u8 = reader.read_u8()?;
         let transfer_characteristics: u8 = reader.read_u8()?;
         let matrix_coefficients: u8 = reader.read_u8()?;
         let codec_initialization_data_size: u16 = reader.read_u16::<BigEndian>()?;
@@ -65,7 +66,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for VpccBox {
             bit_depth,
             chroma_subsampling,
             video_full_range_flag,
-            color_primaries: 0,
+            color_primaries,
             transfer_characteristics,
             matrix_coefficients,
             codec_initialization_data_size,
@@ -112,9 +113,9 @@ mod tests {
             bit_depth: VpccBox::DEFAULT_BIT_DEPTH,
             chroma_subsampling: 0,
             video_full_range_flag: false,
-            color_primaries: 0,
-            transfer_characteristics: 0,
-            matrix_coefficients: 0,
+            color_primaries: 1,
+            transfer_characteristics: 1,
+            matrix_coefficients: 1,
             codec_initialization_data_size: 0,
         };
         let mut buf = Vec::new();
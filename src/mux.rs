@@ -0,0 +1,764 @@
+//! Parsing of raw elementary streams into tracks for [Mp4Writer]:
+//!
+//! * H.264 and HEVC Annex-B byte streams, split into access units, with the
+//!   composition offsets derived from the picture order counts.
+//! * AAC as ADTS frames.
+//! * VP9 as IVF frames.
+
+use bytes::Bytes;
+use std::convert::{TryFrom, TryInto};
+use std::io::{Seek, Write};
+
+use crate::hevc::HevcPps;
+use crate::nal::{self, AnnexBNalUnits, NalFormat};
+use crate::*;
+
+/// Frame rate of Annex-B streams without timing information, as ffmpeg.
+const DEFAULT_FRAME_RATE: (u32, u32) = (25, 1);
+const AAC_FRAME_SIZE: u32 = 1024;
+
+/// A track parsed from an elementary stream, with its samples in decoding
+/// order.
+#[derive(Debug)]
+pub struct ElementaryStream {
+    pub track_config: TrackConfig,
+    pub samples: Vec<Mp4Sample>,
+
+    /// Composition time of the first presented sample, skipped by the edit
+    /// list of the track. Non-zero for H.264 and HEVC streams with
+    /// reordered pictures.
+    pub media_time: u64,
+}
+
+impl ElementaryStream {
+    /// Parse an H.264 or HEVC Annex-B byte stream.
+    ///
+    /// `frame_rate` is the number of frames per second as a fraction, such
+    /// as `(30000, 1001)`. It defaults to the timing information of the
+    /// H.264 SPS, or to 25 frames per second.
+    ///
+    /// For H.264 the first SPS and PPS describe the track, and other
+    /// parameter sets stay in-band in the samples. For HEVC every distinct
+    /// VPS, SPS and PPS is stored in the track configuration. Repeats of the
+    /// parameter sets of the track configuration are left out of the
+    /// samples, and an SPS changing after its first occurrence is an error.
+    pub fn from_annexb(
+        data: &[u8],
+        format: NalFormat,
+        frame_rate: Option<(u32, u32)>,
+    ) -> Result<Self> {
+        let mut parser = PocParser::new(format);
+        // Parameter sets of the track configuration.
+        let mut parameter_sets: Vec<&[u8]> = Vec::new();
+        let mut spss: Vec<(u32, &[u8])> = Vec::new();
+        let mut pictures = Vec::new();
+        for access_unit in access_units(data, format) {
+            let mut bytes = Vec::new();
+            let mut picture = None;
+            for nal in access_unit {
+                if format.is_parameter_set(nal) {
+                    if let Some(sps_id) = parser.parameter_set(nal)? {
+                        match spss.iter().find(|(id, _)| *id == sps_id) {
+                            Some((_, sps)) if *sps != nal => {
+                                return Err(Error::InvalidData("SPS changes in the Annex-B stream"))
+                            }
+                            Some(_) => {}
+                            None => spss.push((sps_id, nal)),
+                        }
+                    }
+                    if parameter_sets.contains(&nal) {
+                        continue;
+                    }
+                    let nal_type = format.nal_unit_type(nal);
+                    if format == NalFormat::Hevc
+                        || !parameter_sets
+                            .iter()
+                            .any(|other| format.nal_unit_type(other) == nal_type)
+                    {
+                        parameter_sets.push(nal);
+                        continue;
+                    }
+                }
+                if picture.is_none() && format.is_vcl(nal) {
+                    picture = Some(parser.picture(nal)?);
+                }
+                bytes.extend_from_slice(&(nal.len() as u32).to_be_bytes());
+                bytes.extend_from_slice(nal);
+            }
+            // Parameter sets or SEI messages after the last picture.
+            if let Some(picture) = picture {
+                pictures.push((picture, bytes));
+            }
+        }
+        if pictures.is_empty() {
+            return Err(Error::InvalidData("no pictures in the Annex-B stream"));
+        }
+
+        let media_conf = match format {
+            NalFormat::Avc => {
+                let sps = parameter_sets.iter().find(|nal| nal[0] & 0x1f == 7);
+                let pps = parameter_sets.iter().find(|nal| nal[0] & 0x1f == 8);
+                match (sps, pps) {
+                    (Some(sps), Some(pps)) => {
+                        MediaConfig::AvcConfig(AvcConfig::from_sps_pps(sps, pps)?)
+                    }
+                    _ => return Err(Error::InvalidData("no SPS and PPS in the Annex-B stream")),
+                }
+            }
+            NalFormat::Hevc => MediaConfig::HevcConfig(HevcConfig::from_nal_units(parameter_sets)?),
+        };
+        let (timescale, frame_duration) = frame_rate
+            .or_else(|| parser.frame_rate())
+            .unwrap_or(DEFAULT_FRAME_RATE);
+        if timescale == 0 || frame_duration == 0 {
+            return Err(Error::InvalidData("invalid frame rate"));
+        }
+
+        // Presentation order within each coded video sequence, as the
+        // pictures are sorted by picture order count.
+        let mut presentation = vec![0usize; pictures.len()];
+        let mut start = 0;
+        while start < pictures.len() {
+            let end = (start + 1..pictures.len())
+                .find(|i| pictures[*i].0.poc_reset)
+                .unwrap_or(pictures.len());
+            let mut order: Vec<usize> = (start..end).collect();
+            order.sort_by_key(|i| pictures[*i].0.poc);
+            for (rank, i) in order.into_iter().enumerate() {
+                presentation[i] = start + rank;
+            }
+            start = end;
+        }
+        // Delay all the pictures by the deepest reordering, so that none is
+        // presented before it is decoded. The edit list removes the delay.
+        let delay = presentation
+            .iter()
+            .enumerate()
+            .map(|(i, presented)| i.saturating_sub(*presented))
+            .max()
+            .unwrap_or(0);
+
+        let samples = pictures
+            .into_iter()
+            .enumerate()
+            .map(|(i, (picture, bytes))| Mp4Sample {
+                start_time: i as u64 * frame_duration as u64,
+                duration: frame_duration,
+                rendering_offset: ((presentation[i] + delay) as i64 - i as i64) as i32
+                    * frame_duration as i32,
+                is_sync: picture.is_sync,
+                bytes: Bytes::from(bytes),
+                encryption: None,
+            })
+            .collect();
+
+        Ok(Self {
+            track_config: TrackConfig {
+                timescale,
+                ..TrackConfig::from(media_conf)
+            },
+            samples,
+            media_time: delay as u64 * frame_duration as u64,
+        })
+    }
+
+    /// Parse a stream of ADTS frames, each holding one AAC frame.
+    pub fn from_adts(data: &[u8]) -> Result<Self> {
+        let mut header = None;
+        let mut samples = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let frame = &data[pos..];
+            if frame.len() < 7 || frame[0] != 0xff || frame[1] & 0xf6 != 0xf0 {
+                return Err(Error::InvalidData("missing ADTS sync word"));
+            }
+            let protection_absent = frame[1] & 0x1 == 1;
+            let profile = frame[2] >> 6;
+            let freq_index = frame[2] >> 2 & 0xf;
+            let chan_conf = (frame[2] & 0x1) << 2 | frame[3] >> 6;
+            let frame_length = ((frame[3] & 0x3) as usize) << 11
+                | (frame[4] as usize) << 3
+                | (frame[5] as usize) >> 5;
+            if frame[6] & 0x3 != 0 {
+                return Err(Error::InvalidData(
+                    "ADTS frames with several raw data blocks are not supported",
+                ));
+            }
+            let header_size = if protection_absent { 7 } else { 9 };
+            if frame_length < header_size || frame_length > frame.len() {
+                return Err(Error::InvalidData("invalid ADTS frame length"));
+            }
+            match header {
+                None => header = Some((profile, freq_index, chan_conf)),
+                Some(first) if first != (profile, freq_index, chan_conf) => {
+                    return Err(Error::InvalidData(
+                        "ADTS configuration changes in the stream",
+                    ))
+                }
+                _ => {}
+            }
+
+            samples.push(Mp4Sample {
+                start_time: samples.len() as u64 * AAC_FRAME_SIZE as u64,
+                duration: AAC_FRAME_SIZE,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: Bytes::copy_from_slice(&frame[header_size..frame_length]),
+                encryption: None,
+            });
+            pos += frame_length;
+        }
+
+        let (profile, freq_index, chan_conf) =
+            header.ok_or(Error::InvalidData("no frames in the ADTS stream"))?;
+        let freq_index = SampleFreqIndex::try_from(freq_index)?;
+        let total_size: u64 = samples.iter().map(|sample| sample.bytes.len() as u64).sum();
+        let bitrate = total_size * 8 * freq_index.freq() as u64
+            / (samples.len() as u64 * AAC_FRAME_SIZE as u64);
+        let aac_config = AacConfig {
+            bitrate: bitrate as u32,
+            profile: AudioObjectType::try_from(profile + 1)?,
+            freq_index,
+            chan_conf: ChannelConfig::try_from(chan_conf)?,
+            ..AacConfig::default()
+        };
+
+        Ok(Self {
+            track_config: TrackConfig {
+                timescale: freq_index.freq(),
+                ..TrackConfig::from(aac_config)
+            },
+            samples,
+            media_time: 0,
+        })
+    }
+
+    /// Parse an IVF file of VP9 frames, using its time base as timescale.
+    pub fn from_ivf(data: &[u8]) -> Result<Self> {
+        if data.len() < 32 || &data[..4] != b"DKIF" {
+            return Err(Error::InvalidData("missing IVF signature"));
+        }
+        if &data[8..12] != b"VP90" {
+            return Err(Error::InvalidData("IVF stream is not VP9"));
+        }
+        let header_size = u16::from_le_bytes([data[6], data[7]]) as usize;
+        let width = u16::from_le_bytes([data[12], data[13]]);
+        let height = u16::from_le_bytes([data[14], data[15]]);
+        let rate = u32::from_le_bytes(data[16..20].try_into().unwrap());
+        let scale = u32::from_le_bytes(data[20..24].try_into().unwrap());
+        if rate == 0 || scale == 0 {
+            return Err(Error::InvalidData("invalid IVF time base"));
+        }
+
+        let mut frames = Vec::new();
+        let mut pos = header_size;
+        while pos < data.len() {
+            let frame_header = data
+                .get(pos..pos + 12)
+                .ok_or(Error::InvalidData("truncated IVF frame header"))?;
+            let size = u32::from_le_bytes(frame_header[..4].try_into().unwrap()) as usize;
+            let pts = u64::from_le_bytes(frame_header[4..].try_into().unwrap());
+            let frame = data
+                .get(pos + 12..pos + 12 + size)
+                .ok_or(Error::InvalidData("truncated IVF frame"))?;
+            frames.push((pts * scale as u64, frame));
+            pos += 12 + size;
+        }
+
+        let mut vp9_config = None;
+        let mut samples: Vec<Mp4Sample> = Vec::with_capacity(frames.len());
+        for (i, (start_time, frame)) in frames.iter().enumerate() {
+            let header = Vp9FrameHeader::parse(frame)?;
+            if vp9_config.is_none() {
+                vp9_config = header.config(width, height);
+            }
+            let duration = match frames.get(i + 1) {
+                Some((next, _)) => next.saturating_sub(*start_time),
+                None => samples
+                    .last()
+                    .map_or(scale as u64, |last| last.duration as u64),
+            };
+            samples.push(Mp4Sample {
+                start_time: *start_time,
+                duration: u32::try_from(duration)
+                    .map_err(|_| Error::InvalidData("IVF frame duration too long"))?,
+                rendering_offset: 0,
+                is_sync: header.is_key_frame,
+                bytes: Bytes::copy_from_slice(frame),
+                encryption: None,
+            });
+        }
+        let vp9_config = vp9_config.ok_or(Error::InvalidData("no key frame in the IVF stream"))?;
+
+        Ok(Self {
+            track_config: TrackConfig {
+                timescale: rate,
+                ..TrackConfig::from(vp9_config)
+            },
+            samples,
+            media_time: 0,
+        })
+    }
+}
+
+/// Write elementary streams as the tracks of an MP4 file, interleaving
+/// their samples by decoding time. Each track gets an edit list starting
+/// its presentation at [ElementaryStream::media_time], so that all tracks
+/// start together.
+pub fn mux_streams<W: Write + Seek>(
+    writer: W,
+    config: &Mp4Config,
+    streams: &[ElementaryStream],
+) -> Result<W> {
+    let mut writer = Mp4Writer::write_start(writer, config)?;
+    let mut track_ids = Vec::with_capacity(streams.len());
+    for stream in streams {
+        track_ids.push(writer.add_track(&stream.track_config)?);
+    }
+
+    let mut next = vec![0usize; streams.len()];
+    loop {
+        // The stream whose next sample comes first.
+        let earliest = (0..streams.len())
+            .filter(|i| next[*i] < streams[*i].samples.len())
+            .min_by(|a, b| {
+                let time = |i: usize| {
+                    streams[i].samples[next[i]].start_time as f64
+                        / streams[i].track_config.timescale as f64
+                };
+                time(*a).total_cmp(&time(*b))
+            });
+        let i = match earliest {
+            Some(i) => i,
+            None => break,
+        };
+        writer.write_sample(track_ids[i], &streams[i].samples[next[i]])?;
+        next[i] += 1;
+    }
+
+    for (stream, track_id) in streams.iter().zip(track_ids) {
+        let duration: u64 = stream
+            .samples
+            .iter()
+            .map(|sample| sample.duration as u64)
+            .sum();
+        let duration_us = duration * 1_000_000 / stream.track_config.timescale as u64;
+        writer.update_offset(track_id, stream.media_time, duration_us)?;
+    }
+
+    writer.write_end()?;
+    Ok(writer.into_writer())
+}
+
+/// Split an Annex-B byte stream into access units (ITU-T H.264, 7.4.1.2.3
+/// and H.265, 7.4.2.4.4): a new one starts with an access unit delimiter,
+/// a parameter set or prefix SEI after a picture, or the first slice of
+/// another picture.
+fn access_units(data: &[u8], format: NalFormat) -> Vec<Vec<&[u8]>> {
+    let mut access_units: Vec<Vec<&[u8]>> = Vec::new();
+    let mut current: Vec<&[u8]> = Vec::new();
+    let mut has_picture = false;
+    for nal in AnnexBNalUnits::new(data) {
+        let nal_type = format.nal_unit_type(nal);
+        let starts_access_unit = if format.is_vcl(nal) {
+            // first_mb_in_slice == 0, or first_slice_segment_in_pic_flag.
+            nal.get(format.header_size())
+                .is_some_and(|byte| byte & 0x80 != 0)
+        } else {
+            match format {
+                NalFormat::Avc => matches!(nal_type, 6..=9 | 14..=18),
+                NalFormat::Hevc => matches!(nal_type, 32..=35 | 39 | 41..=44 | 48..=55),
+            }
+        };
+        if starts_access_unit && has_picture {
+            access_units.push(std::mem::take(&mut current));
+            has_picture = false;
+        }
+        has_picture |= format.is_vcl(nal);
+        current.push(nal);
+    }
+    if !current.is_empty() {
+        access_units.push(current);
+    }
+    access_units
+}
+
+#[derive(Debug)]
+struct Picture {
+    poc: i64,
+    /// Starts a coded video sequence, where picture order counts restart.
+    poc_reset: bool,
+    is_sync: bool,
+}
+
+/// Picture order count decoding (ITU-T H.264, 8.2.1 and H.265, 8.3.1).
+///
+/// H.264 streams with `pic_order_cnt_type` 1 or 2 are taken as presented
+/// in decoding order.
+struct PocParser {
+    format: NalFormat,
+    avc_sps: Option<AvcSps>,
+    hevc_sps: Option<HevcSps>,
+    hevc_ppss: Vec<HevcPps>,
+    prev_poc_msb: i64,
+    prev_poc_lsb: i64,
+    /// Pictures since the last reset, for streams without `pic_order_cnt_lsb`.
+    count: i64,
+    first: bool,
+}
+
+impl PocParser {
+    fn new(format: NalFormat) -> Self {
+        Self {
+            format,
+            avc_sps: None,
+            hevc_sps: None,
+            hevc_ppss: Vec::new(),
+            prev_poc_msb: 0,
+            prev_poc_lsb: 0,
+            count: 0,
+            first: true,
+        }
+    }
+
+    /// Take in a parameter set, returning its id if it is an SPS.
+    fn parameter_set(&mut self, nal: &[u8]) -> Result<Option<u32>> {
+        match (self.format, self.format.nal_unit_type(nal)) {
+            (NalFormat::Avc, 7) => {
+                let sps = AvcSps::parse(nal)?;
+                let sps_id = sps.seq_parameter_set_id;
+                self.avc_sps = Some(sps);
+                return Ok(Some(sps_id));
+            }
+            (NalFormat::Hevc, 33) => {
+                let sps = HevcSps::parse(nal)?;
+                let sps_id = sps.seq_parameter_set_id;
+                self.hevc_sps = Some(sps);
+                return Ok(Some(sps_id));
+            }
+            (NalFormat::Hevc, 34) => {
+                let pps = HevcPps::parse(nal)?;
+                self.hevc_ppss
+                    .retain(|other| other.pic_parameter_set_id != pps.pic_parameter_set_id);
+                self.hevc_ppss.push(pps);
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    /// Frame rate of the H.264 VUI, which counts fields.
+    fn frame_rate(&self) -> Option<(u32, u32)> {
+        match self.avc_sps.as_ref()?.timing {
+            Some((num_units_in_tick, time_scale)) if num_units_in_tick > 0 && time_scale > 0 => {
+                Some((time_scale, num_units_in_tick.checked_mul(2)?))
+            }
+            _ => None,
+        }
+    }
+
+    /// Decode the picture order count of the picture starting with `nal`,
+    /// its first slice.
+    fn picture(&mut self, nal: &[u8]) -> Result<Picture> {
+        let nal_type = self.format.nal_unit_type(nal);
+        let (lsb, max_lsb, poc_reset, is_reference) = match self.format {
+            NalFormat::Avc => {
+                let sps = self
+                    .avc_sps
+                    .as_ref()
+                    .ok_or(Error::InvalidData("slice before the first SPS"))?;
+                let lsb = sps.slice_pic_order_cnt_lsb(nal)?;
+                let nal_ref_idc = nal[0] >> 5 & 0x3;
+                (
+                    lsb,
+                    1i64 << sps.log2_max_pic_order_cnt_lsb,
+                    nal_type == 5,
+                    nal_ref_idc != 0,
+                )
+            }
+            NalFormat::Hevc => {
+                let sps = self
+                    .hevc_sps
+                    .as_ref()
+                    .ok_or(Error::InvalidData("slice before the first SPS"))?;
+                let lsb = sps.slice_pic_order_cnt_lsb(nal, &self.hevc_ppss)?;
+                // IDR and BLA pictures, and a CRA picture starting the stream.
+                let poc_reset = (16..=20).contains(&nal_type) || (nal_type == 21 && self.first);
+                let temporal_id = (nal.get(1).copied().unwrap_or(1) & 0x7).saturating_sub(1);
+                // Not RADL, RASL or a sub-layer non-reference picture.
+                let is_reference = temporal_id == 0
+                    && !(6..=9).contains(&nal_type)
+                    && !(nal_type <= 14 && nal_type & 1 == 0);
+                (
+                    lsb,
+                    1i64 << sps.log2_max_pic_order_cnt_lsb,
+                    poc_reset,
+                    is_reference,
+                )
+            }
+        };
+        self.first = false;
+
+        if poc_reset {
+            self.prev_poc_msb = 0;
+            self.prev_poc_lsb = 0;
+            self.count = 0;
+        }
+        let poc = match lsb {
+            Some(lsb) => {
+                let lsb = lsb as i64;
+                let msb = if lsb < self.prev_poc_lsb && self.prev_poc_lsb - lsb >= max_lsb / 2 {
+                    self.prev_poc_msb + max_lsb
+                } else if lsb > self.prev_poc_lsb && lsb - self.prev_poc_lsb > max_lsb / 2 {
+                    self.prev_poc_msb - max_lsb
+                } else {
+                    self.prev_poc_msb
+                };
+                if is_reference || poc_reset {
+                    self.prev_poc_msb = msb;
+                    self.prev_poc_lsb = lsb;
+                }
+                msb + lsb
+            }
+            None => self.count,
+        };
+        self.count += 1;
+
+        Ok(Picture {
+            poc,
+            poc_reset,
+            is_sync: self.format.is_random_access(nal),
+        })
+    }
+}
+
+/// The start of the uncompressed header of a VP9 frame (VP9 bitstream
+/// specification, 6.2).
+struct Vp9FrameHeader {
+    profile: u8,
+    is_key_frame: bool,
+    /// Bit depth, color space, color range and subsampling of key frames.
+    color_config: Option<(u8, u8, bool, (bool, bool))>,
+}
+
+impl Vp9FrameHeader {
+    fn parse(frame: &[u8]) -> Result<Self> {
+        let mut reader = nal::BitReader::new(frame);
+        if reader.read_bits(2)? != 0b10 {
+            return Err(Error::InvalidData("invalid VP9 frame marker"));
+        }
+        let profile_low_bit = reader.read_bit()? as u8;
+        let profile = (reader.read_bit()? as u8) << 1 | profile_low_bit;
+        if profile == 3 {
+            reader.read_bit()?; // reserved_zero
+        }
+        let mut header = Vp9FrameHeader {
+            profile,
+            is_key_frame: false,
+            color_config: None,
+        };
+        if reader.read_bit()? {
+            // show_existing_frame
+            return Ok(header);
+        }
+        header.is_key_frame = !reader.read_bit()?; // frame_type
+        if !header.is_key_frame {
+            return Ok(header);
+        }
+        reader.skip_bits(2)?; // show_frame, error_resilient_mode
+        if reader.read_bits(24)? != 0x49_83_42 {
+            return Err(Error::InvalidData("invalid VP9 frame sync code"));
+        }
+
+        let bit_depth = if profile >= 2 {
+            if reader.read_bit()? {
+                12
+            } else {
+                10
+            }
+        } else {
+            8
+        };
+        let color_space = reader.read_bits(3)? as u8;
+        let (color_range, subsampling) = if color_space != 7 {
+            let color_range = reader.read_bit()?;
+            let subsampling = if profile == 1 || profile == 3 {
+                (reader.read_bit()?, reader.read_bit()?)
+            } else {
+                (true, true)
+            };
+            (color_range, subsampling)
+        } else {
+            // sRGB
+            (true, (false, false))
+        };
+        header.color_config = Some((bit_depth, color_space, color_range, subsampling));
+        Ok(header)
+    }
+
+    fn config(&self, width: u16, height: u16) -> Option<Vp9Config> {
+        let (bit_depth, color_space, color_range, subsampling) = self.color_config?;
+        // Colour primaries, transfer characteristics and matrix coefficients
+        // (ISO/IEC 23091-2) of the VP9 color spaces.
+        let (color_primaries, transfer_characteristics, matrix_coefficients) = match color_space {
+            1 | 3 => (6, 6, 6),
+            2 => (1, 1, 1),
+            4 => (7, 7, 7),
+            5 => (9, 2, 9),
+            7 => (1, 13, 0),
+            _ => (2, 2, 2),
+        };
+        Some(Vp9Config {
+            width,
+            height,
+            profile: self.profile,
+            // Not signalled in the bitstream.
+            level: 0,
+            bit_depth,
+            chroma_subsampling: match subsampling {
+                (true, true) => 1,
+                (true, false) => 2,
+                _ => 3,
+            },
+            video_full_range_flag: color_range,
+            color_primaries,
+            transfer_characteristics,
+            matrix_coefficients,
+            codec_initialization_data_size: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn annexb(nal_units: &[&[u8]]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for nal in nal_units {
+            data.extend_from_slice(&[0, 0, 0, 1]);
+            data.extend_from_slice(nal);
+        }
+        data
+    }
+
+    #[test]
+    fn test_avc_composition_offsets() {
+        // Main profile with pic_order_cnt_type 0, and slices of the pictures
+        // I0 P6 B2 B4 P12 B8, then I0 P6 after a second IDR.
+        let sps: &[u8] = &[0x67, 0x4d, 0x00, 0x1e, 0xed, 0x82, 0x83, 0xf2];
+        let pps: &[u8] = &[0x68, 0xce, 0x38, 0x80];
+        let aud: &[u8] = &[0x09, 0xf0];
+        let sei: &[u8] = &[0x06, 0x05, 0x01, 0x00, 0x80];
+        let idr: &[u8] = &[0x65, 0x88, 0x84, 0x05, 0xa8];
+        let p6: &[u8] = &[0x41, 0x9a, 0x23, 0x2d, 0x40];
+        let b2: &[u8] = &[0x01, 0x9e, 0x41, 0x2d, 0x40];
+        let b4: &[u8] = &[0x01, 0x9e, 0x42, 0x2d, 0x40];
+        let p12: &[u8] = &[0x41, 0x9a, 0x46, 0x2d, 0x40];
+        let b8: &[u8] = &[0x01, 0x9e, 0x64, 0x2d, 0x40];
+        let data = annexb(&[
+            aud, sps, pps, idr, aud, p6, b2, sei, b4, p12, b8, sps, pps, idr, p6,
+        ]);
+
+        let stream =
+            ElementaryStream::from_annexb(&data, NalFormat::Avc, Some((30000, 1001))).unwrap();
+        assert_eq!(stream.track_config.timescale, 30000);
+        match stream.track_config.media_conf {
+            MediaConfig::AvcConfig(ref config) => {
+                assert_eq!((config.width, config.height), (320, 240));
+                assert_eq!(config.seq_param_set, sps);
+                assert_eq!(config.pic_param_set, pps);
+            }
+            _ => panic!("not an H.264 track"),
+        }
+
+        let offsets: Vec<i32> = stream
+            .samples
+            .iter()
+            .map(|sample| sample.rendering_offset / 1001)
+            .collect();
+        assert_eq!(offsets, vec![1, 3, 0, 0, 2, 0, 1, 1]);
+        let sync: Vec<bool> = stream.samples.iter().map(|sample| sample.is_sync).collect();
+        assert_eq!(
+            sync,
+            vec![true, false, false, false, false, false, true, false]
+        );
+        assert_eq!(stream.samples[3].start_time, 3 * 1001);
+
+        // Parameter sets are left out, the rest is length-prefixed.
+        assert_eq!(
+            stream.samples[0].bytes[..],
+            [&[0, 0, 0, 2], aud, &[0, 0, 0, 5], idr].concat()
+        );
+        assert_eq!(
+            stream.samples[3].bytes[..],
+            [&[0, 0, 0, 5], sei, &[0, 0, 0, 5], b4].concat()
+        );
+    }
+
+    #[test]
+    fn test_avc_parameter_sets() {
+        let sps: &[u8] = &[0x67, 0x4d, 0x00, 0x1e, 0xed, 0x82, 0x83, 0xf2];
+        let pps: &[u8] = &[0x68, 0xce, 0x38, 0x80];
+        let pps1: &[u8] = &[0x68, 0x53, 0x8e, 0x20];
+        let idr: &[u8] = &[0x65, 0x88, 0x84, 0x05, 0xa8];
+        let p6: &[u8] = &[0x41, 0x9a, 0x23, 0x2d, 0x40];
+
+        // The first SPS and PPS go in avcC, a second PPS stays in-band.
+        let data = annexb(&[sps, pps, idr, sps, pps, pps1, p6]);
+        let stream = ElementaryStream::from_annexb(&data, NalFormat::Avc, None).unwrap();
+        match stream.track_config.media_conf {
+            MediaConfig::AvcConfig(ref config) => {
+                assert_eq!(config.seq_param_set, sps);
+                assert_eq!(config.pic_param_set, pps);
+            }
+            _ => panic!("not an H.264 track"),
+        }
+        assert_eq!(stream.samples[0].bytes[..], [&[0, 0, 0, 5], idr].concat());
+        assert_eq!(
+            stream.samples[1].bytes[..],
+            [&[0, 0, 0, 4], pps1, &[0, 0, 0, 5], p6].concat()
+        );
+
+        // Another SPS with the same id.
+        let sps_level_31: &[u8] = &[0x67, 0x4d, 0x00, 0x1f, 0xed, 0x82, 0x83, 0xf2];
+        let data = annexb(&[sps, pps, idr, sps_level_31, pps, p6]);
+        assert!(matches!(
+            ElementaryStream::from_annexb(&data, NalFormat::Avc, None),
+            Err(Error::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_hevc_composition_offsets() {
+        let vps: &[u8] = &[0x40, 0x01, 0x0c, 0x01];
+        let sps: &[u8] = &[
+            0x42, 0x01, 0x01, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00,
+            0x00, 0x03, 0x00, 0x5d, 0xa0, 0x02, 0x80, 0x80, 0x2e, 0x1f, 0x13, 0xc0,
+        ];
+        let pps: &[u8] = &[0x44, 0x01, 0xc1, 0x72];
+        // IDR_W_RADL, then TRAIL_R with POC 2 and TRAIL_N with POC 1, with
+        // 4 bit slice_pic_order_cnt_lsb.
+        let idr: &[u8] = &[0x26, 0x01, 0xb0];
+        let p2: &[u8] = &[0x02, 0x01, 0xd1, 0x40];
+        let b1: &[u8] = &[0x00, 0x01, 0xd0, 0xc0];
+        let data = annexb(&[vps, sps, pps, idr, p2, b1]);
+
+        let stream = ElementaryStream::from_annexb(&data, NalFormat::Hevc, None).unwrap();
+        assert_eq!(stream.track_config.timescale, 25);
+        match stream.track_config.media_conf {
+            MediaConfig::HevcConfig(ref config) => {
+                assert_eq!((config.width, config.height), (Some(1280), Some(720)));
+                assert_eq!(config.arrays.as_ref().unwrap().len(), 3);
+            }
+            _ => panic!("not an HEVC track"),
+        }
+        let offsets: Vec<i32> = stream
+            .samples
+            .iter()
+            .map(|sample| sample.rendering_offset)
+            .collect();
+        assert_eq!(offsets, vec![1, 2, 0]);
+        assert!(stream.samples[0].is_sync);
+    }
+}
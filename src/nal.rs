@@ -0,0 +1,445 @@
+//! NAL units of H.264 and HEVC samples.
+//!
+//! Samples of `avc1`, `hev1` and `hvc1` tracks store each NAL unit behind
+//! its big-endian length, on the number of bytes given by the
+//! `length_size_minus_one` of `avcC` or `hvcC`. Encoders and decoders
+//! usually exchange Annex-B byte streams instead, where NAL units follow
+//! `00 00 00 01` start codes and the parameter sets are sent in-band.
+
+use std::convert::TryFrom;
+
+use crate::mp4box::stsd::StsdBox;
+use crate::*;
+
+const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+/// Syntax of the NAL unit headers of a track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NalFormat {
+    Avc,
+    Hevc,
+}
+
+impl NalFormat {
+    /// Size of the NAL unit header, 1 byte for H.264 and 2 for HEVC.
+    pub fn header_size(&self) -> usize {
+        match self {
+            NalFormat::Avc => 1,
+            NalFormat::Hevc => 2,
+        }
+    }
+
+    /// `nal_unit_type` of a NAL unit, 0 for an empty one.
+    pub fn nal_unit_type(&self, nal: &[u8]) -> u8 {
+        match (self, nal.first()) {
+            (_, None) => 0,
+            (NalFormat::Avc, Some(byte)) => byte & 0x1f,
+            (NalFormat::Hevc, Some(byte)) => byte >> 1 & 0x3f,
+        }
+    }
+
+    /// Whether the NAL unit holds a slice of a picture.
+    pub fn is_vcl(&self, nal: &[u8]) -> bool {
+        let nal_type = self.nal_unit_type(nal);
+        match self {
+            NalFormat::Avc => (1..=5).contains(&nal_type),
+            NalFormat::Hevc => nal_type < 32,
+        }
+    }
+
+    /// Whether the NAL unit holds a slice of an IDR picture, or of any
+    /// IRAP picture for HEVC.
+    pub fn is_random_access(&self, nal: &[u8]) -> bool {
+        let nal_type = self.nal_unit_type(nal);
+        match self {
+            NalFormat::Avc => nal_type == 5,
+            NalFormat::Hevc => (16..=23).contains(&nal_type),
+        }
+    }
+
+    /// Whether the NAL unit is a VPS, SPS or PPS.
+    pub fn is_parameter_set(&self, nal: &[u8]) -> bool {
+        let nal_type = self.nal_unit_type(nal);
+        match self {
+            NalFormat::Avc => nal_type == 7 || nal_type == 8,
+            NalFormat::Hevc => (32..=34).contains(&nal_type),
+        }
+    }
+}
+
+/// NAL unit format and length size of the sample entry of a track, `None`
+/// for tracks other than `avc1`, `hev1` and `hvc1`.
+pub(crate) fn sample_entry_format(stsd: &StsdBox) -> Option<(NalFormat, usize)> {
+    if let Some(ref avc1) = stsd.avc1 {
+        Some((
+            NalFormat::Avc,
+            (avc1.avcc.length_size_minus_one & 0x3) as usize + 1,
+        ))
+    } else if let Some(ref hev1) = stsd.hev1 {
+        Some((
+            NalFormat::Hevc,
+            (hev1.hvcc.length_size_minus_one & 0x3) as usize + 1,
+        ))
+    } else {
+        stsd.hvc1.as_ref().map(|hvc1| {
+            (
+                NalFormat::Hevc,
+                (hvc1.hvcc.length_size_minus_one & 0x3) as usize + 1,
+            )
+        })
+    }
+}
+
+/// NAL units of a length-prefixed sample, without their lengths.
+///
+/// Yields an error, then stops, when a length runs past the sample.
+pub struct NalUnits<'a> {
+    data: &'a [u8],
+    length_size: usize,
+    pos: usize,
+}
+
+impl<'a> NalUnits<'a> {
+    /// `length_size` is `length_size_minus_one + 1`, from 1 to 4.
+    pub fn new(data: &'a [u8], length_size: usize) -> Result<Self> {
+        check_length_size(length_size)?;
+        Ok(Self {
+            data,
+            length_size,
+            pos: 0,
+        })
+    }
+}
+
+fn check_length_size(length_size: usize) -> Result<()> {
+    if !(1..=4).contains(&length_size) {
+        return Err(Error::InvalidData("NAL unit length size must be 1 to 4"));
+    }
+    Ok(())
+}
+
+impl<'a> Iterator for NalUnits<'a> {
+    type Item = Result<&'a [u8]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let start = self.pos + self.length_size;
+        let nal = match self.data.get(self.pos..start) {
+            Some(length) => {
+                let size = length
+                    .iter()
+                    .fold(0usize, |size, byte| size << 8 | *byte as usize);
+                self.data.get(start..start + size)
+            }
+            None => None,
+        };
+        match nal {
+            Some(nal) => {
+                self.pos = start + nal.len();
+                Some(Ok(nal))
+            }
+            None => {
+                self.pos = self.data.len();
+                Some(Err(Error::InvalidData(
+                    "NAL unit length exceeds the sample",
+                )))
+            }
+        }
+    }
+}
+
+/// NAL units of an Annex-B byte stream, without their start codes.
+pub struct AnnexBNalUnits<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> AnnexBNalUnits<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+}
+
+impl<'a> Iterator for AnnexBNalUnits<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let start = find_start_code(self.data, self.pos)? + 3;
+            let end = find_start_code(self.data, start).unwrap_or(self.data.len());
+            self.pos = end;
+
+            // Zero bytes before the next start code belong to it.
+            let mut nal = &self.data[start..end];
+            while let [rest @ .., 0] = nal {
+                nal = rest;
+            }
+            if !nal.is_empty() {
+                return Some(nal);
+            }
+        }
+    }
+}
+
+fn find_start_code(data: &[u8], from: usize) -> Option<usize> {
+    data.get(from..)?
+        .windows(3)
+        .position(|window| window == [0, 0, 1])
+        .map(|pos| from + pos)
+}
+
+/// Convert a length-prefixed sample to Annex-B, with a 4 byte start code
+/// before every NAL unit.
+pub fn to_annexb(data: &[u8], length_size: usize) -> Result<Vec<u8>> {
+    let mut annexb = Vec::with_capacity(data.len() + 4);
+    for nal in NalUnits::new(data, length_size)? {
+        annexb.extend_from_slice(&START_CODE);
+        annexb.extend_from_slice(nal?);
+    }
+    Ok(annexb)
+}
+
+/// Convert a length-prefixed sample to Annex-B, inserting `parameter_sets`
+/// before the first slice of a random access picture unless the sample
+/// already carries parameter sets in-band. Decoders fed from the middle of
+/// a stream need them at every IDR picture.
+pub fn to_annexb_with_parameter_sets(
+    data: &[u8],
+    length_size: usize,
+    format: NalFormat,
+    parameter_sets: &[&[u8]],
+) -> Result<Vec<u8>> {
+    let nal_units = NalUnits::new(data, length_size)?.collect::<Result<Vec<_>>>()?;
+    let insert_at = if nal_units.iter().any(|nal| format.is_parameter_set(nal)) {
+        None
+    } else {
+        nal_units
+            .iter()
+            .position(|nal| format.is_random_access(nal))
+    };
+
+    let size = parameter_sets
+        .iter()
+        .map(|nal| nal.len() + 4)
+        .sum::<usize>()
+        + data.len()
+        + 4;
+    let mut annexb = Vec::with_capacity(size);
+    for (i, nal) in nal_units.iter().enumerate() {
+        if insert_at == Some(i) {
+            for parameter_set in parameter_sets {
+                annexb.extend_from_slice(&START_CODE);
+                annexb.extend_from_slice(parameter_set);
+            }
+        }
+        annexb.extend_from_slice(&START_CODE);
+        annexb.extend_from_slice(nal);
+    }
+    Ok(annexb)
+}
+
+/// Convert an Annex-B access unit to a length-prefixed sample.
+pub fn from_annexb(data: &[u8], length_size: usize) -> Result<Vec<u8>> {
+    check_length_size(length_size)?;
+    let max_size = (1u64 << (8 * length_size)) - 1;
+    let mut sample = Vec::with_capacity(data.len());
+    for nal in AnnexBNalUnits::new(data) {
+        if nal.len() as u64 > max_size {
+            return Err(Error::InvalidData("NAL unit too large for the length size"));
+        }
+        let length = (nal.len() as u64).to_be_bytes();
+        sample.extend_from_slice(&length[8 - length_size..]);
+        sample.extend_from_slice(nal);
+    }
+    Ok(sample)
+}
+
+/// Strip the emulation prevention bytes of a NAL unit.
+pub(crate) fn rbsp(nal: &[u8]) -> Vec<u8> {
+    let mut rbsp = Vec::with_capacity(nal.len());
+    let mut zeros = 0;
+    for byte in nal.iter() {
+        if zeros >= 2 && *byte == 3 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if *byte == 0 { zeros + 1 } else { 0 };
+        rbsp.push(*byte);
+    }
+    rbsp
+}
+
+/// MSB first reader of the fields of a parameter set, including the
+/// exp-Golomb coded ones.
+pub(crate) struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub(crate) fn read_bit(&mut self) -> Result<bool> {
+        let byte = self
+            .data
+            .get(self.pos / 8)
+            .ok_or(Error::InvalidData("truncated parameter set"))?;
+        let bit = byte >> (7 - self.pos % 8) & 1;
+        self.pos += 1;
+        Ok(bit == 1)
+    }
+
+    /// Read `n` bits, at most 32.
+    pub(crate) fn read_bits(&mut self, n: u32) -> Result<u32> {
+        let mut value = 0u32;
+        for _ in 0..n {
+            value = value << 1 | self.read_bit()? as u32;
+        }
+        Ok(value)
+    }
+
+    pub(crate) fn skip_bits(&mut self, n: usize) -> Result<()> {
+        if self.pos + n > self.data.len() * 8 {
+            return Err(Error::InvalidData("truncated parameter set"));
+        }
+        self.pos += n;
+        Ok(())
+    }
+
+    /// Unsigned exp-Golomb code, `ue(v)`.
+    pub(crate) fn read_ue(&mut self) -> Result<u32> {
+        let mut leading_zeros = 0;
+        while !self.read_bit()? {
+            leading_zeros += 1;
+            if leading_zeros > 31 {
+                return Err(Error::InvalidData("exp-Golomb code too long"));
+            }
+        }
+        let value = (1u64 << leading_zeros) - 1 + self.read_bits(leading_zeros)? as u64;
+        u32::try_from(value).map_err(|_| Error::InvalidData("exp-Golomb code too long"))
+    }
+
+    /// Signed exp-Golomb code, `se(v)`.
+    pub(crate) fn read_se(&mut self) -> Result<i32> {
+        let value = self.read_ue()? as i64;
+        let value = if value % 2 == 1 {
+            (value + 1) / 2
+        } else {
+            -(value / 2)
+        };
+        Ok(value as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_annexb_nal_units() {
+        let data = [
+            0, 0, 0, 1, 0x67, 0x42, 0, 0, 1, 0x68, 0xce, 0, 0, 0, 0, 1, 0x65, 0x88, 0, 0, 3, 1,
+        ];
+        let nal_units: Vec<&[u8]> = AnnexBNalUnits::new(&data).collect();
+        assert_eq!(
+            nal_units,
+            vec![&[0x67, 0x42][..], &[0x68, 0xce], &[0x65, 0x88, 0, 0, 3, 1]]
+        );
+        assert_eq!(rbsp(nal_units[2]), vec![0x65, 0x88, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_length_prefixed_nal_units() {
+        let sample = [0, 0, 0, 2, 0x09, 0xf0, 0, 0, 0, 3, 0x65, 0x88, 0x84];
+        let nal_units = NalUnits::new(&sample, 4)
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(nal_units, vec![&[0x09, 0xf0][..], &[0x65, 0x88, 0x84]]);
+
+        let annexb = to_annexb(&sample, 4).unwrap();
+        assert_eq!(
+            annexb,
+            vec![0, 0, 0, 1, 0x09, 0xf0, 0, 0, 0, 1, 0x65, 0x88, 0x84]
+        );
+        assert_eq!(from_annexb(&annexb, 4).unwrap(), sample);
+        assert_eq!(
+            from_annexb(&annexb, 1).unwrap(),
+            vec![2, 0x09, 0xf0, 3, 0x65, 0x88, 0x84]
+        );
+        let mut large = vec![0, 0, 1];
+        large.resize(3 + 256, 0x65);
+        assert!(from_annexb(&large, 1).is_err());
+
+        let mut results = NalUnits::new(&sample[..12], 4).unwrap();
+        assert!(results.next().unwrap().is_ok());
+        assert!(results.next().unwrap().is_err());
+        assert!(results.next().is_none());
+    }
+
+    #[test]
+    fn test_invalid_length_size() {
+        let sample = [0, 0, 0, 2, 0x09, 0xf0];
+        let annexb = [0, 0, 0, 1, 0x09, 0xf0];
+        for length_size in [0, 5] {
+            assert!(matches!(
+                NalUnits::new(&sample, length_size),
+                Err(Error::InvalidData(_))
+            ));
+            assert!(matches!(
+                to_annexb(&sample, length_size),
+                Err(Error::InvalidData(_))
+            ));
+            assert!(matches!(
+                from_annexb(&annexb, length_size),
+                Err(Error::InvalidData(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parameter_set_injection() {
+        let sps: &[u8] = &[0x67, 0x64];
+        let pps: &[u8] = &[0x68, 0xeb];
+        // Access unit delimiter, then an IDR slice.
+        let idr = [0, 0, 0, 2, 0x09, 0xf0, 0, 0, 0, 2, 0x65, 0x88];
+        let annexb = to_annexb_with_parameter_sets(&idr, 4, NalFormat::Avc, &[sps, pps]).unwrap();
+        let nal_units: Vec<&[u8]> = AnnexBNalUnits::new(&annexb).collect();
+        assert_eq!(nal_units, vec![&[0x09, 0xf0][..], sps, pps, &[0x65, 0x88]]);
+
+        // Not before other slices, nor twice.
+        let non_idr = [0, 0, 0, 2, 0x41, 0x9a];
+        let annexb =
+            to_annexb_with_parameter_sets(&non_idr, 4, NalFormat::Avc, &[sps, pps]).unwrap();
+        assert_eq!(annexb, to_annexb(&non_idr, 4).unwrap());
+        let with_sps = [&idr[..6], &[0, 0, 0, 2], sps, &idr[6..]].concat();
+        let annexb =
+            to_annexb_with_parameter_sets(&with_sps, 4, NalFormat::Avc, &[sps, pps]).unwrap();
+        assert_eq!(AnnexBNalUnits::new(&annexb).count(), 3);
+
+        // HEVC CRA picture.
+        let cra = [0, 2, 0x2a, 0x01];
+        assert!(NalFormat::Hevc.is_random_access(&cra[2..]));
+        let vps: &[u8] = &[0x40, 0x01];
+        let annexb = to_annexb_with_parameter_sets(&cra, 2, NalFormat::Hevc, &[vps]).unwrap();
+        assert_eq!(annexb, vec![0, 0, 0, 1, 0x40, 0x01, 0, 0, 0, 1, 0x2a, 0x01]);
+    }
+
+    #[test]
+    fn test_exp_golomb() {
+        // 1, 010, 011, 00100, 00101 then 0b0001 in plain bits.
+        let data = [0b1010_0110, 0b0100_0010, 0b1000_1000];
+        let mut reader = BitReader::new(&data);
+        assert_eq!(reader.read_ue().unwrap(), 0);
+        assert_eq!(reader.read_ue().unwrap(), 1);
+        assert_eq!(reader.read_se().unwrap(), -1);
+        assert_eq!(reader.read_se().unwrap(), 2);
+        assert_eq!(reader.read_ue().unwrap(), 4);
+        assert_eq!(reader.read_bits(4).unwrap(), 1);
+        assert!(reader.read_bits(4).is_err());
+    }
+}
@@ -0,0 +1,538 @@
+//! Push parser for MP4 data received in chunks.
+//!
+//! [Mp4Parser] never reads or seeks on its own: bytes are handed to
+//! [Mp4Parser::push] as they arrive, in chunks of any size, and come back as
+//! [Mp4Event]s. Top-level boxes are reported as they start and end, `ftyp`,
+//! `moov`, `moof` and `emsg` boxes are parsed, and the payload of `mdat` boxes
+//! is split into the samples described by the last `moov` and `moof` boxes:
+//!
+//! ```rust
+//! use std::collections::HashMap;
+//! use mp4::{Mp4Event, Mp4Parser};
+//!
+//! # fn main() -> mp4::Result<()> {
+//! let data = std::fs::read("tests/samples/minimal.mp4")?;
+//! let mut parser = Mp4Parser::new();
+//! let mut samples: HashMap<(u32, u32), Vec<u8>> = HashMap::new();
+//! for chunk in data.chunks(100) {
+//!     for event in parser.push(chunk)? {
+//!         if let Mp4Event::Sample(sample) = event {
+//!             samples
+//!                 .entry((sample.track_id, sample.sample_id))
+//!                 .or_default()
+//!                 .extend_from_slice(sample.data);
+//!         }
+//!     }
+//! }
+//! parser.finish()?;
+//! assert_eq!(samples[&(1, 1)].len(), 751);
+//! # Ok(()) }
+//! ```
+//!
+//! Samples are only found in `mdat` boxes that follow the box describing
+//! them, as in fragmented and fast start files. The bytes of other boxes are
+//! dropped once parsed, so memory use does not grow with the stream.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::Cursor;
+
+use crate::mp4box::traf::TrafBox;
+use crate::track::SampleIndexEntry;
+use crate::*;
+
+/// Something found in the data pushed to a [Mp4Parser].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mp4Event<'a> {
+    /// The header of a top-level box was read.
+    BoxStart {
+        box_type: BoxType,
+        /// Offset of the box header in the stream.
+        offset: u64,
+        header_size: u64,
+        /// Size of the box, header included, or None for a box that extends
+        /// to the end of the stream.
+        size: Option<u64>,
+    },
+    /// All of a top-level box was read.
+    BoxEnd {
+        box_type: BoxType,
+        offset: u64,
+    },
+    Ftyp(FtypBox),
+    Moov(Box<MoovBox>),
+    Moof(Box<MoofBox>),
+    Emsg(EmsgBox),
+    /// Part of a sample, from inside an `mdat` box.
+    Sample(SampleData<'a>),
+}
+
+/// Part of the payload of a sample, borrowed from the chunk passed to
+/// [Mp4Parser::push]. A sample split across chunks comes in several parts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SampleData<'a> {
+    pub track_id: u32,
+    pub sample_id: u32,
+    /// Offset of the sample in the stream.
+    pub sample_offset: u64,
+    pub sample_size: u32,
+    /// Position of `data` in the sample.
+    pub position: u32,
+    pub data: &'a [u8],
+
+    pub start_time: u64,
+    pub duration: u32,
+    pub rendering_offset: i32,
+    pub is_sync: bool,
+}
+
+impl SampleData<'_> {
+    /// Whether `data` ends the sample.
+    pub fn is_last(&self) -> bool {
+        self.position as u64 + self.data.len() as u64 == self.sample_size as u64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Reading a box header, gathered in `buf`.
+    Header,
+    /// Gathering a box to parse in `buf`, header included.
+    Buffer {
+        box_type: BoxType,
+        offset: u64,
+        size: u64,
+    },
+    /// Passing over the payload of a box, sample by sample for `mdat`.
+    Payload {
+        box_type: BoxType,
+        offset: u64,
+        end: Option<u64>,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PendingSample {
+    track_id: u32,
+    sample_id: u32,
+    entry: SampleIndexEntry,
+}
+
+impl PendingSample {
+    fn end(&self) -> u64 {
+        self.entry.offset + self.entry.size as u64
+    }
+}
+
+/// Sans-IO MP4 parser, see the [module documentation](self).
+#[derive(Debug)]
+pub struct Mp4Parser {
+    /// Offset in the stream of the next byte pushed.
+    offset: u64,
+    state: State,
+    buf: Vec<u8>,
+
+    tracks: HashMap<u32, Mp4Track>,
+    /// Samples not yet found in an `mdat` box, by offset.
+    samples: VecDeque<PendingSample>,
+    /// Id and start time of the next fragment sample of each track.
+    next_samples: HashMap<u32, (u32, u64)>,
+}
+
+impl Default for Mp4Parser {
+    fn default() -> Self {
+        Mp4Parser::new()
+    }
+}
+
+impl Mp4Parser {
+    pub fn new() -> Self {
+        Mp4Parser {
+            offset: 0,
+            state: State::Header,
+            buf: Vec::new(),
+            tracks: HashMap::new(),
+            samples: VecDeque::new(),
+            next_samples: HashMap::new(),
+        }
+    }
+
+    /// Number of bytes pushed so far.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The tracks of the last `moov` box, without their fragments.
+    pub fn tracks(&self) -> &HashMap<u32, Mp4Track> {
+        &self.tracks
+    }
+
+    /// Parse the next chunk of the stream.
+    pub fn push<'a>(&mut self, data: &'a [u8]) -> Result<Vec<Mp4Event<'a>>> {
+        let mut events = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let chunk = &data[pos..];
+            let consumed = match self.state {
+                State::Header => self.read_header(chunk, &mut events)?,
+                State::Buffer { size, .. } => {
+                    let len = chunk.len().min((size - self.buf.len() as u64) as usize);
+                    self.buf.extend_from_slice(&chunk[..len]);
+                    len
+                }
+                State::Payload { box_type, end, .. } => {
+                    let len = match end {
+                        Some(end) => chunk.len().min((end - self.offset) as usize),
+                        None => chunk.len(),
+                    };
+                    if box_type == BoxType::MdatBox {
+                        self.read_samples(&chunk[..len], &mut events);
+                    }
+                    len
+                }
+            };
+            pos += consumed;
+            self.offset += consumed as u64;
+            self.end_box(&mut events)?;
+        }
+        Ok(events)
+    }
+
+    /// Signal the end of the stream, ending a box that extends to it.
+    pub fn finish(&mut self) -> Result<Vec<Mp4Event<'static>>> {
+        match self.state {
+            State::Header if self.buf.is_empty() => Ok(Vec::new()),
+            State::Payload {
+                box_type,
+                offset,
+                end: None,
+            } => {
+                self.state = State::Header;
+                Ok(vec![Mp4Event::BoxEnd { box_type, offset }])
+            }
+            _ => Err(Error::InvalidData("stream ends inside a box")),
+        }
+    }
+
+    /// Gather the bytes of a box header, then start the box once complete.
+    fn read_header(&mut self, chunk: &[u8], events: &mut Vec<Mp4Event>) -> Result<usize> {
+        // A size of 1 is followed by a 64-bit size.
+        let header_size = if self.buf.len() >= 4 && self.buf[..4] == [0, 0, 0, 1] {
+            HEADER_SIZE + 8
+        } else {
+            HEADER_SIZE
+        };
+        let len = chunk.len().min(header_size as usize - self.buf.len());
+        self.buf.extend_from_slice(&chunk[..len]);
+        if self.buf.len() < header_size as usize
+            || self.buf[..4] == [0, 0, 0, 1] && header_size == HEADER_SIZE
+        {
+            return Ok(len);
+        }
+
+        let offset = self.offset + len as u64 - header_size;
+        let box_type = BoxType::from(u32::from_be_bytes([
+            self.buf[4],
+            self.buf[5],
+            self.buf[6],
+            self.buf[7],
+        ]));
+        let size = match u32::from_be_bytes([self.buf[0], self.buf[1], self.buf[2], self.buf[3]]) {
+            0 => None,
+            1 => Some(u64::from_be_bytes([
+                self.buf[8],
+                self.buf[9],
+                self.buf[10],
+                self.buf[11],
+                self.buf[12],
+                self.buf[13],
+                self.buf[14],
+                self.buf[15],
+            ])),
+            size => Some(size as u64),
+        };
+        if size.is_some_and(|size| size < header_size) {
+            return Err(Error::InvalidData("box size smaller than its header"));
+        }
+
+        events.push(Mp4Event::BoxStart {
+            box_type,
+            offset,
+            header_size,
+            size,
+        });
+        self.state = match (box_type, size) {
+            (
+                BoxType::FtypBox | BoxType::MoovBox | BoxType::MoofBox | BoxType::EmsgBox,
+                Some(size),
+            ) => State::Buffer {
+                box_type,
+                offset,
+                size,
+            },
+            _ => {
+                self.buf.clear();
+                State::Payload {
+                    box_type,
+                    offset,
+                    end: size.map(|size| offset + size),
+                }
+            }
+        };
+        Ok(len)
+    }
+
+    /// End the current box if all of it was read.
+    fn end_box(&mut self, events: &mut Vec<Mp4Event>) -> Result<()> {
+        let (box_type, offset) = match self.state {
+            State::Buffer {
+                box_type,
+                offset,
+                size,
+            } if self.buf.len() as u64 == size => {
+                let buf = std::mem::take(&mut self.buf);
+                self.parse_box(box_type, offset, buf, events)?;
+                (box_type, offset)
+            }
+            State::Payload {
+                box_type,
+                offset,
+                end: Some(end),
+            } if self.offset == end => (box_type, offset),
+            _ => return Ok(()),
+        };
+        self.state = State::Header;
+        events.push(Mp4Event::BoxEnd { box_type, offset });
+        Ok(())
+    }
+
+    fn parse_box(
+        &mut self,
+        box_type: BoxType,
+        offset: u64,
+        buf: Vec<u8>,
+        events: &mut Vec<Mp4Event>,
+    ) -> Result<()> {
+        let mut reader = Cursor::new(buf);
+        let header = BoxHeader::read(&mut reader)?;
+        match box_type {
+            BoxType::FtypBox => {
+                events.push(Mp4Event::Ftyp(FtypBox::read_box(&mut reader, header.size)?));
+            }
+            BoxType::MoovBox => {
+                let moov = MoovBox::read_box(&mut reader, header.size)?;
+                self.add_moov_samples(&moov)?;
+                events.push(Mp4Event::Moov(Box::new(moov)));
+            }
+            BoxType::MoofBox => {
+                let moof = MoofBox::read_box(&mut reader, header.size)?;
+                self.add_moof_samples(&moof, offset)?;
+                events.push(Mp4Event::Moof(Box::new(moof)));
+            }
+            BoxType::EmsgBox => {
+                events.push(Mp4Event::Emsg(EmsgBox::read_box(&mut reader, header.size)?));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn add_moov_samples(&mut self, moov: &MoovBox) -> Result<()> {
+        if moov.traks.iter().any(|trak| trak.tkhd.track_id == 0) {
+            return Err(Error::InvalidData("illegal track id 0"));
+        }
+
+        self.tracks.clear();
+        self.next_samples.clear();
+        let mut samples = Vec::new();
+        for trak in moov.traks.iter() {
+            let track_id = trak.tkhd.track_id;
+            let mut track = Mp4Track::from(trak);
+            if let Some(trex) = moov.mvex.as_ref().and_then(|mvex| mvex.trex(track_id)) {
+                track.default_sample_duration = trex.default_sample_duration;
+                track.default_sample_size = trex.default_sample_size;
+                track.default_sample_flags = trex.default_sample_flags;
+            }
+
+            let entries = track
+                .index_stbl_samples()
+                .ok_or(Error::InvalidData("moov sample tables are inconsistent"))?;
+            let next_start_time = entries
+                .last()
+                .map_or(0, |entry| entry.start_time + entry.duration as u64);
+            self.next_samples
+                .insert(track_id, (entries.len() as u32 + 1, next_start_time));
+            samples.extend(
+                entries
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, entry)| PendingSample {
+                        track_id,
+                        sample_id: i as u32 + 1,
+                        entry,
+                    }),
+            );
+            self.tracks.insert(track_id, track);
+        }
+        self.add_samples(samples);
+        Ok(())
+    }
+
+    fn add_moof_samples(&mut self, moof: &MoofBox, moof_offset: u64) -> Result<()> {
+        let mut track_ids: Vec<u32> = Vec::new();
+        for traf in moof.trafs.iter() {
+            if !track_ids.contains(&traf.tfhd.track_id) {
+                track_ids.push(traf.tfhd.track_id);
+            }
+        }
+
+        let mut samples = Vec::new();
+        for track_id in track_ids {
+            let track = self
+                .tracks
+                .get(&track_id)
+                .ok_or(Error::TrakNotFound(track_id))?;
+            let trafs: Vec<&TrafBox> = moof
+                .trafs
+                .iter()
+                .filter(|traf| traf.tfhd.track_id == track_id)
+                .collect();
+            let (next_sample_id, next_start_time) =
+                self.next_samples.get(&track_id).copied().unwrap_or((1, 0));
+            let entries =
+                track.index_traf_samples(&trafs, moof_offset, next_sample_id, next_start_time)?;
+
+            let next_start_time = entries.last().map_or(next_start_time, |entry| {
+                entry.start_time + entry.duration as u64
+            });
+            self.next_samples.insert(
+                track_id,
+                (next_sample_id + entries.len() as u32, next_start_time),
+            );
+            samples.extend(
+                entries
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, entry)| PendingSample {
+                        track_id,
+                        sample_id: next_sample_id + i as u32,
+                        entry,
+                    }),
+            );
+        }
+        self.add_samples(samples);
+        Ok(())
+    }
+
+    fn add_samples(&mut self, samples: Vec<PendingSample>) {
+        let mut pending: Vec<PendingSample> = self.samples.drain(..).chain(samples).collect();
+        pending.sort_by_key(|sample| sample.entry.offset);
+        self.samples = pending.into();
+    }
+
+    /// Split `data`, the part of an `mdat` payload at the current offset,
+    /// into the samples it holds.
+    fn read_samples<'a>(&mut self, data: &'a [u8], events: &mut Vec<Mp4Event<'a>>) {
+        let start = self.offset;
+        let end = start + data.len() as u64;
+
+        // Samples that should have been found earlier are not in this stream.
+        while self
+            .samples
+            .front()
+            .is_some_and(|sample| sample.end() <= start)
+        {
+            self.samples.pop_front();
+        }
+
+        while let Some(sample) = self.samples.front() {
+            if sample.entry.offset >= end {
+                break;
+            }
+            let from = sample.entry.offset.max(start);
+            let to = sample.end().min(end);
+            events.push(Mp4Event::Sample(SampleData {
+                track_id: sample.track_id,
+                sample_id: sample.sample_id,
+                sample_offset: sample.entry.offset,
+                sample_size: sample.entry.size,
+                position: (from - sample.entry.offset) as u32,
+                data: &data[(from - start) as usize..(to - start) as usize],
+                start_time: sample.entry.start_time,
+                duration: sample.entry.duration,
+                rendering_offset: sample.entry.rendering_offset,
+                is_sync: sample.entry.is_sync,
+            }));
+            if sample.end() > end {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_box_events() {
+        let mut data = Vec::new();
+        data.extend_from_slice(&12u32.to_be_bytes());
+        data.extend_from_slice(b"free");
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&1u32.to_be_bytes());
+        data.extend_from_slice(b"free");
+        data.extend_from_slice(&16u64.to_be_bytes());
+        data.extend_from_slice(&0u32.to_be_bytes());
+        data.extend_from_slice(b"mdat");
+        data.extend_from_slice(&[1, 2, 3]);
+
+        // Byte by byte, so that headers are split across chunks.
+        let mut parser = Mp4Parser::new();
+        let mut events = Vec::new();
+        for byte in data.chunks(1) {
+            events.extend(parser.push(byte).unwrap());
+        }
+        events.extend(parser.finish().unwrap());
+        assert_eq!(parser.offset(), data.len() as u64);
+        assert_eq!(
+            events,
+            vec![
+                Mp4Event::BoxStart {
+                    box_type: BoxType::FreeBox,
+                    offset: 0,
+                    header_size: 8,
+                    size: Some(12),
+                },
+                Mp4Event::BoxEnd {
+                    box_type: BoxType::FreeBox,
+                    offset: 0,
+                },
+                Mp4Event::BoxStart {
+                    box_type: BoxType::FreeBox,
+                    offset: 12,
+                    header_size: 16,
+                    size: Some(16),
+                },
+                Mp4Event::BoxEnd {
+                    box_type: BoxType::FreeBox,
+                    offset: 12,
+                },
+                Mp4Event::BoxStart {
+                    box_type: BoxType::MdatBox,
+                    offset: 28,
+                    header_size: 8,
+                    size: None,
+                },
+                Mp4Event::BoxEnd {
+                    box_type: BoxType::MdatBox,
+                    offset: 28,
+                },
+            ]
+        );
+
+        let mut parser = Mp4Parser::new();
+        parser.push(&data[..14]).unwrap();
+        assert!(parser.finish().is_err());
+    }
+}
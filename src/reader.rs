@@ -1,5 +1,6 @@
 use std::collections::HashMap;
-use std::io::{Read, Seek};
+use std::convert::TryFrom;
+use std::io::{Read, Seek, Write};
 use std::time::Duration;
 
 use crate::meta::MetaBox;
@@ -16,6 +17,7 @@ pub struct Mp4Reader<R> {
 
     tracks: HashMap<u32, Mp4Track>,
     size: u64,
+    keys: HashMap<[u8; 16], [u8; 16]>,
 }
 
 impl<R: Read + Seek> Mp4Reader<R> {
@@ -102,18 +104,17 @@ impl<R: Read + Seek> Mp4Reader<R> {
 
         // Update tracks if any fragmented (moof) boxes are found.
         if !moofs.is_empty() {
-            let mut default_sample_duration = 0;
-            if let Some(ref moov) = moov {
-                if let Some(ref mvex) = &moov.mvex {
-                    default_sample_duration = mvex.trex.default_sample_duration
-                }
-            }
+            let mvex = moov.as_ref().and_then(|moov| moov.mvex.as_ref());
 
             for (moof, moof_offset) in moofs.iter().zip(moof_offsets) {
                 for traf in moof.trafs.iter() {
                     let track_id = traf.tfhd.track_id;
                     if let Some(track) = tracks.get_mut(&track_id) {
-                        track.default_sample_duration = default_sample_duration;
+                        if let Some(trex) = mvex.and_then(|mvex| mvex.trex(track_id)) {
+                            track.default_sample_duration = trex.default_sample_duration;
+                            track.default_sample_size = trex.default_sample_size;
+                            track.default_sample_flags = trex.default_sample_flags;
+                        }
                         track.moof_offsets.push(moof_offset);
                         track.trafs.push(traf.clone())
                     } else {
@@ -127,11 +128,12 @@ impl<R: Read + Seek> Mp4Reader<R> {
             reader,
             ftyp: ftyp.unwrap(),
             moov: moov.unwrap(),
-            sidx: sidx,
+            sidx,
             moofs,
             emsgs,
             size,
             tracks,
+            keys: HashMap::new(),
         })
     }
 
@@ -192,16 +194,17 @@ impl<R: Read + Seek> Mp4Reader<R> {
             .map(|trak| (trak.tkhd.track_id, Mp4Track::from(trak)))
             .collect();
 
-        let mut default_sample_duration = 0;
-        if let Some(ref mvex) = &self.moov.mvex {
-            default_sample_duration = mvex.trex.default_sample_duration
-        }
+        let mvex = self.moov.mvex.as_ref();
 
         for (moof, moof_offset) in moofs.iter().zip(moof_offsets) {
             for traf in moof.trafs.iter() {
                 let track_id = traf.tfhd.track_id;
                 if let Some(track) = tracks.get_mut(&track_id) {
-                    track.default_sample_duration = default_sample_duration;
+                    if let Some(trex) = mvex.and_then(|mvex| mvex.trex(track_id)) {
+                        track.default_sample_duration = trex.default_sample_duration;
+                        track.default_sample_size = trex.default_sample_size;
+                        track.default_sample_flags = trex.default_sample_flags;
+                    }
                     track.moof_offsets.push(moof_offset);
                     track.trafs.push(traf.clone())
                 } else {
@@ -219,6 +222,7 @@ impl<R: Read + Seek> Mp4Reader<R> {
             emsgs: Vec::new(),
             tracks,
             size,
+            keys: self.keys.clone(),
         })
     }
 
@@ -254,6 +258,14 @@ impl<R: Read + Seek> Mp4Reader<R> {
         &self.tracks
     }
 
+    /// Index the samples of every track, see [Mp4Track::build_sample_index].
+    /// Worth doing before reading most of the samples of a long file.
+    pub fn build_sample_index(&mut self) {
+        for track in self.tracks.values_mut() {
+            track.build_sample_index();
+        }
+    }
+
     pub fn sample_count(&self, track_id: u32) -> Result<u32> {
         if let Some(track) = self.tracks.get(&track_id) {
             Ok(track.sample_count())
@@ -262,14 +274,46 @@ impl<R: Read + Seek> Mp4Reader<R> {
         }
     }
     //Mp4SampleMetadata
+    /// Keys to decrypt the samples of encrypted tracks with, by key ID.
+    ///
+    /// Once set, [Self::read_sample] returns the samples of tracks whose
+    /// default key ID has a key in the clear, with no `encryption`.
+    pub fn set_decryption_keys(&mut self, keys: HashMap<[u8; 16], [u8; 16]>) {
+        self.keys = keys;
+    }
+
     pub fn read_sample(&mut self, track_id: u32, sample_id: u32) -> Result<Option<Mp4Sample>> {
         if let Some(track) = self.tracks.get(&track_id) {
-            track.read_sample(&mut self.reader, sample_id)
+            let mut sample = track.read_sample(&mut self.reader, sample_id)?;
+            if let (Some(sample), Some(key)) = (
+                sample.as_mut(),
+                track.default_kid().and_then(|kid| self.keys.get(&kid)),
+            ) {
+                track.decrypt_sample(sample, key)?;
+            }
+            Ok(sample)
         } else {
             Err(Error::TrakNotFound(track_id))
         }
     }
 
+    /// Write the samples of a track as an elementary stream, see
+    /// [ElementaryStreamWriter] for the formats.
+    pub fn demux_track<W: Write>(&mut self, track_id: u32, writer: W) -> Result<W> {
+        let track = self
+            .tracks
+            .get(&track_id)
+            .ok_or(Error::TrakNotFound(track_id))?;
+        let sample_count = track.sample_count();
+        let mut stream = ElementaryStreamWriter::new(track, writer)?;
+        for sample_id in 1..=sample_count {
+            if let Some(sample) = self.read_sample(track_id, sample_id)? {
+                stream.write_sample(&sample)?;
+            }
+        }
+        stream.finish()
+    }
+
     pub fn read_sample_metadata(
         &mut self,
         track_id: u32,
@@ -282,6 +326,22 @@ impl<R: Read + Seek> Mp4Reader<R> {
         }
     }
 
+    pub fn sample_at_time(&self, track_id: u32, time: Duration) -> Result<Option<u32>> {
+        if let Some(track) = self.tracks.get(&track_id) {
+            track.sample_at_time(time)
+        } else {
+            Err(Error::TrakNotFound(track_id))
+        }
+    }
+
+    pub fn nearest_sync_sample_before(&self, track_id: u32, time: Duration) -> Result<Option<u32>> {
+        if let Some(track) = self.tracks.get(&track_id) {
+            track.nearest_sync_sample_before(time)
+        } else {
+            Err(Error::TrakNotFound(track_id))
+        }
+    }
+
     pub fn sample_offset(&mut self, track_id: u32, sample_id: u32) -> Result<u64> {
         if let Some(track) = self.tracks.get(&track_id) {
             track.sample_offset(sample_id)
@@ -289,15 +349,96 @@ impl<R: Read + Seek> Mp4Reader<R> {
             Err(Error::TrakNotFound(track_id))
         }
     }
+
+    /// The chapters of the movie, read from the text track referenced by a
+    /// `chap` track reference, or else from the Nero `chpl` box.
+    pub fn chapters(&mut self) -> Result<Vec<Chapter>> {
+        let chapter_track_id = self
+            .moov
+            .traks
+            .iter()
+            .filter_map(|trak| trak.tref.as_ref())
+            .flat_map(|tref| tref.track_ids(TrackReferenceType::Chapter))
+            .find(|track_id| self.tracks.contains_key(track_id));
+
+        if let Some(track_id) = chapter_track_id {
+            let timescale = self.tracks[&track_id].timescale() as u128;
+            if timescale == 0 {
+                return Err(Error::InvalidData("chapter track has a zero timescale"));
+            }
+            let mut chapters = Vec::new();
+            for sample_id in 1..=self.sample_count(track_id)? {
+                if let Some(sample) = self.read_sample(track_id, sample_id)? {
+                    // Skip chapters starting past u64::MAX microseconds.
+                    let micros = sample.start_time as u128 * 1_000_000 / timescale;
+                    if let Ok(micros) = u64::try_from(micros) {
+                        chapters.push(Chapter {
+                            start: Duration::from_micros(micros),
+                            title: text_sample_to_string(&sample.bytes),
+                        });
+                    }
+                }
+            }
+            return Ok(chapters);
+        }
+
+        let chpl = self.moov.udta.as_ref().and_then(|udta| udta.chpl.as_ref());
+        Ok(chpl
+            .map(|chpl| {
+                chpl.chapters
+                    .iter()
+                    .filter_map(|chapter| {
+                        let nanos = chapter
+                            .start
+                            .checked_mul(1_000_000_000 / ChplBox::TIMESCALE)?;
+                        Some(Chapter {
+                            start: Duration::from_nanos(nanos),
+                            title: chapter.title.clone(),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
 }
 
 impl<R> Mp4Reader<R> {
+    /// `pssh` boxes of the movie and of every fragment, by DRM system ID.
+    pub fn pssh(&self) -> HashMap<[u8; 16], Vec<&PsshBox>> {
+        let mut pssh: HashMap<[u8; 16], Vec<&PsshBox>> = HashMap::new();
+        let moofs = self.moofs.iter().flat_map(|moof| moof.psshs.iter());
+        for pssh_box in self.moov.psshs.iter().chain(moofs) {
+            pssh.entry(pssh_box.system_id).or_default().push(pssh_box);
+        }
+        pssh
+    }
+
     pub fn metadata(&self) -> impl Metadata<'_> {
         self.moov.udta.as_ref().and_then(|udta| {
             udta.meta.as_ref().and_then(|meta| match meta {
-                MetaBox::Mdir { ilst } => ilst.as_ref(),
+                MetaBox::Mdir { ilst, .. } => ilst.as_ref(),
                 _ => None,
             })
         })
     }
 }
+
+/// Decode a `tx3g` or QuickTime `text` sample: a 16-bit length followed by
+/// UTF-8 text, or UTF-16 text when it starts with a byte order mark.
+fn text_sample_to_string(bytes: &[u8]) -> String {
+    let len = match bytes.get(..2) {
+        Some(len) => u16::from_be_bytes([len[0], len[1]]) as usize,
+        None => return String::new(),
+    };
+    let text = &bytes[2..];
+    let text = &text[..len.min(text.len())];
+    if text.starts_with(&[0xfe, 0xff]) {
+        let units: Vec<u16> = text[2..]
+            .chunks_exact(2)
+            .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        String::from_utf8_lossy(text).into_owned()
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+
+use crate::mp4box::*;
+use crate::track::Mp4TrackWriter;
+use crate::writer::relocate_moov;
+use crate::*;
+
+/// Writer for progressive MP4 on sinks that cannot seek, such as pipes and
+/// sockets.
+///
+/// Every sample is declared up front with [Self::add_sample_metadata], in the
+/// order its data will be written. This is enough to lay out `moov` and the
+/// `mdat` size before any media, so the output is `ftyp` + `moov` + `mdat`,
+/// as with [Mp4Writer::write_end_faststart]. Consecutive samples of the same
+/// track are stored as one chunk, so the declaration order also decides the
+/// interleaving.
+///
+/// When samples are not known in advance, use [Mp4FragmentWriter] instead.
+#[derive(Debug)]
+pub struct Mp4StreamWriter<W> {
+    writer: W,
+    tracks: HashMap<u32, Mp4TrackWriter>,
+    ftyp_size: u64,
+    timescale: u32,
+    duration: u64,
+    samples: VecDeque<(u32, u32)>,
+    moov_written: bool,
+}
+
+impl<W> Mp4StreamWriter<W> {
+    /// Consume self, returning the inner writer.
+    pub fn into_writer(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Mp4StreamWriter<W> {
+    /// Write the `ftyp` box and return a writer ready to accept tracks.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mp4::{AacConfig, Mp4Config, Mp4Sample, Mp4SampleMetadata, Mp4StreamWriter, TrackConfig};
+    ///
+    /// # fn main() -> mp4::Result<()> {
+    /// let config = Mp4Config {
+    ///     major_brand: str::parse("isom").unwrap(),
+    ///     minor_version: 512,
+    ///     compatible_brands: vec![str::parse("isom").unwrap()],
+    ///     timescale: 1000,
+    /// };
+    ///
+    /// let mut writer = Mp4StreamWriter::write_start(Vec::new(), &config)?;
+    /// let track_id = writer.add_track(&TrackConfig::from(AacConfig::default()))?;
+    /// let metadata = Mp4SampleMetadata {
+    ///     start_time: 0,
+    ///     duration: 1024,
+    ///     rendering_offset: 0,
+    ///     is_sync: true,
+    ///     sample_flags: None,
+    /// };
+    /// writer.add_sample_metadata(track_id, 4, &metadata)?;
+    ///
+    /// let sample = Mp4Sample {
+    ///     start_time: 0,
+    ///     duration: 1024,
+    ///     rendering_offset: 0,
+    ///     is_sync: true,
+    ///     bytes: mp4::Bytes::from_static(&[0x21, 0x10, 0x04, 0x60]),
+    ///     encryption: None,
+    /// };
+    /// writer.write_sample(track_id, &sample)?;
+    /// writer.write_end()?;
+    ///
+    /// let data: Vec<u8> = writer.into_writer();
+    /// # Ok(()) }
+    /// ```
+    pub fn write_start(mut writer: W, config: &Mp4Config) -> Result<Self> {
+        let ftyp = FtypBox {
+            major_brand: config.major_brand,
+            minor_version: config.minor_version,
+            compatible_brands: config.compatible_brands.clone(),
+        };
+        ftyp.write_box(&mut writer)?;
+
+        Ok(Self {
+            writer,
+            tracks: HashMap::new(),
+            ftyp_size: ftyp.box_size(),
+            timescale: config.timescale,
+            duration: 0,
+            samples: VecDeque::new(),
+            moov_written: false,
+        })
+    }
+
+    pub fn add_track(&mut self, config: &TrackConfig) -> Result<u32> {
+        if self.moov_written {
+            return Err(Error::InvalidData(
+                "tracks must be added before the moov box is written",
+            ));
+        }
+        let track_id = match config.track_id {
+            Some(track_id) => track_id,
+            None => self.tracks.len() as u32 + 1,
+        };
+        if track_id == 0 {
+            return Err(Error::InvalidData("illegal track id 0"));
+        }
+        if self.tracks.contains_key(&track_id) {
+            return Err(Error::InvalidData("track_id already exists"));
+        }
+        let track = Mp4TrackWriter::new(track_id, config)?;
+        self.tracks.insert(track_id, track);
+        Ok(track_id)
+    }
+
+    pub fn track_ids(&self) -> Vec<u32> {
+        self.tracks.keys().cloned().collect()
+    }
+
+    /// Declare the next sample to be written, with the size of its data.
+    pub fn add_sample_metadata(
+        &mut self,
+        track_id: u32,
+        size: u32,
+        metadata: &Mp4SampleMetadata,
+    ) -> Result<()> {
+        if self.moov_written {
+            return Err(Error::InvalidData(
+                "samples must be declared before the moov box is written",
+            ));
+        }
+        let track_dur = if let Some(track) = self.tracks.get_mut(&track_id) {
+            track.add_sample(size, metadata, self.timescale)
+        } else {
+            return Err(Error::TrakNotFound(track_id));
+        };
+        if track_dur > self.duration {
+            self.duration = track_dur;
+        }
+        self.samples.push_back((track_id, size));
+        Ok(())
+    }
+
+    /// Write the `moov` box and the `mdat` header.
+    ///
+    /// This is done implicitly by the first call to [Self::write_sample] or
+    /// [Self::write_end]. No samples can be declared afterwards.
+    pub fn write_moov(&mut self) -> Result<()> {
+        if self.moov_written {
+            return Ok(());
+        }
+
+        // Lay out chunks as runs of consecutive samples of the same track,
+        // at offsets relative to the start of the mdat payload.
+        let mut sample_ids: HashMap<u32, u32> = HashMap::new();
+        let mut chunk: Option<(u32, u64, u32, u32)> = None;
+        let mut data_size = 0;
+        for &(track_id, size) in self.samples.iter() {
+            let sample_id = sample_ids.entry(track_id).or_insert(0);
+            *sample_id += 1;
+            match chunk {
+                Some((id, _, _, ref mut sample_count)) if id == track_id => {
+                    *sample_count += 1;
+                }
+                _ => {
+                    if let Some((id, offset, first_sample, sample_count)) = chunk {
+                        self.tracks.get_mut(&id).unwrap().add_chunk(
+                            offset,
+                            first_sample,
+                            sample_count,
+                        );
+                    }
+                    chunk = Some((track_id, data_size, *sample_id, 1));
+                }
+            }
+            data_size += size as u64;
+        }
+        if let Some((id, offset, first_sample, sample_count)) = chunk {
+            self.tracks
+                .get_mut(&id)
+                .unwrap()
+                .add_chunk(offset, first_sample, sample_count);
+        }
+
+        let mut mdat_size = HEADER_SIZE + data_size;
+        if mdat_size > u32::MAX as u64 {
+            // largesize
+            mdat_size += 8;
+        }
+
+        let mut moov = MoovBox::default();
+        let mut track_ids = self.track_ids();
+        track_ids.sort_unstable();
+        for track_id in track_ids {
+            if let Some(track) = self.tracks.get_mut(&track_id) {
+                moov.traks.push(track.finish());
+            }
+        }
+        moov.mvhd.timescale = self.timescale;
+        moov.mvhd.duration = self.duration;
+        if moov.mvhd.duration > (u32::MAX as u64) {
+            moov.mvhd.version = 1
+        }
+
+        let moov = relocate_moov(&moov, self.ftyp_size + mdat_size - data_size);
+        moov.write_box(&mut self.writer)?;
+        BoxHeader::new(BoxType::MdatBox, mdat_size).write(&mut self.writer)?;
+
+        self.moov_written = true;
+        Ok(())
+    }
+
+    /// Write the data of the next declared sample.
+    pub fn write_sample(&mut self, track_id: u32, sample: &Mp4Sample) -> Result<()> {
+        if !self.tracks.contains_key(&track_id) {
+            return Err(Error::TrakNotFound(track_id));
+        }
+        self.write_moov()?;
+
+        match self.samples.front() {
+            Some(&(id, size)) if id == track_id && size as usize == sample.bytes.len() => {}
+            _ => {
+                return Err(Error::InvalidData(
+                    "sample does not match the declared samples",
+                ))
+            }
+        }
+        self.writer.write_all(&sample.bytes)?;
+        self.samples.pop_front();
+        Ok(())
+    }
+
+    pub fn write_end(&mut self) -> Result<()> {
+        self.write_moov()?;
+        if !self.samples.is_empty() {
+            return Err(Error::InvalidData("declared samples were not written"));
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
@@ -1,20 +1,25 @@
 use bytes::BytesMut;
 use std::cmp;
+use std::collections::VecDeque;
 use std::convert::TryFrom;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 use std::time::Duration;
 
+use crate::cenc::SampleEncryptor;
 use crate::elst::ElstEntry;
 use crate::hvc1::Hvc1Box;
 use crate::mp4a::EsdsBox;
 use crate::mp4box::traf::TrafBox;
 use crate::mp4box::trak::TrakBox;
+use crate::mp4box::trex::TrexBox;
 use crate::mp4box::trun::TrunBox;
 use crate::mp4box::{
-    avc1::Avc1Box, co64::Co64Box, ctts::CttsBox, ctts::CttsEntry, hev1::Hev1Box, mp4a::Mp4aBox,
-    opus::OpusBox, smhd::SmhdBox, stco::StcoBox, stsc::StscEntry, stss::StssBox, stts::SttsEntry,
-    tkhd::Matrix, tx3g::Tx3gBox, vmhd::VmhdBox, vp09::Vp09Box,
+    ac3::Ac3Box, av01::Av01Box, avc1::Avc1Box, co64::Co64Box, ctts::CttsBox, ctts::CttsEntry,
+    ec3::Ec3Box, flac::FlacBox, hev1::Hev1Box, mp4a::Mp4aBox, opus::OpusBox, smhd::SmhdBox,
+    stco::StcoBox, stsc::StscEntry, stss::StssBox, stts::SttsEntry, tenc::TencBox, tkhd::Matrix,
+    tx3g::Tx3gBox, vmhd::VmhdBox, vp09::Vp09Box,
 };
+use crate::nal::{self, NalFormat, NalUnits};
 use crate::*;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -25,6 +30,15 @@ pub struct TrackConfig {
     pub language: String,
     pub media_conf: MediaConfig,
     pub matrix: Option<Vec<i32>>,
+
+    /// Number of samples per chunk. Takes precedence over
+    /// `duration_per_chunk` when set.
+    pub samples_per_chunk: Option<u32>,
+    /// Duration of a chunk in the track timescale. Defaults to one second.
+    pub duration_per_chunk: Option<u32>,
+
+    /// References to other tracks of the file by id, written to `tref`.
+    pub references: Vec<TrackReference>,
 }
 
 impl Default for TrackConfig {
@@ -36,6 +50,9 @@ impl Default for TrackConfig {
             language: String::from("und"),
             media_conf: MediaConfig::AvcConfig(AvcConfig::default()),
             matrix: None,
+            samples_per_chunk: None,
+            duration_per_chunk: None,
+            references: Vec::new(),
         }
     }
 }
@@ -48,7 +65,11 @@ impl From<MediaConfig> for TrackConfig {
             MediaConfig::AacConfig(aac_conf) => Self::from(aac_conf),
             MediaConfig::TtxtConfig(ttxt_conf) => Self::from(ttxt_conf),
             MediaConfig::Vp9Config(vp9_config) => Self::from(vp9_config),
+            MediaConfig::Av1Config(av1_config) => Self::from(av1_config),
             MediaConfig::OpusConfig(opus_config) => Self::from(opus_config),
+            MediaConfig::Ac3Config(ac3_config) => Self::from(ac3_config),
+            MediaConfig::Ec3Config(ec3_config) => Self::from(ec3_config),
+            MediaConfig::FlacConfig(flac_config) => Self::from(flac_config),
         }
     }
 }
@@ -62,6 +83,9 @@ impl From<AvcConfig> for TrackConfig {
             language: String::from("und"), // XXX
             media_conf: MediaConfig::AvcConfig(avc_conf),
             matrix: None,
+            samples_per_chunk: None,
+            duration_per_chunk: None,
+            references: Vec::new(),
         }
     }
 }
@@ -75,6 +99,9 @@ impl From<HevcConfig> for TrackConfig {
             language: String::from("und"), // XXX
             media_conf: MediaConfig::HevcConfig(hevc_conf),
             matrix: None,
+            samples_per_chunk: None,
+            duration_per_chunk: None,
+            references: Vec::new(),
         }
     }
 }
@@ -88,6 +115,9 @@ impl From<AacConfig> for TrackConfig {
             language: String::from("und"), // XXX
             media_conf: MediaConfig::AacConfig(aac_conf),
             matrix: None,
+            samples_per_chunk: None,
+            duration_per_chunk: None,
+            references: Vec::new(),
         }
     }
 }
@@ -101,6 +131,9 @@ impl From<TtxtConfig> for TrackConfig {
             language: String::from("und"), // XXX
             media_conf: MediaConfig::TtxtConfig(txtt_conf),
             matrix: None,
+            samples_per_chunk: None,
+            duration_per_chunk: None,
+            references: Vec::new(),
         }
     }
 }
@@ -114,6 +147,25 @@ impl From<Vp9Config> for TrackConfig {
             language: String::from("und"), // XXX
             media_conf: MediaConfig::Vp9Config(vp9_conf),
             matrix: None,
+            samples_per_chunk: None,
+            duration_per_chunk: None,
+            references: Vec::new(),
+        }
+    }
+}
+
+impl From<Av1Config> for TrackConfig {
+    fn from(av1_conf: Av1Config) -> Self {
+        Self {
+            track_id: None,
+            track_type: TrackType::Video,
+            timescale: 1000,               // XXX
+            language: String::from("und"), // XXX
+            media_conf: MediaConfig::Av1Config(av1_conf),
+            matrix: None,
+            samples_per_chunk: None,
+            duration_per_chunk: None,
+            references: Vec::new(),
         }
     }
 }
@@ -127,10 +179,244 @@ impl From<OpusConfig> for TrackConfig {
             language: String::from("und"), // XXX
             media_conf: MediaConfig::OpusConfig(opus_conf),
             matrix: None,
+            samples_per_chunk: None,
+            duration_per_chunk: None,
+            references: Vec::new(),
+        }
+    }
+}
+
+impl From<Ac3Config> for TrackConfig {
+    fn from(ac3_conf: Ac3Config) -> Self {
+        Self {
+            track_id: None,
+            track_type: TrackType::Audio,
+            timescale: 1000,               // XXX
+            language: String::from("und"), // XXX
+            media_conf: MediaConfig::Ac3Config(ac3_conf),
+            matrix: None,
+            samples_per_chunk: None,
+            duration_per_chunk: None,
+            references: Vec::new(),
+        }
+    }
+}
+
+impl From<FlacConfig> for TrackConfig {
+    fn from(flac_conf: FlacConfig) -> Self {
+        Self {
+            track_id: None,
+            track_type: TrackType::Audio,
+            timescale: 1000,               // XXX
+            language: String::from("und"), // XXX
+            media_conf: MediaConfig::FlacConfig(flac_conf),
+            matrix: None,
+            samples_per_chunk: None,
+            duration_per_chunk: None,
+            references: Vec::new(),
         }
     }
 }
 
+impl From<Ec3Config> for TrackConfig {
+    fn from(ec3_conf: Ec3Config) -> Self {
+        Self {
+            track_id: None,
+            track_type: TrackType::Audio,
+            timescale: 1000,               // XXX
+            language: String::from("und"), // XXX
+            media_conf: MediaConfig::Ec3Config(ec3_conf),
+            matrix: None,
+            samples_per_chunk: None,
+            duration_per_chunk: None,
+            references: Vec::new(),
+        }
+    }
+}
+
+/// Location and timing of a single sample, resolved from the sample tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SampleIndexEntry {
+    pub(crate) offset: u64,
+    pub(crate) start_time: u64,
+    pub(crate) size: u32,
+    pub(crate) duration: u32,
+    pub(crate) rendering_offset: i32,
+    pub(crate) is_sync: bool,
+    pub(crate) sample_flags: Option<SampleFlags>,
+}
+
+/// Sample runs of a track, resolved once by [Mp4Track::build_sample_index]
+/// so that sample lookups no longer walk the tables.
+///
+/// It holds one entry per chunk, `trun` and `stts` or `ctts` entry, plus
+/// running totals of the sample sizes and durations that the file lists one
+/// by one, so it grows with the tables and not with their sample counts.
+/// Whatever a table gives directly, such as the sizes in `stsz` or the sync
+/// samples in `stss`, is still read from it.
+#[derive(Debug, Clone)]
+enum SampleIndex {
+    Stbl(StblIndex),
+    Fragments(FragmentIndex),
+}
+
+#[derive(Debug, Clone, Default)]
+struct StblIndex {
+    /// First sample and offset of each chunk.
+    chunks: Vec<(u32, u64)>,
+    /// First sample and decode time of each `stts` entry.
+    stts_runs: Vec<(u32, u64)>,
+    /// First sample of each `ctts` entry.
+    ctts_runs: Vec<u32>,
+    /// Running totals of the `stsz` sample sizes, empty if they are constant.
+    size_sums: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct FragmentIndex {
+    truns: Vec<TrunRun>,
+    size_sums: Vec<u64>,
+    duration_sums: Vec<u64>,
+}
+
+/// The samples of a `trun`, stored back to back.
+#[derive(Debug, Clone, Copy)]
+struct TrunRun {
+    first_sample: u32,
+    /// Position of the `trun` in `Mp4Track::trafs` and in its `traf`.
+    traf: usize,
+    trun: usize,
+    offset: u64,
+    start_time: u64,
+    sizes: RunValues,
+    durations: RunValues,
+    default_sample_flags: u32,
+}
+
+/// Sizes or durations of the samples of a run: the same for all of them, or
+/// listed in running totals from the given position on.
+#[derive(Debug, Clone, Copy)]
+enum RunValues {
+    Constant(u32),
+    Listed(usize),
+}
+
+impl RunValues {
+    /// Sum of the values of the first `n` samples of the run.
+    fn total(&self, sums: &[u64], n: u32) -> u64 {
+        match *self {
+            RunValues::Constant(value) => value as u64 * n as u64,
+            RunValues::Listed(start) => sums[start + n as usize] - sums[start],
+        }
+    }
+
+    fn get(&self, sums: &[u64], i: u32) -> u32 {
+        (self.total(sums, i + 1) - self.total(sums, i)) as u32
+    }
+}
+
+/// Append the running totals of `values`, from 0, to `sums` and return
+/// where they start.
+fn push_running_totals(sums: &mut Vec<u64>, values: &[u32]) -> usize {
+    let start = sums.len();
+    let mut total = 0;
+    sums.push(total);
+    for value in values.iter() {
+        total += *value as u64;
+        sums.push(total);
+    }
+    start
+}
+
+/// Position of the run holding `sample_id` among runs sorted by their first
+/// sample.
+fn run_index<T>(runs: &[T], sample_id: u32, first_sample: impl Fn(&T) -> u32) -> Option<usize> {
+    runs.partition_point(|run| first_sample(run) <= sample_id)
+        .checked_sub(1)
+}
+
+impl StblIndex {
+    fn sizes(stsz: &StszBox, first_sample: u32) -> RunValues {
+        if stsz.sample_size > 0 {
+            RunValues::Constant(stsz.sample_size)
+        } else {
+            RunValues::Listed(first_sample as usize - 1)
+        }
+    }
+
+    fn sample_offset(&self, stbl: &StblBox, sample_id: u32) -> Option<u64> {
+        let (first_sample, offset) =
+            self.chunks[run_index(&self.chunks, sample_id, |chunk| chunk.0)?];
+        let sizes = Self::sizes(&stbl.stsz, first_sample);
+        Some(offset + sizes.total(&self.size_sums, sample_id - first_sample))
+    }
+
+    fn sample_time(&self, stbl: &StblBox, sample_id: u32) -> Option<(u64, u32)> {
+        let i = run_index(&self.stts_runs, sample_id, |run| run.0)?;
+        let (first_sample, start_time) = self.stts_runs[i];
+        let sample_delta = stbl.stts.entries[i].sample_delta;
+        Some((
+            start_time + (sample_id - first_sample) as u64 * sample_delta as u64,
+            sample_delta,
+        ))
+    }
+
+    fn rendering_offset(&self, stbl: &StblBox, sample_id: u32) -> Option<i32> {
+        let i = run_index(&self.ctts_runs, sample_id, |first_sample| *first_sample)?;
+        Some(stbl.ctts.as_ref()?.entries[i].sample_offset)
+    }
+}
+
+impl FragmentIndex {
+    fn sample(&self, trafs: &[TrafBox], sample_id: u32) -> Option<SampleIndexEntry> {
+        let run = &self.truns[run_index(&self.truns, sample_id, |run| run.first_sample)?];
+        let trun = &trafs[run.traf].truns[run.trun];
+        let i = sample_id - run.first_sample;
+        if i >= trun.sample_count {
+            return None;
+        }
+        let (rendering_offset, sample_flags) =
+            trun_sample_flags(trun, i as usize, run.default_sample_flags);
+        Some(SampleIndexEntry {
+            offset: run.offset + run.sizes.total(&self.size_sums, i),
+            start_time: run.start_time + run.durations.total(&self.duration_sums, i),
+            size: run.sizes.get(&self.size_sums, i),
+            duration: run.durations.get(&self.duration_sums, i),
+            rendering_offset,
+            is_sync: sample_flags.map_or(true, |flags| flags.is_sync()),
+            sample_flags,
+        })
+    }
+}
+
+/// Per sample values a `trun` may leave out, from the `tfhd` of its `traf`
+/// or else from the track defaults.
+#[derive(Debug, Clone, Copy)]
+struct TrunDefaults {
+    base_data_offset: u64,
+    sample_duration: u32,
+    sample_size: u32,
+    sample_flags: u32,
+}
+
+/// Composition offset and flags of sample `i` of `trun`.
+fn trun_sample_flags(
+    trun: &TrunBox,
+    i: usize,
+    default_sample_flags: u32,
+) -> (i32, Option<SampleFlags>) {
+    let rendering_offset = trun.sample_cts.get(i).map_or(0, |cts| *cts as i32);
+    let sample_flags = if TrunBox::FLAG_SAMPLE_FLAGS & trun.flags != 0 {
+        trun.sample_flags.get(i).copied()
+    } else if i == 0 && trun.first_sample_flags.is_some() {
+        trun.first_sample_flags
+    } else {
+        Some(default_sample_flags)
+    }
+    .map(SampleFlags::from);
+    (rendering_offset, sample_flags)
+}
+
 #[derive(Debug)]
 pub struct Mp4Track {
     pub trak: TrakBox,
@@ -139,6 +425,10 @@ pub struct Mp4Track {
 
     // Fragmented Tracks Defaults.
     pub default_sample_duration: u32,
+    pub default_sample_size: u32,
+    pub default_sample_flags: u32,
+
+    sample_index: Option<SampleIndex>,
 }
 
 impl Mp4Track {
@@ -149,6 +439,395 @@ impl Mp4Track {
             trafs: Vec::new(),
             moof_offsets: Vec::new(),
             default_sample_duration: 0,
+            default_sample_size: 0,
+            default_sample_flags: 0,
+            sample_index: None,
+        }
+    }
+
+    /// Index the sample runs of the track, so that sample lookups no longer
+    /// walk the sample tables and reading every sample of a long file takes
+    /// linear time.
+    ///
+    /// This is left to the caller, see [Mp4Reader::build_sample_index]. The
+    /// index has to be built again if `trak` or `trafs` are modified
+    /// afterwards. If the tables are inconsistent no index is built and
+    /// lookups keep walking them.
+    pub fn build_sample_index(&mut self) {
+        self.sample_index = if self.trafs.is_empty() {
+            self.index_stbl().map(SampleIndex::Stbl)
+        } else {
+            self.index_fragments().map(SampleIndex::Fragments)
+        };
+    }
+
+    fn index_stbl(&self) -> Option<StblIndex> {
+        let stbl = &self.trak.mdia.minf.stbl;
+        let sample_count = stbl.stsz.sample_count as u64;
+        let chunk_offsets: Vec<u64> = if let Some(ref stco) = stbl.stco {
+            stco.entries.iter().map(|offset| *offset as u64).collect()
+        } else if let Some(ref co64) = stbl.co64 {
+            co64.entries.clone()
+        } else {
+            return None;
+        };
+        let mut index = StblIndex::default();
+        if stbl.stsz.sample_size == 0 {
+            let sample_sizes = stbl.stsz.sample_sizes.get(..sample_count as usize)?;
+            push_running_totals(&mut index.size_sums, sample_sizes);
+        }
+
+        let mut stsc_entries = stbl.stsc.entries.iter().peekable();
+        let mut samples_per_chunk = 0;
+        let mut first_sample = 1;
+        for (i, offset) in chunk_offsets.iter().enumerate() {
+            if first_sample > sample_count {
+                break;
+            }
+            let chunk_id = i as u32 + 1;
+            while let Some(entry) = stsc_entries.next_if(|entry| entry.first_chunk <= chunk_id) {
+                samples_per_chunk = entry.samples_per_chunk;
+            }
+            let samples = (samples_per_chunk as u64).min(sample_count + 1 - first_sample);
+            StblIndex::sizes(&stbl.stsz, first_sample as u32)
+                .total(&index.size_sums, samples as u32)
+                .checked_add(*offset)?;
+            index.chunks.push((first_sample as u32, *offset));
+            first_sample += samples;
+        }
+        if first_sample <= sample_count {
+            return None;
+        }
+
+        let mut first_sample = 1;
+        let mut start_time = 0u64;
+        for entry in stbl.stts.entries.iter() {
+            if first_sample > sample_count {
+                break;
+            }
+            index.stts_runs.push((first_sample as u32, start_time));
+            first_sample += entry.sample_count as u64;
+            start_time =
+                start_time.checked_add(entry.sample_count as u64 * entry.sample_delta as u64)?;
+        }
+        if first_sample <= sample_count {
+            return None;
+        }
+
+        if let Some(ref ctts) = stbl.ctts {
+            let mut first_sample = 1;
+            for entry in ctts.entries.iter() {
+                if first_sample > sample_count {
+                    break;
+                }
+                index.ctts_runs.push(first_sample as u32);
+                first_sample += entry.sample_count as u64;
+            }
+            if first_sample <= sample_count {
+                return None;
+            }
+        }
+        Some(index)
+    }
+
+    fn index_fragments(&self) -> Option<FragmentIndex> {
+        let mut index = FragmentIndex::default();
+        let mut first_sample = 1u64;
+        let mut start_time = 0u64;
+        for (i, (traf, moof_offset)) in self.trafs.iter().zip(self.moof_offsets.iter()).enumerate()
+        {
+            if let Some(ref tfdt) = traf.tfdt {
+                start_time = tfdt.base_media_decode_time;
+            }
+            let defaults = self.trun_defaults(traf, *moof_offset);
+
+            let mut offset = defaults.base_data_offset;
+            for (j, trun) in traf.truns.iter().enumerate() {
+                if let Some(data_offset) = trun.data_offset {
+                    offset = defaults
+                        .base_data_offset
+                        .checked_add_signed(data_offset as i64)?;
+                }
+                let sample_count = trun.sample_count as usize;
+                let sizes = if TrunBox::FLAG_SAMPLE_SIZE & trun.flags != 0 {
+                    let sample_sizes = trun.sample_sizes.get(..sample_count)?;
+                    RunValues::Listed(push_running_totals(&mut index.size_sums, sample_sizes))
+                } else {
+                    RunValues::Constant(defaults.sample_size)
+                };
+                let durations = if TrunBox::FLAG_SAMPLE_DURATION & trun.flags != 0 {
+                    let sample_durations = trun.sample_durations.get(..sample_count)?;
+                    RunValues::Listed(push_running_totals(
+                        &mut index.duration_sums,
+                        sample_durations,
+                    ))
+                } else {
+                    RunValues::Constant(defaults.sample_duration)
+                };
+
+                index.truns.push(TrunRun {
+                    first_sample: u32::try_from(first_sample).ok()?,
+                    traf: i,
+                    trun: j,
+                    offset,
+                    start_time,
+                    sizes,
+                    durations,
+                    default_sample_flags: defaults.sample_flags,
+                });
+                offset = offset.checked_add(sizes.total(&index.size_sums, trun.sample_count))?;
+                start_time = start_time
+                    .checked_add(durations.total(&index.duration_sums, trun.sample_count))?;
+                first_sample += trun.sample_count as u64;
+            }
+        }
+        Some(index)
+    }
+
+    /// Resolve every sample of the sample tables, in order.
+    pub(crate) fn index_stbl_samples(&self) -> Option<Vec<SampleIndexEntry>> {
+        let stbl = &self.trak.mdia.minf.stbl;
+        let sample_count = stbl.stsz.sample_count as usize;
+        let chunk_offsets: Vec<u64> = if let Some(ref stco) = stbl.stco {
+            stco.entries.iter().map(|offset| *offset as u64).collect()
+        } else if let Some(ref co64) = stbl.co64 {
+            co64.entries.clone()
+        } else {
+            return None;
+        };
+
+        let mut durations = stbl.stts.entries.iter().flat_map(|entry| {
+            std::iter::repeat(entry.sample_delta).take(entry.sample_count as usize)
+        });
+        let mut rendering_offsets = stbl.ctts.as_ref().map(|ctts| {
+            ctts.entries.iter().flat_map(|entry| {
+                std::iter::repeat(entry.sample_offset).take(entry.sample_count as usize)
+            })
+        });
+        let mut sync_samples = stbl
+            .stss
+            .as_ref()
+            .map(|stss| stss.entries.iter().peekable());
+
+        let mut samples = Vec::new();
+        let mut stsc_entries = stbl.stsc.entries.iter().peekable();
+        let mut samples_per_chunk = 0;
+        let mut start_time = 0u64;
+        for (i, chunk_offset) in chunk_offsets.iter().enumerate() {
+            let chunk_id = i as u32 + 1;
+            while let Some(entry) = stsc_entries.next_if(|entry| entry.first_chunk <= chunk_id) {
+                samples_per_chunk = entry.samples_per_chunk;
+            }
+
+            let mut offset = *chunk_offset;
+            for _ in 0..samples_per_chunk {
+                if samples.len() == sample_count {
+                    break;
+                }
+                let sample_id = samples.len() as u32 + 1;
+                let size = if stbl.stsz.sample_size > 0 {
+                    stbl.stsz.sample_size
+                } else {
+                    *stbl.stsz.sample_sizes.get(samples.len())?
+                };
+                let duration = durations.next()?;
+                let rendering_offset = match rendering_offsets {
+                    Some(ref mut rendering_offsets) => rendering_offsets.next()?,
+                    None => 0,
+                };
+                let is_sync = match sync_samples {
+                    Some(ref mut sync_samples) => sync_samples.next_if_eq(&&sample_id).is_some(),
+                    None => true,
+                };
+
+                samples.push(SampleIndexEntry {
+                    offset,
+                    start_time,
+                    size,
+                    duration,
+                    rendering_offset,
+                    is_sync,
+                    sample_flags: None,
+                });
+                offset = offset.checked_add(size as u64)?;
+                start_time = start_time.checked_add(duration as u64)?;
+            }
+        }
+
+        if samples.len() == sample_count {
+            Some(samples)
+        } else {
+            None
+        }
+    }
+
+    /// Resolve the samples of `trafs`, fragments of this track found in the
+    /// `moof` box at `moof_offset`. The first sample is numbered
+    /// `first_sample_id` and starts at `start_time` unless a `tfdt` says
+    /// otherwise.
+    pub(crate) fn index_traf_samples(
+        &self,
+        trafs: &[&TrafBox],
+        moof_offset: u64,
+        first_sample_id: u32,
+        start_time: u64,
+    ) -> Result<Vec<SampleIndexEntry>> {
+        let mut samples = Vec::new();
+        self.walk_trafs(
+            trafs.iter().map(|traf| (*traf, moof_offset)),
+            first_sample_id,
+            start_time,
+            |entry| {
+                samples.push(entry);
+                true
+            },
+        )?;
+        Ok(samples)
+    }
+
+    fn trun_defaults(&self, traf: &TrafBox, moof_offset: u64) -> TrunDefaults {
+        TrunDefaults {
+            base_data_offset: traf.tfhd.base_data_offset.unwrap_or(moof_offset),
+            sample_duration: traf
+                .tfhd
+                .default_sample_duration
+                .unwrap_or(self.default_sample_duration),
+            sample_size: traf
+                .tfhd
+                .default_sample_size
+                .unwrap_or(self.default_sample_size),
+            sample_flags: traf
+                .tfhd
+                .default_sample_flags
+                .unwrap_or(self.default_sample_flags),
+        }
+    }
+
+    fn walk_fragment_samples<F>(&self, f: F) -> Result<()>
+    where
+        F: FnMut(SampleIndexEntry) -> bool,
+    {
+        let trafs = self.trafs.iter().zip(self.moof_offsets.iter().copied());
+        self.walk_trafs(trafs, 1, 0, f)
+    }
+
+    /// Resolve the samples of every `trun` in order, numbered from
+    /// `first_sample_id`, until `f` returns false. Per sample values missing
+    /// from a `trun` fall back to the defaults of its `tfhd`, then to the
+    /// track defaults from `trex`.
+    fn walk_trafs<'a, I, F>(
+        &self,
+        trafs: I,
+        first_sample_id: u32,
+        mut start_time: u64,
+        mut f: F,
+    ) -> Result<()>
+    where
+        I: IntoIterator<Item = (&'a TrafBox, u64)>,
+        F: FnMut(SampleIndexEntry) -> bool,
+    {
+        let mut sample_id = first_sample_id;
+        for (traf, moof_offset) in trafs {
+            if let Some(ref tfdt) = traf.tfdt {
+                start_time = tfdt.base_media_decode_time;
+            }
+            let defaults = self.trun_defaults(traf, moof_offset);
+
+            // A trun without a data offset follows the data of the previous one.
+            let mut offset = defaults.base_data_offset;
+            for trun in traf.truns.iter() {
+                if let Some(data_offset) = trun.data_offset {
+                    offset = defaults
+                        .base_data_offset
+                        .checked_add_signed(data_offset as i64)
+                        .ok_or(Error::InvalidData(
+                            "attempt to calculate trun sample offset with overflow",
+                        ))?;
+                }
+
+                for i in 0..trun.sample_count as usize {
+                    let size = if TrunBox::FLAG_SAMPLE_SIZE & trun.flags != 0 {
+                        trun.sample_sizes.get(i).copied()
+                    } else {
+                        Some(defaults.sample_size)
+                    };
+                    let duration = if TrunBox::FLAG_SAMPLE_DURATION & trun.flags != 0 {
+                        trun.sample_durations.get(i).copied()
+                    } else {
+                        Some(defaults.sample_duration)
+                    };
+                    let (size, duration) = match (size, duration) {
+                        (Some(size), Some(duration)) => (size, duration),
+                        _ => {
+                            return Err(Error::EntryInTrunNotFound(
+                                self.track_id(),
+                                BoxType::TrunBox,
+                                sample_id,
+                            ))
+                        }
+                    };
+                    let (rendering_offset, sample_flags) =
+                        trun_sample_flags(trun, i, defaults.sample_flags);
+
+                    let entry = SampleIndexEntry {
+                        offset,
+                        start_time,
+                        size,
+                        duration,
+                        rendering_offset,
+                        is_sync: sample_flags.map_or(true, |flags| flags.is_sync()),
+                        sample_flags,
+                    };
+                    if !f(entry) {
+                        return Ok(());
+                    }
+
+                    offset = offset.checked_add(size as u64).ok_or(Error::InvalidData(
+                        "attempt to calculate trun entry sample offset with overflow",
+                    ))?;
+                    start_time =
+                        start_time
+                            .checked_add(duration as u64)
+                            .ok_or(Error::InvalidData(
+                                "attempt to sum sample durations with overflow",
+                            ))?;
+                    sample_id += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn fragment_sample(&self, sample_id: u32) -> Result<SampleIndexEntry> {
+        if sample_id == 0 {
+            return Err(Error::BoxInTrafNotFound(self.track_id(), BoxType::TrafBox));
+        }
+        if let Some(SampleIndex::Fragments(ref index)) = self.sample_index {
+            return index
+                .sample(&self.trafs, sample_id)
+                .ok_or(Error::BoxInTrafNotFound(self.track_id(), BoxType::TrafBox));
+        }
+        let mut remaining = sample_id;
+        let mut sample = None;
+        self.walk_fragment_samples(|entry| {
+            remaining -= 1;
+            if remaining == 0 {
+                sample = Some(entry);
+            }
+            remaining > 0
+        })?;
+        sample.ok_or(Error::BoxInTrafNotFound(self.track_id(), BoxType::TrafBox))
+    }
+
+    /// The index of a track with sample tables, if it covers `sample_id`.
+    fn stbl_index(&self, sample_id: u32) -> Option<&StblIndex> {
+        match self.sample_index {
+            Some(SampleIndex::Stbl(ref index))
+                if sample_id <= self.trak.mdia.minf.stbl.stsz.sample_count =>
+            {
+                Some(index)
+            }
+            _ => None,
         }
     }
 
@@ -156,6 +835,14 @@ impl Mp4Track {
         self.trak.tkhd.track_id
     }
 
+    /// References from this track to other tracks, e.g. to its chapter track.
+    pub fn references(&self) -> &[TrackReference] {
+        match self.trak.tref {
+            Some(ref tref) => &tref.references,
+            None => &[],
+        }
+    }
+
     pub fn track_type(&self) -> Result<TrackType> {
         TrackType::try_from(&self.trak.mdia.hdlr.handler_type)
     }
@@ -169,18 +856,33 @@ impl Mp4Track {
             Ok(MediaType::H265)
         } else if self.trak.mdia.minf.stbl.stsd.vp09.is_some() {
             Ok(MediaType::VP9)
+        } else if self.trak.mdia.minf.stbl.stsd.av01.is_some() {
+            Ok(MediaType::AV1)
         } else if self.trak.mdia.minf.stbl.stsd.mp4a.is_some() {
             Ok(MediaType::AAC)
         } else if self.trak.mdia.minf.stbl.stsd.tx3g.is_some() {
             Ok(MediaType::TTXT)
         } else if self.trak.mdia.minf.stbl.stsd.opus.is_some() {
             Ok(MediaType::OPUS)
+        } else if self.trak.mdia.minf.stbl.stsd.ac3.is_some() {
+            Ok(MediaType::AC3)
+        } else if self.trak.mdia.minf.stbl.stsd.ec3.is_some() {
+            Ok(MediaType::EAC3)
+        } else if self.trak.mdia.minf.stbl.stsd.flac.is_some() {
+            Ok(MediaType::FLAC)
         } else {
             Err(Error::InvalidData("unsupported media type"))
         }
     }
 
+    /// Type of the sample entry as stored, `encv` or `enca` for encrypted
+    /// tracks. See [Self::original_format] for the type before encryption.
     pub fn box_type(&self) -> Result<FourCC> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        if stsd.sinf.is_some() && stsd.has_entry() {
+            return Ok(FourCC::from(stsd.protected_type()));
+        }
+
         if self.trak.mdia.minf.stbl.stsd.avc1.is_some() {
             Ok(FourCC::from(BoxType::Avc1Box))
         } else if self.trak.mdia.minf.stbl.stsd.hev1.is_some() {
@@ -189,12 +891,20 @@ impl Mp4Track {
             Ok(FourCC::from(BoxType::Hvc1Box))
         } else if self.trak.mdia.minf.stbl.stsd.vp09.is_some() {
             Ok(FourCC::from(BoxType::Vp09Box))
+        } else if self.trak.mdia.minf.stbl.stsd.av01.is_some() {
+            Ok(FourCC::from(BoxType::Av01Box))
         } else if self.trak.mdia.minf.stbl.stsd.mp4a.is_some() {
             Ok(FourCC::from(BoxType::Mp4aBox))
         } else if self.trak.mdia.minf.stbl.stsd.tx3g.is_some() {
             Ok(FourCC::from(BoxType::Tx3gBox))
         } else if self.trak.mdia.minf.stbl.stsd.opus.is_some() {
             Ok(FourCC::from(BoxType::OpusBox))
+        } else if self.trak.mdia.minf.stbl.stsd.ac3.is_some() {
+            Ok(FourCC::from(BoxType::Ac3Box))
+        } else if self.trak.mdia.minf.stbl.stsd.ec3.is_some() {
+            Ok(FourCC::from(BoxType::Ec3Box))
+        } else if self.trak.mdia.minf.stbl.stsd.flac.is_some() {
+            Ok(FourCC::from(BoxType::FlacBox))
         } else {
             Err(Error::InvalidData("unsupported sample entry box"))
         }
@@ -207,6 +917,8 @@ impl Mp4Track {
             hev1.width
         } else if let Some(ref hvc1) = self.trak.mdia.minf.stbl.stsd.hvc1 {
             hvc1.width
+        } else if let Some(ref av01) = self.trak.mdia.minf.stbl.stsd.av01 {
+            av01.width
         } else {
             self.trak.tkhd.width.value()
         }
@@ -219,6 +931,8 @@ impl Mp4Track {
             hev1.height
         } else if let Some(ref hvc1) = self.trak.mdia.minf.stbl.stsd.hvc1 {
             hvc1.height
+        } else if let Some(ref av01) = self.trak.mdia.minf.stbl.stsd.av01 {
+            av01.height
         } else {
             self.trak.tkhd.height.value()
         }
@@ -252,6 +966,12 @@ impl Mp4Track {
                     vec![BoxType::DopsBox],
                 ))
             }
+        } else if let Some(ref ac3) = self.trak.mdia.minf.stbl.stsd.ac3 {
+            SampleFreqIndex::try_from(ac3.dac3.sample_rate()?)
+        } else if let Some(ref ec3) = self.trak.mdia.minf.stbl.stsd.ec3 {
+            SampleFreqIndex::try_from(ec3.dec3.sample_rate()?)
+        } else if let Some(ref flac) = self.trak.mdia.minf.stbl.stsd.flac {
+            SampleFreqIndex::try_from(flac.dfla.stream_info.sample_rate)
         } else {
             Err(Error::BoxInStblNotFound(
                 self.track_id(),
@@ -279,6 +999,74 @@ impl Mp4Track {
                     vec![BoxType::DopsBox],
                 ))
             }
+        } else if let Some(ref ac3) = self.trak.mdia.minf.stbl.stsd.ac3 {
+            ac3.dac3.channel_config()
+        } else if let Some(ref ec3) = self.trak.mdia.minf.stbl.stsd.ec3 {
+            ec3.dec3.channel_config()
+        } else if let Some(ref flac) = self.trak.mdia.minf.stbl.stsd.flac {
+            flac.dfla.channel_config()
+        } else {
+            Err(Error::BoxInStblNotFound(
+                self.track_id(),
+                vec![BoxType::Mp4aBox],
+            ))
+        }
+    }
+
+    /// Sample rate in Hz. Unlike [`Self::sample_freq_index`], this covers
+    /// rates outside the AAC table, such as 192 kHz FLAC.
+    pub fn sample_rate(&self) -> Result<u32> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        if let Some(ref flac) = stsd.flac {
+            Ok(flac.dfla.stream_info.sample_rate)
+        } else if let Some(ref ac3) = stsd.ac3 {
+            ac3.dac3.sample_rate()
+        } else if let Some(ref ec3) = stsd.ec3 {
+            ec3.dec3.sample_rate()
+        } else if let Some(ref opus) = stsd.opus {
+            Ok(opus.sample_rate.value() as u32)
+        } else if stsd.mp4a.is_some() {
+            Ok(self.sample_freq_index()?.freq())
+        } else {
+            Err(Error::BoxInStblNotFound(
+                self.track_id(),
+                vec![BoxType::Mp4aBox],
+            ))
+        }
+    }
+
+    pub fn channel_count(&self) -> Result<u16> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        if let Some(ref flac) = stsd.flac {
+            Ok(flac.dfla.stream_info.channels as u16)
+        } else if let Some(ref ac3) = stsd.ac3 {
+            Ok(ac3.dac3.channel_count())
+        } else if let Some(ref ec3) = stsd.ec3 {
+            Ok(ec3.dec3.channel_count())
+        } else if let Some(ref opus) = stsd.opus {
+            Ok(opus.channel_count)
+        } else if let Some(ref mp4a) = stsd.mp4a {
+            Ok(mp4a.channelcount)
+        } else {
+            Err(Error::BoxInStblNotFound(
+                self.track_id(),
+                vec![BoxType::Mp4aBox],
+            ))
+        }
+    }
+
+    pub fn bits_per_sample(&self) -> Result<u16> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        if let Some(ref flac) = stsd.flac {
+            Ok(flac.dfla.stream_info.bits_per_sample as u16)
+        } else if let Some(ref ac3) = stsd.ac3 {
+            Ok(ac3.sample_size)
+        } else if let Some(ref ec3) = stsd.ec3 {
+            Ok(ec3.sample_size)
+        } else if let Some(ref opus) = stsd.opus {
+            Ok(opus.sample_size)
+        } else if let Some(ref mp4a) = stsd.mp4a {
+            Ok(mp4a.samplesize)
         } else {
             Err(Error::BoxInStblNotFound(
                 self.track_id(),
@@ -309,6 +1097,10 @@ impl Mp4Track {
                 0
             }
             // mp4a.esds.es_desc.dec_config.avg_bitrate
+        } else if let Some(ref ac3) = self.trak.mdia.minf.stbl.stsd.ac3 {
+            ac3.dac3.bitrate().unwrap_or(0)
+        } else if let Some(ref ec3) = self.trak.mdia.minf.stbl.stsd.ec3 {
+            ec3.dec3.bitrate()
         } else {
             let dur = self.duration();
             if dur.is_zero() {
@@ -323,12 +1115,10 @@ impl Mp4Track {
     pub fn sample_count(&self) -> u32 {
         if !self.trafs.is_empty() {
             let mut sample_count = 0u32;
-            for traf in self.trafs.iter() {
-                if let Some(ref trun) = traf.trun {
-                    sample_count = sample_count
-                        .checked_add(trun.sample_count)
-                        .expect("attempt to sum trun sample_count with overflow");
-                }
+            for trun in self.trafs.iter().flat_map(|traf| traf.truns.iter()) {
+                sample_count = sample_count
+                    .checked_add(trun.sample_count)
+                    .expect("attempt to sum trun sample_count with overflow");
             }
             sample_count
         } else {
@@ -444,6 +1234,73 @@ impl Mp4Track {
         }
     }
 
+    /// NAL unit format and length size of an `avc1`, `hev1` or `hvc1` track.
+    pub fn nal_format(&self) -> Result<(NalFormat, usize)> {
+        nal::sample_entry_format(&self.trak.mdia.minf.stbl.stsd).ok_or_else(|| {
+            Error::BoxInStblNotFound(
+                self.track_id(),
+                vec![BoxType::Avc1Box, BoxType::Hev1Box, BoxType::Hvc1Box],
+            )
+        })
+    }
+
+    /// Parameter sets of the sample entry, VPS first, then SPS and PPS.
+    pub fn parameter_sets(&self) -> Result<Vec<&[u8]>> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        let hvcc = if let Some(ref avc1) = stsd.avc1 {
+            let avcc = &avc1.avcc;
+            return Ok(avcc
+                .sequence_parameter_sets
+                .iter()
+                .chain(avcc.picture_parameter_sets.iter())
+                .map(|nal| nal.bytes.as_ref())
+                .collect());
+        } else if let Some(ref hev1) = stsd.hev1 {
+            &hev1.hvcc
+        } else if let Some(ref hvc1) = stsd.hvc1 {
+            &hvc1.hvcc
+        } else {
+            return Err(Error::BoxInStblNotFound(
+                self.track_id(),
+                vec![BoxType::Avc1Box, BoxType::Hev1Box, BoxType::Hvc1Box],
+            ));
+        };
+        let mut arrays: Vec<&HvcCArray> = hvcc
+            .arrays
+            .iter()
+            .filter(|array| (32..=34).contains(&array.nal_unit_type))
+            .collect();
+        arrays.sort_by_key(|array| array.nal_unit_type);
+        Ok(arrays
+            .iter()
+            .flat_map(|array| array.nalus.iter().map(|nalu| nalu.data.as_ref()))
+            .collect())
+    }
+
+    /// NAL units of a sample of this track.
+    pub fn nal_units<'a>(&self, sample: &'a Mp4Sample) -> Result<NalUnits<'a>> {
+        let (_, length_size) = self.nal_format()?;
+        NalUnits::new(&sample.bytes, length_size)
+    }
+
+    /// Convert a sample of this track to an Annex-B access unit, with the
+    /// parameter sets of the sample entry before IDR pictures.
+    pub fn sample_to_annexb(&self, sample: &Mp4Sample) -> Result<Vec<u8>> {
+        let (format, length_size) = self.nal_format()?;
+        nal::to_annexb_with_parameter_sets(
+            &sample.bytes,
+            length_size,
+            format,
+            &self.parameter_sets()?,
+        )
+    }
+
+    /// Convert an Annex-B access unit to the sample data of this track.
+    pub fn sample_from_annexb(&self, data: &[u8]) -> Result<Bytes> {
+        let (_, length_size) = self.nal_format()?;
+        Ok(Bytes::from(nal::from_annexb(data, length_size)?))
+    }
+
     pub fn audio_profile(&self) -> Result<AudioObjectType> {
         if let Some(ref mp4a) = self.trak.mdia.minf.stbl.stsd.mp4a {
             if let Some(ref esds) = mp4a.esds {
@@ -462,6 +1319,94 @@ impl Mp4Track {
         }
     }
 
+    pub fn is_encrypted(&self) -> bool {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        stsd.sinf.is_some() && stsd.has_entry()
+    }
+
+    /// Sample entry type before encryption, from `frma`.
+    pub fn original_format(&self) -> Option<FourCC> {
+        let sinf = self.trak.mdia.minf.stbl.stsd.sinf.as_ref()?;
+        Some(sinf.frma.original_format)
+    }
+
+    /// Protection scheme from `schm`, such as `cenc` or `cbcs`.
+    pub fn scheme_type(&self) -> Option<FourCC> {
+        let sinf = self.trak.mdia.minf.stbl.stsd.sinf.as_ref()?;
+        sinf.schm.as_ref().map(|schm| schm.scheme_type)
+    }
+
+    pub fn tenc(&self) -> Option<&TencBox> {
+        self.trak.mdia.minf.stbl.stsd.sinf.as_ref()?.tenc()
+    }
+
+    pub fn default_kid(&self) -> Option<[u8; 16]> {
+        self.tenc().map(|tenc| tenc.default_kid)
+    }
+
+    pub fn default_per_sample_iv_size(&self) -> Option<u8> {
+        self.tenc().map(|tenc| tenc.default_per_sample_iv_size)
+    }
+
+    /// Find the sample presented at `time`, measured on the media timeline
+    /// of the track (edit lists are not applied). Returns `None` if `time`
+    /// is past the last sample.
+    pub fn sample_at_time(&self, time: Duration) -> Result<Option<u32>> {
+        let sample_count = self.sample_count();
+        if sample_count == 0 {
+            return Ok(None);
+        }
+        let time = self.media_ticks(time);
+
+        // Decode times increase with the sample id, so find the last sample
+        // decoded at or before `time`.
+        let (mut lo, mut hi) = (1, sample_count);
+        while lo < hi {
+            let mid = lo + (hi - lo).div_ceil(2);
+            if self.sample_time(mid)?.0 <= time {
+                lo = mid;
+            } else {
+                hi = mid - 1;
+            }
+        }
+        let decoded = lo;
+
+        // Composition offsets reorder samples between sync samples, so look
+        // for the sample presented at `time` among those sharing a sync
+        // sample with the one decoded at `time`.
+        let first = self.sync_sample_at_or_before(decoded);
+        let last = self
+            .next_sync_sample_after(decoded)
+            .map(|sample_id| sample_id - 1)
+            .unwrap_or(sample_count);
+        for sample_id in first..=last {
+            let (start_time, duration) = self.sample_time(sample_id)?;
+            let start_time = start_time as i64 + self.sample_rendering_offset(sample_id) as i64;
+            if start_time <= time as i64 && (time as i64) < start_time + duration as i64 {
+                return Ok(Some(sample_id));
+            }
+        }
+
+        let (start_time, duration) = self.sample_time(decoded)?;
+        if decoded == sample_count && time >= start_time + duration as u64 {
+            Ok(None)
+        } else {
+            Ok(Some(decoded))
+        }
+    }
+
+    /// Find the sample to start decoding from to present `time`: the last
+    /// sync sample at or before the sample presented at `time`, or the last
+    /// sync sample of the track if `time` is past the end.
+    pub fn nearest_sync_sample_before(&self, time: Duration) -> Result<Option<u32>> {
+        let sample_id = match self.sample_at_time(time)? {
+            Some(sample_id) => sample_id,
+            None if self.sample_count() > 0 => self.sample_count(),
+            None => return Ok(None),
+        };
+        Ok(Some(self.sync_sample_at_or_before(sample_id)))
+    }
+
     fn stsc_index(&self, sample_id: u32) -> Result<usize> {
         if self.trak.mdia.minf.stbl.stsc.entries.is_empty() {
             return Err(Error::InvalidData("no stsc entries"));
@@ -529,45 +1474,9 @@ impl Mp4Track {
         ))
     }
 
-    /// return `(traf_idx, sample_idx_in_trun)`
-    fn find_traf_idx_and_sample_idx(&self, sample_id: u32) -> Option<(usize, usize)> {
-        let global_idx = sample_id - 1;
-        let mut offset = 0;
-        for traf_idx in 0..self.trafs.len() {
-            if let Some(trun) = &self.trafs[traf_idx].trun {
-                let sample_count = trun.sample_count;
-                if sample_count > (global_idx - offset) {
-                    return Some((traf_idx, (global_idx - offset) as _));
-                }
-                offset = offset
-                    .checked_add(sample_count)
-                    .expect("attempt to sum trun sample_count with overflow");
-            }
-        }
-        None
-    }
-
     fn sample_size(&self, sample_id: u32) -> Result<u32> {
         if !self.trafs.is_empty() {
-            if let Some((traf_idx, sample_idx)) = self.find_traf_idx_and_sample_idx(sample_id) {
-                if let Some(size) = self.trafs[traf_idx]
-                    .trun
-                    .as_ref()
-                    .unwrap()
-                    .sample_sizes
-                    .get(sample_idx)
-                {
-                    Ok(*size)
-                } else {
-                    Err(Error::EntryInTrunNotFound(
-                        self.track_id(),
-                        BoxType::TrunBox,
-                        sample_id,
-                    ))
-                }
-            } else {
-                Err(Error::BoxInTrafNotFound(self.track_id(), BoxType::TrafBox))
-            }
+            Ok(self.fragment_sample(sample_id)?.size)
         } else {
             let stsz = &self.trak.mdia.minf.stbl.stsz;
             if stsz.sample_size > 0 {
@@ -599,36 +1508,15 @@ impl Mp4Track {
     }
 
     pub fn sample_offset(&self, sample_id: u32) -> Result<u64> {
+        let stbl = &self.trak.mdia.minf.stbl;
+        if let Some(offset) = self
+            .stbl_index(sample_id)
+            .and_then(|index| index.sample_offset(stbl, sample_id))
+        {
+            return Ok(offset);
+        }
         if !self.trafs.is_empty() {
-            if let Some((traf_idx, sample_idx)) = self.find_traf_idx_and_sample_idx(sample_id) {
-                let mut sample_offset = self.trafs[traf_idx]
-                    .tfhd
-                    .base_data_offset
-                    .unwrap_or(self.moof_offsets[traf_idx]);
-
-                if let Some(data_offset) = self.trafs[traf_idx]
-                    .trun
-                    .as_ref()
-                    .and_then(|trun| trun.data_offset)
-                {
-                    sample_offset = sample_offset.checked_add_signed(data_offset as i64).ok_or(
-                        Error::InvalidData("attempt to calculate trun sample offset with overflow"),
-                    )?;
-                }
-
-                let first_sample_in_trun = sample_id - sample_idx as u32;
-                for i in first_sample_in_trun..sample_id {
-                    sample_offset = sample_offset
-                        .checked_add(self.sample_size(i)? as u64)
-                        .ok_or(Error::InvalidData(
-                            "attempt to calculate trun entry sample offset with overflow",
-                        ))?;
-                }
-
-                Ok(sample_offset)
-            } else {
-                Err(Error::BoxInTrafNotFound(self.track_id(), BoxType::TrafBox))
-            }
+            Ok(self.fragment_sample(sample_id)?.offset)
         } else {
             let stsc_index = self.stsc_index(sample_id)?;
 
@@ -661,32 +1549,16 @@ impl Mp4Track {
     }
 
     fn sample_time(&self, sample_id: u32) -> Result<(u64, u32)> {
+        let stbl = &self.trak.mdia.minf.stbl;
+        if let Some(time) = self
+            .stbl_index(sample_id)
+            .and_then(|index| index.sample_time(stbl, sample_id))
+        {
+            return Ok(time);
+        }
         if !self.trafs.is_empty() {
-            let mut base_start_time = 0;
-            let mut default_sample_duration = self.default_sample_duration;
-            if let Some((traf_idx, sample_idx)) = self.find_traf_idx_and_sample_idx(sample_id) {
-                let traf = &self.trafs[traf_idx];
-                if let Some(tfdt) = &traf.tfdt {
-                    base_start_time = tfdt.base_media_decode_time;
-                }
-                if let Some(duration) = traf.tfhd.default_sample_duration {
-                    default_sample_duration = duration;
-                }
-                if let Some(trun) = &traf.trun {
-                    if TrunBox::FLAG_SAMPLE_DURATION & trun.flags != 0 {
-                        let mut start_offset = 0u64;
-                        for duration in &trun.sample_durations[..sample_idx] {
-                            start_offset = start_offset.checked_add(*duration as u64).ok_or(
-                                Error::InvalidData("attempt to sum sample durations with overflow"),
-                            )?;
-                        }
-                        let duration = trun.sample_durations[sample_idx];
-                        return Ok((base_start_time + start_offset, duration));
-                    }
-                }
-            }
-            let start_offset = ((sample_id - 1) * default_sample_duration) as u64;
-            Ok((base_start_time + start_offset, default_sample_duration))
+            let sample = self.fragment_sample(sample_id)?;
+            Ok((sample.start_time, sample.duration))
         } else {
             let stts = &self.trak.mdia.minf.stbl.stts;
 
@@ -719,15 +1591,16 @@ impl Mp4Track {
     }
 
     fn sample_rendering_offset(&self, sample_id: u32) -> i32 {
+        let stbl = &self.trak.mdia.minf.stbl;
+        if let Some(rendering_offset) = self
+            .stbl_index(sample_id)
+            .and_then(|index| index.rendering_offset(stbl, sample_id))
+        {
+            return rendering_offset;
+        }
         if !self.trafs.is_empty() {
-            if let Some((traf_idx, sample_idx)) = self.find_traf_idx_and_sample_idx(sample_id) {
-                if let Some(cts) = self.trafs[traf_idx]
-                    .trun
-                    .as_ref()
-                    .and_then(|trun| trun.sample_cts.get(sample_idx))
-                {
-                    return *cts as i32;
-                }
+            if let Ok(sample) = self.fragment_sample(sample_id) {
+                return sample.rendering_offset;
             }
         } else if let Some(ref ctts) = self.trak.mdia.minf.stbl.ctts {
             if let Ok((ctts_index, _)) = self.ctts_index(sample_id) {
@@ -740,8 +1613,9 @@ impl Mp4Track {
 
     fn is_sync_sample(&self, sample_id: u32) -> bool {
         if !self.trafs.is_empty() {
-            let sample_sizes_count = self.sample_count() / self.trafs.len() as u32;
-            return sample_id == 1 || sample_id % sample_sizes_count == 0;
+            return self
+                .fragment_sample(sample_id)
+                .is_ok_and(|sample| sample.is_sync);
         }
 
         if let Some(ref stss) = self.trak.mdia.minf.stbl.stss {
@@ -751,16 +1625,72 @@ impl Mp4Track {
         }
     }
 
+    fn media_ticks(&self, time: Duration) -> u64 {
+        (time.as_micros() * self.timescale() as u128 / 1_000_000) as u64
+    }
+
+    /// Last sync sample at or before `sample_id`, or the first sample if
+    /// there is none.
+    fn sync_sample_at_or_before(&self, sample_id: u32) -> u32 {
+        if !self.trafs.is_empty() {
+            return (1..=sample_id)
+                .rev()
+                .find(|sample_id| self.is_sync_sample(*sample_id))
+                .unwrap_or(1);
+        }
+
+        if let Some(ref stss) = self.trak.mdia.minf.stbl.stss {
+            match stss.entries.binary_search(&sample_id) {
+                Ok(_) => sample_id,
+                Err(0) => 1,
+                Err(i) => stss.entries[i - 1],
+            }
+        } else {
+            sample_id
+        }
+    }
+
+    fn next_sync_sample_after(&self, sample_id: u32) -> Option<u32> {
+        if !self.trafs.is_empty() {
+            return (sample_id + 1..=self.sample_count())
+                .find(|sample_id| self.is_sync_sample(*sample_id));
+        }
+
+        if let Some(ref stss) = self.trak.mdia.minf.stbl.stss {
+            let i = match stss.entries.binary_search(&sample_id) {
+                Ok(i) => i + 1,
+                Err(i) => i,
+            };
+            stss.entries.get(i).copied()
+        } else if sample_id < self.sample_count() {
+            Some(sample_id + 1)
+        } else {
+            None
+        }
+    }
+
+    fn sample_flags(&self, sample_id: u32) -> Option<SampleFlags> {
+        if !self.trafs.is_empty() {
+            return self
+                .fragment_sample(sample_id)
+                .ok()
+                .and_then(|sample| sample.sample_flags);
+        }
+        None
+    }
+
     pub(crate) fn read_sample_metadata(&self, sample_id: u32) -> Result<Option<Mp4SampleMetadata>> {
         let (start_time, duration) = self.sample_time(sample_id).unwrap();
         let rendering_offset = self.sample_rendering_offset(sample_id);
         let is_sync = self.is_sync_sample(sample_id);
+        let sample_flags = self.sample_flags(sample_id);
 
         Ok(Some(Mp4SampleMetadata {
             start_time,
             duration,
             rendering_offset,
             is_sync,
+            sample_flags,
         }))
     }
 
@@ -787,6 +1717,7 @@ impl Mp4Track {
         let (start_time, duration) = self.sample_time(sample_id).unwrap(); // XXX
         let rendering_offset = self.sample_rendering_offset(sample_id);
         let is_sync = self.is_sync_sample(sample_id);
+        let encryption = self.sample_encryption(reader, sample_id)?;
 
         Ok(Some(Mp4Sample {
             start_time,
@@ -794,8 +1725,124 @@ impl Mp4Track {
             rendering_offset,
             is_sync,
             bytes: Bytes::from(buffer),
+            encryption,
         }))
     }
+
+    /// Decrypt an encrypted sample of this track with `key`, leaving samples
+    /// stored in the clear untouched.
+    pub(crate) fn decrypt_sample(&self, sample: &mut Mp4Sample, key: &[u8; 16]) -> Result<()> {
+        let (entry, tenc, scheme_type) =
+            match (sample.encryption.as_ref(), self.tenc(), self.scheme_type()) {
+                (Some(entry), Some(tenc), Some(scheme_type)) => (entry, tenc, scheme_type),
+                _ => return Ok(()),
+            };
+        let mut bytes = sample.bytes.to_vec();
+        decrypt_sample(&mut bytes, key, scheme_type, tenc, entry)?;
+        sample.bytes = Bytes::from(bytes);
+        sample.encryption = None;
+        Ok(())
+    }
+
+    /// Encryption parameters of a sample, from `senc` or from the auxiliary
+    /// information that `saiz` and `saio` point at. Samples without either
+    /// are treated as unencrypted.
+    fn sample_encryption<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        sample_id: u32,
+    ) -> Result<Option<SampleEncryptionEntry>> {
+        let tenc = match self.tenc() {
+            Some(tenc) if tenc.default_is_protected != 0 => tenc,
+            _ => return Ok(None),
+        };
+        let iv_size = tenc.default_per_sample_iv_size;
+
+        // The boxes holding the sample, the base of saio offsets and the
+        // index of the sample among the ones they describe.
+        let (senc, saiz, saio, base_offset, index) = if self.trafs.is_empty() {
+            let stbl = &self.trak.mdia.minf.stbl;
+            let index = match sample_id.checked_sub(1) {
+                Some(index) => index,
+                None => return Ok(None),
+            };
+            (
+                stbl.senc.as_ref(),
+                stbl.saiz.as_ref(),
+                stbl.saio.as_ref(),
+                0,
+                index,
+            )
+        } else {
+            let mut first_sample = 1;
+            let mut found = None;
+            for (traf, moof_offset) in self.trafs.iter().zip(self.moof_offsets.iter()) {
+                let sample_count: u32 = traf.truns.iter().map(|trun| trun.sample_count).sum();
+                if sample_id >= first_sample && sample_id - first_sample < sample_count {
+                    let base_offset = traf.tfhd.base_data_offset.unwrap_or(*moof_offset);
+                    found = Some((
+                        traf.senc.as_ref(),
+                        traf.saiz.as_ref(),
+                        traf.saio.as_ref(),
+                        base_offset,
+                        sample_id - first_sample,
+                    ));
+                    break;
+                }
+                first_sample += sample_count;
+            }
+            match found {
+                Some(found) => found,
+                None => return Ok(None),
+            }
+        };
+
+        let entry = if let Some(senc) = senc {
+            senc.entry(iv_size, index)?
+        } else if let (Some(saiz), Some(saio)) = (saiz, saio) {
+            // Only auxiliary information stored in one run is supported.
+            match (saiz.sample_info_size(index), saio.offsets.as_slice()) {
+                (Some(info_size), [offset]) => {
+                    let preceding: u64 = if saiz.default_sample_info_size != 0 {
+                        index as u64 * saiz.default_sample_info_size as u64
+                    } else {
+                        saiz.sample_info_sizes[..index as usize]
+                            .iter()
+                            .map(|size| *size as u64)
+                            .sum()
+                    };
+                    let mut buffer = vec![0; info_size as usize];
+                    reader.seek(SeekFrom::Start(base_offset + offset + preceding))?;
+                    reader.read_exact(&mut buffer)?;
+                    Some(SampleEncryptionEntry::read(
+                        &mut Cursor::new(buffer),
+                        iv_size,
+                        info_size > iv_size,
+                    )?)
+                }
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        Ok(entry.map(|mut entry| {
+            if entry.iv.is_empty() {
+                if let Some(ref constant_iv) = tenc.default_constant_iv {
+                    entry.iv = constant_iv.clone();
+                }
+            }
+            entry
+        }))
+    }
+}
+
+#[derive(Debug)]
+struct Mp4Chunk {
+    start_time: u64,
+    first_sample: u32,
+    sample_count: u32,
+    bytes: Bytes,
 }
 
 // TODO creation_time, modification_time
@@ -806,9 +1853,13 @@ pub(crate) struct Mp4TrackWriter {
     sample_id: u32,
     fixed_sample_size: u32,
     is_fixed_sample_size: bool,
+    chunk_first_sample: u32,
+    chunk_start_time: u64,
     chunk_samples: u32,
     chunk_duration: u32,
     chunk_buffer: BytesMut,
+    pending_chunks: VecDeque<Mp4Chunk>,
+    ended: bool,
 
     samples_per_chunk: u32,
     duration_per_chunk: u32,
@@ -816,86 +1867,131 @@ pub(crate) struct Mp4TrackWriter {
     duration: u64,
 }
 
-impl Mp4TrackWriter {
-    pub(crate) fn new(track_id: u32, config: &TrackConfig) -> Result<Self> {
-        let mut trak = TrakBox::default();
-        trak.tkhd.track_id = track_id;
-        trak.mdia.mdhd.timescale = config.timescale;
-        config.language.clone_into(&mut trak.mdia.mdhd.language);
-        trak.mdia.hdlr.handler_type = config.track_type.into();
-        trak.mdia.hdlr.name = config.track_type.into();
-        trak.mdia.minf.stbl.co64 = Some(Co64Box::default());
-        
-        // Set matrix if provided in config
-        if let Some(matrix_values) = &config.matrix {
-            if matrix_values.len() == 9 {
-                trak.tkhd.matrix = Matrix {
-                    a: matrix_values[0],
-                    b: matrix_values[1],
-                    u: matrix_values[2],
-                    c: matrix_values[3],
-                    d: matrix_values[4],
-                    v: matrix_values[5],
-                    x: matrix_values[6],
-                    y: matrix_values[7],
-                    w: matrix_values[8],
-                };
-            }
+/// Build the `trak` skeleton (headers and sample description) for a track
+/// described by `config`, leaving the sample tables empty.
+pub(crate) fn new_trak(track_id: u32, config: &TrackConfig) -> TrakBox {
+    let mut trak = TrakBox::default();
+    trak.tkhd.track_id = track_id;
+    trak.mdia.mdhd.timescale = config.timescale;
+    config.language.clone_into(&mut trak.mdia.mdhd.language);
+    trak.mdia.hdlr.handler_type = config.track_type.into();
+    trak.mdia.hdlr.name = config.track_type.into();
+    if !config.references.is_empty() {
+        trak.tref = Some(TrefBox {
+            references: config.references.clone(),
+        });
+    }
+
+    // Set matrix if provided in config
+    if let Some(matrix_values) = &config.matrix {
+        if matrix_values.len() == 9 {
+            trak.tkhd.matrix = Matrix {
+                a: matrix_values[0],
+                b: matrix_values[1],
+                u: matrix_values[2],
+                c: matrix_values[3],
+                d: matrix_values[4],
+                v: matrix_values[5],
+                x: matrix_values[6],
+                y: matrix_values[7],
+                w: matrix_values[8],
+            };
         }
-        
-        match config.media_conf {
-            MediaConfig::AvcConfig(ref avc_config) => {
-                trak.tkhd.set_width(avc_config.width);
-                trak.tkhd.set_height(avc_config.height);
+    }
 
-                let vmhd = VmhdBox::default();
-                trak.mdia.minf.vmhd = Some(vmhd);
+    match config.media_conf {
+        MediaConfig::AvcConfig(ref avc_config) => {
+            trak.tkhd.set_width(avc_config.width);
+            trak.tkhd.set_height(avc_config.height);
 
-                let avc1 = Avc1Box::new(avc_config);
-                trak.mdia.minf.stbl.stsd.avc1 = Some(avc1);
-            }
-            MediaConfig::HevcConfig(ref hevc_config) => {
-                trak.tkhd.set_width(hevc_config.width.unwrap_or(0));
-                trak.tkhd.set_height(hevc_config.height.unwrap_or(0));
+            let vmhd = VmhdBox::default();
+            trak.mdia.minf.vmhd = Some(vmhd);
 
-                let vmhd = VmhdBox::default();
-                trak.mdia.minf.vmhd = Some(vmhd);
+            let avc1 = Avc1Box::new(avc_config);
+            trak.mdia.minf.stbl.stsd.avc1 = Some(avc1);
+        }
+        MediaConfig::HevcConfig(ref hevc_config) => {
+            trak.tkhd.set_width(hevc_config.width.unwrap_or(0));
+            trak.tkhd.set_height(hevc_config.height.unwrap_or(0));
 
-                if hevc_config.use_hvc1 {
-                    let hvc1 = Hvc1Box::new(hevc_config);
-                    trak.mdia.minf.stbl.stsd.hvc1 = Some(hvc1);
-                } else {
-                    let hev1 = Hev1Box::new(hevc_config);
-                    trak.mdia.minf.stbl.stsd.hev1 = Some(hev1);
-                }
-            }
-            MediaConfig::Vp9Config(ref config) => {
-                trak.tkhd.set_width(config.width);
-                trak.tkhd.set_height(config.height);
+            let vmhd = VmhdBox::default();
+            trak.mdia.minf.vmhd = Some(vmhd);
 
-                trak.mdia.minf.stbl.stsd.vp09 = Some(Vp09Box::new(config));
+            if hevc_config.use_hvc1 {
+                let hvc1 = Hvc1Box::new(hevc_config);
+                trak.mdia.minf.stbl.stsd.hvc1 = Some(hvc1);
+            } else {
+                let hev1 = Hev1Box::new(hevc_config);
+                trak.mdia.minf.stbl.stsd.hev1 = Some(hev1);
             }
-            MediaConfig::AacConfig(ref aac_config) => {
-                let smhd = SmhdBox::default();
-                trak.mdia.minf.smhd = Some(smhd);
+        }
+        MediaConfig::Vp9Config(ref config) => {
+            trak.tkhd.set_width(config.width);
+            trak.tkhd.set_height(config.height);
 
-                let mp4a = Mp4aBox::new(aac_config);
-                trak.mdia.minf.stbl.stsd.mp4a = Some(mp4a);
-            }
-            MediaConfig::TtxtConfig(ref _ttxt_config) => {
-                let tx3g = Tx3gBox::default();
-                trak.mdia.minf.stbl.stsd.tx3g = Some(tx3g);
-            }
-            MediaConfig::OpusConfig(ref opus_config) => {
-                let opus = OpusBox::new(opus_config);
-                trak.mdia.minf.stbl.stsd.opus = Some(opus);
-            }
+            trak.mdia.minf.stbl.stsd.vp09 = Some(Vp09Box::new(config));
+        }
+        MediaConfig::Av1Config(ref av1_config) => {
+            trak.tkhd.set_width(av1_config.width);
+            trak.tkhd.set_height(av1_config.height);
+
+            let vmhd = VmhdBox::default();
+            trak.mdia.minf.vmhd = Some(vmhd);
+
+            let av01 = Av01Box::new(av1_config);
+            trak.mdia.minf.stbl.stsd.av01 = Some(av01);
+        }
+        MediaConfig::AacConfig(ref aac_config) => {
+            let smhd = SmhdBox::default();
+            trak.mdia.minf.smhd = Some(smhd);
+
+            let mp4a = Mp4aBox::new(aac_config);
+            trak.mdia.minf.stbl.stsd.mp4a = Some(mp4a);
+        }
+        MediaConfig::TtxtConfig(ref _ttxt_config) => {
+            let tx3g = Tx3gBox::default();
+            trak.mdia.minf.stbl.stsd.tx3g = Some(tx3g);
         }
+        MediaConfig::OpusConfig(ref opus_config) => {
+            let opus = OpusBox::new(opus_config);
+            trak.mdia.minf.stbl.stsd.opus = Some(opus);
+        }
+        MediaConfig::Ac3Config(ref ac3_config) => {
+            let smhd = SmhdBox::default();
+            trak.mdia.minf.smhd = Some(smhd);
+
+            let ac3 = Ac3Box::new(ac3_config);
+            trak.mdia.minf.stbl.stsd.ac3 = Some(ac3);
+        }
+        MediaConfig::Ec3Config(ref ec3_config) => {
+            let smhd = SmhdBox::default();
+            trak.mdia.minf.smhd = Some(smhd);
+
+            let ec3 = Ec3Box::new(ec3_config);
+            trak.mdia.minf.stbl.stsd.ec3 = Some(ec3);
+        }
+        MediaConfig::FlacConfig(ref flac_config) => {
+            let smhd = SmhdBox::default();
+            trak.mdia.minf.smhd = Some(smhd);
+
+            let flac = FlacBox::new(flac_config);
+            trak.mdia.minf.stbl.stsd.flac = Some(flac);
+        }
+    }
+    trak
+}
+
+impl Mp4TrackWriter {
+    pub(crate) fn new(track_id: u32, config: &TrackConfig) -> Result<Self> {
+        let mut trak = new_trak(track_id, config);
+        trak.mdia.minf.stbl.co64 = Some(Co64Box::default());
+
         Ok(Mp4TrackWriter {
             trak,
             chunk_buffer: BytesMut::new(),
             sample_id: 1,
-            duration_per_chunk: config.timescale, // 1 second
+            samples_per_chunk: config.samples_per_chunk.unwrap_or(0),
+            duration_per_chunk: config.duration_per_chunk.unwrap_or(config.timescale), // 1 second
             ..Self::default()
         })
     }
@@ -1021,27 +2117,51 @@ impl Mp4TrackWriter {
         }
     }
 
-    pub(crate) fn write_sample<W: Write + Seek>(
-        &mut self,
-        writer: &mut W,
-        sample: &Mp4Sample,
-        movie_timescale: u32,
-    ) -> Result<u64> {
+    /// Add a sample to the current chunk, queueing the chunk to be written
+    /// once it is full.
+    pub(crate) fn write_sample(&mut self, sample: &Mp4Sample, movie_timescale: u32) -> Result<u64> {
+        if self.ended {
+            return Err(Error::InvalidData(
+                "sample written after the end of the track",
+            ));
+        }
+        if self.chunk_samples == 0 {
+            self.chunk_first_sample = self.sample_id;
+            self.chunk_start_time = self.trak.mdia.mdhd.duration;
+        }
         self.chunk_buffer.extend_from_slice(&sample.bytes);
         self.chunk_samples += 1;
         self.chunk_duration += sample.duration;
-        self.update_sample_sizes(sample.bytes.len() as u32);
-        self.update_sample_times(sample.duration);
-        self.update_rendering_offsets(sample.rendering_offset);
-        self.update_sync_samples(sample.is_sync);
         if self.is_chunk_full() {
-            self.write_chunk(writer)?;
+            self.close_chunk();
         }
-        self.update_durations(sample.duration, movie_timescale);
+
+        let metadata = Mp4SampleMetadata {
+            start_time: sample.start_time,
+            duration: sample.duration,
+            rendering_offset: sample.rendering_offset,
+            is_sync: sample.is_sync,
+            sample_flags: None,
+        };
+        Ok(self.add_sample(sample.bytes.len() as u32, &metadata, movie_timescale))
+    }
+
+    /// Record a sample in the sample tables without buffering its data.
+    pub(crate) fn add_sample(
+        &mut self,
+        size: u32,
+        metadata: &Mp4SampleMetadata,
+        movie_timescale: u32,
+    ) -> u64 {
+        self.update_sample_sizes(size);
+        self.update_sample_times(metadata.duration);
+        self.update_rendering_offsets(metadata.rendering_offset);
+        self.update_sync_samples(metadata.is_sync);
+        self.update_durations(metadata.duration, movie_timescale);
 
         self.sample_id += 1;
 
-        Ok(self.trak.tkhd.duration)
+        self.trak.tkhd.duration
     }
 
     fn chunk_count(&self) -> u32 {
@@ -1049,18 +2169,18 @@ impl Mp4TrackWriter {
         co64.entries.len() as u32
     }
 
-    fn update_sample_to_chunk(&mut self, chunk_id: u32) {
+    fn update_sample_to_chunk(&mut self, chunk_id: u32, first_sample: u32, sample_count: u32) {
         if let Some(entry) = self.trak.mdia.minf.stbl.stsc.entries.last() {
-            if entry.samples_per_chunk == self.chunk_samples {
+            if entry.samples_per_chunk == sample_count {
                 return;
             }
         }
 
         let entry = StscEntry {
             first_chunk: chunk_id,
-            samples_per_chunk: self.chunk_samples,
+            samples_per_chunk: sample_count,
             sample_description_index: 1,
-            first_sample: self.sample_id - self.chunk_samples + 1,
+            first_sample,
         };
         self.trak.mdia.minf.stbl.stsc.entries.push(entry);
     }
@@ -1070,24 +2190,81 @@ impl Mp4TrackWriter {
         co64.entries.push(offset);
     }
 
-    fn write_chunk<W: Write + Seek>(&mut self, writer: &mut W) -> Result<()> {
-        if self.chunk_buffer.is_empty() {
-            return Ok(());
+    /// Queue the samples buffered so far as a chunk waiting to be written.
+    pub(crate) fn close_chunk(&mut self) {
+        if self.chunk_samples == 0 {
+            return;
         }
-        let chunk_offset = writer.stream_position()?;
+        self.pending_chunks.push_back(Mp4Chunk {
+            start_time: self.chunk_start_time,
+            first_sample: self.chunk_first_sample,
+            sample_count: self.chunk_samples,
+            bytes: self.chunk_buffer.split().freeze(),
+        });
+        self.chunk_samples = 0;
+        self.chunk_duration = 0;
+    }
+
+    /// Decode time of the oldest chunk waiting to be written.
+    pub(crate) fn pending_chunk_time(&self) -> Option<Duration> {
+        self.pending_chunks
+            .front()
+            .map(|chunk| self.media_time(chunk.start_time))
+    }
 
-        writer.write_all(&self.chunk_buffer)?;
+    /// Queue the last chunk and accept no more samples.
+    pub(crate) fn end(&mut self) {
+        self.close_chunk();
+        self.ended = true;
+    }
 
-        self.update_sample_to_chunk(self.chunk_count() + 1);
-        self.update_chunk_offsets(chunk_offset);
+    /// Number of bytes queued in chunks waiting to be written.
+    pub(crate) fn pending_size(&self) -> u64 {
+        self.pending_chunks
+            .iter()
+            .map(|chunk| chunk.bytes.len() as u64)
+            .sum()
+    }
 
-        self.chunk_buffer.clear();
-        self.chunk_samples = 0;
-        self.chunk_duration = 0;
+    /// Decode time of the earliest chunk this track can still queue: the
+    /// start of the chunk being filled, or the end of the samples so far.
+    /// `None` before the first sample and after the end of the track.
+    pub(crate) fn open_chunk_time(&self) -> Option<Duration> {
+        if self.ended || self.sample_id == 1 {
+            None
+        } else if self.chunk_samples > 0 {
+            Some(self.media_time(self.chunk_start_time))
+        } else {
+            Some(self.media_time(self.trak.mdia.mdhd.duration))
+        }
+    }
+
+    fn media_time(&self, time: u64) -> Duration {
+        Duration::from_micros(time * 1_000_000 / self.trak.mdia.mdhd.timescale as u64)
+    }
+
+    /// Write the oldest queued chunk.
+    pub(crate) fn write_pending_chunk<W: Write + Seek>(&mut self, writer: &mut W) -> Result<()> {
+        let chunk = match self.pending_chunks.pop_front() {
+            Some(chunk) => chunk,
+            None => return Ok(()),
+        };
+        let chunk_offset = writer.stream_position()?;
+
+        writer.write_all(&chunk.bytes)?;
+
+        self.add_chunk(chunk_offset, chunk.first_sample, chunk.sample_count);
 
         Ok(())
     }
 
+    /// Record a chunk of `sample_count` samples starting at `first_sample`,
+    /// stored at `offset`.
+    pub(crate) fn add_chunk(&mut self, offset: u64, first_sample: u32, sample_count: u32) {
+        self.update_sample_to_chunk(self.chunk_count() + 1, first_sample, sample_count);
+        self.update_chunk_offsets(offset);
+    }
+
     fn max_sample_size(&self) -> u32 {
         if self.trak.mdia.minf.stbl.stsz.sample_size > 0 {
             self.trak.mdia.minf.stbl.stsz.sample_size
@@ -1107,8 +2284,16 @@ impl Mp4TrackWriter {
     }
 
     pub(crate) fn write_end<W: Write + Seek>(&mut self, writer: &mut W) -> Result<TrakBox> {
-        self.write_chunk(writer)?;
+        self.close_chunk();
+        while !self.pending_chunks.is_empty() {
+            self.write_pending_chunk(writer)?;
+        }
+
+        Ok(self.finish())
+    }
 
+    /// Complete the `trak` box from the samples and chunks recorded so far.
+    pub(crate) fn finish(&mut self) -> TrakBox {
         let max_sample_size = self.max_sample_size();
         self.trak.edts = Some(EdtsBox {
             elst: Some(ElstBox {
@@ -1121,6 +2306,8 @@ impl Mp4TrackWriter {
                     media_rate_fraction: 0,
                 }],
             }),
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
         });
         if let Some(ref mut mp4a) = self.trak.mdia.minf.stbl.stsd.mp4a {
             if let Some(ref mut esds) = mp4a.esds {
@@ -1135,6 +2322,422 @@ impl Mp4TrackWriter {
             self.trak.mdia.minf.stbl.co64 = None;
         }
 
-        Ok(self.trak.clone())
+        self.trak.clone()
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Mp4FragmentTrackWriter {
+    trak: TrakBox,
+
+    base_media_decode_time: u64,
+    sample_durations: Vec<u32>,
+    sample_sizes: Vec<u32>,
+    sample_flags: Vec<u32>,
+    sample_cts: Vec<i32>,
+    buffer: BytesMut,
+
+    encryptor: Option<SampleEncryptor>,
+    encryption_entries: Vec<SampleEncryptionEntry>,
+}
+
+impl Mp4FragmentTrackWriter {
+    pub(crate) fn new(track_id: u32, config: &TrackConfig) -> Result<Self> {
+        let mut trak = new_trak(track_id, config);
+        trak.mdia.minf.stbl.stco = Some(StcoBox::default());
+
+        Ok(Mp4FragmentTrackWriter {
+            trak,
+            ..Self::default()
+        })
+    }
+
+    pub(crate) fn track_id(&self) -> u32 {
+        self.trak.tkhd.track_id
+    }
+
+    pub(crate) fn timescale(&self) -> u32 {
+        self.trak.mdia.mdhd.timescale
+    }
+
+    pub(crate) fn trak(&self) -> &TrakBox {
+        &self.trak
+    }
+
+    /// Encrypt the samples written from now on, and describe the protection
+    /// in the sample entry.
+    pub(crate) fn set_encryption(&mut self, config: &EncryptionConfig) -> Result<()> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        let original_format = stsd
+            .entry_type()
+            .ok_or(Error::InvalidData("no sample entry to encrypt"))?;
+        let encryptor = SampleEncryptor::new(config, &self.trak)?;
+        self.trak.mdia.minf.stbl.stsd.sinf = Some(encryptor.sinf(original_format.into()));
+        self.encryptor = Some(encryptor);
+        Ok(())
+    }
+
+    pub(crate) fn psshs(&self) -> &[PsshBox] {
+        match self.encryptor {
+            Some(ref encryptor) => encryptor.psshs(),
+            None => &[],
+        }
+    }
+
+    pub(crate) fn trex(&self) -> TrexBox {
+        TrexBox {
+            track_id: self.track_id(),
+            default_sample_description_index: 1,
+            ..TrexBox::default()
+        }
+    }
+
+    pub(crate) fn is_video(&self) -> bool {
+        self.trak.mdia.minf.vmhd.is_some()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.sample_sizes.is_empty()
+    }
+
+    /// Duration of the samples buffered for the current fragment.
+    pub(crate) fn buffered_duration(&self) -> Duration {
+        let ticks: u64 = self.sample_durations.iter().map(|d| *d as u64).sum();
+        Duration::from_micros(ticks * 1_000_000 / self.timescale() as u64)
+    }
+
+    pub(crate) fn write_sample(&mut self, sample: &Mp4Sample) -> Result<()> {
+        if let Some(ref mut encryptor) = self.encryptor {
+            let mut bytes = sample.bytes.to_vec();
+            self.encryption_entries.push(encryptor.encrypt(&mut bytes)?);
+            self.buffer.extend_from_slice(&bytes);
+        } else {
+            self.buffer.extend_from_slice(&sample.bytes);
+        }
+        self.sample_durations.push(sample.duration);
+        self.sample_sizes.push(sample.bytes.len() as u32);
+        let sample_flags = if sample.is_sync {
+            SampleFlags {
+                depends_on: 2,
+                ..SampleFlags::default()
+            }
+        } else {
+            SampleFlags {
+                depends_on: 1,
+                is_non_sync_sample: true,
+                ..SampleFlags::default()
+            }
+        };
+        self.sample_flags.push(sample_flags.into());
+        self.sample_cts.push(sample.rendering_offset);
+        Ok(())
+    }
+
+    /// Drain the buffered samples into a `traf` and its media data. The
+    /// `trun` data offset is left at zero for the caller to fill in once
+    /// the layout of the fragment is known.
+    pub(crate) fn take_fragment(&mut self) -> Option<(TrafBox, BytesMut)> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let mut flags = TrunBox::FLAG_DATA_OFFSET
+            | TrunBox::FLAG_SAMPLE_DURATION
+            | TrunBox::FLAG_SAMPLE_SIZE
+            | TrunBox::FLAG_SAMPLE_FLAGS;
+        let mut version = 0;
+        if self.sample_cts.iter().any(|cts| *cts != 0) {
+            flags |= TrunBox::FLAG_SAMPLE_CTS;
+        }
+        if self.sample_cts.iter().any(|cts| *cts < 0) {
+            // Version 1 signals signed composition offsets.
+            version = 1;
+        }
+        let sample_cts = if TrunBox::FLAG_SAMPLE_CTS & flags > 0 {
+            self.sample_cts.drain(..).map(|cts| cts as u32).collect()
+        } else {
+            self.sample_cts.clear();
+            Vec::new()
+        };
+
+        let sample_durations: Vec<u32> = self.sample_durations.drain(..).collect();
+        let duration: u64 = sample_durations.iter().map(|d| *d as u64).sum();
+
+        let mut traf = TrafBox {
+            tfhd: TfhdBox {
+                flags: TfhdBox::FLAG_DEFAULT_BASE_IS_MOOF,
+                track_id: self.track_id(),
+                ..TfhdBox::default()
+            },
+            tfdt: Some(TfdtBox {
+                version: 1,
+                flags: 0,
+                base_media_decode_time: self.base_media_decode_time,
+            }),
+            truns: vec![TrunBox {
+                version,
+                flags,
+                sample_count: self.sample_sizes.len() as u32,
+                data_offset: Some(0),
+                first_sample_flags: None,
+                sample_durations,
+                sample_sizes: self.sample_sizes.drain(..).collect(),
+                sample_flags: self.sample_flags.drain(..).collect(),
+                sample_cts,
+            }],
+            ..TrafBox::default()
+        };
+        self.base_media_decode_time += duration;
+
+        if self.encryptor.is_some() {
+            let entries: Vec<SampleEncryptionEntry> = self.encryption_entries.drain(..).collect();
+            let senc = SencBox::new(&entries);
+            let sample_info_sizes: Vec<u8> = entries
+                .iter()
+                .map(|entry| entry.size(senc.has_subsamples()) as u8)
+                .collect();
+            let default_sample_info_size = match sample_info_sizes.split_first() {
+                Some((first, rest)) if rest.iter().all(|size| size == first) => *first,
+                _ => 0,
+            };
+            traf.saiz = Some(SaizBox {
+                default_sample_info_size,
+                sample_count: entries.len() as u32,
+                sample_info_sizes: if default_sample_info_size == 0 {
+                    sample_info_sizes
+                } else {
+                    Vec::new()
+                },
+                ..SaizBox::default()
+            });
+            // Pointed at the senc entries once the layout of the moof is known.
+            traf.saio = Some(SaioBox {
+                offsets: vec![0],
+                ..SaioBox::default()
+            });
+            traf.senc = Some(senc);
+        }
+
+        Some((traf, self.buffer.split()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::BufReader;
+
+    fn assert_index_matches_tables(track: &Mp4Track) {
+        let mut indexed = Mp4Track {
+            trak: track.trak.clone(),
+            trafs: track.trafs.clone(),
+            moof_offsets: track.moof_offsets.clone(),
+            default_sample_duration: track.default_sample_duration,
+            default_sample_size: track.default_sample_size,
+            default_sample_flags: track.default_sample_flags,
+            sample_index: None,
+        };
+        indexed.build_sample_index();
+        assert!(indexed.sample_index.is_some());
+
+        for sample_id in 1..=track.sample_count() + 1 {
+            assert_eq!(
+                indexed.sample_offset(sample_id).ok(),
+                track.sample_offset(sample_id).ok()
+            );
+            assert_eq!(
+                indexed.sample_size(sample_id).ok(),
+                track.sample_size(sample_id).ok()
+            );
+            assert_eq!(
+                indexed.sample_time(sample_id).ok(),
+                track.sample_time(sample_id).ok()
+            );
+            assert_eq!(
+                indexed.sample_rendering_offset(sample_id),
+                track.sample_rendering_offset(sample_id)
+            );
+            assert_eq!(
+                indexed.is_sync_sample(sample_id),
+                track.is_sync_sample(sample_id)
+            );
+            assert_eq!(
+                indexed.sample_flags(sample_id),
+                track.sample_flags(sample_id)
+            );
+        }
+    }
+
+    #[test]
+    fn test_sample_index() {
+        let f = File::open("tests/samples/minimal.mp4").unwrap();
+        let size = f.metadata().unwrap().len();
+        let mp4 = Mp4Reader::read_header(BufReader::new(f), size).unwrap();
+        for track in mp4.tracks().values() {
+            assert_index_matches_tables(track);
+        }
+    }
+
+    #[test]
+    fn test_sample_index_fragmented() {
+        let f = File::open("tests/samples/minimal_init.mp4").unwrap();
+        let size = f.metadata().unwrap().len();
+        let init = Mp4Reader::read_header(BufReader::new(f), size).unwrap();
+
+        let f = File::open("tests/samples/minimal_fragment.m4s").unwrap();
+        let size = f.metadata().unwrap().len();
+        let mp4 = init.read_fragment_header(BufReader::new(f), size).unwrap();
+        for track in mp4.tracks().values() {
+            assert_index_matches_tables(track);
+        }
+    }
+
+    #[test]
+    fn test_fragment_sample_defaults() {
+        let mut track = Mp4Track::from(&TrakBox::default());
+        track.default_sample_duration = 1024;
+        track.default_sample_size = 10;
+        track.trafs.push(TrafBox {
+            tfhd: TfhdBox {
+                track_id: 1,
+                default_sample_size: Some(20),
+                ..TfhdBox::default()
+            },
+            tfdt: Some(TfdtBox {
+                version: 1,
+                flags: 0,
+                base_media_decode_time: 2048,
+            }),
+            truns: vec![
+                TrunBox {
+                    flags: TrunBox::FLAG_DATA_OFFSET,
+                    sample_count: 2,
+                    data_offset: Some(100),
+                    ..TrunBox::default()
+                },
+                TrunBox {
+                    flags: TrunBox::FLAG_SAMPLE_DURATION | TrunBox::FLAG_SAMPLE_SIZE,
+                    sample_count: 2,
+                    sample_durations: vec![512, 512],
+                    sample_sizes: vec![30, 40],
+                    ..TrunBox::default()
+                },
+            ],
+            ..TrafBox::default()
+        });
+        track.moof_offsets.push(1000);
+        track.trafs.push(TrafBox {
+            tfhd: TfhdBox {
+                track_id: 1,
+                ..TfhdBox::default()
+            },
+            tfdt: None,
+            truns: vec![TrunBox {
+                flags: TrunBox::FLAG_DATA_OFFSET,
+                sample_count: 1,
+                data_offset: Some(50),
+                ..TrunBox::default()
+            }],
+            ..TrafBox::default()
+        });
+        track.moof_offsets.push(2000);
+
+        assert_eq!(track.sample_count(), 5);
+        let offsets: Vec<u64> = (1..=5).map(|i| track.sample_offset(i).unwrap()).collect();
+        assert_eq!(offsets, vec![1100, 1120, 1140, 1170, 2050]);
+        let sizes: Vec<u32> = (1..=5).map(|i| track.sample_size(i).unwrap()).collect();
+        assert_eq!(sizes, vec![20, 20, 30, 40, 10]);
+        let times: Vec<(u64, u32)> = (1..=5).map(|i| track.sample_time(i).unwrap()).collect();
+        assert_eq!(
+            times,
+            vec![
+                (2048, 1024),
+                (3072, 1024),
+                (4096, 512),
+                (4608, 512),
+                (5120, 1024)
+            ]
+        );
+        assert!(track.sample_offset(6).is_err());
+
+        assert_index_matches_tables(&track);
+    }
+
+    #[test]
+    fn test_fragment_missing_entry() {
+        let track = Mp4Track::from(&TrakBox::default());
+        let traf = TrafBox {
+            truns: vec![TrunBox {
+                flags: TrunBox::FLAG_SAMPLE_SIZE,
+                sample_count: 3,
+                sample_sizes: vec![10, 20],
+                ..TrunBox::default()
+            }],
+            ..TrafBox::default()
+        };
+        // The third sample of a fragment following ten samples.
+        assert!(matches!(
+            track.index_traf_samples(&[&traf], 0, 11, 0),
+            Err(Error::EntryInTrunNotFound(_, BoxType::TrunBox, 13))
+        ));
+    }
+
+    #[test]
+    fn test_sample_index_hostile_counts() {
+        // Counts far beyond the entries in the file must not size the index.
+        let mut trak = TrakBox::default();
+        let stbl = &mut trak.mdia.minf.stbl;
+        stbl.stsz = StszBox {
+            sample_size: 1,
+            sample_count: u32::MAX,
+            ..StszBox::default()
+        };
+        stbl.stsc.entries = vec![StscEntry {
+            first_chunk: 1,
+            samples_per_chunk: u32::MAX,
+            sample_description_index: 1,
+            first_sample: 1,
+        }];
+        stbl.stco = Some(StcoBox {
+            entries: vec![100],
+            ..StcoBox::default()
+        });
+        stbl.stts.entries = vec![SttsEntry {
+            sample_count: u32::MAX,
+            sample_delta: 1,
+        }];
+        let mut track = Mp4Track::from(&trak);
+        track.build_sample_index();
+        assert!(track.sample_index.is_some());
+        assert_eq!(track.sample_offset(u32::MAX).unwrap(), 99 + u32::MAX as u64);
+        assert_eq!(
+            track.sample_time(u32::MAX).unwrap(),
+            (u32::MAX as u64 - 1, 1)
+        );
+
+        // Too few sample durations for the sample count.
+        track.trak.mdia.minf.stbl.stts.entries[0].sample_count -= 1;
+        track.build_sample_index();
+        assert!(track.sample_index.is_none());
+
+        let mut track = Mp4Track::from(&TrakBox::default());
+        track.default_sample_duration = 1;
+        track.default_sample_size = 1;
+        track.trafs.push(TrafBox {
+            truns: vec![TrunBox {
+                sample_count: u32::MAX,
+                ..TrunBox::default()
+            }],
+            ..TrafBox::default()
+        });
+        track.moof_offsets.push(0);
+        track.build_sample_index();
+        assert!(track.sample_index.is_some());
+        assert_eq!(track.sample_offset(u32::MAX).unwrap(), u32::MAX as u64 - 1);
+        assert_eq!(
+            track.sample_time(u32::MAX).unwrap(),
+            (u32::MAX as u64 - 1, 1)
+        );
     }
 }
@@ -0,0 +1,496 @@
+//! Generic tree of the boxes of a file.
+//!
+//! [BoxTree::read] walks every box of a file in one pass, including the ones
+//! [crate::Mp4Reader] skips or does not model, recording where each box lies
+//! and parsing the boxes the crate knows into [ParsedBox]. Boxes are found
+//! with slash separated paths of box types, where `[n]` selects the n-th
+//! (0-based) box of that type among its siblings:
+//!
+//! ```rust
+//! use std::fs::File;
+//! use std::io::BufReader;
+//! use mp4::{BoxTree, ParsedBox};
+//!
+//! # fn main() -> mp4::Result<()> {
+//! let f = File::open("tests/samples/minimal.mp4")?;
+//! let size = f.metadata()?.len();
+//! let tree = BoxTree::read(&mut BufReader::new(f), size)?;
+//!
+//! assert_eq!(tree.get_all("moov/trak").len(), 2);
+//! let stsd = tree.get("moov/trak[1]/mdia/minf/stbl/stsd").unwrap();
+//! assert!(matches!(stsd.children[0].parsed, Some(ParsedBox::Mp4a(_))));
+//! # Ok(()) }
+//! ```
+
+use byteorder::{BigEndian, ReadBytesExt};
+use serde::Serialize;
+use std::convert::TryFrom;
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::mp4box::unknown::serialize_box_type;
+use crate::mp4box::*;
+use crate::*;
+
+/// The boxes of a file, in file order.
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct BoxTree {
+    pub boxes: Vec<BoxNode>,
+}
+
+/// A box and, for container boxes, the boxes inside it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BoxNode {
+    #[serde(serialize_with = "serialize_box_type")]
+    pub box_type: BoxType,
+    /// Offset of the box header in the file.
+    pub offset: u64,
+    /// Size of the box header: 8, or 16 with a 64-bit size.
+    pub header_size: u64,
+    /// Size of the box, header included.
+    pub size: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<BoxNode>,
+    /// The box parsed by the crate, for boxes it models that are not plain
+    /// containers and that parse without error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parsed: Option<ParsedBox>,
+}
+
+/// Callbacks for a depth-first walk of a [BoxTree].
+pub trait BoxVisitor {
+    /// Called before the children of `node`. Return false to skip them.
+    fn enter(&mut self, node: &BoxNode, depth: usize) -> bool;
+
+    /// Called after the children of `node`.
+    fn leave(&mut self, _node: &BoxNode, _depth: usize) {}
+}
+
+macro_rules! parsed_box {
+    ($( $box_type:ident => $variant:ident($parsed:ty) ),* $(,)?) => {
+        /// A box modeled by the crate.
+        #[derive(Debug, Clone, PartialEq, Serialize)]
+        #[serde(untagged)]
+        pub enum ParsedBox {
+            $( $variant($parsed), )*
+        }
+
+        impl ParsedBox {
+            /// Parse a box whose 8-byte header was just read, or return None
+            /// for box types that are not modeled.
+            fn read<R: Read + Seek>(
+                reader: &mut R,
+                box_type: BoxType,
+                size: u64,
+            ) -> Option<Result<Self>> {
+                match box_type {
+                    $( BoxType::$box_type => {
+                        Some(<$parsed>::read_box(reader, size).map(ParsedBox::$variant))
+                    } )*
+                    _ => None,
+                }
+            }
+
+            pub fn summary(&self) -> Result<String> {
+                match self {
+                    $( ParsedBox::$variant(parsed) => parsed.summary(), )*
+                }
+            }
+
+            pub fn to_json(&self) -> Result<String> {
+                match self {
+                    $( ParsedBox::$variant(parsed) => parsed.to_json(), )*
+                }
+            }
+        }
+    };
+}
+
+parsed_box! {
+    FtypBox => Ftyp(FtypBox),
+    MvhdBox => Mvhd(MvhdBox),
+    MehdBox => Mehd(MehdBox),
+    TrexBox => Trex(TrexBox),
+    TkhdBox => Tkhd(TkhdBox),
+    TrefBox => Tref(TrefBox),
+    ElstBox => Elst(ElstBox),
+    MdhdBox => Mdhd(MdhdBox),
+    HdlrBox => Hdlr(HdlrBox),
+    VmhdBox => Vmhd(VmhdBox),
+    SmhdBox => Smhd(SmhdBox),
+    Avc1Box => Avc1(Avc1Box),
+    AvcCBox => AvcC(AvcCBox),
+    Hev1Box => Hev1(Hev1Box),
+    Hvc1Box => Hvc1(Hvc1Box),
+    HvcCBox => HvcC(HvcCBox),
+    Vp09Box => Vp09(Vp09Box),
+    VpccBox => Vpcc(VpccBox),
+    Av01Box => Av01(Av01Box),
+    Av1CBox => Av1C(Av1CBox),
+    Mp4aBox => Mp4a(Mp4aBox),
+    Ac3Box => Ac3(Ac3Box),
+    Dac3Box => Dac3(Dac3Box),
+    Ec3Box => Ec3(Ec3Box),
+    Dec3Box => Dec3(Dec3Box),
+    FlacBox => Flac(FlacBox),
+    DflaBox => Dfla(DflaBox),
+    OpusBox => Opus(OpusBox),
+    Tx3gBox => Tx3g(Tx3gBox),
+    FrmaBox => Frma(FrmaBox),
+    SchmBox => Schm(SchmBox),
+    TencBox => Tenc(TencBox),
+    SttsBox => Stts(SttsBox),
+    CttsBox => Ctts(CttsBox),
+    StssBox => Stss(StssBox),
+    StscBox => Stsc(StscBox),
+    StszBox => Stsz(StszBox),
+    StcoBox => Stco(StcoBox),
+    Co64Box => Co64(Co64Box),
+    SaizBox => Saiz(SaizBox),
+    SaioBox => Saio(SaioBox),
+    SencBox => Senc(SencBox),
+    PsshBox => Pssh(PsshBox),
+    ChplBox => Chpl(ChplBox),
+    DataBox => Data(DataBox),
+    SidxBox => Sidx(SidxBox),
+    EmsgBox => Emsg(EmsgBox),
+    MfhdBox => Mfhd(MfhdBox),
+    TfhdBox => Tfhd(TfhdBox),
+    TfdtBox => Tfdt(TfdtBox),
+    TrunBox => Trun(TrunBox),
+}
+
+impl BoxTree {
+    /// Read the boxes from the current position of `reader` up to `size`,
+    /// the length of the file.
+    pub fn read<R: Read + Seek>(reader: &mut R, size: u64) -> Result<Self> {
+        let start = reader.stream_position()?;
+        let boxes = read_boxes(reader, start, size, None)?;
+        Ok(BoxTree { boxes })
+    }
+
+    /// The first box matching `path`, e.g. `moov/trak[1]/mdia/minf/stbl/stsd`.
+    pub fn get(&self, path: &str) -> Option<&BoxNode> {
+        self.get_all(path).into_iter().next()
+    }
+
+    /// All the boxes matching `path`: `moov/trak` gives every track.
+    pub fn get_all(&self, path: &str) -> Vec<&BoxNode> {
+        find_all(&self.boxes, path)
+    }
+
+    /// Walk the boxes depth first, in file order.
+    pub fn visit<V: BoxVisitor>(&self, visitor: &mut V) {
+        for node in self.boxes.iter() {
+            node.visit(visitor, 0);
+        }
+    }
+}
+
+impl BoxNode {
+    /// Offset of the box payload, after its header.
+    pub fn data_offset(&self) -> u64 {
+        self.offset + self.header_size
+    }
+
+    /// Size of the box payload, without its header.
+    pub fn data_size(&self) -> u64 {
+        self.size - self.header_size
+    }
+
+    /// The first box under this one matching `path`.
+    pub fn get(&self, path: &str) -> Option<&BoxNode> {
+        self.get_all(path).into_iter().next()
+    }
+
+    /// All the boxes under this one matching `path`.
+    pub fn get_all(&self, path: &str) -> Vec<&BoxNode> {
+        find_all(&self.children, path)
+    }
+
+    /// Walk this box and the boxes under it depth first, `depth` being the
+    /// depth of this box.
+    pub fn visit<V: BoxVisitor>(&self, visitor: &mut V, depth: usize) {
+        if visitor.enter(self, depth) {
+            for child in self.children.iter() {
+                child.visit(visitor, depth + 1);
+            }
+        }
+        visitor.leave(self, depth);
+    }
+}
+
+fn read_boxes<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    end: u64,
+    parent: Option<BoxType>,
+) -> Result<Vec<BoxNode>> {
+    let mut boxes = Vec::new();
+    let mut offset = start;
+    // Anything shorter than a box header at the end is padding.
+    while offset + HEADER_SIZE <= end {
+        reader.seek(SeekFrom::Start(offset))?;
+        let header = BoxHeader::read(reader)?;
+        let header_size = reader.stream_position()? - offset;
+        // A 64-bit size comes back without the 8 bytes of the size field.
+        let size = match header.size {
+            0 => end - offset,
+            size => size + header_size - HEADER_SIZE,
+        };
+        if size < header_size || size > end - offset {
+            return Err(Error::InvalidData(
+                "box size does not fit in its parent box",
+            ));
+        }
+
+        // The modeled boxes expect an 8-byte header.
+        let parsed = if header_size == HEADER_SIZE {
+            ParsedBox::read(reader, header.name, size).and_then(|parsed| parsed.ok())
+        } else {
+            None
+        };
+
+        let data_offset = offset + header_size;
+        let children = match children_offset(reader, header.name, parent, data_offset)? {
+            Some(children_offset) if data_offset + children_offset <= offset + size => read_boxes(
+                reader,
+                data_offset + children_offset,
+                offset + size,
+                Some(header.name),
+            )?,
+            _ => Vec::new(),
+        };
+
+        boxes.push(BoxNode {
+            box_type: header.name,
+            offset,
+            header_size,
+            size,
+            children,
+            parsed,
+        });
+        offset += size;
+    }
+    Ok(boxes)
+}
+
+/// Number of bytes between the header of a box and its first child box, or
+/// None for boxes that do not hold other boxes.
+fn children_offset<R: Read + Seek>(
+    reader: &mut R,
+    box_type: BoxType,
+    parent: Option<BoxType>,
+    data_offset: u64,
+) -> Result<Option<u64>> {
+    // Every item of an ilst box holds data (or mean and name) boxes.
+    if parent == Some(BoxType::IlstBox) {
+        return Ok(Some(0));
+    }
+
+    let offset = match box_type {
+        BoxType::MoovBox
+        | BoxType::TrakBox
+        | BoxType::EdtsBox
+        | BoxType::MdiaBox
+        | BoxType::MinfBox
+        | BoxType::DinfBox
+        | BoxType::StblBox
+        | BoxType::MvexBox
+        | BoxType::MoofBox
+        | BoxType::TrafBox
+        | BoxType::UdtaBox
+        | BoxType::IlstBox
+        | BoxType::SinfBox
+        | BoxType::SchiBox
+        | BoxType::WaveBox => 0,
+        BoxType::MetaBox => {
+            // Like MetaBox::read_box, accept meta boxes without the version
+            // and flags of a full box.
+            reader.seek(SeekFrom::Start(data_offset))?;
+            let mut buf = [0u8; 8];
+            if reader.read_exact(&mut buf).is_ok()
+                && buf[..4] != [0; 4]
+                && BoxType::from(u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]))
+                    == BoxType::HdlrBox
+            {
+                0
+            } else {
+                HEADER_EXT_SIZE
+            }
+        }
+        // Version, flags and entry count.
+        BoxType::StsdBox | BoxType::DrefBox => HEADER_EXT_SIZE + 4,
+        // VisualSampleEntry fields.
+        BoxType::Avc1Box
+        | BoxType::Hev1Box
+        | BoxType::Hvc1Box
+        | BoxType::Vp09Box
+        | BoxType::Av01Box
+        | BoxType::EncvBox => 78,
+        // AudioSampleEntry fields, extended by QuickTime sound descriptions
+        // version 1 and 2.
+        BoxType::Mp4aBox
+        | BoxType::Ac3Box
+        | BoxType::Ec3Box
+        | BoxType::FlacBox
+        | BoxType::OpusBox
+        | BoxType::EncaBox => {
+            reader.seek(SeekFrom::Start(data_offset + 8))?;
+            match reader.read_u16::<BigEndian>() {
+                Ok(1) => 28 + 16,
+                Ok(2) => 28 + 36,
+                _ => 28,
+            }
+        }
+        // The display flags, justification, colors, box and style records.
+        BoxType::Tx3gBox => 38,
+        _ => return Ok(None),
+    };
+    Ok(Some(offset))
+}
+
+struct PathSegment {
+    box_type: BoxType,
+    index: Option<usize>,
+}
+
+/// Parse a path segment such as `trak`, `trak[1]` or `©nam`, mapping each
+/// character of the box type to one byte.
+fn parse_segment(segment: &str) -> Option<PathSegment> {
+    let (name, index) = match segment.find('[') {
+        Some(pos) => {
+            let index = segment[pos + 1..].strip_suffix(']')?.parse().ok()?;
+            (&segment[..pos], Some(index))
+        }
+        None => (segment, None),
+    };
+    let mut value = [0u8; 4];
+    let mut chars = name.chars();
+    for byte in value.iter_mut() {
+        *byte = u8::try_from(u32::from(chars.next()?)).ok()?;
+    }
+    if chars.next().is_some() {
+        return None;
+    }
+    Some(PathSegment {
+        box_type: BoxType::from(u32::from_be_bytes(value)),
+        index,
+    })
+}
+
+fn find_all<'a>(boxes: &'a [BoxNode], path: &str) -> Vec<&'a BoxNode> {
+    let segments: Option<Vec<PathSegment>> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(parse_segment)
+        .collect();
+    let mut found = Vec::new();
+    if let Some(segments) = segments {
+        if !segments.is_empty() {
+            find_segments(boxes, &segments, &mut found);
+        }
+    }
+    found
+}
+
+fn find_segments<'a>(boxes: &'a [BoxNode], segments: &[PathSegment], found: &mut Vec<&'a BoxNode>) {
+    let (segment, rest) = match segments.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let matching = boxes
+        .iter()
+        .filter(|node| node.box_type == segment.box_type)
+        .enumerate()
+        .filter(|(i, _)| segment.index.map_or(true, |index| index == *i));
+    for (_, node) in matching {
+        if rest.is_empty() {
+            found.push(node);
+        } else {
+            find_segments(&node.children, rest, found);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn write_box(buf: &mut Vec<u8>, box_type: &[u8; 4], payload: &[u8]) {
+        buf.extend_from_slice(&(8 + payload.len() as u32).to_be_bytes());
+        buf.extend_from_slice(box_type);
+        buf.extend_from_slice(payload);
+    }
+
+    #[test]
+    fn test_parse_segment() {
+        let segment = parse_segment("trak[1]").unwrap();
+        assert_eq!(segment.box_type, BoxType::TrakBox);
+        assert_eq!(segment.index, Some(1));
+        let segment = parse_segment("\u{a9}nam").unwrap();
+        assert_eq!(segment.box_type, BoxType::NameBox);
+        assert_eq!(segment.index, None);
+        assert!(parse_segment("trak[").is_none());
+        assert!(parse_segment("tra").is_none());
+        assert!(parse_segment("trakk").is_none());
+    }
+
+    #[test]
+    fn test_box_tree() {
+        let mut ilst = Vec::new();
+        let mut data = Vec::new();
+        write_box(&mut data, b"data", b"\0\0\0\x01\0\0\0\0title");
+        write_box(&mut ilst, b"\xa9nam", &data);
+        let mut meta = vec![0; 4];
+        write_box(&mut meta, b"ilst", &ilst);
+        let mut udta = Vec::new();
+        write_box(&mut udta, b"meta", &meta);
+        write_box(&mut udta, b"xyz1", &[1, 2, 3]);
+        let mut file = Vec::new();
+        write_box(&mut file, b"udta", &udta);
+        // 64-bit size.
+        file.extend_from_slice(&1u32.to_be_bytes());
+        file.extend_from_slice(b"mdat");
+        file.extend_from_slice(&20u64.to_be_bytes());
+        file.extend_from_slice(&[0; 4]);
+
+        let size = file.len() as u64;
+        let tree = BoxTree::read(&mut Cursor::new(file), size).unwrap();
+        assert_eq!(tree.boxes.len(), 2);
+
+        let data = tree.get("udta/meta/ilst/\u{a9}nam/data").unwrap();
+        assert_eq!(data.offset, 36);
+        assert_eq!(data.size, 21);
+        match data.parsed {
+            Some(ParsedBox::Data(ref data)) => assert_eq!(data.data, b"title"),
+            _ => panic!("data box not parsed"),
+        }
+        let unknown = tree.get("udta/xyz1").unwrap();
+        assert_eq!(unknown.data_size(), 3);
+        assert!(unknown.parsed.is_none());
+
+        let mdat = tree.get("mdat").unwrap();
+        assert_eq!((mdat.offset, mdat.header_size, mdat.size), (68, 16, 20));
+
+        struct Depths(Vec<(BoxType, usize)>);
+        impl BoxVisitor for Depths {
+            fn enter(&mut self, node: &BoxNode, depth: usize) -> bool {
+                self.0.push((node.box_type, depth));
+                node.box_type != BoxType::IlstBox
+            }
+        }
+        let mut depths = Depths(Vec::new());
+        tree.visit(&mut depths);
+        assert_eq!(
+            depths.0,
+            vec![
+                (BoxType::UdtaBox, 0),
+                (BoxType::MetaBox, 1),
+                (BoxType::IlstBox, 2),
+                (BoxType::UnknownBox(0x78797a31), 1),
+                (BoxType::MdatBox, 0),
+            ]
+        );
+    }
+}
@@ -2,6 +2,7 @@ use serde::Serialize;
 use std::borrow::Cow;
 use std::convert::TryFrom;
 use std::fmt;
+use std::time::Duration;
 
 use crate::hev1::HvcCArray;
 use crate::mp4a::EsdsBox;
@@ -232,17 +233,25 @@ impl From<TrackType> for String {
 const MEDIA_TYPE_H264: &str = "h264";
 const MEDIA_TYPE_H265: &str = "h265";
 const MEDIA_TYPE_VP9: &str = "vp9";
+const MEDIA_TYPE_AV1: &str = "av1";
 const MEDIA_TYPE_AAC: &str = "aac";
 const MEDIA_TYPE_TTXT: &str = "ttxt";
 const MEDIA_TYPE_OPUS: &str = "opus";
+const MEDIA_TYPE_AC3: &str = "ac3";
+const MEDIA_TYPE_EAC3: &str = "eac3";
+const MEDIA_TYPE_FLAC: &str = "flac";
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MediaType {
     H264,
     H265,
     VP9,
+    AV1,
     AAC,
     OPUS,
+    AC3,
+    EAC3,
+    FLAC,
     TTXT,
 }
 
@@ -260,9 +269,13 @@ impl TryFrom<&str> for MediaType {
             MEDIA_TYPE_H264 => Ok(MediaType::H264),
             MEDIA_TYPE_H265 => Ok(MediaType::H265),
             MEDIA_TYPE_VP9 => Ok(MediaType::VP9),
+            MEDIA_TYPE_AV1 => Ok(MediaType::AV1),
             MEDIA_TYPE_AAC => Ok(MediaType::AAC),
             MEDIA_TYPE_TTXT => Ok(MediaType::TTXT),
             MEDIA_TYPE_OPUS => Ok(MediaType::OPUS),
+            MEDIA_TYPE_AC3 => Ok(MediaType::AC3),
+            MEDIA_TYPE_EAC3 => Ok(MediaType::EAC3),
+            MEDIA_TYPE_FLAC => Ok(MediaType::FLAC),
             _ => Err(Error::InvalidData("unsupported media type")),
         }
     }
@@ -274,9 +287,13 @@ impl From<MediaType> for &str {
             MediaType::H264 => MEDIA_TYPE_H264,
             MediaType::H265 => MEDIA_TYPE_H265,
             MediaType::VP9 => MEDIA_TYPE_VP9,
+            MediaType::AV1 => MEDIA_TYPE_AV1,
             MediaType::AAC => MEDIA_TYPE_AAC,
             MediaType::TTXT => MEDIA_TYPE_TTXT,
             MediaType::OPUS => MEDIA_TYPE_OPUS,
+            MediaType::AC3 => MEDIA_TYPE_AC3,
+            MediaType::EAC3 => MEDIA_TYPE_EAC3,
+            MediaType::FLAC => MEDIA_TYPE_FLAC,
         }
     }
 }
@@ -287,9 +304,13 @@ impl From<&MediaType> for &str {
             MediaType::H264 => MEDIA_TYPE_H264,
             MediaType::H265 => MEDIA_TYPE_H265,
             MediaType::VP9 => MEDIA_TYPE_VP9,
+            MediaType::AV1 => MEDIA_TYPE_AV1,
             MediaType::AAC => MEDIA_TYPE_AAC,
             MediaType::TTXT => MEDIA_TYPE_TTXT,
             MediaType::OPUS => MEDIA_TYPE_OPUS,
+            MediaType::AC3 => MEDIA_TYPE_AC3,
+            MediaType::EAC3 => MEDIA_TYPE_EAC3,
+            MediaType::FLAC => MEDIA_TYPE_FLAC,
         }
     }
 }
@@ -617,7 +638,7 @@ pub enum HevcBoxType {
     Hvc1,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub struct HevcConfig {
     pub width: Option<u16>,
     pub height: Option<u16>,
@@ -642,34 +663,6 @@ pub struct HevcConfig {
     pub use_hvc1: bool,
 }
 
-impl Default for HevcConfig {
-    fn default() -> Self {
-        Self {
-            width: None,
-            height: None,
-            configuration_version: None,
-            general_profile_space: None,
-            general_tier_flag: None,
-            general_profile_idc: None,
-            general_profile_compatibility_flags: None,
-            general_constraint_indicator_flag: None,
-            general_level_idc: None,
-            min_spatial_segmentation_idc: None,
-            parallelism_type: None,
-            chroma_format_idc: None,
-            bit_depth_luma_minus8: None,
-            bit_depth_chroma_minus8: None,
-            avg_frame_rate: None,
-            constant_frame_rate: None,
-            num_temporal_layers: None,
-            temporal_id_nested: None,
-            length_size_minus_one: None,
-            arrays: None,
-            use_hvc1: false,
-        }
-    }
-}
-
 impl HevcConfig {
     pub fn new() -> Self {
         Self::default()
@@ -714,6 +707,23 @@ pub struct Vp9Config {
     pub codec_initialization_data_size: u16,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Av1Config {
+    pub width: u16,
+    pub height: u16,
+    pub seq_profile: u8,
+    pub seq_level_idx_0: u8,
+    pub seq_tier_0: bool,
+    pub high_bitdepth: bool,
+    pub twelve_bit: bool,
+    pub monochrome: bool,
+    pub chroma_subsampling_x: bool,
+    pub chroma_subsampling_y: bool,
+    pub chroma_sample_position: u8,
+    pub initial_presentation_delay_minus_one: Option<u8>,
+    pub config_obus: Vec<u8>,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct AacConfig {
     pub bitrate: u32,
@@ -883,14 +893,74 @@ impl Default for OpusConfig {
     }
 }
 
+/// AC-3 stream parameters, as carried in the `dac3` box.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Ac3Config {
+    pub fscod: u8,
+    pub bsid: u8,
+    pub bsmod: u8,
+    pub acmod: u8,
+    pub lfeon: bool,
+    pub bit_rate_code: u8,
+}
+
+impl Default for Ac3Config {
+    fn default() -> Self {
+        Self {
+            fscod: 0, // 48 kHz
+            bsid: 8,
+            bsmod: 0,
+            acmod: 2, // 2/0
+            lfeon: false,
+            bit_rate_code: 10, // 192 kbit/s
+        }
+    }
+}
+
+/// E-AC-3 stream parameters, as carried in the `dec3` box.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Ec3Config {
+    /// Data rate in kbit/s.
+    pub data_rate: u16,
+    pub substreams: Vec<Ec3Substream>,
+    pub complexity_index_type_a: Option<u8>,
+}
+
+impl Default for Ec3Config {
+    fn default() -> Self {
+        Self {
+            data_rate: 192,
+            substreams: vec![Ec3Substream {
+                fscod: 0, // 48 kHz
+                bsid: 16,
+                acmod: 2, // 2/0
+                ..Ec3Substream::default()
+            }],
+            complexity_index_type_a: None,
+        }
+    }
+}
+
+/// FLAC stream parameters, as carried in the `dfLa` box.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct FlacConfig {
+    pub stream_info: FlacStreamInfo,
+    /// Metadata blocks to store after STREAMINFO.
+    pub metadata_blocks: Vec<FlacMetadataBlock>,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum MediaConfig {
     AvcConfig(AvcConfig),
     HevcConfig(HevcConfig),
     Vp9Config(Vp9Config),
+    Av1Config(Av1Config),
     AacConfig(AacConfig),
     TtxtConfig(TtxtConfig),
     OpusConfig(OpusConfig),
+    Ac3Config(Ac3Config),
+    Ec3Config(Ec3Config),
+    FlacConfig(FlacConfig),
 }
 
 #[derive(Debug)]
@@ -900,6 +970,9 @@ pub struct Mp4Sample {
     pub rendering_offset: i32,
     pub is_sync: bool,
     pub bytes: Bytes,
+    /// IV and subsample map of a sample of an encrypted track, `None` for
+    /// samples stored in the clear.
+    pub encryption: Option<SampleEncryptionEntry>,
 }
 
 #[derive(Debug)]
@@ -908,6 +981,56 @@ pub struct Mp4SampleMetadata {
     pub duration: u32,
     pub rendering_offset: i32,
     pub is_sync: bool,
+    /// Flags of a sample in a fragmented track. `None` for samples described
+    /// by the `moov` sample tables, which do not store them.
+    pub sample_flags: Option<SampleFlags>,
+}
+
+/// Sample flags as stored in `trex`, `tfhd` and `trun` boxes
+/// (ISO/IEC 14496-12, 8.8.3.1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SampleFlags {
+    pub is_leading: u8,
+    /// 1: depends on other samples, 2: does not (an I picture).
+    pub depends_on: u8,
+    /// 1: other samples depend on this one, 2: none do (disposable).
+    pub is_depended_on: u8,
+    pub has_redundancy: u8,
+    pub padding_value: u8,
+    pub is_non_sync_sample: bool,
+    pub degradation_priority: u16,
+}
+
+impl SampleFlags {
+    pub fn is_sync(&self) -> bool {
+        !self.is_non_sync_sample
+    }
+}
+
+impl From<u32> for SampleFlags {
+    fn from(flags: u32) -> Self {
+        Self {
+            is_leading: ((flags >> 26) & 0x3) as u8,
+            depends_on: ((flags >> 24) & 0x3) as u8,
+            is_depended_on: ((flags >> 22) & 0x3) as u8,
+            has_redundancy: ((flags >> 20) & 0x3) as u8,
+            padding_value: ((flags >> 17) & 0x7) as u8,
+            is_non_sync_sample: (flags >> 16) & 0x1 != 0,
+            degradation_priority: (flags & 0xffff) as u16,
+        }
+    }
+}
+
+impl From<SampleFlags> for u32 {
+    fn from(flags: SampleFlags) -> u32 {
+        ((flags.is_leading as u32 & 0x3) << 26)
+            | ((flags.depends_on as u32 & 0x3) << 24)
+            | ((flags.is_depended_on as u32 & 0x3) << 22)
+            | ((flags.has_redundancy as u32 & 0x3) << 20)
+            | ((flags.padding_value as u32 & 0x7) << 17)
+            | ((flags.is_non_sync_sample as u32) << 16)
+            | flags.degradation_priority as u32
+    }
 }
 
 impl PartialEq for Mp4Sample {
@@ -917,6 +1040,7 @@ impl PartialEq for Mp4Sample {
             && self.rendering_offset == other.rendering_offset
             && self.is_sync == other.is_sync
             && self.bytes.len() == other.bytes.len() // XXX for easy check
+            && self.encryption == other.encryption
     }
 }
 
@@ -948,7 +1072,9 @@ pub enum DataType {
     Binary = 0x000000,
     Text = 0x000001,
     Image = 0x00000D,
+    Png = 0x00000E,
     TempoCpil = 0x000015,
+    Bmp = 0x00001B,
 }
 
 #[allow(clippy::derivable_impls)]
@@ -965,33 +1091,128 @@ impl TryFrom<u32> for DataType {
             0x000000 => Ok(DataType::Binary),
             0x000001 => Ok(DataType::Text),
             0x00000D => Ok(DataType::Image),
+            0x00000E => Ok(DataType::Png),
             0x000015 => Ok(DataType::TempoCpil),
+            0x00001B => Ok(DataType::Bmp),
             _ => Err(Error::InvalidData("invalid data type")),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
 pub enum MetadataKey {
     Title,
     Year,
     Poster,
     Summary,
+    Artist,
+    AlbumArtist,
+    Album,
+    Genre,
+    /// The `gnre` item, an ID3v1 genre numbered from 1, read by
+    /// [Metadata::genre] when there is no free text genre.
+    GenreId,
+    Composer,
+    Comment,
+    TrackNumber,
+    DiscNumber,
+    Tempo,
+    Compilation,
+    EncoderTool,
+    SortTitle,
+    SortArtist,
+    SortAlbumArtist,
+    SortAlbum,
+    SortComposer,
+    Rating,
 }
 
 pub trait Metadata<'a> {
     /// The video's title
-    fn title(&self) -> Option<Cow<str>>;
+    fn title(&self) -> Option<Cow<'_, str>>;
     /// The video's release year
     fn year(&self) -> Option<u32>;
     /// The video's poster (cover art)
     fn poster(&self) -> Option<&[u8]>;
     /// The video's summary
-    fn summary(&self) -> Option<Cow<str>>;
+    fn summary(&self) -> Option<Cow<'_, str>>;
+    /// The artist
+    fn artist(&self) -> Option<Cow<'_, str>> {
+        None
+    }
+    /// The album artist
+    fn album_artist(&self) -> Option<Cow<'_, str>> {
+        None
+    }
+    /// The album
+    fn album(&self) -> Option<Cow<'_, str>> {
+        None
+    }
+    /// The genre, as free text or from the numeric `gnre` item
+    fn genre(&self) -> Option<Cow<'_, str>> {
+        None
+    }
+    /// The composer
+    fn composer(&self) -> Option<Cow<'_, str>> {
+        None
+    }
+    /// The comment
+    fn comment(&self) -> Option<Cow<'_, str>> {
+        None
+    }
+    /// The track number and the total number of tracks (0 if unknown)
+    fn track_number(&self) -> Option<(u16, u16)> {
+        None
+    }
+    /// The disc number and the total number of discs (0 if unknown)
+    fn disc_number(&self) -> Option<(u16, u16)> {
+        None
+    }
+    /// The tempo in beats per minute
+    fn tempo(&self) -> Option<u16> {
+        None
+    }
+    /// Whether the item is part of a compilation
+    fn compilation(&self) -> Option<bool> {
+        None
+    }
+    /// The tool that encoded the file
+    fn encoder_tool(&self) -> Option<Cow<'_, str>> {
+        None
+    }
+    /// The title used for sorting
+    fn sort_title(&self) -> Option<Cow<'_, str>> {
+        None
+    }
+    /// The artist used for sorting
+    fn sort_artist(&self) -> Option<Cow<'_, str>> {
+        None
+    }
+    /// The album artist used for sorting
+    fn sort_album_artist(&self) -> Option<Cow<'_, str>> {
+        None
+    }
+    /// The album used for sorting
+    fn sort_album(&self) -> Option<Cow<'_, str>> {
+        None
+    }
+    /// The composer used for sorting
+    fn sort_composer(&self) -> Option<Cow<'_, str>> {
+        None
+    }
+    /// The content rating: 0 for none, 1 or 4 for explicit, 2 for clean
+    fn rating(&self) -> Option<u8> {
+        None
+    }
+    /// The value of a free-form (`----`) item, e.g. `com.apple.iTunes` and
+    /// `iTunNORM`
+    fn free_form(&self, _mean: &str, _name: &str) -> Option<Cow<'_, str>> {
+        None
+    }
 }
 
 impl<'a, T: Metadata<'a>> Metadata<'a> for &'a T {
-    fn title(&self) -> Option<Cow<str>> {
+    fn title(&self) -> Option<Cow<'_, str>> {
         (**self).title()
     }
 
@@ -1003,13 +1224,85 @@ impl<'a, T: Metadata<'a>> Metadata<'a> for &'a T {
         (**self).poster()
     }
 
-    fn summary(&self) -> Option<Cow<str>> {
+    fn summary(&self) -> Option<Cow<'_, str>> {
         (**self).summary()
     }
+
+    fn artist(&self) -> Option<Cow<'_, str>> {
+        (**self).artist()
+    }
+
+    fn album_artist(&self) -> Option<Cow<'_, str>> {
+        (**self).album_artist()
+    }
+
+    fn album(&self) -> Option<Cow<'_, str>> {
+        (**self).album()
+    }
+
+    fn genre(&self) -> Option<Cow<'_, str>> {
+        (**self).genre()
+    }
+
+    fn composer(&self) -> Option<Cow<'_, str>> {
+        (**self).composer()
+    }
+
+    fn comment(&self) -> Option<Cow<'_, str>> {
+        (**self).comment()
+    }
+
+    fn track_number(&self) -> Option<(u16, u16)> {
+        (**self).track_number()
+    }
+
+    fn disc_number(&self) -> Option<(u16, u16)> {
+        (**self).disc_number()
+    }
+
+    fn tempo(&self) -> Option<u16> {
+        (**self).tempo()
+    }
+
+    fn compilation(&self) -> Option<bool> {
+        (**self).compilation()
+    }
+
+    fn encoder_tool(&self) -> Option<Cow<'_, str>> {
+        (**self).encoder_tool()
+    }
+
+    fn sort_title(&self) -> Option<Cow<'_, str>> {
+        (**self).sort_title()
+    }
+
+    fn sort_artist(&self) -> Option<Cow<'_, str>> {
+        (**self).sort_artist()
+    }
+
+    fn sort_album_artist(&self) -> Option<Cow<'_, str>> {
+        (**self).sort_album_artist()
+    }
+
+    fn sort_album(&self) -> Option<Cow<'_, str>> {
+        (**self).sort_album()
+    }
+
+    fn sort_composer(&self) -> Option<Cow<'_, str>> {
+        (**self).sort_composer()
+    }
+
+    fn rating(&self) -> Option<u8> {
+        (**self).rating()
+    }
+
+    fn free_form(&self, mean: &str, name: &str) -> Option<Cow<'_, str>> {
+        (**self).free_form(mean, name)
+    }
 }
 
 impl<'a, T: Metadata<'a>> Metadata<'a> for Option<T> {
-    fn title(&self) -> Option<Cow<str>> {
+    fn title(&self) -> Option<Cow<'_, str>> {
         self.as_ref().and_then(|t| t.title())
     }
 
@@ -1021,7 +1314,86 @@ impl<'a, T: Metadata<'a>> Metadata<'a> for Option<T> {
         self.as_ref().and_then(|t| t.poster())
     }
 
-    fn summary(&self) -> Option<Cow<str>> {
+    fn summary(&self) -> Option<Cow<'_, str>> {
         self.as_ref().and_then(|t| t.summary())
     }
+
+    fn artist(&self) -> Option<Cow<'_, str>> {
+        self.as_ref().and_then(|t| t.artist())
+    }
+
+    fn album_artist(&self) -> Option<Cow<'_, str>> {
+        self.as_ref().and_then(|t| t.album_artist())
+    }
+
+    fn album(&self) -> Option<Cow<'_, str>> {
+        self.as_ref().and_then(|t| t.album())
+    }
+
+    fn genre(&self) -> Option<Cow<'_, str>> {
+        self.as_ref().and_then(|t| t.genre())
+    }
+
+    fn composer(&self) -> Option<Cow<'_, str>> {
+        self.as_ref().and_then(|t| t.composer())
+    }
+
+    fn comment(&self) -> Option<Cow<'_, str>> {
+        self.as_ref().and_then(|t| t.comment())
+    }
+
+    fn track_number(&self) -> Option<(u16, u16)> {
+        self.as_ref().and_then(|t| t.track_number())
+    }
+
+    fn disc_number(&self) -> Option<(u16, u16)> {
+        self.as_ref().and_then(|t| t.disc_number())
+    }
+
+    fn tempo(&self) -> Option<u16> {
+        self.as_ref().and_then(|t| t.tempo())
+    }
+
+    fn compilation(&self) -> Option<bool> {
+        self.as_ref().and_then(|t| t.compilation())
+    }
+
+    fn encoder_tool(&self) -> Option<Cow<'_, str>> {
+        self.as_ref().and_then(|t| t.encoder_tool())
+    }
+
+    fn sort_title(&self) -> Option<Cow<'_, str>> {
+        self.as_ref().and_then(|t| t.sort_title())
+    }
+
+    fn sort_artist(&self) -> Option<Cow<'_, str>> {
+        self.as_ref().and_then(|t| t.sort_artist())
+    }
+
+    fn sort_album_artist(&self) -> Option<Cow<'_, str>> {
+        self.as_ref().and_then(|t| t.sort_album_artist())
+    }
+
+    fn sort_album(&self) -> Option<Cow<'_, str>> {
+        self.as_ref().and_then(|t| t.sort_album())
+    }
+
+    fn sort_composer(&self) -> Option<Cow<'_, str>> {
+        self.as_ref().and_then(|t| t.sort_composer())
+    }
+
+    fn rating(&self) -> Option<u8> {
+        self.as_ref().and_then(|t| t.rating())
+    }
+
+    fn free_form(&self, mean: &str, name: &str) -> Option<Cow<'_, str>> {
+        self.as_ref().and_then(|t| t.free_form(mean, name))
+    }
+}
+
+/// A chapter of the movie, lasting until the start of the next one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    pub start: Duration,
+    pub title: String,
 }
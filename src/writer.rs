@@ -1,12 +1,17 @@
 use byteorder::{BigEndian, WriteBytesExt};
 use std::cmp;
 use std::collections::HashMap;
-use std::io::{Seek, SeekFrom, Write};
+use std::convert::TryFrom;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::time::Duration;
 
 use crate::mp4box::*;
 use crate::track::Mp4TrackWriter;
 use crate::*;
 
+const MOVE_BUFFER_SIZE: usize = 64 * 1024;
+const INTERLEAVE_BUFFER_SIZE: u64 = 32 * 1024 * 1024;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Mp4Config {
     pub major_brand: FourCC,
@@ -22,6 +27,10 @@ pub struct Mp4Writer<W> {
     mdat_pos: u64,
     timescale: u32,
     duration: u64,
+    interleave_window: Option<Duration>,
+    interleave_buffer_size: u64,
+    metadata: Option<IlstBox>,
+    chapters: Vec<Chapter>,
 }
 
 impl<W> Mp4Writer<W> {
@@ -84,22 +93,82 @@ impl<W: Write + Seek> Mp4Writer<W> {
             mdat_pos,
             timescale,
             duration,
+            interleave_window: None,
+            interleave_buffer_size: INTERLEAVE_BUFFER_SIZE,
+            metadata: None,
+            chapters: Vec::new(),
         })
     }
 
-    pub fn add_track(&mut self, config: &TrackConfig) -> Result<u32> {  
+    /// Interleave the chunks of all tracks by decode time.
+    ///
+    /// By default a chunk is written as soon as it is full. With a window
+    /// set, full chunks are held back and written in decode time order: a
+    /// chunk is only written once no other track can still produce a chunk
+    /// starting more than `window` before it. Samples should be written in
+    /// roughly increasing decode time across tracks, as chunks are buffered
+    /// in memory until the other tracks catch up.
+    ///
+    /// Tracks without samples yet, and tracks ended with
+    /// [Mp4Writer::end_track], do not hold chunks back. Once more than the
+    /// buffer size set with [Mp4Writer::set_interleave_buffer_size] (32 MiB
+    /// by default) is queued, the oldest chunks are written regardless.
+    pub fn set_interleave_window(&mut self, window: Duration) {
+        self.interleave_window = Some(window);
+    }
+
+    /// Limit the bytes held back by the interleave window.
+    pub fn set_interleave_buffer_size(&mut self, size: u64) {
+        self.interleave_buffer_size = size;
+    }
+
+    /// Write `ilst` metadata in `moov/udta/meta` when the file is finished.
+    ///
+    /// ```rust
+    /// use mp4::{IlstBox, MetadataKey, Mp4Config, Mp4Writer};
+    /// use std::io::Cursor;
+    ///
+    /// # fn main() -> mp4::Result<()> {
+    /// # let config = Mp4Config {
+    /// #     major_brand: str::parse("M4A ").unwrap(),
+    /// #     minor_version: 0,
+    /// #     compatible_brands: vec![str::parse("isom").unwrap()],
+    /// #     timescale: 1000,
+    /// # };
+    /// let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config)?;
+    ///
+    /// let mut ilst = IlstBox::default();
+    /// ilst.set_text(MetadataKey::Title, "Big Buck Bunny");
+    /// ilst.set_text(MetadataKey::Artist, "Blender Foundation");
+    /// ilst.set_track_number(1, 12);
+    /// ilst.set_free_form("com.apple.iTunes", "MusicBrainz Track Id", "0e6b5a2f");
+    /// writer.set_metadata(ilst);
+    ///
+    /// writer.write_end()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn set_metadata(&mut self, ilst: IlstBox) {
+        self.metadata = Some(ilst);
+    }
+
+    /// Write chapters when the file is finished, both as a disabled `tx3g`
+    /// text track referenced with `chap` by the audio and video tracks, and
+    /// as a Nero `chpl` box. The `chpl` box is left out beyond 255 chapters,
+    /// and titles are cut to 255 bytes in it.
+    pub fn set_chapters(&mut self, chapters: Vec<Chapter>) {
+        self.chapters = chapters;
+        self.chapters.sort_by_key(|chapter| chapter.start);
+    }
+
+    pub fn add_track(&mut self, config: &TrackConfig) -> Result<u32> {
         let track_id = match config.track_id {
-            Some(track_id) =>  track_id,
-            None => self.tracks.len() as u32 + 1,  
-        };  
+            Some(track_id) => track_id,
+            None => self.tracks.len() as u32 + 1,
+        };
         let track = Mp4TrackWriter::new(track_id, config)?;
         match self.tracks.insert(track_id, track) {
-            Some(_) => {
-                return Err(Error::InvalidData("track_id already exists"));
-            }
-            None => {
-                Ok(track_id)
-            }
+            Some(_) => Err(Error::InvalidData("track_id already exists")),
+            None => Ok(track_id),
         }
     }
 
@@ -127,16 +196,68 @@ impl<W: Write + Seek> Mp4Writer<W> {
         }
 
         let track_dur = if let Some(ref mut track) = self.tracks.get_mut(&track_id) {
-            track.write_sample(&mut self.writer, sample, self.timescale)?
+            track.write_sample(sample, self.timescale)?
         } else {
             return Err(Error::TrakNotFound(track_id));
         };
 
         self.update_durations(track_dur);
+        self.write_pending_chunks(false)?;
 
         Ok(())
     }
 
+    /// Mark the end of a track, so it no longer holds back the chunks of the
+    /// other tracks. No more samples can be written to it.
+    pub fn end_track(&mut self, track_id: u32) -> Result<()> {
+        match self.tracks.get_mut(&track_id) {
+            Some(track) => track.end(),
+            None => return Err(Error::TrakNotFound(track_id)),
+        }
+        self.write_pending_chunks(false)
+    }
+
+    /// Write queued chunks in decode time order. Unless `flush` is set, stop
+    /// at the first chunk starting beyond the interleave window, as long as
+    /// the queued chunks fit in the interleave buffer.
+    fn write_pending_chunks(&mut self, flush: bool) -> Result<()> {
+        loop {
+            let next = self
+                .tracks
+                .iter()
+                .filter_map(|(track_id, track)| {
+                    track.pending_chunk_time().map(|time| (time, *track_id))
+                })
+                .min();
+            let (time, track_id) = match next {
+                Some(next) => next,
+                None => return Ok(()),
+            };
+
+            if let (false, Some(window)) = (flush, self.interleave_window) {
+                let open_chunk_time = self
+                    .tracks
+                    .iter()
+                    .filter(|(id, _)| **id != track_id)
+                    .filter_map(|(_, track)| track.open_chunk_time())
+                    .min();
+                let pending_size: u64 =
+                    self.tracks.values().map(|track| track.pending_size()).sum();
+                if let Some(open_chunk_time) = open_chunk_time {
+                    if time > open_chunk_time + window
+                        && pending_size <= self.interleave_buffer_size
+                    {
+                        return Ok(());
+                    }
+                }
+            }
+
+            if let Some(track) = self.tracks.get_mut(&track_id) {
+                track.write_pending_chunk(&mut self.writer)?;
+            }
+        }
+    }
+
     fn update_mdat_size(&mut self) -> Result<()> {
         let mdat_end = self.writer.stream_position()?;
         let mdat_size = mdat_end - self.mdat_pos;
@@ -153,19 +274,147 @@ impl<W: Write + Seek> Mp4Writer<W> {
         Ok(())
     }
 
-    pub fn write_end(&mut self) -> Result<()> {
+    /// Add the text track holding the chapters, returning its id.
+    fn write_chapter_track(&mut self) -> Result<Option<u32>> {
+        if self.chapters.is_empty() {
+            return Ok(None);
+        }
+
+        let track_id = self.tracks.keys().max().copied().unwrap_or(0) + 1;
+        let config = TrackConfig {
+            track_id: Some(track_id),
+            track_type: TrackType::Subtitle,
+            timescale: self.timescale,
+            media_conf: MediaConfig::TtxtConfig(TtxtConfig {}),
+            ..TrackConfig::default()
+        };
+        let mut track = Mp4TrackWriter::new(track_id, &config)?;
+
+        let timescale = self.timescale;
+        let to_ticks =
+            |time: Duration| (time.as_nanos() * timescale as u128 / 1_000_000_000) as u64;
+        // The first chapter also covers any time before its start.
+        let mut start_time = 0;
+        let mut track_dur = 0;
+        for (i, chapter) in self.chapters.iter().enumerate() {
+            let end_time = match self.chapters.get(i + 1) {
+                Some(next) => to_ticks(next.start),
+                None => cmp::max(self.duration, to_ticks(chapter.start) + 1),
+            };
+            let title = truncate_str(&chapter.title, u16::MAX as usize).as_bytes();
+            let mut bytes = Vec::with_capacity(2 + title.len());
+            bytes.extend_from_slice(&(title.len() as u16).to_be_bytes());
+            bytes.extend_from_slice(title);
+            let sample = Mp4Sample {
+                start_time,
+                duration: u32::try_from(end_time.saturating_sub(start_time))
+                    .map_err(|_| Error::InvalidData("chapter too long"))?,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: Bytes::from(bytes),
+                encryption: None,
+            };
+            track_dur = track.write_sample(&sample, timescale)?;
+            start_time = end_time;
+        }
+        self.update_durations(track_dur);
+
+        self.tracks.insert(track_id, track);
+        Ok(Some(track_id))
+    }
+
+    fn finish_moov(&mut self) -> Result<MoovBox> {
         let mut moov = MoovBox::default();
 
-        for(_, track) in self.tracks.iter_mut() {
+        let chapter_track_id = self.write_chapter_track()?;
+        for track in self.tracks.values_mut() {
+            track.close_chunk();
+        }
+        self.write_pending_chunks(true)?;
+        for (_, track) in self.tracks.iter_mut() {
             moov.traks.push(track.write_end(&mut self.writer)?);
         }
         self.update_mdat_size()?;
 
+        let track_ids: Vec<u32> = moov.traks.iter().map(|trak| trak.tkhd.track_id).collect();
+        let references = moov
+            .traks
+            .iter()
+            .filter_map(|trak| trak.tref.as_ref())
+            .flat_map(|tref| tref.references.iter());
+        for reference in references {
+            if let Some(track_id) = reference
+                .track_ids
+                .iter()
+                .find(|track_id| !track_ids.contains(track_id))
+            {
+                return Err(Error::TrakNotFound(*track_id));
+            }
+        }
+
+        if let Some(chapter_track_id) = chapter_track_id {
+            for trak in moov.traks.iter_mut() {
+                if trak.tkhd.track_id == chapter_track_id {
+                    trak.tkhd.flags &= !(tkhd::TrackFlag::TrackEnabled as u32);
+                    continue;
+                }
+                match TrackType::try_from(&trak.mdia.hdlr.handler_type) {
+                    Ok(TrackType::Video) | Ok(TrackType::Audio) => {
+                        trak.tref
+                            .get_or_insert_with(TrefBox::default)
+                            .references
+                            .push(TrackReference {
+                                reference_type: TrackReferenceType::Chapter,
+                                track_ids: vec![chapter_track_id],
+                            });
+                    }
+                    _ => {}
+                }
+            }
+        }
+
         moov.mvhd.timescale = self.timescale;
         moov.mvhd.duration = self.duration;
         if moov.mvhd.duration > (u32::MAX as u64) {
             moov.mvhd.version = 1
         }
+
+        let meta = self.metadata.as_ref().map(|ilst| MetaBox::Mdir {
+            ilst: Some(ilst.clone()),
+            unknown_boxes: Vec::new(),
+            child_order: ChildOrder::default(),
+        });
+        let chpl = if !self.chapters.is_empty() && self.chapters.len() <= u8::MAX as usize {
+            let chapters = self
+                .chapters
+                .iter()
+                .map(|chapter| ChplChapter {
+                    start: (chapter.start.as_nanos() * ChplBox::TIMESCALE as u128 / 1_000_000_000)
+                        as u64,
+                    title: truncate_str(&chapter.title, u8::MAX as usize).to_string(),
+                })
+                .collect();
+            Some(ChplBox {
+                version: 1,
+                flags: 0,
+                chapters,
+            })
+        } else {
+            None
+        };
+        if meta.is_some() || chpl.is_some() {
+            moov.udta = Some(UdtaBox {
+                meta,
+                chpl,
+                unknown_boxes: Vec::new(),
+                child_order: ChildOrder::default(),
+            });
+        }
+        Ok(moov)
+    }
+
+    pub fn write_end(&mut self) -> Result<()> {
+        let moov = self.finish_moov()?;
         moov.write_box(&mut self.writer)?;
         Ok(())
     }
@@ -174,3 +423,227 @@ impl<W: Write + Seek> Mp4Writer<W> {
         self.tracks.keys().cloned().collect()
     }
 }
+
+impl<W: Read + Write + Seek> Mp4Writer<W> {
+    /// Finish the file like [Mp4Writer::write_end], but place `moov` before
+    /// `mdat` so the file can be played while it is being downloaded.
+    ///
+    /// The media data is moved in place through the writer in fixed size
+    /// blocks, and every chunk offset is shifted by the size of `moov`. Tracks
+    /// whose offsets no longer fit in 32 bits are switched from `stco` to
+    /// `co64`.
+    pub fn write_end_faststart(&mut self) -> Result<()> {
+        let moov = self.finish_moov()?;
+        let mdat_end = self.writer.stream_position()?;
+
+        let moov = relocate_moov(&moov, 0);
+        let moov_size = moov.box_size();
+        move_bytes(&mut self.writer, self.mdat_pos, mdat_end, moov_size)?;
+        self.writer.seek(SeekFrom::Start(self.mdat_pos))?;
+        moov.write_box(&mut self.writer)?;
+        self.writer.seek(SeekFrom::Start(mdat_end + moov_size))?;
+        Ok(())
+    }
+}
+
+/// Shift the chunk offsets of `moov` by its own size plus `shift`, for it
+/// to be written ahead of the media it describes.
+pub(crate) fn relocate_moov(moov: &MoovBox, shift: u64) -> MoovBox {
+    // Upgrading to co64 grows moov, which in turn grows the shift, so
+    // repeat until the size settles.
+    let mut moov_size = moov.box_size();
+    loop {
+        let mut shifted = moov.clone();
+        for trak in shifted.traks.iter_mut() {
+            shift_chunk_offsets(trak, moov_size + shift);
+        }
+        if shifted.box_size() == moov_size {
+            return shifted;
+        }
+        moov_size = shifted.box_size();
+    }
+}
+
+fn shift_chunk_offsets(trak: &mut TrakBox, shift: u64) {
+    let stbl = &mut trak.mdia.minf.stbl;
+    let mut co64 = if let Some(ref stco) = stbl.stco {
+        Co64Box::from(stco)
+    } else if let Some(ref co64) = stbl.co64 {
+        co64.clone()
+    } else {
+        return;
+    };
+    for offset in co64.entries.iter_mut() {
+        *offset += shift;
+    }
+
+    if let Ok(stco) = StcoBox::try_from(&co64) {
+        stbl.stco = Some(stco);
+        stbl.co64 = None;
+    } else {
+        stbl.stco = None;
+        stbl.co64 = Some(co64);
+    }
+}
+
+/// Move the bytes in `start..end` forward by `shift`, starting from the end
+/// so that overlapping ranges are not clobbered.
+fn move_bytes<S: Read + Write + Seek>(
+    stream: &mut S,
+    start: u64,
+    end: u64,
+    shift: u64,
+) -> Result<()> {
+    let mut buf = vec![0u8; MOVE_BUFFER_SIZE];
+    let mut pos = end;
+    while pos > start {
+        let len = cmp::min(buf.len() as u64, pos - start) as usize;
+        pos -= len as u64;
+        stream.seek(SeekFrom::Start(pos))?;
+        stream.read_exact(&mut buf[..len])?;
+        stream.seek(SeekFrom::Start(pos + shift))?;
+        stream.write_all(&buf[..len])?;
+    }
+    Ok(())
+}
+
+/// Cut `s` to at most `len` bytes on a character boundary.
+fn truncate_str(s: &str, len: usize) -> &str {
+    if s.len() <= len {
+        return s;
+    }
+    let mut end = len;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_shift_chunk_offsets_upgrades_to_co64() {
+        let mut trak = TrakBox::default();
+        trak.mdia.minf.stbl.stco = Some(StcoBox {
+            version: 0,
+            flags: 0,
+            entries: vec![48, u32::MAX - 100],
+        });
+
+        shift_chunk_offsets(&mut trak, 50);
+        assert_eq!(trak.mdia.minf.stbl.stco.as_ref().unwrap().entries[0], 98);
+
+        shift_chunk_offsets(&mut trak, 100);
+        assert!(trak.mdia.minf.stbl.stco.is_none());
+        let co64 = trak.mdia.minf.stbl.co64.as_ref().unwrap();
+        assert_eq!(co64.entries, vec![198, u32::MAX as u64 + 50]);
+    }
+
+    fn interleaved_writer() -> (Mp4Writer<Cursor<Vec<u8>>>, u32, u32) {
+        let config = Mp4Config {
+            major_brand: str::parse("isom").unwrap(),
+            minor_version: 512,
+            compatible_brands: vec![str::parse("isom").unwrap()],
+            timescale: 1000,
+        };
+        let mut writer = Mp4Writer::write_start(Cursor::new(Vec::new()), &config).unwrap();
+        writer.set_interleave_window(Duration::from_millis(500));
+        let video_track = writer
+            .add_track(&TrackConfig {
+                timescale: 1000,
+                media_conf: MediaConfig::AvcConfig(AvcConfig {
+                    width: 320,
+                    height: 240,
+                    seq_param_set: vec![0x67, 0x64, 0x00, 0x0d],
+                    pic_param_set: vec![0x68, 0xeb, 0xe3, 0xcb],
+                }),
+                ..TrackConfig::default()
+            })
+            .unwrap();
+        let audio_track = writer
+            .add_track(&TrackConfig::from(AacConfig::default()))
+            .unwrap();
+        (writer, video_track, audio_track)
+    }
+
+    fn write_samples(writer: &mut Mp4Writer<Cursor<Vec<u8>>>, track_id: u32, count: u64) {
+        for i in 0..count {
+            let sample = Mp4Sample {
+                start_time: i * 100,
+                duration: 100,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: Bytes::from(vec![0; 100]),
+                encryption: None,
+            };
+            writer.write_sample(track_id, &sample).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_interleave_ended_track() {
+        let (mut writer, video_track, audio_track) = interleaved_writer();
+        write_samples(&mut writer, audio_track, 10);
+        writer.end_track(audio_track).unwrap();
+        let mdat_start = writer.writer.get_ref().len();
+
+        // Ten seconds of video after the audio stopped at one second.
+        write_samples(&mut writer, video_track, 100);
+        assert!(writer.writer.get_ref().len() >= mdat_start + 8 * 1000);
+        let sample = Mp4Sample {
+            start_time: 1000,
+            duration: 100,
+            rendering_offset: 0,
+            is_sync: true,
+            bytes: Bytes::from(vec![0; 100]),
+            encryption: None,
+        };
+        assert!(writer.write_sample(audio_track, &sample).is_err());
+        writer.write_end().unwrap();
+    }
+
+    #[test]
+    fn test_interleave_empty_track() {
+        let (mut writer, video_track, _) = interleaved_writer();
+        let mdat_start = writer.writer.get_ref().len();
+
+        write_samples(&mut writer, video_track, 100);
+        assert!(writer.writer.get_ref().len() >= mdat_start + 8 * 1000);
+        writer.write_end().unwrap();
+    }
+
+    #[test]
+    fn test_interleave_buffer_size() {
+        let (mut writer, video_track, audio_track) = interleaved_writer();
+        writer.set_interleave_buffer_size(2000);
+        write_samples(&mut writer, audio_track, 1);
+        let mdat_start = writer.writer.get_ref().len();
+
+        // The stalled audio track holds the video back until the buffer is
+        // full.
+        write_samples(&mut writer, video_track, 100);
+        let written = writer.writer.get_ref().len() - mdat_start;
+        assert!(written >= 7 * 1000);
+        writer.write_end().unwrap();
+    }
+
+    #[test]
+    fn test_move_bytes() {
+        let mut stream = Cursor::new((0..200u32).map(|i| i as u8).collect::<Vec<u8>>());
+        move_bytes(&mut stream, 10, 200, 30).unwrap();
+
+        let data = stream.into_inner();
+        assert_eq!(data.len(), 230);
+        assert_eq!(
+            &data[..40],
+            &(0..40u32).map(|i| i as u8).collect::<Vec<u8>>()[..]
+        );
+        assert_eq!(
+            &data[40..],
+            &(10..200u32).map(|i| i as u8).collect::<Vec<u8>>()[..]
+        );
+    }
+}
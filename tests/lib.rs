@@ -1,9 +1,21 @@
+use mp4::mux_streams;
+use mp4::nal::{self, NalFormat};
 use mp4::{
-    AudioObjectType, AvcProfile, ChannelConfig, MediaType, Metadata, Mp4Reader, SampleFreqIndex,
-    TrackType,
+    AacConfig, Ac3Config, AudioObjectType, Av1Config, AvcConfig, AvcProfile, BoxHeader, BoxNode,
+    BoxTree, BoxType, BoxVisitor, ChannelConfig, Chapter, ChplChapter, DataType, Ec3Config,
+    Ec3Substream, ElementaryStream, EncryptionConfig, FlacConfig, FlacMetadataBlock,
+    FlacStreamInfo, FourCC, FragmentConfig, FrmaBox, HevcConfig, IlstBox, MediaConfig, MediaType,
+    Metadata, MetadataKey, MoofBox, MoovBox, Mp4Box, Mp4Config, Mp4Event, Mp4FragmentWriter,
+    Mp4Parser, Mp4Reader, Mp4SampleMetadata, Mp4StreamWriter, Mp4Writer, OpusConfig, ParsedBox,
+    PsshBox, ReadBox, SaioBox, SaizBox, SampleEncryptionEntry, SampleFlags, SampleFreqIndex,
+    SchiBox, SchmBox, SencBox, SinfBox, SubsampleEntry, TencBox, TrackConfig, TrackReference,
+    TrackReferenceType, TrackType, TtxtConfig, Vp9Config, WriteBox, HEADER_SIZE, SCHEME_CENC,
 };
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryInto;
 use std::fs::{self, File};
-use std::io::BufReader;
+use std::io::{BufReader, Cursor};
 use std::time::Duration;
 
 #[test]
@@ -44,6 +56,7 @@ fn test_read_mp4() {
             rendering_offset: 0,
             is_sync: true,
             bytes: mp4::Bytes::from(vec![0x0u8; 751]),
+            encryption: None,
         }
     );
     let eos = mp4.read_sample(1, 2).unwrap();
@@ -61,6 +74,7 @@ fn test_read_mp4() {
             rendering_offset: 0,
             is_sync: true,
             bytes: mp4::Bytes::from(vec![0x0u8; 179]),
+            encryption: None,
         }
     );
 
@@ -73,6 +87,7 @@ fn test_read_mp4() {
             rendering_offset: 0,
             is_sync: true,
             bytes: mp4::Bytes::from(vec![0x0u8; 180]),
+            encryption: None,
         }
     );
 
@@ -85,6 +100,7 @@ fn test_read_mp4() {
             rendering_offset: 0,
             is_sync: true,
             bytes: mp4::Bytes::from(vec![0x0u8; 160]),
+            encryption: None,
         }
     );
 
@@ -153,6 +169,224 @@ fn test_read_extended_audio_object_type() {
     assert_eq!(track.bitrate(), 839250);
 }
 
+#[test]
+fn test_unknown_boxes_round_trip() {
+    let mp4 = get_reader("tests/samples/extended_audio_object_type.mp4");
+    let iods = BoxType::from(u32::from(str::parse::<FourCC>("iods").unwrap()));
+    assert_eq!(mp4.moov.unknown_boxes.len(), 1);
+    assert_eq!(mp4.moov.unknown_boxes[0].box_type, iods);
+    assert_eq!(mp4.moov.unknown_boxes[0].box_size(), 27);
+
+    // Rewriting moov and moof keeps every box in its place. The bytes may
+    // differ where a box is re-encoded, as esds is.
+    let exact = [
+        "tests/samples/minimal_init.mp4",
+        "tests/samples/minimal_fragment.m4s",
+    ];
+    for path in [
+        "tests/samples/minimal.mp4",
+        "tests/samples/minimal_init.mp4",
+        "tests/samples/minimal_fragment.m4s",
+        "tests/samples/extended_audio_object_type.mp4",
+        "tests/samples/big_buck_bunny_metadata.m4v",
+    ] {
+        let data = fs::read(path).unwrap();
+        let tree = BoxTree::read(&mut Cursor::new(&data), data.len() as u64).unwrap();
+        for node in tree.boxes.iter() {
+            let mut reader = Cursor::new(&data);
+            reader.set_position(node.data_offset());
+            let mut buf = Vec::new();
+            match node.box_type {
+                BoxType::MoovBox => MoovBox::read_box(&mut reader, node.size)
+                    .unwrap()
+                    .write_box(&mut buf)
+                    .unwrap(),
+                BoxType::MoofBox => MoofBox::read_box(&mut reader, node.size)
+                    .unwrap()
+                    .write_box(&mut buf)
+                    .unwrap(),
+                _ => continue,
+            };
+
+            let input = &data[node.offset as usize..(node.offset + node.size) as usize];
+            let output = BoxTree::read(&mut Cursor::new(&buf), buf.len() as u64).unwrap();
+            assert_eq!(
+                box_paths(&output.boxes),
+                box_paths(std::slice::from_ref(node)),
+                "{}",
+                path
+            );
+            if exact.contains(&path) {
+                assert!(buf == input, "{}", path);
+            }
+        }
+    }
+}
+
+/// Paths of a box and its descendants, in file order.
+fn box_paths(nodes: &[BoxNode]) -> Vec<String> {
+    let mut paths = Vec::new();
+    for node in nodes {
+        paths.push(node.box_type.to_string());
+        for path in box_paths(&node.children) {
+            paths.push(format!("{}/{}", node.box_type, path));
+        }
+    }
+    paths
+}
+
+#[test]
+fn test_read_box_tree() {
+    let f = File::open("tests/samples/minimal.mp4").unwrap();
+    let size = f.metadata().unwrap().len();
+    let tree = BoxTree::read(&mut BufReader::new(f), size).unwrap();
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+
+    let top: Vec<BoxType> = tree.boxes.iter().map(|node| node.box_type).collect();
+    assert!(top.contains(&BoxType::FtypBox));
+    assert_eq!(tree.boxes.iter().map(|node| node.size).sum::<u64>(), size);
+
+    assert_eq!(tree.get_all("moov/trak").len(), 2);
+    assert!(tree.get("moov/trak[2]").is_none());
+    let tkhd = tree.get("moov/trak[1]/tkhd").unwrap();
+    match tkhd.parsed {
+        Some(ParsedBox::Tkhd(ref tkhd)) => assert_eq!(tkhd.track_id, 2),
+        _ => panic!("tkhd box not parsed"),
+    }
+    let mvhd = tree.get("moov/mvhd").unwrap();
+    match mvhd.parsed {
+        Some(ParsedBox::Mvhd(ref mvhd)) => assert_eq!(mvhd.timescale, mp4.timescale()),
+        _ => panic!("mvhd box not parsed"),
+    }
+
+    // Sample entries are parsed and walked.
+    let avc1 = tree.get("moov/trak[0]/mdia/minf/stbl/stsd/avc1").unwrap();
+    assert!(matches!(avc1.parsed, Some(ParsedBox::Avc1(_))));
+    assert!(avc1.get("avcC").is_some());
+
+    // The samples lie in the mdat box.
+    let mdat = tree.get("mdat").unwrap();
+    let stco = tree.get("moov/trak[0]/mdia/minf/stbl/stco").unwrap();
+    match stco.parsed {
+        Some(ParsedBox::Stco(ref stco)) => {
+            let chunk_offset = stco.entries[0] as u64;
+            assert!(chunk_offset >= mdat.data_offset());
+            assert!(chunk_offset < mdat.offset + mdat.size);
+        }
+        _ => panic!("stco box not parsed"),
+    }
+
+    struct Count {
+        boxes: usize,
+        max_depth: usize,
+    }
+    impl BoxVisitor for Count {
+        fn enter(&mut self, _node: &BoxNode, depth: usize) -> bool {
+            self.boxes += 1;
+            self.max_depth = self.max_depth.max(depth);
+            true
+        }
+    }
+    let mut count = Count {
+        boxes: 0,
+        max_depth: 0,
+    };
+    tree.visit(&mut count);
+    assert!(count.boxes > 20);
+    // moov/trak/mdia/minf/stbl/stsd/avc1/avcC
+    assert_eq!(count.max_depth, 7);
+}
+
+#[test]
+fn test_push_parser() {
+    let data = fs::read("tests/samples/minimal.mp4").unwrap();
+    let mut mp4 = get_reader("tests/samples/minimal.mp4");
+
+    let mut parser = Mp4Parser::new();
+    let mut top_boxes = Vec::new();
+    let mut samples: HashMap<(u32, u32), Vec<u8>> = HashMap::new();
+    let mut sample_times = HashMap::new();
+    for chunk in data.chunks(7) {
+        for event in parser.push(chunk).unwrap() {
+            match event {
+                Mp4Event::BoxStart { box_type, .. } => top_boxes.push(box_type),
+                Mp4Event::Moov(moov) => assert_eq!(*moov, mp4.moov),
+                Mp4Event::Sample(sample) => {
+                    let bytes = samples
+                        .entry((sample.track_id, sample.sample_id))
+                        .or_default();
+                    assert_eq!(bytes.len(), sample.position as usize);
+                    bytes.extend_from_slice(sample.data);
+                    if sample.is_last() {
+                        sample_times.insert(
+                            (sample.track_id, sample.sample_id),
+                            (sample.start_time, sample.duration, sample.is_sync),
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    assert!(parser.finish().unwrap().is_empty());
+    assert_eq!(parser.offset(), data.len() as u64);
+    assert_eq!(
+        top_boxes,
+        vec![
+            BoxType::FtypBox,
+            BoxType::MoovBox,
+            BoxType::FreeBox,
+            BoxType::MdatBox
+        ]
+    );
+    assert_eq!(parser.tracks().len(), 2);
+
+    assert_eq!(samples.len(), 4);
+    for track_id in [1, 2] {
+        for sample_id in 1..=mp4.sample_count(track_id).unwrap() {
+            let sample = mp4.read_sample(track_id, sample_id).unwrap().unwrap();
+            assert_eq!(samples[&(track_id, sample_id)], sample.bytes);
+            assert_eq!(
+                sample_times[&(track_id, sample_id)],
+                (sample.start_time, sample.duration, sample.is_sync)
+            );
+        }
+    }
+}
+
+#[test]
+fn test_push_parser_fragments() {
+    let fragment = fs::read("tests/samples/minimal_fragment.m4s").unwrap();
+    let mut data = fs::read("tests/samples/minimal_init.mp4").unwrap();
+    data.extend_from_slice(&fragment);
+
+    let mut parser = Mp4Parser::new();
+    let mut events = Vec::new();
+    let mut sample = Vec::new();
+    for chunk in data.chunks(13) {
+        for event in parser.push(chunk).unwrap() {
+            match event {
+                Mp4Event::Sample(part) => {
+                    assert_eq!((part.track_id, part.sample_id), (1, 1));
+                    assert_eq!((part.start_time, part.duration), (0, 512));
+                    sample.extend_from_slice(part.data);
+                }
+                Mp4Event::Ftyp(_) => events.push("ftyp"),
+                Mp4Event::Moov(_) => events.push("moov"),
+                Mp4Event::Moof(moof) => {
+                    assert_eq!(moof.trafs.len(), 1);
+                    events.push("moof");
+                }
+                _ => {}
+            }
+        }
+    }
+    parser.finish().unwrap();
+    assert_eq!(events, vec!["ftyp", "moov", "moof"]);
+    // The only sample fills the mdat box following the 108-byte moof box.
+    assert_eq!(sample, &fragment[116..]);
+}
+
 fn get_reader(path: &str) -> Mp4Reader<BufReader<File>> {
     let f = File::open(path).unwrap();
     let f_size = f.metadata().unwrap().len();
@@ -175,6 +409,216 @@ fn test_read_metadata() {
     let poster = metadata.poster().unwrap();
     assert_eq!(poster.len(), want_poster.len());
     assert_eq!(poster, want_poster.as_slice());
+
+    assert_eq!(metadata.artist(), Some("Blender Foundation".into()));
+    assert_eq!(metadata.composer(), Some("Blender Foundation".into()));
+    assert_eq!(metadata.encoder_tool(), Some("Lavf58.29.100".into()));
+    assert_eq!(metadata.album(), None);
+}
+
+#[test]
+fn test_write_metadata() {
+    let mp4 = get_reader("tests/samples/big_buck_bunny_metadata.m4v");
+    let mut ilst = IlstBox::default();
+    ilst.set_text(MetadataKey::Title, &mp4.metadata().title().unwrap());
+    ilst.set_poster(mp4.metadata().poster().unwrap().to_vec(), DataType::Image);
+    ilst.set_text(MetadataKey::AlbumArtist, "Blender Foundation");
+    ilst.set_text(MetadataKey::Genre, "Animation");
+    ilst.set_text(MetadataKey::SortTitle, "Bunny, Big Buck");
+    ilst.set_disc_number(1, 1);
+    ilst.set_compilation(false);
+    ilst.set_free_form("com.apple.iTunes", "iTunSMPB", " 00000000 00000840");
+
+    let config = Mp4Config {
+        major_brand: str::parse("M4V ").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer.set_metadata(ilst);
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let written = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let metadata = written.metadata();
+    assert_eq!(metadata.title(), Some("Big Buck Bunny".into()));
+    assert_eq!(metadata.poster(), mp4.metadata().poster());
+    assert_eq!(metadata.album_artist(), Some("Blender Foundation".into()));
+    assert_eq!(metadata.genre(), Some("Animation".into()));
+    assert_eq!(metadata.sort_title(), Some("Bunny, Big Buck".into()));
+    assert_eq!(metadata.disc_number(), Some((1, 1)));
+    assert_eq!(metadata.compilation(), Some(false));
+    assert_eq!(metadata.track_number(), None);
+    assert_eq!(
+        metadata.free_form("com.apple.iTunes", "iTunSMPB"),
+        Some(" 00000000 00000840".into())
+    );
+}
+
+#[test]
+fn test_metadata_defaults() {
+    // Implementations written before the music items were added still build.
+    struct Movie;
+
+    impl<'a> Metadata<'a> for Movie {
+        fn title(&self) -> Option<Cow<'_, str>> {
+            Some("Big Buck Bunny".into())
+        }
+
+        fn year(&self) -> Option<u32> {
+            Some(2008)
+        }
+
+        fn poster(&self) -> Option<&[u8]> {
+            None
+        }
+
+        fn summary(&self) -> Option<Cow<'_, str>> {
+            None
+        }
+    }
+
+    assert_eq!(Movie.title(), Some("Big Buck Bunny".into()));
+    assert_eq!(Movie.artist(), None);
+    assert_eq!(Movie.rating(), None);
+    assert_eq!(Movie.free_form("com.apple.iTunes", "iTunNORM"), None);
+}
+
+#[test]
+fn test_write_chapters() {
+    let config = Mp4Config {
+        major_brand: str::parse("M4A ").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let audio_track = writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+    for i in 0..10u64 {
+        let sample = mp4::Mp4Sample {
+            start_time: i * 1000,
+            duration: 1000,
+            rendering_offset: 0,
+            is_sync: true,
+            bytes: mp4::Bytes::from(vec![0xa0; 10]),
+            encryption: None,
+        };
+        writer.write_sample(audio_track, &sample).unwrap();
+    }
+    let chapters = vec![
+        Chapter {
+            start: Duration::from_secs(4),
+            title: String::from("Verse"),
+        },
+        Chapter {
+            start: Duration::ZERO,
+            title: String::from("Intro"),
+        },
+        Chapter {
+            start: Duration::from_millis(7500),
+            title: String::from("Outro \u{266b}"),
+        },
+    ];
+    writer.set_chapters(chapters.clone());
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.duration(), Duration::from_secs(10));
+
+    let mut want = chapters;
+    want.sort_by_key(|chapter| chapter.start);
+    assert_eq!(mp4.chapters().unwrap(), want);
+
+    // The audio track points to the disabled chapter track.
+    let audio = &mp4.tracks()[&audio_track].trak;
+    let chapter_track_id = audio
+        .tref
+        .as_ref()
+        .unwrap()
+        .track_ids(TrackReferenceType::Chapter)[0];
+    let chapter_track = &mp4.tracks()[&chapter_track_id];
+    assert_eq!(chapter_track.trak.tkhd.flags & 1, 0);
+    assert_eq!(chapter_track.sample_count(), 3);
+    assert_eq!(chapter_track.duration(), Duration::from_secs(10));
+
+    // Without the track reference, the chapters come from chpl.
+    for trak in mp4.moov.traks.iter_mut() {
+        trak.tref = None;
+    }
+    assert_eq!(mp4.chapters().unwrap(), want);
+
+    // Chapters starting too late for a Duration are skipped.
+    let chpl = mp4.moov.udta.as_mut().unwrap().chpl.as_mut().unwrap();
+    chpl.chapters.push(ChplChapter {
+        start: u64::MAX,
+        title: String::from("Overflow"),
+    });
+    assert_eq!(mp4.chapters().unwrap(), want);
+}
+
+#[test]
+fn test_write_track_references() {
+    let config = Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let audio_track = writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+    let subtitle_track = writer
+        .add_track(&TrackConfig {
+            references: vec![
+                TrackReference {
+                    reference_type: TrackReferenceType::Sync,
+                    track_ids: vec![audio_track],
+                },
+                TrackReference {
+                    reference_type: TrackReferenceType::Other(FourCC::from(*b"forc")),
+                    track_ids: vec![audio_track],
+                },
+            ],
+            ..TrackConfig::from(MediaConfig::TtxtConfig(TtxtConfig {}))
+        })
+        .unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert!(mp4.tracks()[&audio_track].references().is_empty());
+    let references = mp4.tracks()[&subtitle_track].references();
+    assert_eq!(references.len(), 2);
+    assert_eq!(references[0].reference_type, TrackReferenceType::Sync);
+    assert_eq!(references[0].track_ids, vec![audio_track]);
+    assert_eq!(
+        FourCC::from(references[1].reference_type),
+        FourCC::from(*b"forc")
+    );
+
+    // References must point to tracks of the file.
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    writer
+        .add_track(&TrackConfig {
+            references: vec![TrackReference {
+                reference_type: TrackReferenceType::ContentDescription,
+                track_ids: vec![7],
+            }],
+            ..TrackConfig::from(AacConfig::default())
+        })
+        .unwrap();
+    assert!(matches!(
+        writer.write_end(),
+        Err(mp4::Error::TrakNotFound(7))
+    ));
 }
 
 #[test]
@@ -204,8 +648,1304 @@ fn test_read_fragments() {
             rendering_offset: 0,
             is_sync: true,
             bytes: mp4::Bytes::from(vec![0x0u8; 751]),
+            encryption: None,
         }
     );
     let eos = mp4_fragment.read_sample(1, 2);
     assert!(eos.is_err());
 }
+
+#[test]
+fn test_write_fragments() {
+    let config = Mp4Config {
+        major_brand: str::parse("iso6").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("iso6").unwrap(), str::parse("dash").unwrap()],
+        timescale: 1000,
+    };
+    let fragment_config = FragmentConfig {
+        fragment_duration: Duration::from_secs(1),
+        split_on_sync: true,
+    };
+    let mut writer =
+        Mp4FragmentWriter::write_start(Cursor::new(Vec::new()), &config, &fragment_config).unwrap();
+
+    let video_track = writer
+        .add_track(&TrackConfig {
+            timescale: 1000,
+            media_conf: MediaConfig::AvcConfig(AvcConfig {
+                width: 320,
+                height: 240,
+                seq_param_set: vec![0x67, 0x64, 0x00, 0x0d],
+                pic_param_set: vec![0x68, 0xeb, 0xe3, 0xcb],
+            }),
+            ..TrackConfig::default()
+        })
+        .unwrap();
+    let audio_track = writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+
+    // 3 seconds of 25fps video with a sync sample every 25 frames and 3
+    // seconds of 1000 ticks audio.
+    for i in 0..75u32 {
+        let sample = mp4::Mp4Sample {
+            start_time: i as u64 * 40,
+            duration: 40,
+            rendering_offset: if i % 25 == 0 { 0 } else { 40 },
+            is_sync: i % 25 == 0,
+            bytes: mp4::Bytes::from(vec![i as u8; 100 + i as usize]),
+            encryption: None,
+        };
+        writer.write_sample(video_track, &sample).unwrap();
+        if i % 25 == 0 {
+            let sample = mp4::Mp4Sample {
+                start_time: (i / 25) as u64 * 1000,
+                duration: 1000,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: mp4::Bytes::from(vec![0xa0; 10]),
+                encryption: None,
+            };
+            writer.write_sample(audio_track, &sample).unwrap();
+        }
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    assert!(mp4.is_fragmented());
+    assert_eq!(mp4.moofs.len(), 3);
+    assert_eq!(mp4.moov.mvex.as_ref().unwrap().trexs.len(), 2);
+    assert_eq!(mp4.sample_count(video_track).unwrap(), 75);
+    assert_eq!(mp4.sample_count(audio_track).unwrap(), 3);
+
+    for i in 0..75u32 {
+        let sample = mp4.read_sample(video_track, i + 1).unwrap().unwrap();
+        assert_eq!(sample.start_time, i as u64 * 40);
+        assert_eq!(sample.duration, 40);
+        assert_eq!(sample.rendering_offset, if i % 25 == 0 { 0 } else { 40 });
+        assert_eq!(sample.is_sync, i % 25 == 0);
+        assert_eq!(sample.bytes, vec![i as u8; 100 + i as usize]);
+
+        let metadata = mp4
+            .read_sample_metadata(video_track, i + 1)
+            .unwrap()
+            .unwrap();
+        let sample_flags = metadata.sample_flags.unwrap();
+        assert_eq!(sample_flags.is_sync(), i % 25 == 0);
+        assert_eq!(sample_flags.depends_on, if i % 25 == 0 { 2 } else { 1 });
+    }
+    let sample = mp4.read_sample(audio_track, 3).unwrap().unwrap();
+    assert_eq!(sample.start_time, 2000);
+    assert_eq!(sample.bytes, vec![0xa0; 10]);
+}
+
+#[test]
+fn test_write_encrypted_fragments() {
+    let kid = [0x11; 16];
+    let key = [0x22; 16];
+    let pssh = PsshBox {
+        version: 1,
+        system_id: [0x33; 16],
+        kids: vec![kid],
+        ..PsshBox::default()
+    };
+    let configs = vec![
+        EncryptionConfig::cenc(kid, key, [0, 0, 0, 0, 0, 0, 0, 0xfe]),
+        EncryptionConfig::cbcs(kid, key, [0x44; 16]),
+    ];
+
+    // An SEI NAL unit left in the clear and a 100 byte IDR slice.
+    let mut video_sample = vec![0, 0, 0, 4, 0x06, 1, 2, 3, 0, 0, 0, 100, 0x65];
+    video_sample.extend((0..99).map(|i| i as u8));
+    let audio_sample: Vec<u8> = (0..40).map(|i| 0xa0 ^ i as u8).collect();
+
+    for mut config in configs {
+        config.psshs = vec![pssh.clone()];
+
+        let mp4_config = Mp4Config {
+            major_brand: str::parse("iso6").unwrap(),
+            minor_version: 0,
+            compatible_brands: vec![str::parse("iso6").unwrap()],
+            timescale: 1000,
+        };
+        let fragment_config = FragmentConfig {
+            fragment_duration: Duration::from_secs(1),
+            split_on_sync: true,
+        };
+        let mut writer =
+            Mp4FragmentWriter::write_start(Vec::new(), &mp4_config, &fragment_config).unwrap();
+        let video_track = writer
+            .add_track(&TrackConfig::from(AvcConfig {
+                width: 320,
+                height: 240,
+                seq_param_set: vec![0x67, 0x64, 0x00, 0x0d],
+                pic_param_set: vec![0x68, 0xeb, 0xe3, 0xcb],
+            }))
+            .unwrap();
+        let audio_track = writer
+            .add_track(&TrackConfig::from(AacConfig::default()))
+            .unwrap();
+        writer.set_track_encryption(video_track, &config).unwrap();
+        writer.set_track_encryption(audio_track, &config).unwrap();
+        assert!(writer.set_track_encryption(3, &config).is_err());
+
+        for i in 0..50u32 {
+            let sample = mp4::Mp4Sample {
+                start_time: i as u64 * 40,
+                duration: 40,
+                rendering_offset: 0,
+                is_sync: i % 25 == 0,
+                bytes: mp4::Bytes::from(video_sample.clone()),
+                encryption: None,
+            };
+            writer.write_sample(video_track, &sample).unwrap();
+            let sample = mp4::Mp4Sample {
+                start_time: i as u64 * 40,
+                duration: 40,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: mp4::Bytes::from(audio_sample.clone()),
+                encryption: None,
+            };
+            writer.write_sample(audio_track, &sample).unwrap();
+        }
+        assert!(writer.set_track_encryption(video_track, &config).is_err());
+        writer.write_end().unwrap();
+
+        let data = writer.into_writer();
+        let size = data.len() as u64;
+        let mut mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), size).unwrap();
+        assert_eq!(mp4.moofs.len(), 2);
+        assert_eq!(mp4.moov.psshs, vec![pssh.clone()]);
+
+        let video = mp4.tracks().get(&video_track).unwrap();
+        assert_eq!(video.box_type().unwrap().to_string(), "encv");
+        assert_eq!(video.scheme_type(), Some(config.scheme_type));
+        assert_eq!(video.default_kid(), Some(kid));
+        let audio = mp4.tracks().get(&audio_track).unwrap();
+        assert_eq!(audio.box_type().unwrap().to_string(), "enca");
+        assert_eq!(audio.original_format().unwrap().to_string(), "mp4a");
+
+        // The saio of each traf points at the entries of its senc.
+        let mut reader = Cursor::new(&data);
+        let mut moof_index = 0;
+        while reader.position() < size {
+            let offset = reader.position();
+            let header = BoxHeader::read(&mut reader).unwrap();
+            if header.name == BoxType::MoofBox {
+                for traf in mp4.moofs[moof_index].trafs.iter() {
+                    let senc = traf.senc.as_ref().unwrap();
+                    let aux_info_offset = offset + traf.saio.as_ref().unwrap().offsets[0];
+                    let aux_info = &data[aux_info_offset as usize..][..senc.data.len()];
+                    assert_eq!(aux_info, &senc.data[..]);
+                }
+                moof_index += 1;
+            }
+            reader.set_position(offset + header.size);
+        }
+        assert_eq!(moof_index, 2);
+
+        let sample = mp4.read_sample(video_track, 2).unwrap().unwrap();
+        let encryption = sample.encryption.unwrap();
+        assert_eq!(
+            encryption.subsamples,
+            vec![
+                SubsampleEntry {
+                    bytes_of_clear_data: 8,
+                    bytes_of_protected_data: 0,
+                },
+                SubsampleEntry {
+                    bytes_of_clear_data: if config.scheme_type == SCHEME_CENC {
+                        8
+                    } else {
+                        5
+                    },
+                    bytes_of_protected_data: if config.scheme_type == SCHEME_CENC {
+                        96
+                    } else {
+                        99
+                    },
+                },
+            ]
+        );
+        assert_eq!(&sample.bytes[..13], &video_sample[..13]);
+        assert_ne!(&sample.bytes[13..29], &video_sample[13..29]);
+        let sample = mp4.read_sample(audio_track, 2).unwrap().unwrap();
+        assert!(sample.encryption.unwrap().subsamples.is_empty());
+        assert_ne!(&sample.bytes[..], &audio_sample[..]);
+
+        let mut keys = HashMap::new();
+        keys.insert(kid, key);
+        mp4.set_decryption_keys(keys);
+        for i in 1..=50 {
+            let sample = mp4.read_sample(video_track, i).unwrap().unwrap();
+            assert_eq!(sample.bytes, video_sample);
+            assert_eq!(sample.encryption, None);
+            let sample = mp4.read_sample(audio_track, i).unwrap().unwrap();
+            assert_eq!(sample.bytes, audio_sample);
+        }
+    }
+}
+
+#[test]
+fn test_sample_flags() {
+    let flags = SampleFlags::from(0x0101_0000);
+    assert_eq!(
+        flags,
+        SampleFlags {
+            depends_on: 1,
+            is_non_sync_sample: true,
+            ..SampleFlags::default()
+        }
+    );
+    assert!(!flags.is_sync());
+
+    let flags = SampleFlags {
+        is_leading: 3,
+        depends_on: 2,
+        is_depended_on: 1,
+        has_redundancy: 2,
+        padding_value: 5,
+        is_non_sync_sample: false,
+        degradation_priority: 0xbeef,
+    };
+    assert_eq!(u32::from(flags), 0x0e6a_beef);
+    assert_eq!(SampleFlags::from(u32::from(flags)), flags);
+}
+
+#[test]
+fn test_write_av1() {
+    let config = Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap(), str::parse("av01").unwrap()],
+        timescale: 1000,
+    };
+    let av1_config = Av1Config {
+        width: 1280,
+        height: 720,
+        seq_profile: 0,
+        seq_level_idx_0: 5,
+        chroma_subsampling_x: true,
+        chroma_subsampling_y: true,
+        config_obus: vec![
+            0x0A, 0x0B, 0x00, 0x00, 0x00, 0x2C, 0xCF, 0x7F, 0x0D, 0xF0, 0x22,
+        ],
+        ..Av1Config::default()
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::new()), &config).unwrap();
+    let track_id = writer
+        .add_track(&TrackConfig::from(av1_config.clone()))
+        .unwrap();
+    let sample = mp4::Mp4Sample {
+        start_time: 0,
+        duration: 40,
+        rendering_offset: 0,
+        is_sync: true,
+        bytes: mp4::Bytes::from(vec![0x12, 0x00, 0x32, 0x01, 0x10]),
+        encryption: None,
+    };
+    writer.write_sample(track_id, &sample).unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = mp4.tracks().get(&track_id).unwrap();
+    assert_eq!(track.media_type().unwrap(), MediaType::AV1);
+    assert_eq!(track.box_type().unwrap().to_string(), "av01");
+    assert_eq!(track.width(), 1280);
+    assert_eq!(track.height(), 720);
+
+    let av1c = &track.trak.mdia.minf.stbl.stsd.av01.as_ref().unwrap().av1c;
+    assert_eq!(av1c.seq_level_idx_0, 5);
+    assert_eq!(av1c.bit_depth(), 8);
+    assert_eq!(av1c.config_obus, av1_config.config_obus);
+}
+
+#[test]
+fn test_write_dolby_audio() {
+    let config = Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+    };
+    let ac3_config = Ac3Config {
+        acmod: 7,
+        lfeon: true,
+        bit_rate_code: 15,
+        ..Ac3Config::default()
+    };
+    let ec3_config = Ec3Config {
+        data_rate: 1024,
+        substreams: vec![Ec3Substream {
+            bsid: 16,
+            acmod: 7,
+            lfeon: true,
+            num_dep_sub: 1,
+            chan_loc: 0x02,
+            ..Ec3Substream::default()
+        }],
+        complexity_index_type_a: None,
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::new()), &config).unwrap();
+    let ac3_track = writer.add_track(&TrackConfig::from(ac3_config)).unwrap();
+    let ec3_track = writer.add_track(&TrackConfig::from(ec3_config)).unwrap();
+    for track_id in [ac3_track, ec3_track] {
+        let sample = mp4::Mp4Sample {
+            start_time: 0,
+            duration: 32,
+            rendering_offset: 0,
+            is_sync: true,
+            bytes: mp4::Bytes::from(vec![0x0B, 0x77, 0x00, 0x00]),
+            encryption: None,
+        };
+        writer.write_sample(track_id, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let track = mp4.tracks().get(&ac3_track).unwrap();
+    assert_eq!(track.track_type().unwrap(), TrackType::Audio);
+    assert_eq!(track.media_type().unwrap(), MediaType::AC3);
+    assert_eq!(track.box_type().unwrap().to_string(), "ac-3");
+    assert_eq!(
+        track.sample_freq_index().unwrap(),
+        SampleFreqIndex::Freq48000
+    );
+    assert_eq!(track.channel_config().unwrap(), ChannelConfig::FiveOne);
+    assert_eq!(track.bitrate(), 448000);
+
+    let track = mp4.tracks().get(&ec3_track).unwrap();
+    assert_eq!(track.media_type().unwrap(), MediaType::EAC3);
+    assert_eq!(track.box_type().unwrap().to_string(), "ec-3");
+    assert_eq!(
+        track.sample_freq_index().unwrap(),
+        SampleFreqIndex::Freq48000
+    );
+    assert_eq!(track.channel_config().unwrap(), ChannelConfig::SevenOne);
+    assert_eq!(track.bitrate(), 1024000);
+    assert_eq!(
+        track
+            .trak
+            .mdia
+            .minf
+            .stbl
+            .stsd
+            .ec3
+            .as_ref()
+            .unwrap()
+            .channel_count,
+        8
+    );
+}
+
+#[test]
+fn test_write_flac() {
+    let config = Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+    };
+    let flac_config = FlacConfig {
+        stream_info: FlacStreamInfo {
+            sample_rate: 192000,
+            channels: 6,
+            bits_per_sample: 24,
+            total_samples: 192000,
+            ..FlacStreamInfo::default()
+        },
+        metadata_blocks: vec![FlacMetadataBlock {
+            block_type: 1, // PADDING
+            data: vec![0; 16],
+        }],
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::new()), &config).unwrap();
+    let track_id = writer
+        .add_track(&TrackConfig::from(flac_config.clone()))
+        .unwrap();
+    let sample = mp4::Mp4Sample {
+        start_time: 0,
+        duration: 1000,
+        rendering_offset: 0,
+        is_sync: true,
+        bytes: mp4::Bytes::from(vec![0xFF, 0xF8, 0x00, 0x00]),
+        encryption: None,
+    };
+    writer.write_sample(track_id, &sample).unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let track = mp4.tracks().get(&track_id).unwrap();
+    assert_eq!(track.track_type().unwrap(), TrackType::Audio);
+    assert_eq!(track.media_type().unwrap(), MediaType::FLAC);
+    assert_eq!(track.box_type().unwrap().to_string(), "fLaC");
+    assert_eq!(track.sample_rate().unwrap(), 192000);
+    assert_eq!(track.channel_count().unwrap(), 6);
+    assert_eq!(track.bits_per_sample().unwrap(), 24);
+    assert_eq!(track.channel_config().unwrap(), ChannelConfig::FiveOne);
+
+    let flac = track.trak.mdia.minf.stbl.stsd.flac.as_ref().unwrap();
+    assert_eq!(flac.sample_rate.value(), 0);
+    assert_eq!(flac.dfla.stream_info, flac_config.stream_info);
+    assert_eq!(flac.dfla.metadata_blocks, flac_config.metadata_blocks);
+}
+
+#[test]
+fn test_write_avc_from_annexb() {
+    // Access unit delimiter, SPS, PPS and the start of an IDR slice.
+    let sps = [
+        0x67, 0x64, 0x00, 0x0D, 0xAC, 0xD9, 0x41, 0x41, 0xFA, 0x10, 0x00, 0x00, 0x03, 0x00, 0x10,
+        0x00, 0x00, 0x03, 0x03, 0x20, 0xF1, 0x42, 0x99, 0x60,
+    ];
+    let pps = [0x68, 0xEB, 0xE3, 0xCB, 0x22, 0xC0];
+    let mut access_unit = vec![0, 0, 0, 1, 0x09, 0xf0, 0, 0, 0, 1];
+    access_unit.extend_from_slice(&sps);
+    access_unit.extend_from_slice(&[0, 0, 0, 1]);
+    access_unit.extend_from_slice(&pps);
+    access_unit.extend_from_slice(&[0, 0, 0, 1, 0x65, 0x88, 0x84, 0x00]);
+
+    let avc_config = AvcConfig::from_annexb(&access_unit).unwrap();
+    let config = Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::new()), &config).unwrap();
+    let track_id = writer.add_track(&TrackConfig::from(avc_config)).unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = mp4.tracks().get(&track_id).unwrap();
+    assert_eq!(track.width(), 320);
+    assert_eq!(track.height(), 240);
+    assert_eq!(track.video_profile().unwrap(), AvcProfile::AvcHigh);
+    assert_eq!(track.sequence_parameter_set().unwrap(), sps);
+    assert_eq!(track.picture_parameter_set().unwrap(), pps);
+
+    let avcc = &track.trak.mdia.minf.stbl.stsd.avc1.as_ref().unwrap().avcc;
+    let ext = avcc.high_profile_ext.as_ref().unwrap();
+    assert_eq!(ext.chroma_format, 1);
+    assert_eq!(ext.bit_depth_luma_minus8, 0);
+    assert_eq!(ext.bit_depth_chroma_minus8, 0);
+}
+
+#[test]
+fn test_write_hevc_from_annexb() {
+    let vps = [0x40, 0x01, 0x0c, 0x01];
+    // Main profile, level 3.1, 1280x720 after the conformance window.
+    let sps = [
+        0x42, 0x01, 0x01, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00, 0x00,
+        0x03, 0x00, 0x5d, 0xa0, 0x02, 0x80, 0x80, 0x2e, 0x1f, 0x13, 0xc0,
+    ];
+    let pps = [0x44, 0x01, 0xc1, 0x72];
+    let mut access_unit = Vec::new();
+    for nal in [&vps[..], &sps, &pps, &[0x26, 0x01, 0xaf, 0x08]] {
+        access_unit.extend_from_slice(&[0, 0, 0, 1]);
+        access_unit.extend_from_slice(nal);
+    }
+
+    let hevc_config = HevcConfig::from_annexb(&access_unit)
+        .unwrap()
+        .with_use_hvc1(true);
+    let config = Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::new()), &config).unwrap();
+    let track_id = writer
+        .add_track(&TrackConfig::from(hevc_config.clone()))
+        .unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = mp4.tracks().get(&track_id).unwrap();
+    assert_eq!(track.media_type().unwrap(), MediaType::H265);
+    assert_eq!(track.width(), 1280);
+    assert_eq!(track.height(), 720);
+    assert_eq!(track.video_parameter_set().unwrap(), vps);
+    assert_eq!(track.sequence_parameter_set().unwrap(), sps);
+    assert_eq!(track.picture_parameter_set().unwrap(), pps);
+
+    // Remuxing keeps the hvcC as is.
+    assert_eq!(HevcConfig::from_track(track).unwrap(), hevc_config);
+}
+
+#[test]
+fn test_sample_to_annexb() {
+    let mut mp4 = get_reader("tests/samples/minimal.mp4");
+    let sample = mp4.read_sample(1, 1).unwrap().unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.nal_format().unwrap(), (NalFormat::Avc, 4));
+
+    let nal_units = track
+        .nal_units(&sample)
+        .unwrap()
+        .collect::<mp4::Result<Vec<_>>>()
+        .unwrap();
+    assert!(nal_units
+        .iter()
+        .any(|nal| NalFormat::Avc.is_random_access(nal)));
+
+    // The IDR picture gets the SPS and PPS of the avcC in-band.
+    let annexb = track.sample_to_annexb(&sample).unwrap();
+    let sps = track.sequence_parameter_set().unwrap();
+    let pps = track.picture_parameter_set().unwrap();
+    let annexb_units: Vec<&[u8]> = nal::AnnexBNalUnits::new(&annexb).collect();
+    assert_eq!(annexb_units.len(), nal_units.len() + 2);
+    let idr = annexb_units
+        .iter()
+        .position(|nal| NalFormat::Avc.is_random_access(nal))
+        .unwrap();
+    assert_eq!(annexb_units[idr - 2..idr], [sps, pps]);
+
+    // Back to length-prefixed samples.
+    let plain = nal::to_annexb(&sample.bytes, 4).unwrap();
+    assert_eq!(track.sample_from_annexb(&plain).unwrap(), sample.bytes);
+    let bytes = track.sample_from_annexb(&annexb).unwrap();
+    assert_eq!(bytes.len(), sample.bytes.len() + 8 + sps.len() + pps.len());
+
+    // Audio tracks have no NAL units.
+    assert!(mp4.tracks().get(&2).unwrap().nal_format().is_err());
+}
+
+#[test]
+fn test_demux_minimal() {
+    let mut mp4 = get_reader("tests/samples/minimal.mp4");
+
+    let h264 = mp4.demux_track(1, Vec::new()).unwrap();
+    let nal_units: Vec<&[u8]> = nal::AnnexBNalUnits::new(&h264).collect();
+    let sps = mp4.tracks()[&1].sequence_parameter_set().unwrap();
+    assert!(nal_units.contains(&sps));
+    assert_eq!(h264[..4], [0, 0, 0, 1]);
+
+    // One ADTS frame per AAC sample, AAC-LC at 48 kHz in stereo.
+    let aac = mp4.demux_track(2, Vec::new()).unwrap();
+    let mut frames = 0;
+    let mut pos = 0;
+    while pos < aac.len() {
+        assert_eq!(aac[pos..pos + 3], [0xff, 0xf1, 0x4c]);
+        pos += (aac[pos + 3] as usize & 0x3) << 11
+            | (aac[pos + 4] as usize) << 3
+            | (aac[pos + 5] as usize) >> 5;
+        frames += 1;
+    }
+    assert_eq!(pos, aac.len());
+    assert_eq!(frames, mp4.sample_count(2).unwrap());
+}
+
+#[test]
+fn test_demux_vp9_and_opus() {
+    let config = Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::new()), &config).unwrap();
+    let vp9_track = writer
+        .add_track(&TrackConfig::from(Vp9Config {
+            width: 320,
+            height: 240,
+            ..Vp9Config::default()
+        }))
+        .unwrap();
+    let opus_track = writer
+        .add_track(&TrackConfig::from(OpusConfig::default()))
+        .unwrap();
+    for i in 0..3u64 {
+        let frame = mp4::Mp4Sample {
+            start_time: i * 40,
+            duration: 40,
+            rendering_offset: 0,
+            is_sync: i == 0,
+            bytes: mp4::Bytes::from(vec![0x82, 0x49, 0x83, i as u8]),
+            encryption: None,
+        };
+        writer.write_sample(vp9_track, &frame).unwrap();
+        let packet = mp4::Mp4Sample {
+            start_time: i * 20,
+            duration: 20,
+            rendering_offset: 0,
+            is_sync: true,
+            bytes: mp4::Bytes::from(vec![0xfc, 0xff, 0xfe, i as u8]),
+            encryption: None,
+        };
+        writer.write_sample(opus_track, &packet).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let ivf = mp4.demux_track(vp9_track, Vec::new()).unwrap();
+    assert_eq!(&ivf[..4], b"DKIF");
+    assert_eq!(&ivf[8..12], b"VP90");
+    assert_eq!(ivf[12..16], [64, 1, 240, 0]);
+    assert_eq!(ivf[24..28], 3u32.to_le_bytes());
+    assert_eq!(ivf.len(), 32 + 3 * (12 + 4));
+    // The last frame: size, timestamp and data.
+    assert_eq!(
+        ivf[32 + 2 * 16..],
+        [4, 0, 0, 0, 80, 0, 0, 0, 0, 0, 0, 0, 0x82, 0x49, 0x83, 2]
+    );
+
+    // 20 ms packets, at 48 kHz in granule positions.
+    assert_eq!(mp4.tracks()[&opus_track].timescale(), 1000);
+    let ogg = mp4.demux_track(opus_track, Vec::new()).unwrap();
+    let mut pages = Vec::new();
+    let mut pos = 0;
+    while pos < ogg.len() {
+        assert_eq!(&ogg[pos..pos + 4], b"OggS");
+        let header_type = ogg[pos + 5];
+        let granule = u64::from_le_bytes(ogg[pos + 6..pos + 14].try_into().unwrap());
+        let segments = ogg[pos + 26] as usize;
+        let lacing = &ogg[pos + 27..pos + 27 + segments];
+        let data_start = pos + 27 + segments;
+        let data_size: usize = lacing.iter().map(|size| *size as usize).sum();
+        pages.push((
+            header_type,
+            granule,
+            &ogg[data_start..data_start + data_size],
+        ));
+        pos = data_start + data_size;
+    }
+    assert_eq!(pages.len(), 5);
+    assert_eq!(pages[0].0, 0x02);
+    assert_eq!(&pages[0].2[..8], b"OpusHead");
+    assert_eq!(pages[0].2[9], 2); // channels
+    assert_eq!(&pages[1].2[..8], b"OpusTags");
+    assert_eq!(pages[2], (0, 960, &[0xfc, 0xff, 0xfe, 0][..]));
+    assert_eq!(pages[4], (0x04, 2880, &[0xfc, 0xff, 0xfe, 2][..]));
+}
+
+#[test]
+fn test_mux_demuxed_streams() {
+    let mut mp4 = get_reader("tests/samples/minimal.mp4");
+    let h264 = mp4.demux_track(1, Vec::new()).unwrap();
+    let aac = mp4.demux_track(2, Vec::new()).unwrap();
+
+    // A VP9 key frame then an inter frame, 40 ms apart in a 1/1000 time base.
+    let mut ivf = b"DKIF".to_vec();
+    ivf.extend_from_slice(&[0, 0, 32, 0]);
+    ivf.extend_from_slice(b"VP90");
+    ivf.extend_from_slice(&[
+        64, 1, 240, 0, 0xe8, 0x03, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0,
+    ]);
+    for (pts, frame) in [
+        (0u64, &[0x82, 0x49, 0x83, 0x42, 0x40][..]),
+        (40, &[0x86, 0x00]),
+    ] {
+        ivf.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        ivf.extend_from_slice(&pts.to_le_bytes());
+        ivf.extend_from_slice(frame);
+    }
+
+    let streams = vec![
+        ElementaryStream::from_annexb(&h264, NalFormat::Avc, None).unwrap(),
+        ElementaryStream::from_adts(&aac).unwrap(),
+        ElementaryStream::from_ivf(&ivf).unwrap(),
+    ];
+    let config = Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+    };
+    let data = mux_streams(Cursor::new(Vec::new()), &config, &streams)
+        .unwrap()
+        .into_inner();
+    let size = data.len() as u64;
+    let mut muxed = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    // The SPS timing gives 25 fps.
+    let sample = muxed.read_sample(1, 1).unwrap().unwrap();
+    assert!(sample.is_sync);
+    assert_eq!(sample.duration, 2);
+    let video = &muxed.tracks()[&1];
+    assert_eq!(video.media_type().unwrap(), MediaType::H264);
+    assert_eq!((video.width(), video.height()), (320, 240));
+    assert_eq!(video.timescale(), 50);
+    assert_eq!(
+        video.sequence_parameter_set().unwrap(),
+        mp4.tracks()[&1].sequence_parameter_set().unwrap()
+    );
+
+    // The AAC frames come back as they were.
+    let audio = &muxed.tracks()[&2];
+    assert_eq!(audio.media_type().unwrap(), MediaType::AAC);
+    assert_eq!(
+        audio.sample_freq_index().unwrap(),
+        SampleFreqIndex::Freq48000
+    );
+    assert_eq!(audio.channel_config().unwrap(), ChannelConfig::Mono);
+    assert_eq!(audio.timescale(), 48000);
+    let sample_count = mp4.sample_count(2).unwrap();
+    assert_eq!(muxed.sample_count(2).unwrap(), sample_count);
+    for sample_id in 1..=sample_count {
+        let original = mp4.read_sample(2, sample_id).unwrap().unwrap();
+        let sample = muxed.read_sample(2, sample_id).unwrap().unwrap();
+        assert_eq!(sample.bytes, original.bytes);
+        assert_eq!(sample.start_time, (sample_id as u64 - 1) * 1024);
+    }
+
+    let vp9 = &muxed.tracks()[&3];
+    assert_eq!(vp9.media_type().unwrap(), MediaType::VP9);
+    let vpcc = &vp9.trak.mdia.minf.stbl.stsd.vp09.as_ref().unwrap().vpcc;
+    assert_eq!(vpcc.bit_depth, 8);
+    assert_eq!(vpcc.color_primaries, 1);
+    let frames: Vec<_> = (1..=2)
+        .map(|sample_id| muxed.read_sample(3, sample_id).unwrap().unwrap())
+        .collect();
+    assert!(frames[0].is_sync && !frames[1].is_sync);
+    assert_eq!((frames[1].start_time, frames[1].duration), (40, 40));
+}
+
+#[test]
+fn test_mux_start_alignment() {
+    // I0 P6 B2 B4 with pic_order_cnt_type 0, presented one frame late.
+    let mut h264 = Vec::new();
+    for nal in [
+        &[0x67, 0x4d, 0x00, 0x1e, 0xed, 0x82, 0x83, 0xf2][..],
+        &[0x68, 0xce, 0x38, 0x80],
+        &[0x65, 0x88, 0x84, 0x05, 0xa8],
+        &[0x41, 0x9a, 0x23, 0x2d, 0x40],
+        &[0x01, 0x9e, 0x41, 0x2d, 0x40],
+        &[0x01, 0x9e, 0x42, 0x2d, 0x40],
+    ] {
+        h264.extend_from_slice(&[0, 0, 0, 1]);
+        h264.extend_from_slice(nal);
+    }
+    // AAC LC, 48 kHz mono ADTS frames with a 4 byte payload.
+    let mut adts = Vec::new();
+    for _ in 0..8 {
+        adts.extend_from_slice(&[0xff, 0xf1, 0x4c, 0x40, 0x01, 0x7f, 0xfc, 1, 2, 3, 4]);
+    }
+
+    let video = ElementaryStream::from_annexb(&h264, NalFormat::Avc, Some((25, 1))).unwrap();
+    assert_eq!(video.media_time, 1);
+    let streams = vec![video, ElementaryStream::from_adts(&adts).unwrap()];
+    let config = Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+    };
+    let data = mux_streams(Cursor::new(Vec::new()), &config, &streams)
+        .unwrap()
+        .into_inner();
+    let size = data.len() as u64;
+    let mut muxed = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    // Both tracks present their first sample at the start of the movie.
+    for track_id in [1, 2] {
+        let media_time = muxed.tracks()[&track_id].get_media_time().unwrap();
+        let sample_count = muxed.sample_count(track_id).unwrap();
+        let first_presented = (1..=sample_count)
+            .map(|sample_id| {
+                let sample = muxed.read_sample(track_id, sample_id).unwrap().unwrap();
+                sample.start_time as i64 + sample.rendering_offset as i64
+            })
+            .min()
+            .unwrap();
+        assert_eq!(first_presented, media_time);
+    }
+    let elst = |track_id: u32| {
+        let trak = &muxed.tracks()[&track_id].trak;
+        trak.edts.as_ref().unwrap().elst.as_ref().unwrap().entries[0].clone()
+    };
+    assert_eq!(elst(1).media_time, 1);
+    assert_eq!(elst(1).segment_duration, 160);
+    assert_eq!(elst(2).media_time, 0);
+    assert_eq!(
+        elst(2).segment_duration,
+        muxed.tracks()[&2].trak.tkhd.duration
+    );
+}
+
+#[test]
+fn test_read_cenc() {
+    // W3C Clear Key
+    const CLEAR_KEY_SYSTEM_ID: [u8; 16] = [
+        0x10, 0x77, 0xef, 0xec, 0xc0, 0xb2, 0x4d, 0x02, 0xac, 0xe3, 0x3c, 0x1e, 0x52, 0xe2, 0xfb,
+        0x4b,
+    ];
+
+    let config = Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::new()), &config).unwrap();
+    let track_id = writer
+        .add_track(&TrackConfig::from(AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x0d],
+            pic_param_set: vec![0x68, 0xeb, 0xe3, 0xcb],
+        }))
+        .unwrap();
+    for i in 0..2 {
+        let sample = mp4::Mp4Sample {
+            start_time: i * 40,
+            duration: 40,
+            rendering_offset: 0,
+            is_sync: i == 0,
+            bytes: mp4::Bytes::from(vec![0; 16]),
+            encryption: None,
+        };
+        writer.write_sample(track_id, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+    let data = writer.into_writer().into_inner();
+
+    // Split the file before moov, which the writer puts last.
+    let mut reader = Cursor::new(&data);
+    let moov_offset = loop {
+        let offset = reader.position();
+        let header = BoxHeader::read(&mut reader).unwrap();
+        if header.name == BoxType::MoovBox {
+            break offset as usize;
+        }
+        reader.set_position(offset + header.size);
+    };
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), data.len() as u64).unwrap();
+    let mut moov = mp4.moov.clone();
+
+    let entries = vec![
+        SampleEncryptionEntry {
+            iv: vec![1; 8],
+            subsamples: vec![SubsampleEntry {
+                bytes_of_clear_data: 4,
+                bytes_of_protected_data: 12,
+            }],
+        },
+        SampleEncryptionEntry {
+            iv: vec![2; 8],
+            subsamples: vec![
+                SubsampleEntry {
+                    bytes_of_clear_data: 2,
+                    bytes_of_protected_data: 6,
+                },
+                SubsampleEntry {
+                    bytes_of_clear_data: 8,
+                    bytes_of_protected_data: 0,
+                },
+            ],
+        },
+    ];
+    let stbl = &mut moov.traks[0].mdia.minf.stbl;
+    stbl.stsd.sinf = Some(SinfBox {
+        frma: FrmaBox {
+            original_format: str::parse("avc1").unwrap(),
+        },
+        schm: Some(SchmBox {
+            scheme_type: str::parse("cenc").unwrap(),
+            scheme_version: 0x0001_0000,
+            ..SchmBox::default()
+        }),
+        schi: Some(SchiBox {
+            tenc: Some(TencBox {
+                default_is_protected: 1,
+                default_per_sample_iv_size: 8,
+                default_kid: [7; 16],
+                ..TencBox::default()
+            }),
+            ..SchiBox::default()
+        }),
+        ..SinfBox::default()
+    });
+    stbl.senc = Some(SencBox::new(&entries));
+    moov.psshs.push(PsshBox {
+        version: 1,
+        flags: 0,
+        system_id: CLEAR_KEY_SYSTEM_ID,
+        kids: vec![[7; 16]],
+        data: Vec::new(),
+    });
+
+    let check = |data: Vec<u8>| {
+        let size = data.len() as u64;
+        let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+        let track = mp4.tracks().get(&track_id).unwrap();
+        assert!(track.is_encrypted());
+        assert_eq!(track.media_type().unwrap(), MediaType::H264);
+        assert_eq!(track.box_type().unwrap().to_string(), "encv");
+        assert_eq!(track.original_format().unwrap().to_string(), "avc1");
+        assert_eq!(track.scheme_type().unwrap().to_string(), "cenc");
+        assert_eq!(track.default_kid(), Some([7; 16]));
+        assert_eq!(track.default_per_sample_iv_size(), Some(8));
+        assert_eq!(track.width(), 320);
+
+        let pssh = mp4.pssh();
+        assert_eq!(pssh.len(), 1);
+        assert_eq!(pssh[&CLEAR_KEY_SYSTEM_ID][0].kids, vec![[7; 16]]);
+
+        for (i, entry) in entries.iter().enumerate() {
+            let sample = mp4.read_sample(track_id, i as u32 + 1).unwrap().unwrap();
+            assert_eq!(sample.encryption.as_ref(), Some(entry));
+        }
+    };
+
+    let mut encrypted = data[..moov_offset].to_vec();
+    moov.write_box(&mut encrypted).unwrap();
+    check(encrypted);
+
+    // The same auxiliary information stored in the file, in a free box,
+    // and pointed at by saiz and saio.
+    let mut aux_info = Vec::new();
+    for entry in entries.iter() {
+        entry.write(&mut aux_info, true).unwrap();
+    }
+    let mut encrypted = data[..moov_offset].to_vec();
+    BoxHeader::new(BoxType::FreeBox, HEADER_SIZE + aux_info.len() as u64)
+        .write(&mut encrypted)
+        .unwrap();
+    let aux_info_offset = encrypted.len() as u64;
+    encrypted.extend_from_slice(&aux_info);
+
+    let stbl = &mut moov.traks[0].mdia.minf.stbl;
+    stbl.senc = None;
+    stbl.saiz = Some(SaizBox {
+        sample_count: 2,
+        sample_info_sizes: entries.iter().map(|entry| entry.size(true) as u8).collect(),
+        ..SaizBox::default()
+    });
+    stbl.saio = Some(SaioBox {
+        offsets: vec![aux_info_offset],
+        ..SaioBox::default()
+    });
+    moov.write_box(&mut encrypted).unwrap();
+    check(encrypted);
+}
+
+#[test]
+fn test_write_faststart() {
+    let config = Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap(), str::parse("mp41").unwrap()],
+        timescale: 1000,
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::new()), &config).unwrap();
+    let track_id = writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+    for i in 0..100u32 {
+        let sample = mp4::Mp4Sample {
+            start_time: i as u64 * 1024,
+            duration: 1024,
+            rendering_offset: 0,
+            is_sync: true,
+            bytes: mp4::Bytes::from(vec![i as u8; 200]),
+            encryption: None,
+        };
+        writer.write_sample(track_id, &sample).unwrap();
+    }
+    writer.write_end_faststart().unwrap();
+
+    let data = writer.into_writer().into_inner();
+
+    // ftyp, moov, mdat
+    let mut reader = Cursor::new(&data);
+    let mut box_types = Vec::new();
+    while (reader.position() as usize) < data.len() {
+        let header = BoxHeader::read(&mut reader).unwrap();
+        box_types.push(header.name);
+        reader.set_position(reader.position() + header.size - HEADER_SIZE);
+    }
+    assert_eq!(
+        box_types,
+        vec![BoxType::FtypBox, BoxType::MoovBox, BoxType::MdatBox]
+    );
+
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.sample_count(track_id).unwrap(), 100);
+    for i in 0..100u32 {
+        let sample = mp4.read_sample(track_id, i + 1).unwrap().unwrap();
+        assert_eq!(sample.start_time, i as u64 * 1024);
+        assert_eq!(sample.bytes, vec![i as u8; 200]);
+    }
+}
+
+#[test]
+fn test_write_stream() {
+    let config = Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap(), str::parse("mp41").unwrap()],
+        timescale: 1000,
+    };
+    // Vec<u8> is Write but not Seek.
+    let mut writer = Mp4StreamWriter::write_start(Vec::new(), &config).unwrap();
+    let video_track = writer
+        .add_track(&TrackConfig {
+            timescale: 1000,
+            media_conf: MediaConfig::AvcConfig(AvcConfig {
+                width: 320,
+                height: 240,
+                seq_param_set: vec![0x67, 0x64, 0x00, 0x0d],
+                pic_param_set: vec![0x68, 0xeb, 0xe3, 0xcb],
+            }),
+            ..TrackConfig::default()
+        })
+        .unwrap();
+    let audio_track = writer
+        .add_track(&TrackConfig::from(AacConfig::default()))
+        .unwrap();
+
+    // One second of video followed by one second of audio, twice.
+    let mut samples = Vec::new();
+    for second in 0..2u32 {
+        for i in 0..25u32 {
+            let sample_id = second * 25 + i;
+            samples.push((
+                video_track,
+                mp4::Mp4Sample {
+                    start_time: sample_id as u64 * 40,
+                    duration: 40,
+                    rendering_offset: if i % 2 == 1 { 40 } else { 0 },
+                    is_sync: i == 0,
+                    bytes: mp4::Bytes::from(vec![sample_id as u8; 300 + i as usize]),
+                    encryption: None,
+                },
+            ));
+        }
+        for i in 0..43u32 {
+            let sample_id = second * 43 + i;
+            samples.push((
+                audio_track,
+                mp4::Mp4Sample {
+                    start_time: sample_id as u64 * 1024,
+                    duration: 1024,
+                    rendering_offset: 0,
+                    is_sync: true,
+                    bytes: mp4::Bytes::from(vec![sample_id as u8; 100]),
+                    encryption: None,
+                },
+            ));
+        }
+    }
+
+    for (track_id, sample) in samples.iter() {
+        let metadata = Mp4SampleMetadata {
+            start_time: sample.start_time,
+            duration: sample.duration,
+            rendering_offset: sample.rendering_offset,
+            is_sync: sample.is_sync,
+            sample_flags: None,
+        };
+        writer
+            .add_sample_metadata(*track_id, sample.bytes.len() as u32, &metadata)
+            .unwrap();
+    }
+    let (track_id, sample) = &samples[1];
+    assert!(writer.write_sample(*track_id, sample).is_err());
+    for (track_id, sample) in samples.iter() {
+        writer.write_sample(*track_id, sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer();
+
+    // ftyp, moov, mdat
+    let mut reader = Cursor::new(&data);
+    let mut box_types = Vec::new();
+    while (reader.position() as usize) < data.len() {
+        let header = BoxHeader::read(&mut reader).unwrap();
+        box_types.push(header.name);
+        reader.set_position(reader.position() + header.size - HEADER_SIZE);
+    }
+    assert_eq!(
+        box_types,
+        vec![BoxType::FtypBox, BoxType::MoovBox, BoxType::MdatBox]
+    );
+
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.sample_count(video_track).unwrap(), 50);
+    assert_eq!(mp4.sample_count(audio_track).unwrap(), 86);
+    let mut sample_ids = std::collections::HashMap::new();
+    for (track_id, expected) in samples.iter() {
+        let sample_id = sample_ids.entry(*track_id).or_insert(0);
+        *sample_id += 1;
+        let sample = mp4.read_sample(*track_id, *sample_id).unwrap().unwrap();
+        assert_eq!(sample.start_time, expected.start_time);
+        assert_eq!(sample.rendering_offset, expected.rendering_offset);
+        assert_eq!(sample.is_sync, expected.is_sync);
+        assert_eq!(sample.bytes, expected.bytes);
+    }
+}
+
+#[test]
+fn test_write_interleaved() {
+    let config = Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap(), str::parse("mp41").unwrap()],
+        timescale: 1000,
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::new()), &config).unwrap();
+    writer.set_interleave_window(Duration::from_millis(500));
+
+    let video_track = writer
+        .add_track(&TrackConfig {
+            timescale: 1000,
+            media_conf: MediaConfig::AvcConfig(AvcConfig {
+                width: 320,
+                height: 240,
+                seq_param_set: vec![0x67, 0x64, 0x00, 0x0d],
+                pic_param_set: vec![0x68, 0xeb, 0xe3, 0xcb],
+            }),
+            ..TrackConfig::default()
+        })
+        .unwrap();
+    let audio_track = writer
+        .add_track(&TrackConfig {
+            samples_per_chunk: Some(10),
+            ..TrackConfig::from(AacConfig::default())
+        })
+        .unwrap();
+
+    // The audio is written a full second ahead of the video.
+    for i in 0..100u32 {
+        let sample = mp4::Mp4Sample {
+            start_time: i as u64 * 100,
+            duration: 100,
+            rendering_offset: 0,
+            is_sync: true,
+            bytes: mp4::Bytes::from(vec![0xa0; 10]),
+            encryption: None,
+        };
+        writer.write_sample(audio_track, &sample).unwrap();
+        if i >= 10 {
+            let sample = mp4::Mp4Sample {
+                start_time: (i - 10) as u64 * 100,
+                duration: 100,
+                rendering_offset: 0,
+                is_sync: true,
+                bytes: mp4::Bytes::from(vec![0x10; 100]),
+                encryption: None,
+            };
+            writer.write_sample(video_track, &sample).unwrap();
+        }
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let chunk_offsets = |track_id: u32| {
+        let track = mp4.tracks().get(&track_id).unwrap();
+        track
+            .trak
+            .mdia
+            .minf
+            .stbl
+            .stco
+            .as_ref()
+            .unwrap()
+            .entries
+            .clone()
+    };
+    let video_chunks = chunk_offsets(video_track);
+    let audio_chunks = chunk_offsets(audio_track);
+    assert_eq!(video_chunks.len(), 9);
+    assert_eq!(audio_chunks.len(), 10);
+
+    // Both tracks use one second chunks, so chunk n of either track must
+    // precede chunk n + 1 of the other.
+    for n in 0..video_chunks.len() - 1 {
+        assert!(video_chunks[n] < audio_chunks[n + 1]);
+        assert!(audio_chunks[n] < video_chunks[n + 1]);
+    }
+}
+
+#[test]
+fn test_seek_by_time() {
+    let config = Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap(), str::parse("mp41").unwrap()],
+        timescale: 1000,
+    };
+    let mut writer = Mp4Writer::write_start(Cursor::new(Vec::new()), &config).unwrap();
+    let track_id = writer
+        .add_track(&TrackConfig {
+            timescale: 1000,
+            media_conf: MediaConfig::AvcConfig(AvcConfig {
+                width: 320,
+                height: 240,
+                seq_param_set: vec![0x67, 0x64, 0x00, 0x0d],
+                pic_param_set: vec![0x68, 0xeb, 0xe3, 0xcb],
+            }),
+            ..TrackConfig::default()
+        })
+        .unwrap();
+
+    // Three groups of I P B B P, presented as I B B P P.
+    let rendering_offsets = [40, 120, 0, 0, 40];
+    for i in 0..15u32 {
+        let sample = mp4::Mp4Sample {
+            start_time: i as u64 * 40,
+            duration: 40,
+            rendering_offset: rendering_offsets[i as usize % 5],
+            is_sync: i % 5 == 0,
+            bytes: mp4::Bytes::from(vec![i as u8; 10]),
+            encryption: None,
+        };
+        writer.write_sample(track_id, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let sample_at = |ms| {
+        mp4.sample_at_time(track_id, Duration::from_millis(ms))
+            .unwrap()
+    };
+    let sync_before = |ms| {
+        mp4.nearest_sync_sample_before(track_id, Duration::from_millis(ms))
+            .unwrap()
+    };
+
+    assert_eq!(sample_at(0), Some(1));
+    assert_eq!(sample_at(40), Some(1));
+    assert_eq!(sample_at(85), Some(3));
+    assert_eq!(sample_at(165), Some(2));
+    assert_eq!(sample_at(245), Some(6));
+    assert_eq!(sample_at(570), Some(12));
+    assert_eq!(sample_at(639), Some(15));
+    assert_eq!(sample_at(700), None);
+
+    assert_eq!(sync_before(120), Some(1));
+    assert_eq!(sync_before(365), Some(6));
+    assert_eq!(sync_before(450), Some(11));
+    assert_eq!(sync_before(700), Some(11));
+
+    assert!(mp4.sample_at_time(track_id + 1, Duration::ZERO).is_err());
+}